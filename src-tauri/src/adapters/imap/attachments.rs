@@ -0,0 +1,273 @@
+use imap_proto::types::{BodyContentCommon, BodyContentSinglePart, BodyParams, BodyStructure, SectionPath};
+
+use super::connection::ImapConnection;
+use super::envelopes::decode_rfc2047;
+use super::historical::{decode_charset, encoding_to_string, part_to_string};
+use crate::error::EddieError;
+
+/// Size of each partial FETCH when downloading an attachment.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// An attachment found in a message's BODYSTRUCTURE.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AttachmentPart {
+    pub part: Vec<u32>,
+    pub filename: Option<String>,
+    pub mime_type: String,
+    /// Encoded size as reported by the server (base64 is ~4/3 of the file)
+    pub size_bytes: u32,
+    pub content_id: Option<String>,
+    pub encoding: String,
+    /// Referenced from the HTML body by Content-ID rather than attached
+    pub is_inline: bool,
+}
+
+/// Find all attachment parts in a BODYSTRUCTURE tree. Text and HTML body
+/// parts are skipped unless they carry a filename or an attachment
+/// disposition; forwarded messages (message/rfc822) are listed whole.
+pub fn find_attachments(body: &BodyStructure<'_>, prefix: &[u32]) -> Vec<AttachmentPart> {
+    let path = || if prefix.is_empty() { vec![1] } else { prefix.to_vec() };
+
+    match body {
+        BodyStructure::Basic { common, other, .. } => {
+            single_part(common, other, path(), false).into_iter().collect()
+        }
+        BodyStructure::Text { common, other, .. } => {
+            single_part(common, other, path(), false).into_iter().collect()
+        }
+        BodyStructure::Message { common, other, body, .. } => {
+            if prefix.is_empty() {
+                find_attachments(body, &path())
+            } else {
+                single_part(common, other, path(), true).into_iter().collect()
+            }
+        }
+        BodyStructure::Multipart { bodies, .. } => {
+            let mut results = Vec::new();
+            for (i, part) in bodies.iter().enumerate() {
+                let mut part_path = prefix.to_vec();
+                part_path.push((i + 1) as u32);
+                results.append(&mut find_attachments(part, &part_path));
+            }
+            results
+        }
+    }
+}
+
+fn single_part(
+    common: &BodyContentCommon<'_>,
+    other: &BodyContentSinglePart<'_>,
+    part: Vec<u32>,
+    is_message: bool,
+) -> Option<AttachmentPart> {
+    let disposition = common.disposition.as_ref().map(|d| d.ty.to_lowercase());
+    let filename = common.disposition.as_ref()
+        .and_then(|d| param_value(&d.params, "filename"))
+        .or_else(|| param_value(&common.ty.params, "name"));
+    let ty = common.ty.ty.to_lowercase();
+
+    let is_attachment = is_message
+        || disposition.as_deref() == Some("attachment")
+        || filename.is_some()
+        || ty != "text";
+    if !is_attachment {
+        return None;
+    }
+
+    let content_id = other.id.as_ref()
+        .map(|id| id.trim_matches(|c| c == '<' || c == '>').to_string())
+        .filter(|id| !id.is_empty());
+    let is_inline = content_id.is_some() && disposition.as_deref() != Some("attachment");
+
+    Some(AttachmentPart {
+        part,
+        filename,
+        mime_type: format!("{}/{}", ty, common.ty.subtype.to_lowercase()),
+        size_bytes: other.octets,
+        content_id,
+        encoding: encoding_to_string(&other.transfer_encoding),
+        is_inline,
+    })
+}
+
+/// Look up a Content-Type or Content-Disposition parameter, decoding
+/// RFC 2231 extended values (`name*=utf-8''...`, `name*0*=...; name*1*=...`)
+/// and RFC 2047 encoded words, which many clients use instead.
+pub fn param_value(params: &BodyParams<'_>, name: &str) -> Option<String> {
+    let params = params.as_ref()?;
+    let get = |key: &str| params.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_ref());
+
+    if let Some(value) = get(name) {
+        return Some(decode_rfc2047(value)).filter(|v| !v.is_empty());
+    }
+
+    if let Some(value) = get(&format!("{}*", name)) {
+        let (charset, encoded) = split_charset(value);
+        return Some(decode_charset(&percent_decode(encoded), Some(charset).filter(|c| !c.is_empty())))
+            .filter(|v| !v.is_empty());
+    }
+
+    // Continuations: only the first segment carries charset'language'
+    let mut raw: Vec<u8> = Vec::new();
+    let mut charset = "";
+    for i in 0.. {
+        if let Some(value) = get(&format!("{}*{}*", name, i)) {
+            let encoded = if i == 0 {
+                let (cs, rest) = split_charset(value);
+                charset = cs;
+                rest
+            } else {
+                value
+            };
+            raw.extend(percent_decode(encoded));
+        } else if let Some(value) = get(&format!("{}*{}", name, i)) {
+            raw.extend_from_slice(value.as_bytes());
+        } else {
+            break;
+        }
+    }
+    if raw.is_empty() {
+        return None;
+    }
+    Some(decode_charset(&raw, Some(charset).filter(|c| !c.is_empty())))
+}
+
+/// Split `charset'language'value`; values without the prefix are returned whole.
+fn split_charset(value: &str) -> (&str, &str) {
+    let mut parts = value.splitn(3, '\'');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_lang), Some(rest)) => (charset, rest),
+        _ => ("", value),
+    }
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = |b: u8| (b as char).to_digit(16);
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Download one body part with partial FETCHes of `CHUNK_SIZE` bytes, so a
+/// large attachment doesn't arrive as a single literal. The folder must
+/// already be selected. Returns the part still transfer-encoded.
+pub async fn fetch_part(conn: &mut ImapConnection, uid: u32, part: &[u32]) -> Result<Vec<u8>, EddieError> {
+    let uid_str = uid.to_string();
+    let section = part_to_string(part);
+    let path = SectionPath::Part(part.to_vec(), None);
+
+    let mut data: Vec<u8> = Vec::new();
+    loop {
+        let query = format!("(UID BODY.PEEK[{}]<{}.{}>)", section, data.len(), CHUNK_SIZE);
+        let fetches = super::historical::collect_tolerant(
+            conn.session
+                .uid_fetch(&uid_str, &query)
+                .await
+                .map_err(|e| EddieError::Backend(format!("FETCH attachment failed: {}", e)))?,
+            "attachment part",
+        ).await;
+
+        let chunk = fetches.iter()
+            .find_map(|f| f.section(&path))
+            .ok_or_else(|| EddieError::Backend(format!("Server returned no data for part {}", section)))?;
+        data.extend_from_slice(chunk);
+
+        if chunk.len() < CHUNK_SIZE {
+            return Ok(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use imap_proto::types::{AttributeValue, Response};
+
+    fn parse_bodystructure(raw: &str) -> Vec<AttachmentPart> {
+        let line = format!("* 1 FETCH (UID 7 BODYSTRUCTURE {})\r\n", raw);
+        let (_, response) = imap_proto::parser::parse_response(line.as_bytes()).expect("valid FETCH");
+        let Response::Fetch(_, attrs) = response else { panic!("not a FETCH") };
+        let body = attrs.iter()
+            .find_map(|a| match a {
+                AttributeValue::BodyStructure(b) => Some(b),
+                _ => None,
+            })
+            .expect("BODYSTRUCTURE present");
+        find_attachments(body, &[])
+    }
+
+    #[test]
+    fn test_lists_pdf_and_skips_body_parts() {
+        let parts = parse_bodystructure(concat!(
+            r#"(("TEXT" "PLAIN" ("CHARSET" "utf-8") NIL NIL "7BIT" 12 1 NIL NIL NIL)"#,
+            r#"("APPLICATION" "PDF" ("NAME" "contract.pdf") NIL NIL "BASE64" 4096 NIL ("ATTACHMENT" ("FILENAME" "contract.pdf")) NIL)"#,
+            r#" "MIXED" ("BOUNDARY" "b1") NIL NIL)"#,
+        ));
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].part, vec![2]);
+        assert_eq!(parts[0].filename.as_deref(), Some("contract.pdf"));
+        assert_eq!(parts[0].mime_type, "application/pdf");
+        assert_eq!(parts[0].encoding, "base64");
+        assert_eq!(parts[0].size_bytes, 4096);
+        assert!(!parts[0].is_inline);
+    }
+
+    #[test]
+    fn test_inline_image_with_content_id() {
+        let parts = parse_bodystructure(concat!(
+            r#"((("TEXT" "HTML" ("CHARSET" "utf-8") NIL NIL "QUOTED-PRINTABLE" 100 4 NIL NIL NIL)"#,
+            r#"("IMAGE" "PNG" NIL "<logo@x>" NIL "BASE64" 300 NIL ("INLINE" NIL) NIL)"#,
+            r#" "RELATED" NIL NIL NIL) "MIXED" NIL NIL NIL)"#,
+        ));
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].part, vec![1, 2]);
+        assert_eq!(parts[0].content_id.as_deref(), Some("logo@x"));
+        assert!(parts[0].is_inline);
+    }
+
+    #[test]
+    fn test_rfc2231_continuations() {
+        let parts = parse_bodystructure(concat!(
+            r#"(("TEXT" "PLAIN" NIL NIL NIL "7BIT" 12 1 NIL NIL NIL)"#,
+            r#"("APPLICATION" "OCTET-STREAM" NIL NIL NIL "BASE64" 10 NIL"#,
+            r#" ("ATTACHMENT" ("FILENAME*0*" "utf-8''Vertr%C3%A4ge%20" "FILENAME*1" "2026.pdf")) NIL)"#,
+            r#" "MIXED" NIL NIL NIL)"#,
+        ));
+        assert_eq!(parts[0].filename.as_deref(), Some("Verträge 2026.pdf"));
+    }
+
+    #[test]
+    fn test_param_value_encodings() {
+        let params = |k: &'static str, v: &'static str| -> BodyParams<'static> {
+            Some(vec![(k.into(), v.into())])
+        };
+        assert_eq!(
+            param_value(&params("filename*", "UTF-8'en'%E2%82%AC%20rates.xlsx"), "filename").as_deref(),
+            Some("€ rates.xlsx"),
+        );
+        assert_eq!(
+            param_value(&params("filename*", "iso-8859-1''r%E9sum%E9.doc"), "filename").as_deref(),
+            Some("résumé.doc"),
+        );
+        assert_eq!(
+            param_value(&params("name", "=?UTF-8?B?w6R0LnBkZg==?="), "name").as_deref(),
+            Some("ät.pdf"),
+        );
+        assert_eq!(param_value(&params("name", "x"), "filename"), None);
+        assert_eq!(param_value(&None, "filename"), None);
+    }
+}
@@ -1,5 +1,6 @@
 use async_imap::Session;
-use async_imap::types::Mailbox;
+use async_imap::types::{Fetch, Mailbox, UnsolicitedResponse};
+use imap_proto::{Response, ResponseCode};
 use tokio::net::TcpStream;
 use tokio_rustls::TlsConnector;
 use tokio_rustls::client::TlsStream;
@@ -9,6 +10,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use crate::adapters::oauth::{SaslAuthenticator, SaslMechanism};
 use crate::services::logger;
 use crate::error::EddieError;
 
@@ -61,10 +63,170 @@ impl AsyncWrite for MaybeTlsStream {
 
 pub type ImapSession = Session<MaybeTlsStream>;
 
+/// How the IMAP connection is secured, as stored in `accounts.imap_security`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImapSecurity {
+    /// Plain TCP (usually port 143)
+    None,
+    /// Plain TCP upgraded with the STARTTLS command (usually port 143)
+    Starttls,
+    /// Implicit TLS from the first byte (usually port 993)
+    Tls,
+}
+
+impl ImapSecurity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImapSecurity::None => "none",
+            ImapSecurity::Starttls => "starttls",
+            ImapSecurity::Tls => "tls",
+        }
+    }
+
+    /// Parse a stored value. Unknown values fall back to implicit TLS.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "none" => ImapSecurity::None,
+            "starttls" => ImapSecurity::Starttls,
+            _ => ImapSecurity::Tls,
+        }
+    }
+}
+
+/// Which RFC 7162 extension was enabled on the session after login.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModSeqSupport {
+    None,
+    /// CONDSTORE: HIGHESTMODSEQ on SELECT and FETCH ... (CHANGEDSINCE n)
+    Condstore,
+    /// QRESYNC: CONDSTORE plus VANISHED responses for expunged UIDs
+    Qresync,
+}
+
+/// Server capabilities that change how we talk to the server, read from
+/// CAPABILITY once after login.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+    /// X-GM-EXT-1: Gmail labels, X-GM-MSGID/X-GM-THRID, and an All Mail
+    /// folder that holds every message
+    pub gmail_ext: bool,
+    /// IDLE (RFC 2177)
+    pub idle: bool,
+    /// MOVE (RFC 6851): UID MOVE instead of COPY + STORE \Deleted + EXPUNGE
+    pub uid_move: bool,
+    /// UIDPLUS (RFC 4315): UID EXPUNGE, APPENDUID/COPYUID
+    pub uidplus: bool,
+    /// CONDSTORE (RFC 7162)
+    pub condstore: bool,
+    /// QRESYNC (RFC 7162)
+    pub qresync: bool,
+    /// ENABLE (RFC 5161)
+    pub enable: bool,
+    /// SPECIAL-USE (RFC 6154): \Sent, \Trash, ... attributes on LIST
+    pub special_use: bool,
+    /// ID (RFC 2971)
+    pub id: bool,
+    /// STARTTLS (RFC 3501): only advertised before login on a plain connection
+    pub starttls: bool,
+}
+
+impl Capabilities {
+    pub fn from_server(caps: &async_imap::types::Capabilities) -> Self {
+        use async_imap::types::Capability;
+        Self::from_names(caps.iter().filter_map(|cap| match cap {
+            Capability::Atom(name) => Some(name.as_str()),
+            _ => None,
+        }))
+    }
+
+    /// Build from capability atoms, which are case-insensitive.
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut caps = Capabilities::default();
+        for name in names {
+            let flag = match name.to_ascii_uppercase().as_str() {
+                "X-GM-EXT-1" => &mut caps.gmail_ext,
+                "IDLE" => &mut caps.idle,
+                "MOVE" => &mut caps.uid_move,
+                "UIDPLUS" => &mut caps.uidplus,
+                "CONDSTORE" => &mut caps.condstore,
+                "QRESYNC" => &mut caps.qresync,
+                "ENABLE" => &mut caps.enable,
+                "SPECIAL-USE" => &mut caps.special_use,
+                "ID" => &mut caps.id,
+                "STARTTLS" => &mut caps.starttls,
+                _ => continue,
+            };
+            *flag = true;
+        }
+        caps
+    }
+
+    /// Capabilities listed in a greeting's `[CAPABILITY ...]` response code.
+    /// `None` if the greeting doesn't list them.
+    fn from_greeting(greeting: &Response) -> Option<Self> {
+        match greeting {
+            Response::Data { code: Some(ResponseCode::Capabilities(caps)), .. } => Some(Self::from_parsed(caps)),
+            _ => None,
+        }
+    }
+
+    fn from_parsed(caps: &[imap_proto::Capability]) -> Self {
+        Self::from_names(caps.iter().filter_map(|cap| match cap {
+            imap_proto::Capability::Atom(name) => Some(name.as_ref()),
+            _ => None,
+        }))
+    }
+
+    /// The RFC 7162 ENABLE command to send after login (if any), and the
+    /// support it gives when it succeeds.
+    fn modseq_command(&self) -> (Option<&'static str>, ModSeqSupport) {
+        if self.qresync && self.enable {
+            (Some("ENABLE QRESYNC"), ModSeqSupport::Qresync)
+        } else if self.condstore && self.enable {
+            (Some("ENABLE CONDSTORE"), ModSeqSupport::Condstore)
+        } else if self.condstore {
+            // CHANGEDSINCE is itself a CONDSTORE-enabling command
+            (None, ModSeqSupport::Condstore)
+        } else {
+            (None, ModSeqSupport::None)
+        }
+    }
+
+    /// Names of the advertised capabilities we act on, for logging.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.gmail_ext, "X-GM-EXT-1"),
+            (self.idle, "IDLE"),
+            (self.uid_move, "MOVE"),
+            (self.uidplus, "UIDPLUS"),
+            (self.condstore, "CONDSTORE"),
+            (self.qresync, "QRESYNC"),
+            (self.enable, "ENABLE"),
+            (self.special_use, "SPECIAL-USE"),
+            (self.id, "ID"),
+            (self.starttls, "STARTTLS"),
+        ]
+        .into_iter()
+        .filter(|(has, _)| *has)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// How to authenticate after connecting.
+pub enum ImapAuth {
+    /// IMAP LOGIN
+    Password(String),
+    /// SASL AUTHENTICATE with an OAuth2 access token
+    OAuth2 { mechanism: SaslMechanism, access_token: String },
+}
+
 pub struct ImapConnection {
     pub session: ImapSession,
-    pub has_gmail_ext: bool,
+    pub caps: Capabilities,
     pub write_mode: bool,
+    pub modseq: ModSeqSupport,
 }
 
 impl ImapConnection {
@@ -74,7 +236,7 @@ impl ImapConnection {
         } else {
             self.session.examine(folder).await
         }
-        .map_err(|e| EddieError::Backend(format!("SELECT failed: {}", e)))?;
+        .map_err(|e| command_error("SELECT failed", e))?;
 
         Ok(mailbox)
     }
@@ -89,14 +251,100 @@ impl ImapConnection {
         let _updates = self.session
             .uid_store(&uid_set, flag_op)
             .await
-            .map_err(|e| EddieError::Backend(format!("STORE failed: {}", e)))?;
+            .map_err(|e| command_error("STORE failed", e))?;
         // Consume the stream
         use futures::TryStreamExt;
         let _: Vec<_> = _updates.try_collect().await
-            .map_err(|e| EddieError::Backend(format!("STORE response failed: {}", e)))?;
+            .map_err(|e| command_error("STORE response failed", e))?;
         Ok(())
     }
 
+    /// Move messages from the selected folder to `destination`. Uses UID MOVE
+    /// (RFC 6851) when available, otherwise COPY followed by \Deleted and an
+    /// expunge of just those UIDs.
+    pub async fn move_messages(&mut self, uids: &[u32], destination: &str) -> Result<(), EddieError> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+        if self.caps.uid_move {
+            return self.session
+                .uid_mv(&uid_set, destination)
+                .await
+                .map_err(|e| command_error("MOVE failed", e));
+        }
+
+        self.session
+            .uid_copy(&uid_set, destination)
+            .await
+            .map_err(|e| command_error("COPY failed", e))?;
+        self.delete_messages(uids).await
+    }
+
+    /// Mark messages in the selected folder \Deleted and expunge them.
+    ///
+    /// Without UIDPLUS there is no way to expunge only these UIDs — a plain
+    /// EXPUNGE would also remove anything another client flagged \Deleted —
+    /// so the messages are left flagged for the server or user to purge.
+    pub async fn delete_messages(&mut self, uids: &[u32]) -> Result<(), EddieError> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        self.store_flags(uids, "+FLAGS.SILENT (\\Deleted)").await?;
+
+        if !self.caps.uidplus {
+            logger::debug("Server lacks UIDPLUS — leaving messages flagged \\Deleted instead of expunging");
+            return Ok(());
+        }
+
+        let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+        let expunged = self.session
+            .uid_expunge(&uid_set)
+            .await
+            .map_err(|e| command_error("UID EXPUNGE failed", e))?;
+        use futures::TryStreamExt;
+        let _: Vec<_> = expunged.try_collect().await
+            .map_err(|e| command_error("UID EXPUNGE response failed", e))?;
+        Ok(())
+    }
+
+    /// UID FETCH `items` for every message whose MODSEQ moved past `since`
+    /// (RFC 7162 CHANGEDSINCE). Under QRESYNC the server also reports the UIDs
+    /// expunged since then, returned as the second element.
+    pub async fn fetch_changed_since(
+        &mut self,
+        items: &str,
+        since: u64,
+    ) -> Result<(Vec<Fetch>, Vec<u32>), EddieError> {
+        let modifiers = if self.modseq == ModSeqSupport::Qresync {
+            format!("(CHANGEDSINCE {} VANISHED)", since)
+        } else {
+            format!("(CHANGEDSINCE {})", since)
+        };
+
+        // VANISHED (EARLIER) arrives on the unsolicited channel — drop anything stale first.
+        while self.session.unsolicited_responses.try_recv().is_ok() {}
+
+        let fetches = super::historical::collect_tolerant(
+            self.session
+                .uid_fetch("1:*", format!("{} {}", items, modifiers))
+                .await
+                .map_err(|e| EddieError::Backend(format!("FETCH CHANGEDSINCE failed: {}", e)))?,
+            "changed since modseq",
+        ).await;
+
+        let mut vanished = Vec::new();
+        while let Ok(response) = self.session.unsolicited_responses.try_recv() {
+            if let UnsolicitedResponse::Other(data) = response {
+                if let Response::Vanished { uids, .. } = data.parsed() {
+                    vanished.extend(uids.iter().flat_map(|range| range.clone()));
+                }
+            }
+        }
+
+        Ok((fetches, vanished))
+    }
+
     /// Append a raw RFC 5322 message to a folder.
     /// `flags` should be like &["\\Seen", "\\Flagged"] — they'll be joined into "(\\Seen \\Flagged)".
     pub async fn append_message(&mut self, folder: &str, flags: &[&str], message_bytes: &[u8]) -> Result<(), EddieError> {
@@ -109,62 +357,258 @@ impl ImapConnection {
         self.session
             .append(folder, flags_str.as_deref(), None, message_bytes)
             .await
-            .map_err(|e| EddieError::Backend(format!("APPEND to {} failed: {}", folder, e)))?;
+            .map_err(|e| command_error(&format!("APPEND to {} failed", folder), e))?;
 
         logger::debug(&format!("Appended message to folder {} with flags {:?}", folder, flags));
         Ok(())
     }
 }
 
+/// Connect and log in. With `ImapSecurity::Starttls`, a server that rejects
+/// the upgrade (or a handshake that fails) aborts the connection unless
+/// `allow_plaintext` is set, in which case we reconnect and log in unencrypted.
 pub async fn connect_with_tls(
     host: &str,
     port: u16,
-    use_tls: bool,
+    security: ImapSecurity,
+    allow_plaintext: bool,
     username: &str,
-    password: &str,
+    auth: &ImapAuth,
     write_mode: bool,
 ) -> Result<ImapConnection, EddieError> {
-    logger::debug(&format!("Connecting to IMAP server: host={}, port={}, tls={}", host, port, use_tls));
-
-    let tcp = TcpStream::connect((host, port))
-        .await
-        .map_err(|e| EddieError::Backend(format!("TCP connection failed: {}", e)))?;
+    logger::debug(&format!(
+        "Connecting to IMAP server: host={}, port={}, security={}",
+        host, port, security.as_str()
+    ));
 
-    let stream = if use_tls {
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-
-        let config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+    let stream = match security {
+        ImapSecurity::Tls => MaybeTlsStream::Tls(tls_handshake(host, tcp_connect(host, port).await?).await?),
+        ImapSecurity::None => MaybeTlsStream::Plain(tcp_connect(host, port).await?.compat()),
+        ImapSecurity::Starttls => match starttls(host, port).await {
+            Ok(stream) => stream,
+            Err(e) if allow_plaintext => {
+                logger::warn(&format!("{} — continuing without TLS as allowed for this account", e));
+                MaybeTlsStream::Plain(tcp_connect(host, port).await?.compat())
+            }
+            Err(e) => {
+                return Err(EddieError::Backend(format!(
+                    "{} — refusing to send credentials unencrypted", e
+                )));
+            }
+        },
+    };
 
-        let connector = TlsConnector::from(Arc::new(config));
-        let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
-            .map_err(|e| EddieError::Backend(format!("Invalid server name: {}", e)))?;
+    let client = async_imap::Client::new(stream);
 
-        let tls_stream = connector
-            .connect(server_name, tcp)
+    let mut session = match auth {
+        ImapAuth::Password(password) => client
+            .login(username, password)
             .await
-            .map_err(|e| EddieError::Backend(format!("TLS handshake failed: {}", e)))?;
-
-        MaybeTlsStream::Tls(tls_stream.compat())
-    } else {
-        MaybeTlsStream::Plain(tcp.compat())
+            .map_err(|(e, _)| command_error("Login failed", e))?,
+        ImapAuth::OAuth2 { mechanism, access_token } => {
+            let response = mechanism.initial_response(username, host, port, access_token);
+            client
+                .authenticate(mechanism.as_str(), SaslAuthenticator::new(response))
+                .await
+                .map_err(|(e, _)| command_error(&format!("AUTHENTICATE {} failed", mechanism.as_str()), e))?
+        }
     };
 
-    let client = async_imap::Client::new(stream);
-
-    let session = client
-        .login(username, password)
-        .await
-        .map_err(|(e, _)| EddieError::Backend(format!("Login failed: {}", e)))?;
+    // Capabilities can change after authentication, so ask again rather than
+    // trusting the greeting
+    let caps = Capabilities::from_server(
+        &session
+            .capabilities()
+            .await
+            .map_err(|e| EddieError::Backend(format!("CAPABILITY failed: {}", e)))?,
+    );
+    logger::debug(&format!("{} capabilities: {}", host, caps.names().join(" ")));
 
-    let has_gmail_ext = host.contains("gmail.com")
-        || host.contains("googlemail.com");
+    if caps.id {
+        send_id(&mut session).await;
+    }
+    let modseq = enable_modseq(&mut session, &caps).await;
 
     Ok(ImapConnection {
         session,
-        has_gmail_ext,
+        caps,
         write_mode,
+        modseq,
     })
 }
+
+/// Response codes (RFC 5530) on a NO that mean "not right now" rather than "never".
+const TEMPORARY_CODES: &[&str] = &["[UNAVAILABLE]", "[INUSE]", "[LIMIT]", "[OVERQUOTA]", "[SERVERBUG]"];
+
+/// Map a failed IMAP command. A NO or BAD reply is the server refusing the
+/// command and becomes `Rejected`, unless it carries a temporary response
+/// code; I/O errors and dropped connections stay retryable.
+fn command_error(context: &str, e: async_imap::error::Error) -> EddieError {
+    use async_imap::error::Error;
+    match &e {
+        Error::No(text) if TEMPORARY_CODES.iter().any(|code| text.contains(code)) => {
+            EddieError::Backend(format!("{}: {}", context, e))
+        }
+        Error::No(_) | Error::Bad(_) => EddieError::Rejected(format!("{}: {}", context, e)),
+        _ => EddieError::Backend(format!("{}: {}", context, e)),
+    }
+}
+
+async fn tcp_connect(host: &str, port: u16) -> Result<TcpStream, EddieError> {
+    TcpStream::connect((host, port))
+        .await
+        .map_err(|e| EddieError::Backend(format!("TCP connection failed: {}", e)))
+}
+
+async fn tls_handshake(host: &str, tcp: TcpStream) -> Result<Compat<TlsStream<TcpStream>>, EddieError> {
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+
+    let connector = TlsConnector::from(Arc::new(config));
+    let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
+        .map_err(|e| EddieError::Backend(format!("Invalid server name: {}", e)))?;
+
+    let tls_stream = connector
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| EddieError::Backend(format!("TLS handshake failed: {}", e)))?;
+
+    Ok(tls_stream.compat())
+}
+
+/// Open a plain connection, issue STARTTLS (RFC 3501 §6.2.1) and upgrade it.
+/// Fails without sending the command if the greeting lists capabilities and
+/// STARTTLS isn't one of them.
+async fn starttls(host: &str, port: u16) -> Result<MaybeTlsStream, EddieError> {
+    let mut client = async_imap::Client::new(tcp_connect(host, port).await?.compat());
+    let greeting = client
+        .read_response()
+        .await
+        .map_err(|e| EddieError::Backend(format!("STARTTLS failed: {}", e)))?
+        .ok_or_else(|| EddieError::Backend("STARTTLS failed: connection closed".into()))?;
+    if Capabilities::from_greeting(greeting.parsed()).is_some_and(|caps| !caps.starttls) {
+        return Err(EddieError::Backend("STARTTLS failed: server does not advertise STARTTLS".into()));
+    }
+
+    client
+        .run_command_and_check_ok("STARTTLS", None)
+        .await
+        .map_err(|e| EddieError::Backend(format!("STARTTLS failed: {}", e)))?;
+
+    let tcp = client.into_inner().into_inner();
+    let tls = tls_handshake(host, tcp)
+        .await
+        .map_err(|e| EddieError::Backend(format!("STARTTLS failed: {}", e)))?;
+    Ok(MaybeTlsStream::Tls(tls))
+}
+
+/// ENABLE QRESYNC (or CONDSTORE) when the server advertises it, so SELECT
+/// reports HIGHESTMODSEQ and flag resync can fetch only what changed.
+async fn enable_modseq(session: &mut ImapSession, caps: &Capabilities) -> ModSeqSupport {
+    let (command, support) = match caps.modseq_command() {
+        (Some(command), support) => (command, support),
+        (None, support) => return support,
+    };
+
+    match session.run_command_and_check_ok(command).await {
+        Ok(()) => support,
+        Err(e) => {
+            logger::warn(&format!("{} failed, falling back to full flag scans: {}", command, e));
+            ModSeqSupport::None
+        }
+    }
+}
+
+/// Identify the client (RFC 2971). Some providers (e.g. NetEase) refuse
+/// SELECT until the client has sent ID; everyone else just logs it.
+async fn send_id(session: &mut ImapSession) {
+    let command = format!(
+        "ID (\"name\" \"Eddie\" \"version\" \"{}\")",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Err(e) = session.run_command_and_check_ok(&command).await {
+        logger::debug(&format!("ID failed: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(response: &str) -> Response<'_> {
+        imap_proto::parser::parse_response(response.as_bytes()).unwrap().1
+    }
+
+    fn capabilities(response: &str) -> Capabilities {
+        match parse(response) {
+            Response::Capabilities(caps) => Capabilities::from_parsed(&caps),
+            other => panic!("not a CAPABILITY response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_capabilities_from_server_responses() {
+        let cases = [
+            (
+                "Gmail",
+                "* CAPABILITY IMAP4rev1 UNSELECT IDLE NAMESPACE QUOTA ID XLIST CHILDREN X-GM-EXT-1 UIDPLUS \
+                 COMPRESS=DEFLATE ENABLE MOVE CONDSTORE ESEARCH UTF8=ACCEPT LIST-EXTENDED LIST-STATUS \
+                 LITERAL- SPECIAL-USE APPENDLIMIT=35651584\r\n",
+                vec!["X-GM-EXT-1", "IDLE", "MOVE", "UIDPLUS", "CONDSTORE", "ENABLE", "SPECIAL-USE", "ID"],
+                (Some("ENABLE CONDSTORE"), ModSeqSupport::Condstore),
+            ),
+            (
+                "Dovecot",
+                "* CAPABILITY IMAP4rev1 SASL-IR LOGIN-REFERRALS ID ENABLE IDLE SORT THREAD=REFERENCES \
+                 MULTIAPPEND UNSELECT CHILDREN NAMESPACE UIDPLUS LIST-EXTENDED CONDSTORE QRESYNC ESEARCH \
+                 LIST-STATUS BINARY MOVE SPECIAL-USE\r\n",
+                vec!["IDLE", "MOVE", "UIDPLUS", "CONDSTORE", "QRESYNC", "ENABLE", "SPECIAL-USE", "ID"],
+                (Some("ENABLE QRESYNC"), ModSeqSupport::Qresync),
+            ),
+            (
+                "CONDSTORE without ENABLE",
+                "* CAPABILITY IMAP4rev1 condstore idle\r\n",
+                vec!["IDLE", "CONDSTORE"],
+                (None, ModSeqSupport::Condstore),
+            ),
+            (
+                "QRESYNC without ENABLE",
+                "* CAPABILITY IMAP4rev1 QRESYNC\r\n",
+                vec!["QRESYNC"],
+                (None, ModSeqSupport::None),
+            ),
+            (
+                "Before login",
+                "* CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED AUTH=PLAIN\r\n",
+                vec!["STARTTLS"],
+                (None, ModSeqSupport::None),
+            ),
+        ];
+
+        for (server, response, names, modseq) in cases {
+            let caps = capabilities(response);
+            let mut found = caps.names();
+            let mut expected = names.clone();
+            found.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(found, expected, "{}", server);
+            assert_eq!(caps.modseq_command(), modseq, "{}", server);
+        }
+    }
+
+    #[test]
+    fn test_capabilities_from_greeting() {
+        let greeting = parse("* OK [CAPABILITY IMAP4rev1 SASL-IR ID ENABLE IDLE LITERAL+ STARTTLS AUTH=PLAIN] Dovecot ready.\r\n");
+        assert!(Capabilities::from_greeting(&greeting).unwrap().starttls);
+
+        let greeting = parse("* OK [CAPABILITY IMAP4rev1 IDLE LOGINDISABLED] ready\r\n");
+        assert!(!Capabilities::from_greeting(&greeting).unwrap().starttls);
+
+        let greeting = parse("* OK IMAP4rev1 Service Ready\r\n");
+        assert_eq!(Capabilities::from_greeting(&greeting), None);
+    }
+}
@@ -0,0 +1,273 @@
+use imap_proto::types::{BodyStructure, MessageSection, SectionPath};
+
+use super::attachments::param_value;
+use super::connection::ImapConnection;
+use super::historical::{collect_tolerant, decode_body, encoding_to_string, part_to_string};
+use crate::error::EddieError;
+
+/// Where the machine-readable parts of a delivery status notification
+/// (`multipart/report; report-type=delivery-status`, RFC 3464) are.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ReportParts {
+    /// The message/delivery-status part and its transfer encoding
+    pub status: (Vec<u32>, String),
+    /// The returned message (message/rfc822) or its headers (text/rfc822-headers)
+    pub original: Option<(Vec<u32>, String, bool)>,
+}
+
+/// Delivery outcome for one recipient of a reported message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipientStatus {
+    pub recipient: String,
+    /// failed, delayed, delivered, relayed or expanded
+    pub action: String,
+    /// RFC 3463 status code, e.g. 5.1.1
+    pub status: Option<String>,
+    pub diagnostic: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryReport {
+    /// Message-ID of the message the report is about, without brackets
+    pub original_message_id: Option<String>,
+    pub recipients: Vec<RecipientStatus>,
+}
+
+/// Find the delivery-status and returned-message parts of a DSN. Reports
+/// are usually the top-level part but some servers wrap them in
+/// multipart/mixed.
+pub fn find_delivery_report(body: &BodyStructure<'_>, prefix: &[u32]) -> Option<ReportParts> {
+    let BodyStructure::Multipart { common, bodies, .. } = body else { return None };
+    let child_path = |i: usize| {
+        let mut path = prefix.to_vec();
+        path.push(i as u32 + 1);
+        path
+    };
+
+    let is_report = common.ty.subtype.eq_ignore_ascii_case("report")
+        && param_value(&common.ty.params, "report-type")
+            .is_some_and(|t| t.eq_ignore_ascii_case("delivery-status"));
+    if !is_report {
+        return bodies.iter().enumerate().find_map(|(i, part)| find_delivery_report(part, &child_path(i)));
+    }
+
+    let mut status = None;
+    let mut original = None;
+    for (i, part) in bodies.iter().enumerate() {
+        match part {
+            BodyStructure::Basic { common, other, .. } | BodyStructure::Text { common, other, .. } => {
+                let mime = format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase();
+                let encoding = encoding_to_string(&other.transfer_encoding);
+                match mime.as_str() {
+                    "message/delivery-status" | "message/global-delivery-status" => {
+                        status = Some((child_path(i), encoding));
+                    }
+                    "text/rfc822-headers" | "message/global-headers" => {
+                        original = Some((child_path(i), encoding, false));
+                    }
+                    _ => {}
+                }
+            }
+            BodyStructure::Message { other, .. } => {
+                original = Some((child_path(i), encoding_to_string(&other.transfer_encoding), true));
+            }
+            BodyStructure::Multipart { .. } => {}
+        }
+    }
+    status.map(|status| ReportParts { status, original })
+}
+
+/// Download and parse a DSN's parts. The folder must already be selected.
+/// Only the header of a returned message is fetched.
+pub async fn fetch_delivery_report(
+    conn: &mut ImapConnection,
+    uid: u32,
+    parts: &ReportParts,
+) -> Result<DeliveryReport, EddieError> {
+    let (status_part, status_encoding) = &parts.status;
+    let status_raw = fetch_section(conn, uid, SectionPath::Part(status_part.clone(), None)).await?;
+    let mut report = parse_delivery_status(&decode_body(&status_raw, status_encoding, None)?);
+
+    if let Some((part, encoding, is_message)) = &parts.original {
+        let section = if *is_message {
+            SectionPath::Part(part.clone(), Some(MessageSection::Header))
+        } else {
+            SectionPath::Part(part.clone(), None)
+        };
+        let raw = fetch_section(conn, uid, section).await?;
+        let headers = if *is_message { String::from_utf8_lossy(&raw).to_string() } else { decode_body(&raw, encoding, None)? };
+        if let Some(id) = header_message_id(&headers) {
+            report.original_message_id = Some(id);
+        }
+    }
+    Ok(report)
+}
+
+async fn fetch_section(conn: &mut ImapConnection, uid: u32, path: SectionPath) -> Result<Vec<u8>, EddieError> {
+    let section = match &path {
+        SectionPath::Part(part, Some(MessageSection::Header)) => format!("{}.HEADER", part_to_string(part)),
+        SectionPath::Part(part, _) => part_to_string(part),
+        SectionPath::Full(_) => String::new(),
+    };
+    let fetches = collect_tolerant(
+        conn.session
+            .uid_fetch(uid.to_string(), format!("(UID BODY.PEEK[{}])", section))
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH delivery report failed: {}", e)))?,
+        "delivery report",
+    ).await;
+    fetches.iter()
+        .find_map(|f| f.section(&path).map(|data| data.to_vec()))
+        .ok_or_else(|| EddieError::Backend(format!("Server returned no data for part {}", section)))
+}
+
+/// Parse a message/delivery-status body: per-message fields, then one
+/// blank-line separated group of fields per recipient.
+pub fn parse_delivery_status(text: &str) -> DeliveryReport {
+    let mut report = DeliveryReport { original_message_id: None, recipients: Vec::new() };
+    for group in field_groups(text) {
+        let get = |name: &str| group.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str());
+
+        // Not in RFC 3464, but some MTAs name the reported message this way
+        if let Some(id) = get("Original-Message-ID") {
+            report.original_message_id = Some(strip_brackets(id));
+        }
+        let Some(recipient) = get("Final-Recipient").or_else(|| get("Original-Recipient")) else { continue };
+        report.recipients.push(RecipientStatus {
+            recipient: strip_brackets(without_type(recipient)),
+            action: get("Action").unwrap_or("failed").trim().to_lowercase(),
+            status: get("Status").map(|s| s.split_whitespace().next().unwrap_or_default().to_string()),
+            diagnostic: get("Diagnostic-Code").map(|d| without_type(d).to_string()).filter(|d| !d.is_empty()),
+        });
+    }
+    report
+}
+
+/// The Message-ID from a block of message headers, without brackets.
+pub fn header_message_id(headers: &str) -> Option<String> {
+    field_groups(headers).into_iter().next()?
+        .into_iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("Message-ID"))
+        .map(|(_, v)| strip_brackets(&v))
+        .filter(|id| !id.is_empty())
+}
+
+/// Unfold header-style fields and split them into blank-line separated groups.
+fn field_groups(text: &str) -> Vec<Vec<(String, String)>> {
+    let mut groups = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                groups.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = current.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            current.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+    groups
+}
+
+/// Drop the type prefix of a typed field ("rfc822; a@x", "smtp; 550 ...").
+fn without_type(value: &str) -> &str {
+    value.split_once(';').map(|(_, rest)| rest).unwrap_or(value).trim()
+}
+
+fn strip_brackets(value: &str) -> String {
+    value.trim().trim_matches(|c| c == '<' || c == '>').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use imap_proto::types::{AttributeValue, Response};
+
+    fn report_parts(raw: &str) -> Option<ReportParts> {
+        let line = format!("* 1 FETCH (UID 7 BODYSTRUCTURE {})\r\n", raw);
+        let (_, response) = imap_proto::parser::parse_response(line.as_bytes()).expect("valid FETCH");
+        let Response::Fetch(_, attrs) = response else { panic!("not a FETCH") };
+        let body = attrs.iter()
+            .find_map(|a| match a {
+                AttributeValue::BodyStructure(b) => Some(b),
+                _ => None,
+            })
+            .expect("BODYSTRUCTURE present");
+        find_delivery_report(body, &[])
+    }
+
+    #[test]
+    fn test_finds_report_parts() {
+        let parts = report_parts(concat!(
+            r#"(("TEXT" "PLAIN" ("CHARSET" "us-ascii") NIL NIL "7BIT" 120 3 NIL NIL NIL)"#,
+            r#"("MESSAGE" "DELIVERY-STATUS" NIL NIL NIL "7BIT" 300 NIL NIL NIL)"#,
+            r#"("TEXT" "RFC822-HEADERS" NIL NIL NIL "7BIT" 400 10 NIL NIL NIL)"#,
+            r#" "REPORT" ("REPORT-TYPE" "delivery-status" "BOUNDARY" "b1") NIL NIL)"#,
+        )).expect("delivery report");
+        assert_eq!(parts.status, (vec![2], "7bit".to_string()));
+        assert_eq!(parts.original, Some((vec![3], "7bit".to_string(), false)));
+    }
+
+    #[test]
+    fn test_ignores_other_reports() {
+        let parts = report_parts(concat!(
+            r#"(("TEXT" "PLAIN" NIL NIL NIL "7BIT" 12 1 NIL NIL NIL)"#,
+            r#"("MESSAGE" "DISPOSITION-NOTIFICATION" NIL NIL NIL "7BIT" 300 NIL NIL NIL)"#,
+            r#" "REPORT" ("REPORT-TYPE" "disposition-notification") NIL NIL)"#,
+        ));
+        assert_eq!(parts, None);
+    }
+
+    #[test]
+    fn test_parses_per_recipient_fields() {
+        let report = parse_delivery_status(concat!(
+            "Reporting-MTA: dns; mx.example.com\r\n",
+            "Arrival-Date: Tue, 6 Oct 2026 10:00:00 +0000\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822; <nobody@example.org>\r\n",
+            "Action: failed\r\n",
+            "Status: 5.1.1\r\n",
+            "Diagnostic-Code: smtp; 550 5.1.1 User unknown:\r\n",
+            "  no such mailbox\r\n",
+            "\r\n",
+            "Original-Recipient: rfc822;slow@example.net\r\n",
+            "Final-Recipient: RFC822; slow@example.net\r\n",
+            "Action: Delayed\r\n",
+            "Status: 4.4.1 (connection timed out)\r\n",
+        ));
+        assert_eq!(report.original_message_id, None);
+        assert_eq!(report.recipients, vec![
+            RecipientStatus {
+                recipient: "nobody@example.org".into(),
+                action: "failed".into(),
+                status: Some("5.1.1".into()),
+                diagnostic: Some("550 5.1.1 User unknown: no such mailbox".into()),
+            },
+            RecipientStatus {
+                recipient: "slow@example.net".into(),
+                action: "delayed".into(),
+                status: Some("4.4.1".into()),
+                diagnostic: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_original_message_id() {
+        let headers = "From: me@example.com\r\nMessage-Id:\r\n <abc123@eddie.app>\r\nSubject: Hi\r\n\r\nbody";
+        assert_eq!(header_message_id(headers).as_deref(), Some("abc123@eddie.app"));
+        assert_eq!(header_message_id("Subject: none\r\n"), None);
+
+        let report = parse_delivery_status("Original-Message-ID: <x@y>\n\nFinal-Recipient: rfc822;a@b\nAction: failed\n");
+        assert_eq!(report.original_message_id.as_deref(), Some("x@y"));
+    }
+}
@@ -3,6 +3,9 @@ use std::collections::HashMap;
 use async_imap::types::Fetch;
 use imap_proto::BodyStructure;
 
+use super::attachments::{find_attachments, AttachmentPart};
+use super::dsn::{find_delivery_report, ReportParts};
+
 #[derive(Debug, serde::Serialize)]
 pub struct Envelope {
     pub uid: u32,
@@ -13,9 +16,14 @@ pub struct Envelope {
     pub from_name: Option<String>,
     pub to_addresses: Vec<String>,
     pub cc_addresses: Vec<String>,
+    /// Only present on the sender's own copy
+    pub bcc_addresses: Vec<String>,
     pub imap_flags: Vec<String>,
     pub size_bytes: Option<u32>,
     pub has_attachments: bool,
+    pub attachments: Vec<AttachmentPart>,
+    /// Set when the message is a delivery status notification (bounce)
+    pub delivery_report: Option<ReportParts>,
     pub gmail_labels: Vec<String>,
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
@@ -33,6 +41,13 @@ pub fn parse_envelope(fetch: &Fetch) -> Option<Envelope> {
         .bodystructure()
         .map(|bs| has_attachments(bs))
         .unwrap_or(false);
+    let attachments = fetch
+        .bodystructure()
+        .map(|bs| find_attachments(bs, &[]))
+        .unwrap_or_default();
+    let delivery_report = fetch
+        .bodystructure()
+        .and_then(|bs| find_delivery_report(bs, &[]));
 
     let gmail_labels: Vec<String> = fetch
         .gmail_labels()
@@ -85,6 +100,7 @@ pub fn parse_envelope(fetch: &Fetch) -> Option<Envelope> {
     // Extract To addresses
     let to_addresses = extract_addresses(&envelope.to);
     let cc_addresses = extract_addresses(&envelope.cc);
+    let bcc_addresses = extract_addresses(&envelope.bcc);
 
     let in_reply_to = envelope
         .in_reply_to
@@ -104,9 +120,12 @@ pub fn parse_envelope(fetch: &Fetch) -> Option<Envelope> {
         from_name,
         to_addresses,
         cc_addresses,
+        bcc_addresses,
         imap_flags: flags,
         size_bytes,
         has_attachments,
+        attachments,
+        delivery_report,
         gmail_labels,
         in_reply_to,
         references: vec![],
@@ -114,7 +133,7 @@ pub fn parse_envelope(fetch: &Fetch) -> Option<Envelope> {
     })
 }
 
-fn decode_rfc2047(input: &str) -> String {
+pub fn decode_rfc2047(input: &str) -> String {
     let fake_header = format!("X: {}", input);
     match mailparse::parse_header(fake_header.as_bytes()) {
         Ok((header, _)) => header.get_value(),
@@ -163,9 +182,14 @@ fn has_attachments(body: &BodyStructure) -> bool {
     }
 }
 
+/// Headers kept for building replies. They are always recorded, empty when
+/// absent, so a message without the keys was synced before they were fetched.
+pub const REPLY_HEADERS: &[&str] = &["reply-to", "mail-followup-to"];
+
 /// Parse raw RFC 2822 header bytes into a map of lowercase header names to values.
 /// Handles line continuation (folding) per RFC 2822 §2.2.3.
 /// Only keeps headers in `CLASSIFICATION_HEADERS`; References is handled separately.
+/// Reply-To and Mail-Followup-To are kept for building replies.
 const CLASSIFICATION_HEADERS: &[&str] = &[
     "list-id",
     "auto-submitted",
@@ -174,6 +198,8 @@ const CLASSIFICATION_HEADERS: &[&str] = &[
     "feedback-id",
     "x-mailer",
     "return-path",
+    "reply-to",
+    "mail-followup-to",
 ];
 
 pub fn parse_classification_headers(raw: &[u8]) -> HashMap<String, String> {
@@ -210,6 +236,12 @@ pub fn parse_classification_headers(raw: &[u8]) -> HashMap<String, String> {
         }
     }
 
+    if !raw.is_empty() {
+        for name in REPLY_HEADERS {
+            result.entry(name.to_string()).or_default();
+        }
+    }
+
     result
 }
 
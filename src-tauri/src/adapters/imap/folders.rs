@@ -150,15 +150,116 @@ const SENT_FOLDER_NAMES: &[&str] = &[
     "απεσταλμένα",
 ];
 
+/// Known Archive folder names, used when the server doesn't advertise \Archive.
+const ARCHIVE_FOLDER_NAMES: &[&str] = &[
+    "archive",          // English, most providers
+    "archives",         // Thunderbird
+    "archiv",           // German, Czech
+    "archivo",          // Spanish
+    "archivio",         // Italian
+    "arquivo",          // Portuguese
+    "archief",          // Dutch
+    "arkiv",            // Swedish, Danish, Norwegian
+    "arkisto",          // Finnish
+    "archiwum",         // Polish
+    "arhivă",           // Romanian
+    "архив",            // Russian
+    "arşiv",            // Turkish
+];
+
+/// Known Trash folder names, used when the server doesn't advertise \Trash.
+const TRASH_FOLDER_NAMES: &[&str] = &[
+    // English
+    "trash",
+    "deleted items",    // Outlook/Exchange
+    "deleted messages", // Apple Mail
+    "bin",
+    // German
+    "papierkorb",
+    "gelöschte elemente",
+    "gelöschte objekte",
+    // French
+    "corbeille",
+    "éléments supprimés",
+    // Spanish
+    "papelera",
+    "elementos eliminados",
+    // Portuguese
+    "lixeira",
+    "lixo",
+    "itens excluídos",
+    // Italian
+    "cestino",
+    // Dutch
+    "prullenbak",
+    "verwijderde items",
+    // Swedish
+    "papperskorgen",
+    "borttaget",
+    // Danish / Norwegian
+    "papirkurv",
+    "slettede elementer",
+    // Finnish
+    "roskakori",
+    // Polish
+    "kosz",
+    // Czech
+    "koš",
+    // Hungarian
+    "törölt elemek",
+    // Romanian
+    "coș de gunoi",
+    // Russian
+    "корзина",
+    // Turkish
+    "çöp kutusu",
+];
+
 /// Find the Sent folder using a 3-tier strategy:
 /// 1. IMAP attribute match (\Sent from RFC 6154) — most reliable
 /// 2. Gmail labels (not needed here — Gmail uses All Mail for sync)
 /// 3. Name-based fallback against known Sent folder names across languages
 pub fn find_sent_folder(folders: &[FolderInfo]) -> Option<String> {
+    find_special_folder(folders, "Sent", SENT_FOLDER_NAMES)
+}
+
+/// Find the Archive folder: \Archive attribute first, then known names.
+pub fn find_archive_folder(folders: &[FolderInfo]) -> Option<String> {
+    find_special_folder(folders, "Archive", ARCHIVE_FOLDER_NAMES)
+}
+
+/// Find the Trash folder: \Trash attribute first, then known names.
+pub fn find_trash_folder(folders: &[FolderInfo]) -> Option<String> {
+    find_special_folder(folders, "Trash", TRASH_FOLDER_NAMES)
+}
+
+fn find_special_folder(folders: &[FolderInfo], attribute: &str, known_names: &[&str]) -> Option<String> {
+    match match_special_folder(folders, attribute, known_names) {
+        Some((name, None)) => {
+            logger::debug(&format!("{} folder found by attribute: {}", attribute, name));
+            Some(name)
+        }
+        Some((name, Some(known))) => {
+            logger::debug(&format!("{} folder found by name fallback: {} (matched '{}')", attribute, name, known));
+            Some(name)
+        }
+        None => {
+            logger::debug(&format!("No {} folder found by attribute or name", attribute));
+            None
+        }
+    }
+}
+
+/// The folder carrying `attribute`, or else the first whose leaf name is one
+/// of `known_names` (returned alongside the matched name).
+fn match_special_folder<'a>(
+    folders: &[FolderInfo],
+    attribute: &str,
+    known_names: &[&'a str],
+) -> Option<(String, Option<&'a str>)> {
     // Tier 1: attribute match
-    if let Some(name) = find_folder_by_attribute(folders, "Sent") {
-        logger::debug(&format!("Sent folder found by attribute: {}", name));
-        return Some(name);
+    if let Some(name) = find_folder_by_attribute(folders, attribute) {
+        return Some((name, None));
     }
 
     // Tier 3: name-based fallback (tier 2 = Gmail labels, not applicable here)
@@ -174,15 +275,11 @@ pub fn find_sent_folder(folders: &[FolderInfo]) -> Option<String> {
             .unwrap_or(&folder.name);
         let leaf_lower = leaf.to_lowercase();
 
-        for known in SENT_FOLDER_NAMES {
-            if leaf_lower == *known {
-                logger::debug(&format!("Sent folder found by name fallback: {} (matched '{}')", folder.name, known));
-                return Some(folder.name.clone());
-            }
+        if let Some(known) = known_names.iter().find(|known| leaf_lower == **known) {
+            return Some((folder.name.clone(), Some(*known)));
         }
     }
 
-    logger::debug("No Sent folder found by attribute or name");
     None
 }
 
@@ -200,4 +297,61 @@ pub fn folders_to_sync(folders: &[FolderInfo], is_gmail: bool) -> Vec<&FolderInf
             !f.attributes.iter().any(|attr| SKIP_ATTRIBUTES.contains(&attr.as_str()))
         }).collect()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(name: &str, attributes: &[&str]) -> FolderInfo {
+        let attributes: Vec<String> = attributes.iter().map(|a| a.to_string()).collect();
+        let priority = FolderInfo::compute_priority(name, &attributes);
+        FolderInfo { name: name.to_string(), attributes, priority }
+    }
+
+    fn archive(folders: &[FolderInfo]) -> Option<String> {
+        match_special_folder(folders, "Archive", ARCHIVE_FOLDER_NAMES).map(|(name, _)| name)
+    }
+
+    fn trash(folders: &[FolderInfo]) -> Option<String> {
+        match_special_folder(folders, "Trash", TRASH_FOLDER_NAMES).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn test_special_use_attribute_wins_over_name() {
+        let folders = vec![
+            folder("INBOX", &[]),
+            folder("INBOX.Archive", &[]),
+            folder("Old Mail", &["Archive"]),
+            folder("Deleted Items", &[]),
+            folder("Bin", &["Trash"]),
+        ];
+        assert_eq!(archive(&folders).as_deref(), Some("Old Mail"));
+        assert_eq!(trash(&folders).as_deref(), Some("Bin"));
+    }
+
+    #[test]
+    fn test_name_fallback_matches_leaf() {
+        let folders = vec![
+            folder("INBOX", &[]),
+            folder("INBOX.Archiv", &[]),
+            folder("INBOX/Papierkorb", &[]),
+            folder("Sent Items", &[]),
+        ];
+        assert_eq!(archive(&folders).as_deref(), Some("INBOX.Archiv"));
+        assert_eq!(
+            match_special_folder(&folders, "Trash", TRASH_FOLDER_NAMES),
+            Some(("INBOX/Papierkorb".to_string(), Some("papierkorb"))),
+        );
+        assert_eq!(
+            match_special_folder(&folders, "Sent", SENT_FOLDER_NAMES).map(|(name, _)| name).as_deref(),
+            Some("Sent Items"),
+        );
+    }
+
+    #[test]
+    fn test_no_match() {
+        let folders = vec![folder("INBOX", &[]), folder("Projects", &[])];
+        assert_eq!(archive(&folders), None);
+        assert_eq!(trash(&folders), None);
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use async_imap::types::Fetch;
 use futures::StreamExt;
-use imap_proto::types::{BodyStructure, ContentEncoding, SectionPath};
+use imap_proto::types::{BodyContentCommon, BodyStructure, ContentEncoding, SectionPath};
 use crate::services::logger;
 
 use super::connection::ImapConnection;
@@ -32,6 +32,10 @@ pub async fn collect_tolerant<E: std::fmt::Display>(
 }
 
 
+/// A message's text body to fetch in round trip 3:
+/// (uid, part path, is_html, transfer encoding, charset).
+pub type TextPart = (u32, Vec<u32>, bool, String, Option<String>);
+
 // ---------------------------------------------------------------------------
 // Historical fetch
 // ---------------------------------------------------------------------------
@@ -92,7 +96,7 @@ where
             .collect::<Vec<_>>()
             .join(",");
 
-        let fetch_query = if conn.has_gmail_ext {
+        let fetch_query = if conn.caps.gmail_ext {
             "(UID FLAGS ENVELOPE BODYSTRUCTURE X-GM-LABELS)"
         } else {
             "(UID FLAGS ENVELOPE BODYSTRUCTURE)"
@@ -108,17 +112,17 @@ where
         ).await;
 
         let mut envelopes: Vec<Envelope> = Vec::new();
-        let mut text_parts: Vec<(u32, Vec<u32>, bool, String)> = Vec::new();
+        let mut text_parts: Vec<TextPart> = Vec::new();
 
         for fetch in &fetches {
             if let Some(env) = parse_envelope(fetch) {
                 envelopes.push(env);
             }
             if let (Some(uid), Some(bs)) = (fetch.uid, fetch.bodystructure()) {
-                if let Some((part, encoding)) = find_mime_part(bs, &[], "plain") {
-                    text_parts.push((uid, part, false, encoding_to_string(encoding)));
-                } else if let Some((part, encoding)) = find_mime_part(bs, &[], "html") {
-                    text_parts.push((uid, part, true, encoding_to_string(encoding)));
+                if let Some((part, encoding, charset)) = find_mime_part(bs, &[], "plain") {
+                    text_parts.push((uid, part, false, encoding_to_string(encoding), charset));
+                } else if let Some((part, encoding, charset)) = find_mime_part(bs, &[], "html") {
+                    text_parts.push((uid, part, true, encoding_to_string(encoding), charset));
                 }
             }
         }
@@ -126,7 +130,7 @@ where
         // Round trip 2: References + classification headers (once per batch, full uid_list)
         let refs_fetches = collect_tolerant(
             conn.session
-                .uid_fetch(&uid_list, "(UID BODY.PEEK[HEADER.FIELDS (References List-Id Auto-Submitted List-Unsubscribe Precedence Feedback-ID X-Mailer Return-Path)])")
+                .uid_fetch(&uid_list, "(UID BODY.PEEK[HEADER.FIELDS (References List-Id Auto-Submitted List-Unsubscribe Precedence Feedback-ID X-Mailer Return-Path Reply-To Mail-Followup-To)])")
                 .await
                 .map_err(|e| EddieError::Backend(format!("FETCH refs failed: {}", e)))?,
             &format!("references in {}", folder),
@@ -149,13 +153,17 @@ where
         let mut bodies: Vec<(u32, String, bool)> = Vec::new();
         let mut uid_is_html: HashMap<u32, bool> = HashMap::new();
         let mut uid_encoding: HashMap<u32, String> = HashMap::new();
+        let mut uid_charset: HashMap<u32, String> = HashMap::new();
 
         if !text_parts.is_empty() {
             let mut by_part: HashMap<Vec<u32>, Vec<u32>> = HashMap::new();
-            for (uid, part, is_html, encoding) in &text_parts {
+            for (uid, part, is_html, encoding, charset) in &text_parts {
                 by_part.entry(part.clone()).or_default().push(*uid);
                 uid_is_html.insert(*uid, *is_html);
                 uid_encoding.insert(*uid, encoding.clone());
+                if let Some(charset) = charset {
+                    uid_charset.insert(*uid, charset.clone());
+                }
             }
 
             for (part, part_uids) in &by_part {
@@ -181,7 +189,8 @@ where
                     if let Some(uid) = fetch.uid {
                         if let Some(section_data) = fetch.section(&path) {
                             let encoding = uid_encoding.get(&uid).cloned().unwrap_or_default();
-                            let decoded = decode_body(section_data, &encoding)?;
+                            let charset = uid_charset.get(&uid).map(String::as_str);
+                            let decoded = decode_body(section_data, &encoding, charset)?;
 
                             let is_html = uid_is_html.get(&uid).copied().unwrap_or(false);
                             bodies.push((uid, decoded, is_html));
@@ -205,20 +214,80 @@ where
     Ok(total)
 }
 
+// ---------------------------------------------------------------------------
+// Message-ID scan
+// ---------------------------------------------------------------------------
+
+/// Fetch (UID, Message-ID) for the messages in `uid_set` of the selected folder.
+/// Used to re-map cached messages after a UIDVALIDITY change.
+pub async fn fetch_message_ids(conn: &mut ImapConnection, folder: &str, uid_set: &str) -> Result<Vec<(u32, String)>, EddieError> {
+    let fetches = collect_tolerant(
+        conn.session
+            .uid_fetch(uid_set, "(UID ENVELOPE)")
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH message ids failed: {}", e)))?,
+        &format!("message ids in {}", folder),
+    ).await;
+
+    Ok(fetches.iter()
+        .filter_map(parse_envelope)
+        .map(|env| (env.uid, env.message_id))
+        .collect())
+}
+
+/// Find which of `message_ids` are in the selected folder.
+/// Returns (UID, Message-ID) for each one found.
+pub async fn search_message_ids(
+    conn: &mut ImapConnection,
+    folder: &str,
+    message_ids: &[String],
+) -> Result<Vec<(u32, String)>, EddieError> {
+    let searchable: Vec<&String> = message_ids.iter()
+        .filter(|id| !id.is_empty() && !id.contains(['"', '\\']))
+        .collect();
+
+    let mut uids: Vec<u32> = Vec::new();
+    for chunk in searchable.chunks(20) {
+        // OR is binary: OR a OR b c
+        let mut query = format!("HEADER Message-ID \"<{}>\"", chunk[chunk.len() - 1]);
+        for id in chunk[..chunk.len() - 1].iter().rev() {
+            query = format!("OR HEADER Message-ID \"<{}>\" {}", id, query);
+        }
+        let found = conn.session
+            .uid_search(&query)
+            .await
+            .map_err(|e| EddieError::Backend(format!("SEARCH Message-ID failed: {}", e)))?;
+        uids.extend(found);
+    }
+
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let uid_set = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
+    // HEADER search is a substring match — confirm against the parsed envelope
+    Ok(fetch_message_ids(conn, folder, &uid_set).await?
+        .into_iter()
+        .filter(|(_, id)| message_ids.contains(id))
+        .collect())
+}
+
 // ---------------------------------------------------------------------------
 // MIME part helpers
 // ---------------------------------------------------------------------------
 
+/// Find the first `text/<subtype>` part. Returns its part path, transfer
+/// encoding, and the Content-Type `charset` parameter if there is one.
 pub fn find_mime_part<'a>(
     body: &'a BodyStructure<'a>,
     prefix: &[u32],
     subtype: &str,
-) -> Option<(Vec<u32>, &'a ContentEncoding<'a>)> {
+) -> Option<(Vec<u32>, &'a ContentEncoding<'a>, Option<String>)> {
     match body {
         BodyStructure::Text { common, other, .. } => {
             if common.ty.subtype.to_lowercase() == subtype {
                 let path = if prefix.is_empty() { vec![1] } else { prefix.to_vec() };
-                Some((path, &other.transfer_encoding))
+                Some((path, &other.transfer_encoding, charset_param(common)))
             } else {
                 None
             }
@@ -231,7 +300,7 @@ pub fn find_mime_part<'a>(
             );
             if mime == format!("text/{}", subtype) {
                 let path = if prefix.is_empty() { vec![1] } else { prefix.to_vec() };
-                Some((path, &other.transfer_encoding))
+                Some((path, &other.transfer_encoding, charset_param(common)))
             } else {
                 None
             }
@@ -253,6 +322,14 @@ pub fn find_mime_part<'a>(
     }
 }
 
+fn charset_param(common: &BodyContentCommon<'_>) -> Option<String> {
+    common.ty.params.as_ref()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("charset"))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+}
+
 /// Find all inline image parts in a BODYSTRUCTURE tree.
 /// Returns Vec<(part_path, content_id, mime_type, encoding)>.
 pub fn find_inline_images(
@@ -315,8 +392,54 @@ pub fn part_to_section_path(part: &[u32]) -> SectionPath {
     SectionPath::Part(part.to_vec(), None)
 }
 
-pub fn decode_body(raw: &[u8], encoding: &str) -> Result<String, EddieError> {
-    let bytes = match encoding {
+/// Decode a text or HTML body part to UTF-8: undo the transfer encoding, then
+/// transcode from `charset` (the Content-Type parameter). HTML parts without
+/// one may still declare it in a `<meta>` tag.
+pub fn decode_body(raw: &[u8], encoding: &str, charset: Option<&str>) -> Result<String, EddieError> {
+    let bytes = decode_transfer_encoding(raw, encoding);
+    let meta = if charset.is_none() { meta_charset(&bytes) } else { None };
+    Ok(decode_charset(&bytes, charset.or(meta.as_deref())))
+}
+
+/// Transcode bytes in a MIME charset to UTF-8. Without a charset we can
+/// use, bytes that are valid UTF-8 are taken as such and anything else is
+/// read as Windows-1252, the usual unlabelled legacy encoding.
+pub fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    // Mislabelled "us-ascii" mail is usually UTF-8
+    let charset = charset.filter(|label| !label.trim().eq_ignore_ascii_case("us-ascii"));
+    let encoding = charset.and_then(|label| encoding_rs::Encoding::for_label(label.trim().as_bytes()));
+    match encoding {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned(),
+        },
+    }
+}
+
+/// `<meta charset="...">` or `<meta http-equiv="Content-Type" content="...; charset=...">`
+/// near the start of an HTML part.
+fn meta_charset(html: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&html[..html.len().min(2048)]).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(pos) = rest.find("<meta") {
+        rest = &rest[pos + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        if let Some(at) = tag.find("charset=") {
+            let value = tag[at + 8..].trim_start_matches(['"', '\'']);
+            let end = value.find(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace())
+                .unwrap_or(value.len());
+            if end > 0 {
+                return Some(value[..end].to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Undo a part's Content-Transfer-Encoding. Undecodable input is returned as-is.
+pub fn decode_transfer_encoding(raw: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
         "quoted-printable" => {
             quoted_printable::decode(raw, quoted_printable::ParseMode::Robust)
                 .unwrap_or_else(|_| raw.to_vec())
@@ -332,6 +455,53 @@ pub fn decode_body(raw: &[u8], encoding: &str) -> Result<String, EddieError> {
                 .unwrap_or_else(|_| raw.to_vec())
         }
         _ => raw.to_vec(),
-    };
-    Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_legacy_charsets() {
+        let cases: &[(&[u8], &str, &str)] = &[
+            (b"Gr\xfc\xdfe aus K\xf6ln", "iso-8859-1", "Grüße aus Köln"),
+            (b"\x805 \x96 \x93quoted\x94", "windows-1252", "€5 – “quoted”"),
+            (b"\xc7a va?", "ISO-8859-15", "Ça va?"),
+            (b"\x1b$BF|K\\8l$N%a!<%k\x1b(B", "iso-2022-jp", "日本語のメール"),
+            (b"\xf0\xd2\xc9\xd7\xc5\xd4, \xcd\xc9\xd2", "koi8-r", "Привет, мир"),
+            (b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd", "Shift_JIS", "こんにちは"),
+            (b"\xc4\xe3\xba\xc3", "gb2312", "你好"),
+        ];
+        for (raw, charset, expected) in cases {
+            assert_eq!(decode_body(raw, "8bit", Some(charset)).unwrap(), *expected, "{}", charset);
+        }
+    }
+
+    #[test]
+    fn test_transfer_encoding_then_charset() {
+        assert_eq!(decode_body(b"Gr=FC=DFe", "quoted-printable", Some("iso-8859-1")).unwrap(), "Grüße");
+        assert_eq!(decode_body(b"8NLJ18XU", "base64", Some("koi8-r")).unwrap(), "Привет");
+    }
+
+    #[test]
+    fn test_missing_or_unknown_charset() {
+        assert_eq!(decode_body("Grüße".as_bytes(), "8bit", None).unwrap(), "Grüße");
+        assert_eq!(decode_body(b"Gr\xfc\xdfe", "8bit", None).unwrap(), "Grüße");
+        assert_eq!(decode_body(b"Gr\xfc\xdfe", "8bit", Some("x-unknown")).unwrap(), "Grüße");
+        assert_eq!(decode_body("Grüße".as_bytes(), "8bit", Some("us-ascii")).unwrap(), "Grüße");
+    }
+
+    #[test]
+    fn test_html_meta_charset() {
+        let html = b"<html><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=koi8-r\"></head><body>\xf0\xd2\xc9\xd7\xc5\xd4</body></html>";
+        assert!(decode_body(html, "8bit", None).unwrap().contains("<body>Привет</body>"));
+
+        let html = b"<meta charset='iso-8859-1'><p>K\xf6ln</p>";
+        assert!(decode_body(html, "8bit", None).unwrap().contains("Köln"));
+
+        // The Content-Type parameter wins over the meta tag
+        let html = b"<meta charset=\"iso-8859-1\"><p>K\xc3\xb6ln</p>";
+        assert!(decode_body(html, "8bit", Some("utf-8")).unwrap().contains("Köln"));
+    }
 }
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use async_imap::extensions::idle::IdleResponse;
+use async_imap::imap_proto::{MailboxDatum, Response};
+use async_imap::types::UnsolicitedResponse;
+
+use super::connection::{ImapConnection, ImapSession};
+use crate::error::EddieError;
+
+/// Servers may drop an IDLE client after 30 minutes of inactivity (RFC 2177 §3),
+/// so IDLE is re-issued slightly before that.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(29 * 60);
+
+#[derive(Debug, PartialEq)]
+pub enum IdleEvent {
+    /// The selected mailbox changed (EXISTS, EXPUNGE, FETCH or VANISHED).
+    Changed,
+    /// Nothing relevant happened before the timeout — re-IDLE.
+    Timeout,
+}
+
+/// Run one IDLE cycle on the currently selected mailbox.
+///
+/// IDLE takes ownership of the session, so the connection is handed back
+/// together with the outcome. Responses that don't change the mailbox
+/// contents (keepalive OKs, RECENT, FLAGS) keep the IDLE running until `timeout`.
+pub async fn idle_once(
+    conn: ImapConnection,
+    timeout: Duration,
+) -> Result<(ImapConnection, IdleEvent), EddieError> {
+    let ImapConnection { mut session, caps, write_mode, modseq } = conn;
+
+    // Changes reported before IDLE started were queued as unsolicited responses.
+    if drain_unsolicited(&mut session) {
+        let conn = ImapConnection { session, caps, write_mode, modseq };
+        return Ok((conn, IdleEvent::Changed));
+    }
+
+    let mut handle = session.idle();
+    handle.init()
+        .await
+        .map_err(|e| EddieError::Backend(format!("IDLE failed: {}", e)))?;
+
+    let started = Instant::now();
+    let event = loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            break IdleEvent::Timeout;
+        }
+
+        // The stop source must stay alive while waiting — dropping it interrupts the IDLE.
+        let (wait, _stop) = handle.wait_with_timeout(remaining);
+        match wait.await.map_err(|e| EddieError::Backend(format!("IDLE wait failed: {}", e)))? {
+            IdleResponse::NewData(data) if is_mailbox_change(data.parsed()) => break IdleEvent::Changed,
+            IdleResponse::NewData(_) => continue,
+            IdleResponse::Timeout | IdleResponse::ManualInterrupt => break IdleEvent::Timeout,
+        }
+    };
+
+    let session = handle.done()
+        .await
+        .map_err(|e| EddieError::Backend(format!("IDLE DONE failed: {}", e)))?;
+
+    Ok((ImapConnection { session, caps, write_mode, modseq }, event))
+}
+
+fn is_mailbox_change(response: &Response<'_>) -> bool {
+    matches!(
+        response,
+        Response::MailboxData(MailboxDatum::Exists(_))
+            | Response::Expunge(_)
+            | Response::Vanished { .. }
+            | Response::Fetch(..)
+    )
+}
+
+/// Empty the unsolicited response queue. Returns true if any of the
+/// queued responses reported a mailbox change.
+fn drain_unsolicited(session: &mut ImapSession) -> bool {
+    let mut changed = false;
+    while let Ok(response) = session.unsolicited_responses.try_recv() {
+        changed |= match response {
+            UnsolicitedResponse::Exists(_) | UnsolicitedResponse::Expunge(_) => true,
+            UnsolicitedResponse::Other(data) => is_mailbox_change(data.parsed()),
+            _ => false,
+        };
+    }
+    changed
+}
@@ -1,5 +1,8 @@
+pub mod attachments;
 pub mod connection;
+pub mod dsn;
 pub mod envelopes;
 pub mod folders;
+pub mod idle;
 pub mod sent_scan;
 pub mod historical;
\ No newline at end of file
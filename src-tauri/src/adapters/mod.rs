@@ -1,3 +1,4 @@
 pub mod sqlite;
 pub mod imap;
 pub mod smtp;
+pub mod oauth;
@@ -0,0 +1,8 @@
+mod sasl;
+mod token;
+
+pub use sasl::{SaslAuthenticator, SaslMechanism};
+pub use token::{
+    Pkce, TokenSet,
+    authorization_url, exchange_code, provider, provider_for_domain, refresh, wait_for_redirect,
+};
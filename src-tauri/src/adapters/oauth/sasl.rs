@@ -0,0 +1,54 @@
+/// SASL mechanism used to present an OAuth2 access token to IMAP/SMTP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaslMechanism {
+    /// Google/Microsoft's XOAUTH2
+    XOAuth2,
+    /// OAUTHBEARER (RFC 7628)
+    OAuthBearer,
+}
+
+impl SaslMechanism {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaslMechanism::XOAuth2 => "XOAUTH2",
+            SaslMechanism::OAuthBearer => "OAUTHBEARER",
+        }
+    }
+
+    /// The client's initial response (before base64 encoding).
+    pub fn initial_response(&self, user: &str, host: &str, port: u16, access_token: &str) -> String {
+        match self {
+            SaslMechanism::XOAuth2 => format!("user={}\x01auth=Bearer {}\x01\x01", user, access_token),
+            SaslMechanism::OAuthBearer => format!(
+                "n,a={},\x01host={}\x01port={}\x01auth=Bearer {}\x01\x01",
+                user.replace('=', "=3D").replace(',', "=2C"), host, port, access_token
+            ),
+        }
+    }
+}
+
+/// async-imap authenticator that sends the initial response once. If the
+/// server rejects the token it sends a second challenge with error details,
+/// which must be answered with an empty response to end the exchange.
+pub struct SaslAuthenticator {
+    response: String,
+    sent: bool,
+}
+
+impl SaslAuthenticator {
+    pub fn new(response: String) -> Self {
+        Self { response, sent: false }
+    }
+}
+
+impl async_imap::Authenticator for SaslAuthenticator {
+    type Response = String;
+
+    fn process(&mut self, _challenge: &[u8]) -> String {
+        if self.sent {
+            return String::new();
+        }
+        self.sent = true;
+        self.response.clone()
+    }
+}
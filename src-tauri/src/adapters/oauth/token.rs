@@ -0,0 +1,403 @@
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use super::sasl::SaslMechanism;
+use crate::error::EddieError;
+
+/// Treat tokens as expired this long before the provider says they are,
+/// so a token doesn't lapse between refresh and LOGIN.
+const EXPIRY_MARGIN_MS: i64 = 60_000;
+
+/// An OAuth2 provider's endpoints and client registration.
+#[derive(Debug, Clone)]
+pub struct OAuthProvider {
+    /// Key stored in `accounts.oauth_provider` ("google", "microsoft")
+    pub id: String,
+    pub auth_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    /// Installed-app "secret" (Google requires one even with PKCE)
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    /// Provider-specific authorization parameters (e.g. Google's `access_type=offline`)
+    pub extra_auth_params: Vec<(String, String)>,
+    pub imap_mechanism: SaslMechanism,
+}
+
+/// Look up a built-in provider by id. Client IDs are supplied at build time
+/// via `EDDIE_GOOGLE_CLIENT_ID` / `EDDIE_MICROSOFT_CLIENT_ID`.
+pub fn provider(id: &str) -> Result<OAuthProvider, EddieError> {
+    let missing = |var: &str| EddieError::Config(format!("OAuth client ID not configured ({})", var));
+    match id {
+        "google" => Ok(OAuthProvider {
+            id: id.to_string(),
+            auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
+            token_url: "https://oauth2.googleapis.com/token".to_string(),
+            client_id: option_env!("EDDIE_GOOGLE_CLIENT_ID")
+                .ok_or_else(|| missing("EDDIE_GOOGLE_CLIENT_ID"))?
+                .to_string(),
+            client_secret: option_env!("EDDIE_GOOGLE_CLIENT_SECRET").map(|s| s.to_string()),
+            scopes: vec!["https://mail.google.com/".to_string()],
+            extra_auth_params: vec![
+                ("access_type".to_string(), "offline".to_string()),
+                ("prompt".to_string(), "consent".to_string()),
+            ],
+            imap_mechanism: SaslMechanism::OAuthBearer,
+        }),
+        "microsoft" => Ok(OAuthProvider {
+            id: id.to_string(),
+            auth_url: "https://login.microsoftonline.com/common/oauth2/v2.0/authorize".to_string(),
+            token_url: "https://login.microsoftonline.com/common/oauth2/v2.0/token".to_string(),
+            client_id: option_env!("EDDIE_MICROSOFT_CLIENT_ID")
+                .ok_or_else(|| missing("EDDIE_MICROSOFT_CLIENT_ID"))?
+                .to_string(),
+            client_secret: None,
+            scopes: vec![
+                "https://outlook.office.com/IMAP.AccessAsUser.All".to_string(),
+                "https://outlook.office.com/SMTP.Send".to_string(),
+                "offline_access".to_string(),
+            ],
+            extra_auth_params: vec![],
+            // Exchange Online only speaks XOAUTH2
+            imap_mechanism: SaslMechanism::XOAuth2,
+        }),
+        _ => Err(EddieError::InvalidInput(format!("Unknown OAuth provider: {}", id))),
+    }
+}
+
+/// Map an autodiscovery `provider_id` to the OAuth provider that serves it.
+pub fn provider_for_domain(provider_id: &str) -> Option<&'static str> {
+    match provider_id {
+        "gmail.com" => Some("google"),
+        "outlook.com" => Some("microsoft"),
+        _ => None,
+    }
+}
+
+/// PKCE verifier/challenge pair (RFC 7636, S256).
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        // 64 hex chars — within the 43..128 unreserved-character range
+        Self::from_verifier(format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()))
+    }
+
+    fn from_verifier(verifier: String) -> Self {
+        let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(Sha256::digest(verifier.as_bytes()));
+        Self { verifier, challenge }
+    }
+}
+
+/// Build the URL the user opens in their browser to grant access.
+pub fn authorization_url(
+    provider: &OAuthProvider,
+    redirect_uri: &str,
+    state: &str,
+    pkce: &Pkce,
+    login_hint: Option<&str>,
+) -> Result<String, EddieError> {
+    let scope = provider.scopes.join(" ");
+    let mut params: Vec<(&str, &str)> = vec![
+        ("response_type", "code"),
+        ("client_id", &provider.client_id),
+        ("redirect_uri", redirect_uri),
+        ("scope", &scope),
+        ("state", state),
+        ("code_challenge", &pkce.challenge),
+        ("code_challenge_method", "S256"),
+    ];
+    if let Some(hint) = login_hint {
+        params.push(("login_hint", hint));
+    }
+    for (key, value) in &provider.extra_auth_params {
+        params.push((key, value));
+    }
+
+    let url = reqwest::Url::parse_with_params(&provider.auth_url, &params)
+        .map_err(|e| EddieError::Config(format!("Invalid authorization URL: {}", e)))?;
+    Ok(url.to_string())
+}
+
+/// Tokens returned by the provider. `expires_at` is epoch milliseconds.
+#[derive(Debug, Clone)]
+pub struct TokenSet {
+    pub access_token: String,
+    /// Absent when the provider didn't rotate the refresh token
+    pub refresh_token: Option<String>,
+    pub expires_at: i64,
+}
+
+impl TokenSet {
+    pub fn is_expired(&self, now_ms: i64) -> bool {
+        self.expires_at - EXPIRY_MARGIN_MS <= now_ms
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Redeem an authorization code for tokens.
+pub async fn exchange_code(
+    provider: &OAuthProvider,
+    code: &str,
+    verifier: &str,
+    redirect_uri: &str,
+) -> Result<TokenSet, EddieError> {
+    token_request(provider, &[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("code_verifier", verifier),
+        ("redirect_uri", redirect_uri),
+    ]).await
+}
+
+/// Get a fresh access token. Returns `ReauthRequired` if the refresh token
+/// was revoked or has expired (`invalid_grant`).
+pub async fn refresh(provider: &OAuthProvider, refresh_token: &str) -> Result<TokenSet, EddieError> {
+    token_request(provider, &[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+    ]).await
+}
+
+async fn token_request(provider: &OAuthProvider, grant: &[(&str, &str)]) -> Result<TokenSet, EddieError> {
+    let mut form: Vec<(&str, &str)> = grant.to_vec();
+    form.push(("client_id", &provider.client_id));
+    if let Some(ref secret) = provider.client_secret {
+        form.push(("client_secret", secret));
+    }
+
+    let response = reqwest::Client::new()
+        .post(&provider.token_url)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| EddieError::Backend(format!("Token request failed: {}", e)))?;
+    let status = response.status();
+    let body = response.text()
+        .await
+        .map_err(|e| EddieError::Backend(format!("Token response unreadable: {}", e)))?;
+
+    if !status.is_success() {
+        return Err(match serde_json::from_str::<TokenErrorResponse>(&body) {
+            Ok(err) if err.error == "invalid_grant" => EddieError::ReauthRequired(
+                err.error_description.unwrap_or(err.error)
+            ),
+            Ok(err) => EddieError::Backend(format!(
+                "Token endpoint error: {} {}", err.error, err.error_description.unwrap_or_default()
+            )),
+            Err(_) => EddieError::Backend(format!("Token endpoint returned {}", status)),
+        });
+    }
+
+    let token: TokenResponse = serde_json::from_str(&body)
+        .map_err(|e| EddieError::Backend(format!("Invalid token response: {}", e)))?;
+
+    Ok(TokenSet {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: chrono::Utc::now().timestamp_millis() + token.expires_in.unwrap_or(3600) * 1000,
+    })
+}
+
+/// Accept the browser's redirect on the loopback listener and return the
+/// authorization code. Rejects a response whose `state` doesn't match.
+pub async fn wait_for_redirect(listener: &TcpListener, state: &str) -> Result<String, EddieError> {
+    loop {
+        let (mut stream, _) = listener.accept()
+            .await
+            .map_err(|e| EddieError::Backend(format!("OAuth redirect listener failed: {}", e)))?;
+
+        // Only the request line matters; 8 KB covers any browser's headers
+        let mut buf = vec![0u8; 8192];
+        let mut len = 0;
+        while len < buf.len() {
+            let n = stream.read(&mut buf[len..]).await.unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            len += n;
+            if buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+        }
+        let request = String::from_utf8_lossy(&buf[..len]);
+        let path = request.lines().next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("/");
+
+        let url = reqwest::Url::parse(&format!("http://localhost{}", path))
+            .map_err(|e| EddieError::Backend(format!("Invalid OAuth redirect: {}", e)))?;
+        let param = |name: &str| url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned());
+
+        // Browsers also ask for /favicon.ico — ignore anything without a result
+        let (code, error) = (param("code"), param("error"));
+        if code.is_none() && error.is_none() {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await;
+            continue;
+        }
+
+        let body = "<html><body>You can close this window and return to Eddie.</body></html>";
+        let _ = stream.write_all(format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(), body
+        ).as_bytes()).await;
+
+        if let Some(error) = error {
+            return Err(EddieError::Backend(format!("Authorization denied: {}", error)));
+        }
+        if param("state").as_deref() != Some(state) {
+            return Err(EddieError::Backend("OAuth state mismatch".into()));
+        }
+        return Ok(code.unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve one canned HTTP response and hand back the request it received.
+    async fn stand_in_endpoint(status: &'static str, body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let mut len = 0;
+            loop {
+                let n = stream.read(&mut buf[len..]).await.unwrap();
+                len += n;
+                let text = String::from_utf8_lossy(&buf[..len]).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let content_length = text.lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if len >= end + 4 + content_length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, body.len(), body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..len]).to_string()
+        });
+        (url, handle)
+    }
+
+    fn test_provider(token_url: String) -> OAuthProvider {
+        OAuthProvider {
+            id: "test".to_string(),
+            auth_url: "https://auth.example.com/authorize".to_string(),
+            token_url,
+            client_id: "client-123".to_string(),
+            client_secret: None,
+            scopes: vec!["mail".to_string(), "offline_access".to_string()],
+            extra_auth_params: vec![],
+            imap_mechanism: SaslMechanism::XOAuth2,
+        }
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        let pkce = Pkce::from_verifier("eddie-pkce-verifier-0123456789-abcdefghijklmnopqrstuvwxyz".to_string());
+        assert_eq!(pkce.challenge, "yJQmYAKZ1oq4p69hSaimJAIc9sb73ermhnlJn9RPxRo");
+
+        let generated = Pkce::generate();
+        assert_eq!(generated.verifier.len(), 64);
+        assert_eq!(generated.challenge.len(), 43);
+        assert!(!generated.challenge.contains(['=', '+', '/']));
+    }
+
+    #[test]
+    fn test_authorization_url_params() {
+        let provider = test_provider("https://auth.example.com/token".to_string());
+        let pkce = Pkce::generate();
+        let url = authorization_url(&provider, "http://127.0.0.1:4567/", "st", &pkce, Some("a@b.com")).unwrap();
+        let parsed = reqwest::Url::parse(&url).unwrap();
+        let get = |k: &str| parsed.query_pairs().find(|(key, _)| key == k).map(|(_, v)| v.into_owned());
+        assert_eq!(get("code_challenge").as_deref(), Some(pkce.challenge.as_str()));
+        assert_eq!(get("code_challenge_method").as_deref(), Some("S256"));
+        assert_eq!(get("scope").as_deref(), Some("mail offline_access"));
+        assert_eq!(get("login_hint").as_deref(), Some("a@b.com"));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code() {
+        let (url, request) = stand_in_endpoint(
+            "200 OK",
+            r#"{"access_token":"at-1","refresh_token":"rt-1","expires_in":3600,"token_type":"Bearer"}"#,
+        ).await;
+
+        let tokens = exchange_code(&test_provider(url), "code-xyz", "verifier-abc", "http://127.0.0.1:1/").await.unwrap();
+        assert_eq!(tokens.access_token, "at-1");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("rt-1"));
+        assert!(!tokens.is_expired(chrono::Utc::now().timestamp_millis()));
+
+        let request = request.await.unwrap();
+        assert!(request.contains("grant_type=authorization_code"));
+        assert!(request.contains("code_verifier=verifier-abc"));
+        assert!(request.contains("client_id=client-123"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_without_rotation() {
+        let (url, _) = stand_in_endpoint("200 OK", r#"{"access_token":"at-2","expires_in":60}"#).await;
+        let tokens = refresh(&test_provider(url), "rt-1").await.unwrap();
+        assert_eq!(tokens.access_token, "at-2");
+        assert!(tokens.refresh_token.is_none());
+        // 60s lifetime is inside the expiry margin
+        assert!(tokens.is_expired(chrono::Utc::now().timestamp_millis()));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_invalid_grant_requires_reauth() {
+        let (url, _) = stand_in_endpoint(
+            "400 Bad Request",
+            r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#,
+        ).await;
+        match refresh(&test_provider(url), "rt-old").await {
+            Err(EddieError::ReauthRequired(msg)) => assert!(msg.contains("revoked")),
+            other => panic!("expected ReauthRequired, got {:?}", other.map(|t| t.access_token)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_redirect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut favicon = tokio::net::TcpStream::connect(addr).await.unwrap();
+            favicon.write_all(b"GET /favicon.ico HTTP/1.1\r\nHost: x\r\n\r\n").await.unwrap();
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"GET /?code=abc%2F123&state=s1 HTTP/1.1\r\nHost: x\r\n\r\n").await.unwrap();
+            let mut out = Vec::new();
+            let _ = stream.read_to_end(&mut out).await;
+        });
+        assert_eq!(wait_for_redirect(&listener, "s1").await.unwrap(), "abc/123");
+    }
+}
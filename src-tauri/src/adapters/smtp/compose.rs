@@ -0,0 +1,331 @@
+//! Rendering of composed Markdown into the two bodies of an outgoing
+//! message: sanitized HTML and format=flowed plain text (RFC 3676).
+
+/// Width plain-text lines are wrapped at, before the trailing soft-break space.
+const FLOWED_WIDTH: usize = 76;
+
+/// URL schemes allowed in links; images may only use `cid:` and https.
+const LINK_SCHEMES: &[&str] = &["http://", "https://", "mailto:", "cid:"];
+const IMAGE_SCHEMES: &[&str] = &["cid:", "https://"];
+
+/// Render the Markdown subset used in compose to HTML: paragraphs (single
+/// newlines are line breaks), headings, block quotes, lists, fenced code,
+/// emphasis, inline code, links, `cid:` images and bare URLs. All input
+/// text is escaped, so no HTML from the source reaches the output.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let lines: Vec<&str> = markdown.lines().collect();
+    render_blocks(&lines)
+}
+
+fn render_blocks(lines: &[&str]) -> String {
+    let mut html = String::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            i += 1;
+        } else if trimmed.starts_with("```") {
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with("```") {
+                code.push(lines[i]);
+                i += 1;
+            }
+            i += 1; // closing fence
+            html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&code.join("\n"))));
+        } else if let Some((level, text)) = heading(trimmed) {
+            html.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline(text)));
+            i += 1;
+        } else if trimmed.starts_with('>') {
+            let mut quoted = Vec::new();
+            while i < lines.len() && lines[i].trim_start().starts_with('>') {
+                let rest = &lines[i].trim_start()[1..];
+                quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                i += 1;
+            }
+            html.push_str(&format!("<blockquote>\n{}</blockquote>\n", render_blocks(&quoted)));
+        } else if let Some(ordered) = list_item(trimmed).map(|(ordered, _)| ordered) {
+            let tag = if ordered { "ol" } else { "ul" };
+            html.push_str(&format!("<{}>\n", tag));
+            while i < lines.len() {
+                match list_item(lines[i].trim_start()) {
+                    Some((o, text)) if o == ordered => {
+                        html.push_str(&format!("<li>{}</li>\n", inline(text)));
+                        i += 1;
+                    }
+                    _ => break,
+                }
+            }
+            html.push_str(&format!("</{}>\n", tag));
+        } else {
+            let mut paragraph = Vec::new();
+            while i < lines.len() && starts_paragraph_line(lines[i]) {
+                paragraph.push(inline(lines[i].trim()));
+                i += 1;
+            }
+            html.push_str(&format!("<p>{}</p>\n", paragraph.join("<br>\n")));
+        }
+    }
+    html
+}
+
+/// A line that continues the current paragraph rather than starting a block.
+fn starts_paragraph_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty()
+        && !trimmed.starts_with("```")
+        && !trimmed.starts_with('>')
+        && heading(trimmed).is_none()
+        && list_item(trimmed).is_none()
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+        Some((level, line[level..].trim()))
+    } else {
+        None
+    }
+}
+
+/// `- item`, `* item`, `+ item` (unordered) or `1. item` (ordered).
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(text) = line.strip_prefix(marker) {
+            return Some((false, text));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some((true, &line[digits + 2..]));
+    }
+    None
+}
+
+/// Render inline Markdown in one line of text.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                out.push_str(&format!("<code>{}</code>", escape(&rest[1..1 + end])));
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        if let Some((marker, tag)) = [("**", "strong"), ("__", "strong"), ("~~", "del"), ("*", "em"), ("_", "em")]
+            .into_iter()
+            .find(|(marker, _)| rest.starts_with(marker))
+        {
+            if let Some(inner) = delimited(rest, marker, &out) {
+                out.push_str(&format!("<{0}>{1}</{0}>", tag, inline(inner)));
+                rest = &rest[inner.len() + 2 * marker.len()..];
+                continue;
+            }
+        }
+        if c == '!' || c == '[' {
+            if let Some((label, url, consumed)) = link(if c == '!' { &rest[1..] } else { rest }) {
+                let consumed = consumed + usize::from(c == '!');
+                if c == '!' && IMAGE_SCHEMES.iter().any(|s| url.starts_with(s)) {
+                    out.push_str(&format!("<img src=\"{}\" alt=\"{}\">", escape(url), escape(label)));
+                    rest = &rest[consumed..];
+                    continue;
+                }
+                if c == '[' && LINK_SCHEMES.iter().any(|s| url.starts_with(s)) {
+                    out.push_str(&format!("<a href=\"{}\">{}</a>", escape(url), inline(label)));
+                    rest = &rest[consumed..];
+                    continue;
+                }
+            }
+        }
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            // Trailing punctuation usually ends the sentence, not the URL
+            let url = rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            out.push_str(&format!("<a href=\"{0}\">{0}</a>", escape(url)));
+            rest = &rest[url.len()..];
+            continue;
+        }
+
+        out.push_str(&escape(&rest[..c.len_utf8()]));
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// The text between `marker` at the start of `rest` and its closing
+/// counterpart. `_` only counts at word boundaries, so snake_case is left alone.
+fn delimited<'a>(rest: &'a str, marker: &str, before: &str) -> Option<&'a str> {
+    let body = &rest[marker.len()..];
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let end = body.find(marker)?;
+    let inner = &body[..end];
+    if inner.is_empty() || inner.ends_with(char::is_whitespace) {
+        return None;
+    }
+    if marker.starts_with('_') {
+        let after = body[end + marker.len()..].chars().next();
+        let prev = before.chars().last();
+        if prev.is_some_and(char::is_alphanumeric) || after.is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+    }
+    Some(inner)
+}
+
+/// `[label](url)` at the start of `text`: the label, the URL and the bytes consumed.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.strip_prefix('[')?.find("](")? + 1;
+    let url_start = label_end + 2;
+    let url_end = url_start + text[url_start..].find(')')?;
+    let url = text[url_start..url_end].trim();
+    if url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((&text[1..label_end], url, url_end + 1))
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Wrap plain text as format=flowed (RFC 3676, DelSp=no): long lines are
+/// broken at spaces with a trailing space marking each soft break, hard
+/// line ends lose their trailing spaces, `>` quote prefixes are kept on
+/// every wrapped line, and lines starting with a space or "From " are
+/// space-stuffed.
+pub fn format_flowed(text: &str) -> String {
+    let mut out = Vec::new();
+
+    for line in text.lines() {
+        // The signature separator keeps its trailing space
+        if line == "-- " {
+            out.push(line.to_string());
+            continue;
+        }
+
+        let depth = line.chars().take_while(|c| *c == '>').count();
+        let content = line[depth..].trim_end();
+        let content = if depth > 0 { content.strip_prefix(' ').unwrap_or(content) } else { content };
+        let prefix = ">".repeat(depth);
+
+        for piece in wrap(content, FLOWED_WIDTH.saturating_sub(depth)) {
+            let stuff = depth > 0 || piece.starts_with(' ') || piece.starts_with("From ");
+            out.push(format!("{}{}{}", prefix, if stuff { " " } else { "" }, piece));
+        }
+    }
+    out.join("\r\n")
+}
+
+/// Break `text` at spaces into pieces of at most `width` characters (longer
+/// words stay whole). Every piece but the last keeps its trailing space.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    if text.chars().count() <= width {
+        return vec![text.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in text.split_inclusive(' ') {
+        let len = current.chars().count();
+        if len > 0 && len + word.trim_end().chars().count() > width {
+            pieces.push(std::mem::take(&mut current));
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_blocks() {
+        let html = markdown_to_html("# Plan\n\nFirst line\nsecond line\n\n- one\n- two\n\n1. a\n2. b\n\n> quoted\n> text");
+        assert_eq!(html, concat!(
+            "<h1>Plan</h1>\n",
+            "<p>First line<br>\nsecond line</p>\n",
+            "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n",
+            "<ol>\n<li>a</li>\n<li>b</li>\n</ol>\n",
+            "<blockquote>\n<p>quoted<br>\ntext</p>\n</blockquote>\n",
+        ));
+    }
+
+    #[test]
+    fn test_markdown_inline() {
+        assert_eq!(
+            inline("**bold**, *it*, `a<b`, ~~no~~ and snake_case_name"),
+            "<strong>bold</strong>, <em>it</em>, <code>a&lt;b</code>, <del>no</del> and snake_case_name",
+        );
+        assert_eq!(
+            inline("see [docs](https://x.org/a?b=1&c=2) or https://eddie.app."),
+            "see <a href=\"https://x.org/a?b=1&amp;c=2\">docs</a> or <a href=\"https://eddie.app\">https://eddie.app</a>.",
+        );
+        assert_eq!(inline("![logo](cid:logo@x)"), "<img src=\"cid:logo@x\" alt=\"logo\">");
+    }
+
+    #[test]
+    fn test_markdown_is_sanitized() {
+        let html = markdown_to_html("<script>alert(1)</script> [x](javascript:alert(1)) ![y](file:///etc/passwd)");
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("href=\"javascript"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn test_code_fence_is_escaped_verbatim() {
+        assert_eq!(
+            markdown_to_html("```\nfn x() -> *u8 {}\n```"),
+            "<pre><code>fn x() -&gt; *u8 {}</code></pre>\n",
+        );
+    }
+
+    #[test]
+    fn test_format_flowed_wraps_with_soft_breaks() {
+        let long = "word ".repeat(30);
+        let flowed = format_flowed(long.trim_end());
+        let lines: Vec<&str> = flowed.split("\r\n").collect();
+        assert!(lines.len() > 1);
+        for line in &lines[..lines.len() - 1] {
+            assert!(line.ends_with(' '));
+            assert!(line.len() <= FLOWED_WIDTH + 1);
+        }
+        assert!(!lines.last().unwrap().ends_with(' '));
+    }
+
+    #[test]
+    fn test_format_flowed_quotes_and_stuffing() {
+        assert_eq!(format_flowed("hard break   \nnext"), "hard break\r\nnext");
+        assert_eq!(format_flowed("From here"), " From here");
+        assert_eq!(format_flowed(" indented"), "  indented");
+        assert_eq!(format_flowed("> quoted"), "> quoted");
+        assert_eq!(format_flowed(">> deeper"), ">> deeper");
+        assert_eq!(format_flowed("-- \nMe"), "-- \r\nMe");
+
+        let quoted = format_flowed(&format!("> {}", "quote ".repeat(20).trim_end()));
+        assert!(quoted.split("\r\n").all(|l| l.starts_with("> ")));
+    }
+}
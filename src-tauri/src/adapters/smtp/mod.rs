@@ -1,3 +1,5 @@
+mod compose;
 mod send;
 
-pub use send::{SmtpMessage, send_message};
+pub use compose::markdown_to_html;
+pub use send::{SmtpAuth, SmtpMessage, SmtpAttachment, build_message, check_custom_header, envelope, parse_mailbox, part_paths, submit, with_bcc};
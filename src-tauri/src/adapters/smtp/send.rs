@@ -1,9 +1,14 @@
 use lettre::{
-    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
-    message::{header::References, Mailbox, MessageBuilder},
+    Address, AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+    address::Envelope,
+    message::{
+        header::{self, ContentType, HeaderName, HeaderValue, Headers, References},
+        Attachment, Mailbox, Mailboxes, MessageBuilder, MultiPart, SinglePart,
+    },
     transport::smtp::authentication::{Credentials, Mechanism},
 };
 use uuid::Uuid;
+use super::compose::format_flowed;
 use crate::error::EddieError;
 use crate::services::logger;
 
@@ -13,28 +18,72 @@ fn bracket(id: &str) -> String {
     format!("<{}>", bare)
 }
 
+/// How to authenticate to the SMTP server.
+pub enum SmtpAuth {
+    Password(String),
+    /// SASL XOAUTH2 with an OAuth2 access token
+    XOAuth2(String),
+}
+
 pub struct SmtpMessage {
     pub from: String,
     pub from_name: Option<String>,
     pub to: Vec<String>,
     pub cc: Vec<String>,
+    /// Envelope recipients only; the Bcc header is left out of the built message
+    pub bcc: Vec<String>,
+    pub reply_to: Vec<String>,
+    /// Set when sending on behalf of `from`
+    pub sender: Option<String>,
+    /// List-Post and X- headers, checked with `check_custom_header`
+    pub headers: Vec<(String, String)>,
     pub subject: String,
+    /// Plain text, sent as format=flowed
     pub body: String,
+    /// HTML alternative of `body`, sent alongside it as multipart/alternative
+    pub html: Option<String>,
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
     pub message_id: Option<String>,
+    pub attachments: Vec<SmtpAttachment>,
 }
 
-/// Send an email via SMTP and return the raw RFC 5322 message bytes
-/// (for IMAP APPEND to Sent folder).
-pub async fn send_message(
-    smtp_host: &str,
-    smtp_port: u16,
-    smtp_tls: bool,
-    username: &str,
-    password: &str,
-    message: &SmtpMessage,
-) -> Result<Vec<u8>, EddieError> {
+/// A file attached to an outgoing message. With a `content_id` it is an
+/// inline image referenced from the HTML body as `cid:`.
+pub struct SmtpAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub content: Vec<u8>,
+    pub content_id: Option<String>,
+}
+
+/// Part paths the attachments will have in the message `build_message`
+/// produces, given which are inline: inline images follow the
+/// multipart/alternative body in multipart/related, and the rest follow the
+/// body in multipart/mixed.
+pub fn part_paths(inline: &[bool], has_html: bool) -> Vec<String> {
+    let is_inline = |inline: bool| inline && has_html;
+    let nested = inline.iter().any(|&i| !is_inline(i));
+    let (mut inline_n, mut attached_n) = (1, 1);
+    inline.iter()
+        .map(|&i| {
+            if !is_inline(i) {
+                attached_n += 1;
+                attached_n.to_string()
+            } else {
+                inline_n += 1;
+                if nested { format!("1.{}", inline_n) } else { inline_n.to_string() }
+            }
+        })
+        .collect()
+}
+
+/// Build the raw RFC 5322 message. The body is a format=flowed text/plain
+/// part, in multipart/alternative with the HTML when there is one; inline
+/// images wrap that in multipart/related, and attachments in
+/// multipart/mixed. The same bytes are submitted over SMTP and, with the
+/// Bcc header restored by `with_bcc`, APPENDed to the Sent folder.
+pub fn build_message(message: &SmtpMessage) -> Result<Vec<u8>, EddieError> {
     let from_mailbox: Mailbox = if let Some(ref name) = message.from_name {
         format!("{} <{}>", name, message.from)
             .parse()
@@ -67,6 +116,28 @@ pub async fn send_message(
         builder = builder.cc(mailbox);
     }
 
+    // lettre drops the Bcc header once the message is built
+    for bcc_addr in &message.bcc {
+        let mailbox: Mailbox = bcc_addr.parse()
+            .map_err(|e| EddieError::InvalidInput(format!("Invalid bcc address '{}': {}", bcc_addr, e)))?;
+        builder = builder.bcc(mailbox);
+    }
+
+    for reply_to_addr in &message.reply_to {
+        builder = builder.reply_to(parse_mailbox("reply-to", reply_to_addr)?);
+    }
+
+    if let Some(ref sender) = message.sender {
+        builder = builder.sender(parse_mailbox("sender", sender)?);
+    }
+
+    for (name, value) in &message.headers {
+        check_custom_header(name, value)?;
+        let name = HeaderName::new_from_ascii(name.clone())
+            .map_err(|_| EddieError::InvalidInput(format!("Invalid header name '{}'", name)))?;
+        builder = builder.raw_header(HeaderValue::new(name, value.clone()));
+    }
+
     // Wrap bare IDs in angle brackets per RFC 5322
     if let Some(ref reply_to) = message.in_reply_to {
         builder = builder.in_reply_to(bracket(reply_to));
@@ -77,18 +148,126 @@ pub async fn send_message(
         builder = builder.header(References::from(refs_str));
     }
 
-    let email = builder
-        .body(message.body.clone())
-        .map_err(|e| EddieError::Backend(format!("Failed to build email: {}", e)))?;
+    let text = SinglePart::builder()
+        .header(ContentType::parse("text/plain; charset=utf-8; format=flowed").expect("valid MIME type"))
+        .body(format_flowed(&message.body));
+    let (inline, attached): (Vec<&SmtpAttachment>, Vec<&SmtpAttachment>) = message.attachments.iter()
+        .partition(|att| att.content_id.is_some() && message.html.is_some());
+
+    let alternative = message.html.as_ref().map(|html| {
+        let alternative = MultiPart::alternative()
+            .singlepart(text.clone())
+            .singlepart(SinglePart::html(html_document(html)));
+        if inline.is_empty() {
+            return alternative;
+        }
+        inline.iter().fold(MultiPart::related().multipart(alternative), |related, att| {
+            let cid = att.content_id.clone().unwrap_or_default();
+            related.singlepart(Attachment::new_inline(cid).body(att.content.clone(), content_type(&att.mime_type)))
+        })
+    });
+
+    let email = match (alternative, attached.is_empty()) {
+        (Some(body), true) => builder.multipart(body),
+        (None, true) => builder.singlepart(text),
+        (body, false) => {
+            let mixed = match body {
+                Some(body) => MultiPart::mixed().multipart(body),
+                None => MultiPart::mixed().singlepart(text),
+            };
+            // Filenames are RFC 2231-encoded by lettre when they aren't plain ASCII
+            builder.multipart(attached.iter().fold(mixed, |mixed, att| {
+                mixed.singlepart(Attachment::new(att.filename.clone()).body(att.content.clone(), content_type(&att.mime_type)))
+            }))
+        }
+    }
+    .map_err(|e| EddieError::Backend(format!("Failed to build email: {}", e)))?;
+
+    Ok(email.formatted())
+}
+
+/// Parse an address for the header `kind` (e.g. "reply-to"), so a bad one
+/// can be rejected before the message is queued.
+pub fn parse_mailbox(kind: &str, addr: &str) -> Result<Mailbox, EddieError> {
+    addr.parse()
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid {} address '{}': {}", kind, addr, e)))
+}
+
+/// Only List-Post and X- headers can be added to a message, so custom
+/// headers can't override the ones `build_message` sets.
+pub fn check_custom_header(name: &str, value: &str) -> Result<(), EddieError> {
+    let lower = name.to_ascii_lowercase();
+    let allowed = lower == "list-post" || (lower.starts_with("x-") && lower.len() > 2);
+    if !allowed || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':') {
+        return Err(EddieError::InvalidInput(format!("Header '{}' can't be set; only List-Post and X- headers are allowed", name)));
+    }
+    if value.contains(['\r', '\n']) {
+        return Err(EddieError::InvalidInput(format!("Header '{}' contains a line break", name)));
+    }
+    Ok(())
+}
+
+/// The user's own copy of a built message: the same bytes with a Bcc header
+/// in front, so the Sent folder shows who was blind-copied.
+pub fn with_bcc(raw: &[u8], bcc: &[String]) -> Result<Vec<u8>, EddieError> {
+    if bcc.is_empty() {
+        return Ok(raw.to_vec());
+    }
+    let mailboxes = bcc.iter()
+        .map(|addr| addr.parse::<Mailbox>()
+            .map_err(|e| EddieError::InvalidInput(format!("Invalid bcc address '{}': {}", addr, e))))
+        .collect::<Result<Mailboxes, _>>()?;
+    let mut headers = Headers::new();
+    headers.set(header::Bcc::from(mailboxes));
+
+    let mut copy = headers.to_string().into_bytes();
+    copy.extend_from_slice(raw);
+    Ok(copy)
+}
+
+fn content_type(mime_type: &str) -> ContentType {
+    ContentType::parse(mime_type)
+        .unwrap_or(ContentType::parse("application/octet-stream").expect("valid MIME type"))
+}
 
-    let creds = Credentials::new(username.to_string(), password.to_string());
+fn html_document(body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head><body>\n{}</body></html>\n",
+        body
+    )
+}
 
-    // Save the raw bytes before sending (for IMAP APPEND)
-    let raw_message = email.formatted();
+/// SMTP envelope for a message: the sender and every recipient.
+pub fn envelope(from: &str, recipients: &[String]) -> Result<Envelope, EddieError> {
+    let parse = |addr: &str| addr.parse::<Address>()
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid address '{}': {}", addr, e)));
+    let to = recipients.iter().map(|r| parse(r)).collect::<Result<Vec<_>, _>>()?;
+    Envelope::new(Some(parse(from)?), to)
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid envelope: {}", e)))
+}
 
-    // Allow PLAIN, LOGIN, and XOAUTH2 mechanisms — many servers only advertise
-    // PLAIN/LOGIN after TLS upgrade, so we list them explicitly.
-    let mechanisms = &[Mechanism::Plain, Mechanism::Login, Mechanism::Xoauth2];
+/// Submit already-built message bytes over SMTP.
+pub async fn submit(
+    smtp_host: &str,
+    smtp_port: u16,
+    smtp_tls: bool,
+    username: &str,
+    auth: &SmtpAuth,
+    envelope: &Envelope,
+    raw_message: &[u8],
+) -> Result<(), EddieError> {
+    // Passwords: allow PLAIN and LOGIN — many servers only advertise them
+    // after TLS upgrade, so we list them explicitly. OAuth tokens go via XOAUTH2 only.
+    let (creds, mechanisms): (Credentials, &[Mechanism]) = match auth {
+        SmtpAuth::Password(password) => (
+            Credentials::new(username.to_string(), password.to_string()),
+            &[Mechanism::Plain, Mechanism::Login, Mechanism::Xoauth2],
+        ),
+        SmtpAuth::XOAuth2(access_token) => (
+            Credentials::new(username.to_string(), access_token.to_string()),
+            &[Mechanism::Xoauth2],
+        ),
+    };
 
     let transport = if smtp_tls && smtp_port == 465 {
         // Implicit TLS (port 465)
@@ -115,10 +294,135 @@ pub async fn send_message(
             .build()
     };
 
-    transport.send(email).await
-        .map_err(|e| EddieError::Backend(format!("SMTP send failed: {}", e)))?;
+    // 5xx replies (bad recipient, auth refused, message rejected) won't
+    // change on retry; connection failures and 4xx replies may.
+    transport.send_raw(envelope, raw_message).await
+        .map_err(|e| if e.is_permanent() {
+            EddieError::Rejected(format!("SMTP send failed: {}", e))
+        } else {
+            EddieError::Backend(format!("SMTP send failed: {}", e))
+        })?;
+
+    logger::debug(&format!("Email sent via SMTP to {:?}", envelope.to()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(attachments: Vec<SmtpAttachment>) -> SmtpMessage {
+        SmtpMessage {
+            from: "me@example.com".into(),
+            from_name: None,
+            to: vec!["you@example.com".into()],
+            cc: vec![],
+            bcc: vec![],
+            reply_to: vec![],
+            sender: None,
+            headers: vec![],
+            subject: "Files".into(),
+            body: "See attached".into(),
+            html: None,
+            in_reply_to: None,
+            references: vec![],
+            message_id: Some("abc@eddie.app".into()),
+            attachments,
+        }
+    }
+
+    #[test]
+    fn test_plain_message_is_single_part() {
+        let raw = String::from_utf8(build_message(&message(vec![])).unwrap()).unwrap();
+        assert!(raw.contains("Content-Type: text/plain; charset=utf-8; format=flowed"));
+        assert!(!raw.contains("multipart"));
+        assert!(raw.contains("Message-ID: <abc@eddie.app>"));
+    }
+
+    #[test]
+    fn test_attachments_build_multipart_mixed() {
+        let raw = build_message(&message(vec![
+            SmtpAttachment { filename: "report.pdf".into(), mime_type: "application/pdf".into(), content: b"%PDF".to_vec(), content_id: None },
+            SmtpAttachment { filename: "Grüße.txt".into(), mime_type: "not a type".into(), content: b"hi".to_vec(), content_id: None },
+        ])).unwrap();
+        let raw = String::from_utf8(raw).unwrap();
+        assert!(raw.contains("Content-Type: multipart/mixed"));
+        assert!(raw.contains("Content-Type: application/pdf"));
+        assert!(raw.contains("filename=\"report.pdf\""));
+        assert!(raw.contains("filename*0*=utf-8''Gr%C3%BC%C3%9Fe.txt"));
+        assert!(raw.contains("Content-Type: application/octet-stream"));
+        assert!(raw.contains("See attached"));
+    }
+
+    #[test]
+    fn test_html_with_inline_image_and_attachment() {
+        let mut msg = message(vec![
+            SmtpAttachment { filename: "logo.png".into(), mime_type: "image/png".into(), content: vec![0x89], content_id: Some("logo@x".into()) },
+            SmtpAttachment { filename: "a.pdf".into(), mime_type: "application/pdf".into(), content: b"%PDF".to_vec(), content_id: None },
+        ]);
+        msg.html = Some("<p><img src=\"cid:logo@x\"></p>".into());
+        let raw = String::from_utf8(build_message(&msg).unwrap()).unwrap();
+
+        let mixed = raw.find("multipart/mixed").unwrap();
+        let related = raw.find("multipart/related").unwrap();
+        let alternative = raw.find("multipart/alternative").unwrap();
+        assert!(mixed < related && related < alternative);
+        assert!(raw.contains("Content-ID: <logo@x>"));
+        assert!(raw.contains("Content-Type: text/html; charset=utf-8"));
+    }
+
+    #[test]
+    fn test_part_paths_follow_layout() {
+        assert_eq!(part_paths(&[false, false], true), vec!["2", "3"]);
+        assert_eq!(part_paths(&[true, true], true), vec!["2", "3"]);
+        assert_eq!(part_paths(&[true, false, true], true), vec!["1.2", "2", "1.3"]);
+        // Without HTML, "inline" images are plain attachments
+        assert_eq!(part_paths(&[true, false], false), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn test_bcc_only_in_sent_copy() {
+        let mut msg = message(vec![]);
+        msg.bcc = vec!["hidden@example.com".into()];
+        let raw = build_message(&msg).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("hidden@example.com"));
+
+        let copy = String::from_utf8(with_bcc(&raw, &msg.bcc).unwrap()).unwrap();
+        assert!(copy.starts_with("Bcc: hidden@example.com\r\n"));
+        assert!(copy.ends_with(&*String::from_utf8_lossy(&raw)));
+        assert_eq!(with_bcc(&raw, &[]).unwrap(), raw);
+    }
 
-    logger::debug(&format!("Email sent via SMTP to {:?}", message.to));
+    #[test]
+    fn test_reply_to_sender_and_custom_headers() {
+        let mut msg = message(vec![]);
+        msg.reply_to = vec!["team@example.com".into()];
+        msg.sender = Some("assistant@example.com".into());
+        msg.headers = vec![
+            ("List-Post".into(), "<mailto:dev@lists.example.com>".into()),
+            ("X-Campaign".into(), "spring".into()),
+        ];
+        let raw = String::from_utf8(build_message(&msg).unwrap()).unwrap();
+        assert!(raw.contains("Reply-To: team@example.com"));
+        assert!(raw.contains("Sender: assistant@example.com"));
+        assert!(raw.contains("List-Post: <mailto:dev@lists.example.com>"));
+        assert!(raw.contains("X-Campaign: spring"));
+    }
+
+    #[test]
+    fn test_custom_header_rules() {
+        assert!(check_custom_header("x-priority", "1").is_ok());
+        assert!(check_custom_header("Subject", "spoofed").is_err());
+        assert!(check_custom_header("X-", "empty").is_err());
+        assert!(check_custom_header("X-Bad Name", "v").is_err());
+        assert!(check_custom_header("X-Injected", "a\r\nBcc: evil@example.com").is_err());
+    }
 
-    Ok(raw_message)
+    #[test]
+    fn test_parse_mailbox_rejects_bad_addresses() {
+        assert!(parse_mailbox("reply-to", "Team <team@example.com>").is_ok());
+        assert!(parse_mailbox("sender", "not an address").is_err());
+        assert!(parse_mailbox("reply-to", "a@example.com\r\nBcc: evil@example.com").is_err());
+    }
 }
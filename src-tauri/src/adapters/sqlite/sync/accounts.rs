@@ -3,6 +3,7 @@ use uuid::Uuid;
 use crate::services::logger;
 
 use super::DbPool;
+use crate::adapters::imap::connection::ImapSecurity;
 use crate::error::EddieError;
 
 pub fn insert_account(
@@ -11,10 +12,12 @@ pub fn insert_account(
     password: &str,
     imap_host: &str,
     imap_port: u16,
-    imap_tls: bool,
+    imap_security: ImapSecurity,
+    allow_plaintext: bool,
     smtp_host: &str,
     smtp_port: u16,
     smtp_tls: bool,
+    oauth_provider: Option<&str>,
 ) -> Result<String, EddieError> {
     let conn = pool.get()?;
 
@@ -37,9 +40,14 @@ pub fn insert_account(
 
     conn.execute(
         "INSERT INTO accounts (
-            id, email, password, imap_host, imap_port, imap_tls, smtp_host, smtp_port, smtp_tls, created_at
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-        params![id, email, password, imap_host, imap_port, imap_tls, smtp_host, smtp_port, smtp_tls, now],
+            id, email, password, imap_host, imap_port, imap_tls, imap_security, allow_plaintext,
+            smtp_host, smtp_port, smtp_tls, oauth_provider, created_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            id, email, password, imap_host, imap_port,
+            imap_security != ImapSecurity::None, imap_security.as_str(), allow_plaintext,
+            smtp_host, smtp_port, smtp_tls, oauth_provider, now
+        ],
     )?;
 
     logger::info(&format!("New account created: email={}, id={}", email, id));
@@ -71,25 +79,30 @@ pub fn find_account_for_onboarding(pool: &DbPool) -> Result<Option<String>, Eddi
 pub struct Credentials {
     pub host: String,
     pub port: u16,
-    pub tls: bool,
+    pub security: ImapSecurity,
+    pub allow_plaintext: bool,
     pub email: String,
     pub password: String,
+    /// Set for OAuth2 accounts — `password` is unused and empty
+    pub oauth_provider: Option<String>,
 }
 
 pub fn get_credentials(pool: &DbPool, account_id: &str) -> Result<Option<Credentials>, EddieError> {
     let conn = pool.get()?;
 
     let result = conn.query_row(
-        "SELECT imap_host, imap_port, imap_tls, email, password FROM accounts
-         WHERE id = ?1",
+        "SELECT imap_host, imap_port, imap_security, allow_plaintext, email, password, oauth_provider
+         FROM accounts WHERE id = ?1",
         rusqlite::params![account_id],
         |row| {
             Ok(Credentials {
                 host: row.get(0)?,
                 port: row.get(1)?,
-                tls: row.get(2)?,
-                email: row.get(3)?,
-                password: row.get(4)?,
+                security: ImapSecurity::parse(&row.get::<_, Option<String>>(2)?.unwrap_or_default()),
+                allow_plaintext: row.get(3)?,
+                email: row.get(4)?,
+                password: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                oauth_provider: row.get(6)?,
             })
         },
     );
@@ -107,13 +120,14 @@ pub struct SmtpCredentials {
     pub tls: bool,
     pub email: String,
     pub password: String,
+    pub oauth_provider: Option<String>,
 }
 
 pub fn get_smtp_credentials(pool: &DbPool, account_id: &str) -> Result<Option<SmtpCredentials>, EddieError> {
     let conn = pool.get()?;
 
     let result = conn.query_row(
-        "SELECT smtp_host, smtp_port, smtp_tls, email, password FROM accounts
+        "SELECT smtp_host, smtp_port, smtp_tls, email, password, oauth_provider FROM accounts
          WHERE id = ?1",
         rusqlite::params![account_id],
         |row| {
@@ -122,7 +136,8 @@ pub fn get_smtp_credentials(pool: &DbPool, account_id: &str) -> Result<Option<Sm
                 port: row.get(1)?,
                 tls: row.get(2)?,
                 email: row.get(3)?,
-                password: row.get(4)?,
+                password: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                oauth_provider: row.get(5)?,
             })
         },
     );
@@ -192,8 +207,11 @@ pub fn get_account_details(
 ) -> Result<crate::commands::account::AccountDetails, EddieError> {
     let conn = pool.get()?;
     let details = conn.query_row(
-        "SELECT id, email, display_name, imap_host, imap_port, imap_tls, smtp_host, smtp_port, smtp_tls
-         FROM accounts WHERE id = ?1",
+        "SELECT a.id, a.email, a.display_name, a.imap_host, a.imap_port, a.imap_security, a.allow_plaintext,
+                a.smtp_host, a.smtp_port, a.smtp_tls, a.oauth_provider, COALESCE(t.needs_reauth, 0)
+         FROM accounts a
+         LEFT JOIN oauth_tokens t ON t.account_id = a.id
+         WHERE a.id = ?1",
         rusqlite::params![account_id],
         |row| {
             Ok(crate::commands::account::AccountDetails {
@@ -202,10 +220,13 @@ pub fn get_account_details(
                 display_name: row.get(2)?,
                 imap_host: row.get(3)?,
                 imap_port: row.get(4)?,
-                imap_tls: row.get(5)?,
-                smtp_host: row.get(6)?,
-                smtp_port: row.get(7)?,
-                smtp_tls: row.get(8)?,
+                imap_security: ImapSecurity::parse(&row.get::<_, Option<String>>(5)?.unwrap_or_default()),
+                allow_plaintext: row.get(6)?,
+                smtp_host: row.get(7)?,
+                smtp_port: row.get(8)?,
+                smtp_tls: row.get(9)?,
+                oauth_provider: row.get(10)?,
+                reauth_required: row.get(11)?,
                 aliases: vec![], // populated below
             })
         },
@@ -232,7 +253,8 @@ pub fn update_account(
     password: Option<&str>,
     imap_host: Option<&str>,
     imap_port: Option<u16>,
-    imap_tls: Option<bool>,
+    imap_security: Option<ImapSecurity>,
+    allow_plaintext: Option<bool>,
     smtp_host: Option<&str>,
     smtp_port: Option<u16>,
     smtp_tls: Option<bool>,
@@ -245,7 +267,11 @@ pub fn update_account(
     if let Some(v) = password { sets.push("password = ?"); values.push(Box::new(v.to_string())); }
     if let Some(v) = imap_host { sets.push("imap_host = ?"); values.push(Box::new(v.to_string())); }
     if let Some(v) = imap_port { sets.push("imap_port = ?"); values.push(Box::new(v as i64)); }
-    if let Some(v) = imap_tls { sets.push("imap_tls = ?"); values.push(Box::new(v)); }
+    if let Some(v) = imap_security {
+        sets.push("imap_security = ?"); values.push(Box::new(v.as_str()));
+        sets.push("imap_tls = ?"); values.push(Box::new(v != ImapSecurity::None));
+    }
+    if let Some(v) = allow_plaintext { sets.push("allow_plaintext = ?"); values.push(Box::new(v)); }
     if let Some(v) = smtp_host { sets.push("smtp_host = ?"); values.push(Box::new(v.to_string())); }
     if let Some(v) = smtp_port { sets.push("smtp_port = ?"); values.push(Box::new(v as i64)); }
     if let Some(v) = smtp_tls { sets.push("smtp_tls = ?"); values.push(Box::new(v)); }
@@ -1,9 +1,21 @@
 use rusqlite::params;
+use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
 use super::DbPool;
 use crate::error::EddieError;
 
+/// Delay before the first retry of a transiently failed action; doubles with
+/// each further failure up to `RETRY_DELAY_MAX`.
+const RETRY_DELAY_MIN: Duration = Duration::from_secs(30);
+const RETRY_DELAY_MAX: Duration = Duration::from_secs(60 * 60);
+
+/// Actions applied entirely in SQLite by `queue_action`; they never touch the
+/// IMAP server and need no write mode.
+pub const LOCAL_ACTIONS: &[&str] = &["mute", "pin"];
+
+#[derive(Debug, serde::Serialize)]
 pub struct QueuedAction {
     pub id: String,
     pub account_id: String,
@@ -15,67 +27,201 @@ pub struct QueuedAction {
     pub created_at: i64,
     pub error: Option<String>,
     pub message_id: Option<String>,
+    pub next_attempt_at: Option<i64>,
+    /// Last completed step of a send: 'built', 'submitted', 'appended' or 'confirmed'
+    pub stage: Option<String>,
+    /// Not picked up before this time (undo-send window or scheduled send)
+    pub send_at: Option<i64>,
 }
 
+/// Queue an action. `send_at` holds it back until then (epoch ms).
 pub fn enqueue(
     pool: &DbPool,
     account_id: &str,
     action_type: &str,
     payload: &str,
     message_id: Option<&str>,
+    send_at: Option<i64>,
 ) -> Result<String, EddieError> {
     let conn = pool.get()?;
     let id = Uuid::new_v4().to_string();
     let now = chrono::Utc::now().timestamp_millis();
 
     conn.execute(
-        "INSERT INTO action_queue (id, account_id, action_type, payload, status, retry_count, max_retries, created_at, message_id)
-         VALUES (?1, ?2, ?3, ?4, 'pending', 0, 5, ?5, ?6)",
-        params![id, account_id, action_type, payload, now, message_id],
+        "INSERT INTO action_queue (id, account_id, action_type, payload, status, retry_count, max_retries, created_at, message_id, send_at)
+         VALUES (?1, ?2, ?3, ?4, 'pending', 0, 5, ?5, ?6, ?7)",
+        params![id, account_id, action_type, payload, now, message_id, send_at],
     )?;
 
     Ok(id)
 }
 
+/// When a send requested for `send_at` (epoch ms, `None` = now) is picked
+/// up: never before the undo window of `undo_seconds` from `now` has passed.
+pub fn send_hold_until(send_at: Option<i64>, now: i64, undo_seconds: i64) -> i64 {
+    send_at.unwrap_or(now).max(now + undo_seconds.max(0) * 1000)
+}
+
+/// The earliest future time (epoch ms) a pending action held back by
+/// `send_at` or a retry backoff becomes due, across all accounts. The engine
+/// sleeps no longer than this, so scheduled sends and retries aren't left
+/// waiting for the next regular tick.
+pub fn next_due_at(pool: &DbPool) -> Result<Option<i64>, EddieError> {
+    let conn = pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let due = conn.query_row(
+        "SELECT MIN(due) FROM (
+             SELECT MAX(COALESCE(send_at, 0), COALESCE(next_attempt_at, 0)) AS due FROM action_queue
+             WHERE status = 'pending' AND retry_count < max_retries
+         )
+         WHERE due > ?1",
+        params![now],
+        |row| row.get(0),
+    )?;
+    Ok(due)
+}
+
+const SELECT_COLUMNS: &str =
+    "id, account_id, action_type, payload, status, retry_count, max_retries, created_at, error, message_id,
+     next_attempt_at, stage, send_at";
+
+fn row_to_action(row: &rusqlite::Row) -> rusqlite::Result<QueuedAction> {
+    Ok(QueuedAction {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        action_type: row.get(2)?,
+        payload: row.get(3)?,
+        status: row.get(4)?,
+        retry_count: row.get(5)?,
+        max_retries: row.get(6)?,
+        created_at: row.get(7)?,
+        error: row.get(8)?,
+        message_id: row.get(9)?,
+        next_attempt_at: row.get(10)?,
+        stage: row.get(11)?,
+        send_at: row.get(12)?,
+    })
+}
+
+/// Actions ready to run: pending, past their `send_at`, and past their
+/// backoff if they failed before.
 pub fn get_pending(pool: &DbPool, account_id: &str) -> Result<Vec<QueuedAction>, EddieError> {
     let conn = pool.get()?;
-    let mut stmt = conn.prepare(
-        "SELECT id, account_id, action_type, payload, status, retry_count, max_retries, created_at, error, message_id
-         FROM action_queue
-         WHERE account_id = ?1 AND status IN ('pending', 'failed')
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM action_queue
+         WHERE account_id = ?1 AND status = 'pending'
            AND retry_count < max_retries
+           AND (next_attempt_at IS NULL OR next_attempt_at <= ?2)
+           AND (send_at IS NULL OR send_at <= ?2)
          ORDER BY created_at ASC",
-    )?;
+        SELECT_COLUMNS
+    ))?;
 
-    let rows = stmt.query_map(params![account_id], |row| {
-        Ok(QueuedAction {
-            id: row.get(0)?,
-            account_id: row.get(1)?,
-            action_type: row.get(2)?,
-            payload: row.get(3)?,
-            status: row.get(4)?,
-            retry_count: row.get(5)?,
-            max_retries: row.get(6)?,
-            created_at: row.get(7)?,
-            error: row.get(8)?,
-            message_id: row.get(9)?,
-        })
-    })?;
+    let rows = stmt.query_map(params![account_id, now], row_to_action)?;
 
     let mut actions = Vec::new();
     for row in rows {
-        actions.push(row.map_err(|e| EddieError::Database(e.to_string()))?);
+        actions.push(row?);
     }
     Ok(actions)
 }
 
-pub fn mark_in_progress(pool: &DbPool, action_id: &str) -> Result<(), EddieError> {
+/// Everything not yet confirmed by the server — queued, running, failed, or
+/// completed and awaiting confirmation — oldest first.
+pub fn list_outbox(pool: &DbPool, account_id: &str) -> Result<Vec<QueuedAction>, EddieError> {
     let conn = pool.get()?;
-    conn.execute(
-        "UPDATE action_queue SET status = 'in_progress' WHERE id = ?1",
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM action_queue
+         WHERE account_id = ?1 AND status != 'done'
+         ORDER BY created_at ASC",
+        SELECT_COLUMNS
+    ))?;
+
+    let rows = stmt.query_map(params![account_id], row_to_action)?;
+
+    let mut actions = Vec::new();
+    for row in rows {
+        actions.push(row?);
+    }
+    Ok(actions)
+}
+
+/// Whether a send action for this Message-ID is still queued, in any account
+/// and any status.
+pub fn send_exists(pool: &DbPool, message_id: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM action_queue WHERE action_type = 'send' AND message_id = ?1)",
+        params![message_id],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// The send action for an outgoing message, by its Message-ID.
+pub fn get_send(pool: &DbPool, account_id: &str, message_id: &str) -> Result<Option<QueuedAction>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} FROM action_queue
+             WHERE account_id = ?1 AND message_id = ?2 AND action_type = 'send'",
+            SELECT_COLUMNS
+        ),
+        params![account_id, message_id],
+        row_to_action,
+    );
+
+    match result {
+        Ok(action) => Ok(Some(action)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Message-ID → `send_at` of sends still waiting for their time.
+pub fn get_scheduled_sends(pool: &DbPool, account_id: &str) -> Result<HashMap<String, i64>, EddieError> {
+    let conn = pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut stmt = conn.prepare(
+        "SELECT message_id, send_at FROM action_queue
+         WHERE account_id = ?1 AND action_type = 'send' AND status = 'pending'
+           AND send_at > ?2 AND message_id IS NOT NULL",
+    )?;
+    let rows = stmt.query_map(params![account_id, now], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut scheduled = HashMap::new();
+    for row in rows {
+        let (message_id, send_at): (String, i64) = row?;
+        scheduled.insert(message_id, send_at);
+    }
+    Ok(scheduled)
+}
+
+pub fn get_action(pool: &DbPool, action_id: &str) -> Result<Option<QueuedAction>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        &format!("SELECT {} FROM action_queue WHERE id = ?1", SELECT_COLUMNS),
+        params![action_id],
+        row_to_action,
+    );
+
+    match result {
+        Ok(action) => Ok(Some(action)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Claim a pending action for replay. Returns false if it was cancelled (or
+/// otherwise left `pending`) since it was read.
+pub fn mark_in_progress(pool: &DbPool, action_id: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "UPDATE action_queue SET status = 'in_progress' WHERE id = ?1 AND status = 'pending'",
         params![action_id],
     )?;
-    Ok(())
+    Ok(rows > 0)
 }
 
 pub fn mark_completed(pool: &DbPool, action_id: &str) -> Result<(), EddieError> {
@@ -88,20 +234,144 @@ pub fn mark_completed(pool: &DbPool, action_id: &str) -> Result<(), EddieError>
     Ok(())
 }
 
-pub fn mark_failed(pool: &DbPool, action_id: &str, error: &str) -> Result<(), EddieError> {
+/// Put a transiently failed action back in the queue, not to be picked up
+/// again before `next_attempt_at` (epoch ms).
+pub fn schedule_retry(
+    pool: &DbPool,
+    action_id: &str,
+    error: &str,
+    next_attempt_at: i64,
+) -> Result<(), EddieError> {
     let conn = pool.get()?;
     conn.execute(
-        "UPDATE action_queue SET status = 'failed', retry_count = retry_count + 1, error = ?1 WHERE id = ?2",
-        params![error, action_id],
+        "UPDATE action_queue SET status = 'pending', retry_count = retry_count + 1, error = ?1, next_attempt_at = ?2
+         WHERE id = ?3",
+        params![error, next_attempt_at, action_id],
     )?;
     Ok(())
 }
 
+/// Terminal failure: the action is never retried. `reason` is kept in `error`.
+pub fn mark_failed(pool: &DbPool, action_id: &str, reason: &str) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    conn.execute(
+        "UPDATE action_queue SET status = 'failed', retry_count = retry_count + 1, error = ?1, completed_at = ?2
+         WHERE id = ?3",
+        params![reason, now, action_id],
+    )?;
+    Ok(())
+}
+
+/// Checkpoint a send after building: the raw message is what gets submitted
+/// and appended, so a retry sends exactly the same bytes.
+pub fn save_built(pool: &DbPool, action_id: &str, raw_message: &[u8]) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE action_queue SET stage = 'built', raw_message = ?1 WHERE id = ?2",
+        params![raw_message, action_id],
+    )?;
+    Ok(())
+}
+
+/// Checkpoints of a send action, in order.
+const SEND_STAGES: &[&str] = &["built", "submitted", "appended", "confirmed"];
+
+/// Whether a send at `current` has already completed `stage`.
+pub fn stage_reached(current: Option<&str>, stage: &str) -> bool {
+    let position = |s: &str| SEND_STAGES.iter().position(|known| *known == s);
+    match (current.and_then(position), position(stage)) {
+        (Some(current), Some(stage)) => current >= stage,
+        _ => false,
+    }
+}
+
+pub fn set_stage(pool: &DbPool, action_id: &str, stage: &str) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE action_queue SET stage = ?1 WHERE id = ?2",
+        params![stage, action_id],
+    )?;
+    Ok(())
+}
+
+pub fn get_raw_message(pool: &DbPool, action_id: &str) -> Result<Option<Vec<u8>>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        "SELECT raw_message FROM action_queue WHERE id = ?1",
+        params![action_id],
+        |row| row.get(0),
+    );
+
+    match result {
+        Ok(raw) => Ok(raw),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Queue a failed (or backed-off) action to run on the next tick with a
+/// fresh retry budget. Returns false if it is running or already done.
+pub fn retry_now(pool: &DbPool, action_id: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "UPDATE action_queue SET status = 'pending', retry_count = 0, next_attempt_at = NULL,
+                                 error = NULL, completed_at = NULL
+         WHERE id = ?1 AND status IN ('pending', 'failed')",
+        params![action_id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Remove a queued or failed action. Returns false if it is running or has
+/// already been carried out (including a send that was submitted but not
+/// yet appended), in which case it can't be taken back.
+pub fn cancel(pool: &DbPool, action_id: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "DELETE FROM action_queue
+         WHERE id = ?1 AND status IN ('pending', 'failed') AND (stage IS NULL OR stage = 'built')",
+        params![action_id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Replace the payload of a failed action and drop any message built from
+/// the old one. Returns false if it isn't failed, or was already submitted.
+pub fn update_failed_payload(pool: &DbPool, action_id: &str, payload: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "UPDATE action_queue SET payload = ?1, stage = NULL, raw_message = NULL
+         WHERE id = ?2 AND status = 'failed' AND (stage IS NULL OR stage = 'built')",
+        params![payload, action_id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// How long to wait before retrying an action that has failed `retry_count`
+/// times already: exponential from `RETRY_DELAY_MIN`, capped at
+/// `RETRY_DELAY_MAX`, with "equal jitter" so actions that failed together
+/// (e.g. during one outage) don't all retry in the same tick.
+pub fn retry_delay(retry_count: i32) -> Duration {
+    let jitter = (Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+    backoff(retry_count, jitter)
+}
+
+/// `retry_delay` with the random part passed in: half the exponential delay
+/// is fixed and `jitter` (0.0..1.0) scales the other half.
+fn backoff(retry_count: i32, jitter: f64) -> Duration {
+    let exp = RETRY_DELAY_MIN.saturating_mul(1u32 << retry_count.clamp(0, 16));
+    let delay = exp.min(RETRY_DELAY_MAX);
+    delay / 2 + (delay / 2).mul_f64(jitter.clamp(0.0, 1.0))
+}
+
 pub fn mark_done(pool: &DbPool, action_id: &str) -> Result<(), EddieError> {
     let conn = pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
     conn.execute(
-        "UPDATE action_queue SET status = 'done', completed_at = ?1 WHERE id = ?2",
+        "UPDATE action_queue SET status = 'done', completed_at = ?1, raw_message = NULL,
+                                 stage = CASE WHEN stage IS NULL THEN NULL ELSE 'confirmed' END
+         WHERE id = ?2",
         params![now, action_id],
     )?;
     Ok(())
@@ -125,26 +395,30 @@ pub fn get_completed_by_message_id(
     Ok(ids)
 }
 
-/// Find completed mark_read actions for a given folder (for server confirmation).
+/// Find completed actions of one type awaiting server confirmation.
 /// Returns (action_id, payload) pairs.
-pub fn get_completed_mark_read(
+pub fn get_completed_by_type(
     pool: &DbPool,
     account_id: &str,
+    action_type: &str,
 ) -> Result<Vec<(String, String)>, EddieError> {
     let conn = pool.get()?;
     let mut stmt = conn.prepare(
         "SELECT id, payload FROM action_queue
-         WHERE account_id = ?1 AND action_type = 'mark_read' AND status = 'completed'",
+         WHERE account_id = ?1 AND action_type = ?2 AND status = 'completed'",
     )?;
     let rows: Vec<(String, String)> = stmt
-        .query_map(params![account_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .query_map(params![account_id, action_type], |row| Ok((row.get(0)?, row.get(1)?)))?
         .filter_map(|r| r.ok())
         .collect();
     Ok(rows)
 }
 
-/// Startup cleanup: delete actions older than 72 hours and
-/// reset any in_progress actions back to pending (interrupted by shutdown).
+/// Startup cleanup: delete completed and confirmed actions older than 72 hours
+/// and reset any in_progress actions back to pending (interrupted by shutdown).
+/// Pending and failed actions are kept however old — they are unsent mail or
+/// changes the user hasn't seen go through, and stay in the outbox until
+/// retried or cancelled.
 pub fn purge_old(pool: &DbPool) -> Result<usize, EddieError> {
     let conn = pool.get()?;
 
@@ -156,8 +430,124 @@ pub fn purge_old(pool: &DbPool) -> Result<usize, EddieError> {
 
     let cutoff = chrono::Utc::now().timestamp_millis() - 72 * 60 * 60 * 1000;
     let count = conn.execute(
-        "DELETE FROM action_queue WHERE created_at < ?1",
+        "DELETE FROM action_queue
+         WHERE status IN ('completed', 'done') AND COALESCE(completed_at, created_at) < ?1",
         params![cutoff],
     )?;
     Ok(count)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::test_support;
+
+    fn status(pool: &DbPool, action_id: &str) -> Option<String> {
+        get_action(pool, action_id).unwrap().map(|a| a.status)
+    }
+
+    #[test]
+    fn test_send_hold_until_keeps_the_undo_window() {
+        let now = 1_000_000;
+        assert_eq!(send_hold_until(None, now, 10), now + 10_000);
+        // A schedule inside the undo window (or in the past) is pushed back
+        assert_eq!(send_hold_until(Some(now + 5_000), now, 10), now + 10_000);
+        assert_eq!(send_hold_until(Some(now - 60_000), now, 10), now + 10_000);
+        assert_eq!(send_hold_until(Some(now + 3_600_000), now, 10), now + 3_600_000);
+        assert_eq!(send_hold_until(None, now, 0), now);
+        assert_eq!(send_hold_until(None, now, -5), now);
+    }
+
+    #[test]
+    fn test_held_send_is_not_pending_until_due_and_can_be_unsent() {
+        let pool = test_support::pool();
+        let later = chrono::Utc::now().timestamp_millis() + 60_000;
+        let id = enqueue(&pool, "acc1", "send", "{}", Some("m@x"), Some(later)).unwrap();
+        let retry = enqueue(&pool, "acc1", "flag", "{}", None, None).unwrap();
+        schedule_retry(&pool, &retry, "timeout", later + 1_000).unwrap();
+
+        assert!(get_pending(&pool, "acc1").unwrap().is_empty());
+        assert_eq!(next_due_at(&pool).unwrap(), Some(later));
+
+        // `unsend` takes back the pending send by its Message-ID
+        let send = get_send(&pool, "acc1", "m@x").unwrap().unwrap();
+        assert_eq!((send.id.as_str(), send.status.as_str()), (id.as_str(), "pending"));
+        assert!(cancel(&pool, &id).unwrap());
+        assert!(get_send(&pool, "acc1", "m@x").unwrap().is_none());
+        assert_eq!(next_due_at(&pool).unwrap(), Some(later + 1_000));
+
+        // Once replay has claimed a send, it can't be taken back
+        let id = enqueue(&pool, "acc1", "send", "{}", Some("n@x"), None).unwrap();
+        assert!(mark_in_progress(&pool, &id).unwrap());
+        assert!(!cancel(&pool, &id).unwrap());
+    }
+
+    #[test]
+    fn test_mark_in_progress_only_claims_pending() {
+        let pool = test_support::pool();
+        let id = enqueue(&pool, "acc1", "flag", "{}", None, None).unwrap();
+        assert!(mark_in_progress(&pool, &id).unwrap());
+        // A second worker can't claim it again
+        assert!(!mark_in_progress(&pool, &id).unwrap());
+
+        mark_failed(&pool, &id, "rejected").unwrap();
+        assert!(!mark_in_progress(&pool, &id).unwrap());
+        assert_eq!(status(&pool, &id).as_deref(), Some("failed"));
+    }
+
+    #[test]
+    fn test_list_outbox_retry_and_cancel() {
+        let pool = test_support::pool();
+        let running = enqueue(&pool, "acc1", "flag", "{}", None, None).unwrap();
+        let failed = enqueue(&pool, "acc1", "mark_read", "{}", None, None).unwrap();
+        let done = enqueue(&pool, "acc1", "archive", "{}", None, None).unwrap();
+        let sent = enqueue(&pool, "acc1", "send", "{}", Some("m@x"), None).unwrap();
+        mark_in_progress(&pool, &running).unwrap();
+        mark_failed(&pool, &failed, "rejected").unwrap();
+        mark_done(&pool, &done).unwrap();
+        set_stage(&pool, &sent, "submitted").unwrap();
+        mark_failed(&pool, &sent, "append failed").unwrap();
+
+        let outbox: Vec<String> = list_outbox(&pool, "acc1").unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(outbox, vec![running.clone(), failed.clone(), sent.clone()]);
+        assert!(list_outbox(&pool, "acc2").unwrap().is_empty());
+
+        // Retry gives a failed action a fresh budget; a running one is left alone
+        assert!(retry_now(&pool, &failed).unwrap());
+        let action = get_action(&pool, &failed).unwrap().unwrap();
+        assert_eq!((action.status.as_str(), action.retry_count, action.error), ("pending", 0, None));
+        assert!(!retry_now(&pool, &running).unwrap());
+
+        // Running actions and sends already submitted can't be taken back
+        assert!(!cancel(&pool, &running).unwrap());
+        assert!(!cancel(&pool, &sent).unwrap());
+        assert!(cancel(&pool, &failed).unwrap());
+        assert_eq!(status(&pool, &failed), None);
+    }
+
+    #[test]
+    fn test_stage_reached() {
+        assert!(!stage_reached(None, "built"));
+        assert!(stage_reached(Some("built"), "built"));
+        assert!(!stage_reached(Some("built"), "submitted"));
+        assert!(stage_reached(Some("appended"), "submitted"));
+        assert!(!stage_reached(Some("unknown"), "built"));
+    }
+
+    #[test]
+    fn test_backoff_doubles_until_cap() {
+        assert_eq!(backoff(0, 1.0), Duration::from_secs(30));
+        assert_eq!(backoff(1, 1.0), Duration::from_secs(60));
+        assert_eq!(backoff(4, 1.0), Duration::from_secs(480));
+        assert_eq!(backoff(7, 1.0), RETRY_DELAY_MAX);
+        assert_eq!(backoff(40, 1.0), RETRY_DELAY_MAX);
+    }
+
+    #[test]
+    fn test_backoff_jitter_keeps_half() {
+        assert_eq!(backoff(0, 0.0), Duration::from_secs(15));
+        assert_eq!(backoff(2, 0.5), Duration::from_secs(90));
+        let delay = retry_delay(3);
+        assert!(delay >= Duration::from_secs(120) && delay <= Duration::from_secs(240));
+    }
+}
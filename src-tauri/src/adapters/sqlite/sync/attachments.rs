@@ -0,0 +1,142 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use super::DbPool;
+use crate::error::EddieError;
+
+/// Attachment metadata ready to be stored alongside a message.
+pub struct NewAttachment {
+    pub part_path: String,
+    pub filename: Option<String>,
+    pub mime_type: String,
+    pub size_bytes: u32,
+    pub content_id: Option<String>,
+    pub transfer_encoding: String,
+    pub is_inline: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub message_id: String,
+    pub part_path: String,
+    pub filename: Option<String>,
+    pub mime_type: String,
+    pub size_bytes: u32,
+    pub content_id: Option<String>,
+    pub transfer_encoding: String,
+    pub is_inline: bool,
+    /// SHA-256 of the decoded bytes, set once the part has been downloaded
+    pub content_hash: Option<String>,
+}
+
+/// Replace a message's attachment rows. Called inside `insert_messages`'
+/// transaction, and after a lazy BODYSTRUCTURE fetch for older messages.
+pub(super) fn replace_for_message(
+    conn: &Connection,
+    message_id: &str,
+    attachments: &[NewAttachment],
+) -> Result<(), EddieError> {
+    conn.execute("DELETE FROM attachments WHERE message_id = ?1", params![message_id])?;
+    for att in attachments {
+        conn.execute(
+            "INSERT INTO attachments (
+                message_id, part_path, filename, mime_type, size_bytes,
+                content_id, transfer_encoding, is_inline
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                message_id,
+                att.part_path,
+                att.filename,
+                att.mime_type,
+                att.size_bytes,
+                att.content_id,
+                att.transfer_encoding,
+                att.is_inline as i32,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn replace_attachments(
+    pool: &DbPool,
+    message_id: &str,
+    attachments: &[NewAttachment],
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+    replace_for_message(&tx, message_id, attachments)?;
+    tx.commit()?;
+    Ok(())
+}
+
+const SELECT_COLUMNS: &str =
+    "message_id, part_path, filename, mime_type, size_bytes,
+     content_id, transfer_encoding, is_inline, content_hash";
+
+fn row_to_attachment(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        message_id: row.get(0)?,
+        part_path: row.get(1)?,
+        filename: row.get(2)?,
+        mime_type: row.get(3)?,
+        size_bytes: row.get(4)?,
+        content_id: row.get(5)?,
+        transfer_encoding: row.get(6)?,
+        is_inline: row.get::<_, i32>(7)? != 0,
+        content_hash: row.get(8)?,
+    })
+}
+
+/// Attachments of a message (by DB id), in MIME order.
+pub fn list_attachments(pool: &DbPool, message_id: &str) -> Result<Vec<Attachment>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments WHERE message_id = ?1 ORDER BY id",
+        SELECT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![message_id], row_to_attachment)?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+pub fn get_attachment(
+    pool: &DbPool,
+    message_id: &str,
+    part_path: &str,
+) -> Result<Option<Attachment>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} FROM attachments WHERE message_id = ?1 AND part_path = ?2",
+            SELECT_COLUMNS
+        ),
+        params![message_id, part_path],
+        row_to_attachment,
+    );
+
+    match result {
+        Ok(att) => Ok(Some(att)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Record where the downloaded bytes live in the content-addressed cache.
+pub fn set_content_hash(
+    pool: &DbPool,
+    message_id: &str,
+    part_path: &str,
+    content_hash: &str,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE attachments SET content_hash = ?1 WHERE message_id = ?2 AND part_path = ?3",
+        params![content_hash, message_id, part_path],
+    )?;
+    Ok(())
+}
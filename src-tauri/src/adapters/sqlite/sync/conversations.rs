@@ -1,12 +1,17 @@
-use rusqlite::params;
+use rusqlite::{params, params_from_iter, types::Value, Connection, TransactionBehavior};
 use sha2::{Digest, Sha256};
-use std::collections::{BTreeMap, HashMap, BTreeSet};
+use std::collections::{BTreeMap, HashMap, HashSet, BTreeSet};
 
 use super::DbPool;
-use super::{entities, messages};
+use super::{messages, search, smart_views};
 use crate::error::EddieError;
+use crate::services::search_query;
 use crate::services::sync::helpers::email_normalization::normalize_email;
 
+/// With more queued thread changes than this (e.g. the first sync of an
+/// account), rethreading the whole account is cheaper than going thread by thread.
+const FULL_REBUILD_THRESHOLD: usize = 1000;
+
 pub fn compute_conversation_id(participant_key: &str) -> String {
     let hash = Sha256::digest(participant_key.as_bytes());
     format!("{:x}", hash)[..16].to_string()
@@ -19,92 +24,341 @@ fn compute_thread_id(root_message_id: &str) -> String {
 
 /// Rebuild all conversations for an account from the messages table.
 ///
-/// Groups messages by conversation_id, aggregates participant names,
-/// picks the latest message as preview, and upserts into conversations.
+/// Rethreads every message, aggregates every conversation and drops the
+/// changes queued for `update_conversations`. This is the repair path;
+/// day-to-day changes go through `update_conversations`.
 /// Preserves user preferences (is_muted, is_pinned) on existing conversations.
 pub fn rebuild_conversations(pool: &DbPool, account_id: &str) -> Result<usize, EddieError> {
-    let conn = pool.get()?;
-    let self_emails = super::entities::get_self_emails(pool, account_id)?;
-    let sender_names = name_lookup(pool, account_id)?;
+    let mut conn = pool.get()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let count = rebuild_all(&tx, account_id)?;
+    tx.commit()?;
+    Ok(count)
+}
+
+/// Bring conversations up to date with the messages and entities that
+/// changed since the last call, as queued by the `conversation_changes`
+/// triggers. Only the threads of new, changed or removed messages are
+/// rethreaded, and only the conversations they were or are in get
+/// re-aggregated. Returns the number of conversations written or removed.
+pub fn update_conversations(pool: &DbPool, account_id: &str) -> Result<usize, EddieError> {
+    let mut conn = pool.get()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let changes: Vec<Change> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, kind, message_id, thread_id, conversation_id, email
+             FROM conversation_changes WHERE account_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| {
+            Ok(Change {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                message_id: row.get(2)?,
+                thread_id: row.get(3)?,
+                conversation_id: row.get(4)?,
+                email: row.get(5)?,
+            })
+        })?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let Some(last_id) = changes.last().map(|c| c.id) else {
+        return Ok(0);
+    };
+
+    let thread_changes = changes.iter().filter(|c| c.kind == "thread").count();
+    if thread_changes > FULL_REBUILD_THRESHOLD || changes.iter().any(|c| c.kind == "all") {
+        let count = rebuild_all(&tx, account_id)?;
+        tx.commit()?;
+        return Ok(count);
+    }
+
+    let people = People::load(&tx, account_id)?;
+    let mut affected: BTreeSet<String> = BTreeSet::new();
+
+    // Rethread the touched threads: those of changed or removed messages
+    // before the change, and those any new reference points into
+    let mut old_threads: BTreeSet<String> = BTreeSet::new();
+    let mut seeds: Vec<&str> = Vec::new();
+    for change in changes.iter().filter(|c| c.kind == "thread") {
+        old_threads.extend(change.thread_id.clone());
+        affected.extend(change.conversation_id.clone());
+        seeds.extend(change.message_id.as_deref());
+    }
+    if !seeds.is_empty() || !old_threads.is_empty() {
+        let mut msgs: HashMap<String, ThreadMsg> = HashMap::new();
+        for db_id in seeds {
+            msgs.extend(load_thread_msgs(&tx, account_id, "AND id = ?2", Some(db_id))?.into_iter().map(|m| (m.db_id.clone(), m)));
+        }
+        {
+            let mut stmt = tx.prepare_cached(
+                "SELECT thread_id FROM thread_refs WHERE account_id = ?1 AND ref_id = ?2",
+            )?;
+            for msg in msgs.values() {
+                for ref_id in msg.refs() {
+                    let mut rows = stmt.query(params![account_id, ref_id])?;
+                    if let Some(row) = rows.next()? {
+                        old_threads.insert(row.get(0)?);
+                    }
+                }
+            }
+        }
+        for thread_id in &old_threads {
+            let members = load_thread_msgs(&tx, account_id, "AND thread_id = ?2", Some(thread_id))?;
+            msgs.extend(members.into_iter().map(|m| (m.db_id.clone(), m)));
+            tx.execute(
+                "DELETE FROM thread_refs WHERE account_id = ?1 AND thread_id = ?2",
+                params![account_id, thread_id],
+            )?;
+        }
+
+        let msgs: Vec<ThreadMsg> = msgs.into_values().collect();
+        affected.extend(msgs.iter().filter_map(|m| m.conversation_id.clone()));
+        let threads = assign_threads(&msgs, &people.self_emails);
+        affected.extend(threads.iter().map(|t| t.conversation_id.clone()));
+        write_threads(&tx, account_id, &threads)?;
+    }
+
+    for change in changes.iter().filter(|c| c.kind == "conversation") {
+        affected.extend(change.conversation_id.clone());
+    }
+
+    // A participant's trust level decides whether their conversations are
+    // blocked and how they're classified
+    let emails: HashSet<&str> = changes.iter()
+        .filter(|c| c.kind == "entity")
+        .filter_map(|c| c.email.as_deref())
+        .collect();
+    if !emails.is_empty() {
+        let mut stmt = tx.prepare(
+            "SELECT DISTINCT conversation_id, participant_key FROM messages
+             WHERE account_id = ?1 AND conversation_id IS NOT NULL",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (conversation_id, participant_key) = row?;
+            if participant_key.split('\n').any(|p| emails.contains(p)) {
+                affected.insert(conversation_id);
+            }
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    for conversation_id in &affected {
+        let builder = aggregate(&tx, account_id, Some(conversation_id), &people)?.into_values().next();
+        let written = match builder {
+            Some(builder) => write_conversation(&tx, account_id, &builder, &people, now)?,
+            None => false,
+        };
+        if !written {
+            tx.execute(
+                "DELETE FROM conversations WHERE id = ?1 AND account_id = ?2",
+                params![conversation_id, account_id],
+            )?;
+        }
+    }
+
+    tx.execute(
+        "DELETE FROM conversation_changes WHERE account_id = ?1 AND id <= ?2",
+        params![account_id, last_id],
+    )?;
+    tx.commit()?;
+    Ok(affected.len())
+}
+
+/// A row of `conversation_changes`
+struct Change {
+    id: i64,
+    kind: String,
+    message_id: Option<String>,
+    thread_id: Option<String>,
+    conversation_id: Option<String>,
+    email: Option<String>,
+}
+
+fn rebuild_all(conn: &Connection, account_id: &str) -> Result<usize, EddieError> {
+    // Everything queued so far is covered by this rebuild
+    conn.execute("DELETE FROM conversation_changes WHERE account_id = ?1", params![account_id])?;
+    let people = People::load(conn, account_id)?;
 
     // ========================================
     // PHASE 1: Thread detection + assignments
     // ========================================
 
-    struct ThreadMsg {
-        db_id: String,
-        message_id: String,
-        in_reply_to: Option<String>,
-        references: Vec<String>,
-        date: i64,
-        from_address: String,
-        to_addresses: Vec<String>,
-        cc_addresses: Vec<String>,
-    }
-
-    let thread_msgs: Vec<ThreadMsg> = {
-        let mut stmt = conn.prepare(
-            "SELECT id, message_id, in_reply_to, references_ids,
-                    date, from_address, to_addresses, cc_addresses
-            FROM messages WHERE account_id = ?1
-            ORDER BY date ASC"
-        )?;
+    let thread_msgs = load_thread_msgs(conn, account_id, "", None)?;
+    let threads = assign_threads(&thread_msgs, &people.self_emails);
+    conn.execute("DELETE FROM thread_refs WHERE account_id = ?1", params![account_id])?;
+    write_threads(conn, account_id, &threads)?;
 
+    // ========================================
+    // PHASE 2: Aggregate conversations
+    // ========================================
+
+    let conv_map = aggregate(conn, account_id, None, &people)?;
+
+    // ========================================
+    // PHASE 3: Upsert conversations
+    // ========================================
+
+    // Conversation ids are derived from participants, so muted/pinned carry
+    // over to the rebuilt rows by id.
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut written: HashSet<&str> = HashSet::new();
+    for builder in conv_map.values() {
+        if write_conversation(conn, account_id, builder, &people, now)? {
+            written.insert(&builder.id);
+        }
+    }
+
+    let stale: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT id FROM conversations WHERE account_id = ?1")?;
+        let ids = stmt.query_map(params![account_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids.into_iter().filter(|id| !written.contains(id.as_str())).collect()
+    };
+    for id in &stale {
+        conn.execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+    }
+
+    Ok(written.len())
+}
+
+/// The account's own addresses, and the participants whose trust level
+/// matters to conversations.
+struct People {
+    self_emails: Vec<String>,
+    blocked: HashSet<String>,
+    trusted: HashSet<String>,
+}
+
+impl People {
+    fn load(conn: &Connection, account_id: &str) -> Result<Self, EddieError> {
+        let mut people = People { self_emails: Vec::new(), blocked: HashSet::new(), trusted: HashSet::new() };
+        let mut stmt = conn.prepare("SELECT email, trust_level FROM entities WHERE account_id = ?1")?;
         let rows = stmt.query_map(params![account_id], |row| {
-            let refs_json: String = row.get(3)?;
-            let to_json: String = row.get(6)?;
-            let cc_json: String = row.get(7)?;
-            Ok(ThreadMsg {
-                db_id: row.get(0)?,
-                message_id: row.get(1)?,
-                in_reply_to: row.get(2)?,
-                references: serde_json::from_str(&refs_json).unwrap_or_default(),
-                date: row.get(4)?,
-                from_address: row.get(5)?,
-                to_addresses: serde_json::from_str(&to_json).unwrap_or_default(),
-                cc_addresses: serde_json::from_str(&cc_json).unwrap_or_default(),
-            })
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         })?;
+        for row in rows {
+            let (email, trust_level) = row?;
+            match trust_level.as_str() {
+                "user" | "alias" => people.self_emails.push(email),
+                "blocked" => {
+                    people.blocked.insert(email);
+                }
+                _ => {
+                    people.trusted.insert(email);
+                }
+            }
+        }
+        Ok(people)
+    }
+}
+
+struct ThreadMsg {
+    db_id: String,
+    message_id: String,
+    in_reply_to: Option<String>,
+    references: Vec<String>,
+    date: i64,
+    from_address: String,
+    to_addresses: Vec<String>,
+    cc_addresses: Vec<String>,
+    conversation_id: Option<String>,
+}
 
-        rows.filter_map(|r| r.ok()).collect()
+impl ThreadMsg {
+    /// The Message-IDs that tie this message to a thread. A message
+    /// without a Message-ID is a thread of its own.
+    fn refs(&self) -> impl Iterator<Item = &str> {
+        let has_id = !self.message_id.is_empty();
+        std::iter::once(self.message_id.as_str())
+            .chain(self.in_reply_to.as_deref())
+            .chain(self.references.iter().map(String::as_str))
+            .filter(move |r| has_id && !r.is_empty())
+    }
+}
+
+/// The account's messages matching `filter`, in which ?1 is the account
+/// and ?2 is `arg`. Bounces are never threaded and keep a NULL conversation_id.
+fn load_thread_msgs(
+    conn: &Connection,
+    account_id: &str,
+    filter: &str,
+    arg: Option<&str>,
+) -> Result<Vec<ThreadMsg>, EddieError> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT id, message_id, in_reply_to, references_ids,
+                date, from_address, to_addresses, cc_addresses, conversation_id
+         FROM messages WHERE account_id = ?1 AND classification IS NOT 'bounce' {}",
+        filter
+    ))?;
+    let map_row = |row: &rusqlite::Row| {
+        let refs_json: String = row.get(3)?;
+        let to_json: String = row.get(6)?;
+        let cc_json: String = row.get(7)?;
+        Ok(ThreadMsg {
+            db_id: row.get(0)?,
+            message_id: row.get(1)?,
+            in_reply_to: row.get(2)?,
+            references: serde_json::from_str(&refs_json).unwrap_or_default(),
+            date: row.get(4)?,
+            from_address: row.get(5)?,
+            to_addresses: serde_json::from_str(&to_json).unwrap_or_default(),
+            cc_addresses: serde_json::from_str(&cc_json).unwrap_or_default(),
+            conversation_id: row.get(8)?,
+        })
     };
+    let rows = match arg {
+        Some(arg) => stmt.query_map(params![account_id, arg], map_row)?.collect::<Result<Vec<_>, _>>()?,
+        None => stmt.query_map(params![account_id], map_row)?.collect::<Result<Vec<_>, _>>()?,
+    };
+    Ok(rows)
+}
+
+/// A thread as computed by `assign_threads`
+struct Thread {
+    id: String,
+    participant_key: String,
+    conversation_id: String,
+    refs: BTreeSet<String>,
+    /// (messages.id, participant_changes), oldest first
+    messages: Vec<(String, Option<String>)>,
+}
 
-    // Build threads via union-find
+/// Group messages into threads via union-find over Message-ID, In-Reply-To
+/// and References, and work out each thread's participants. The thread id
+/// comes from the smallest Message-ID in the thread, so rethreading part of
+/// an account gives the same ids as rethreading all of it.
+fn assign_threads(thread_msgs: &[ThreadMsg], self_emails: &[String]) -> Vec<Thread> {
     let mut uf = UnionFind::new();
-    for msg in &thread_msgs {
-        if let Some(ref reply_to) = msg.in_reply_to {
-            uf.union(&msg.message_id, reply_to);
-        }
-        for ref_id in &msg.references {
+    for msg in thread_msgs {
+        for ref_id in msg.refs() {
             uf.union(&msg.message_id, ref_id);
         }
     }
 
     // Group messages by thread root
     // Handle empty message_id: use db_id to avoid merging all no-ID messages
-    let mut threads: HashMap<String, Vec<&ThreadMsg>> = HashMap::new();
-    for msg in &thread_msgs {
+    let mut groups: HashMap<String, Vec<&ThreadMsg>> = HashMap::new();
+    for msg in thread_msgs {
         let root = if msg.message_id.is_empty() {
             msg.db_id.clone()
         } else {
             uf.find(&msg.message_id)
         };
-        threads.entry(root).or_default().push(msg);
+        groups.entry(root).or_default().push(msg);
     }
 
     // Per-thread: compute participant union + participant changes
-    // Maps db_id → (participant_key, conversation_id, participant_changes, thread_id)
-    let mut msg_assignments: HashMap<String, (String, String, Option<String>, String)> = HashMap::new();
-
-    for (root, msgs) in &threads {
-        // Compute thread_id from thread root
-        let thread_id = compute_thread_id(root);
-
+    let mut threads = Vec::with_capacity(groups.len());
+    for (root, mut msgs) in groups {
         // Union of all participants across the thread
         let mut all_participants: BTreeSet<String> = BTreeSet::new();
-        for msg in msgs {
+        for msg in &msgs {
             let p = collect_participants(
-                &msg.from_address, &msg.to_addresses, &msg.cc_addresses, &self_emails,
+                &msg.from_address, &msg.to_addresses, &msg.cc_addresses, self_emails,
             );
             all_participants.extend(p);
         }
@@ -113,14 +367,14 @@ pub fn rebuild_conversations(pool: &DbPool, account_id: &str) -> Result<usize, E
         let conversation_id = compute_conversation_id(&participant_key);
 
         // Compute participant changes per message (sorted by date)
-        let mut sorted: Vec<&&ThreadMsg> = msgs.iter().collect();
-        sorted.sort_by_key(|m| m.date);
+        msgs.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.db_id.cmp(&b.db_id)));
 
         let mut prev_participants: BTreeSet<String> = BTreeSet::new();
+        let mut assignments = Vec::with_capacity(msgs.len());
 
-        for (i, msg) in sorted.iter().enumerate() {
+        for (i, msg) in msgs.iter().enumerate() {
             let current_participants = collect_participants(
-                &msg.from_address, &msg.to_addresses, &msg.cc_addresses, &self_emails,
+                &msg.from_address, &msg.to_addresses, &msg.cc_addresses, self_emails,
             );
 
             let changes = if i > 0 {
@@ -139,208 +393,245 @@ pub fn rebuild_conversations(pool: &DbPool, account_id: &str) -> Result<usize, E
                 None
             };
 
-            msg_assignments.insert(
-                msg.db_id.clone(),
-                (participant_key.clone(), conversation_id.clone(), changes, thread_id.clone()),
-            );
-
+            assignments.push((msg.db_id.clone(), changes));
             prev_participants = current_participants;
         }
+
+        threads.push(Thread {
+            id: compute_thread_id(&root),
+            participant_key,
+            conversation_id,
+            refs: msgs.iter().flat_map(|m| m.refs()).map(str::to_string).collect(),
+            messages: assignments,
+        });
     }
+    threads
+}
 
-    // Write thread assignments back to messages
-    {
-        let tx = conn.unchecked_transaction()?;
-        for (db_id, (pkey, conv_id, changes, thread_id)) in &msg_assignments {
-            tx.execute(
-                "UPDATE messages SET participant_key = ?1, conversation_id = ?2, participant_changes = ?3, thread_id = ?4
-                 WHERE id = ?5",
-                params![pkey, conv_id, changes, thread_id, db_id],
-            )?;
+/// Write thread assignments back to messages, and record which thread
+/// each Message-ID is in.
+fn write_threads(conn: &Connection, account_id: &str, threads: &[Thread]) -> Result<(), EddieError> {
+    let mut update = conn.prepare_cached(
+        "UPDATE messages SET participant_key = ?1, conversation_id = ?2, participant_changes = ?3, thread_id = ?4
+         WHERE id = ?5",
+    )?;
+    let mut insert_ref = conn.prepare_cached(
+        "INSERT OR REPLACE INTO thread_refs (account_id, ref_id, thread_id) VALUES (?1, ?2, ?3)",
+    )?;
+    for thread in threads {
+        for (db_id, changes) in &thread.messages {
+            update.execute(params![thread.participant_key, thread.conversation_id, changes, thread.id, db_id])?;
+        }
+        for ref_id in &thread.refs {
+            insert_ref.execute(params![account_id, ref_id, thread.id])?;
         }
-        tx.commit()?;
     }
+    Ok(())
+}
 
-    // ========================================
-    // PHASE 2: Aggregate conversations
-    // ========================================
-
-    let conv_map = {
-        let mut stmt = conn
-            .prepare(
-                "SELECT m.conversation_id, m.participant_key, m.date,
-                        m.distilled_text,
-                        m.from_address, m.from_name, m.classification, m.is_important,
-                        m.imap_flags,
-                        CASE WHEN e.id IS NOT NULL THEN 1 ELSE 0 END AS is_trusted
-                 FROM messages m
-                 LEFT JOIN entities e ON e.email = m.from_address AND e.account_id = m.account_id
-                                       AND e.trust_level NOT IN ('user', 'alias', 'blocked')
-                 WHERE m.account_id = ?1
-                 ORDER BY m.conversation_id, m.date DESC",
-            )?;
-
-        let rows = stmt
-            .query_map(params![account_id], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,         // conversation_id
-                    row.get::<_, String>(1)?,         // participant_key
-                    row.get::<_, i64>(2)?,            // date
-                    row.get::<_, Option<String>>(3)?, // distilled_text
-                    row.get::<_, String>(4)?,         // from_address
-                    row.get::<_, Option<String>>(5)?, // from_name
-                    row.get::<_, Option<String>>(6)?, // classification
-                    row.get::<_, i32>(7)?,            // is_important
-                    row.get::<_, String>(8)?,         // imap_flags
-                    row.get::<_, i32>(9)?,            // is_trusted
-                ))
-            })?;
-
-        let mut map: HashMap<String, ConversationBuilder> = HashMap::new();
-        for row in rows {
-            let (conv_id, participant_key, date, distilled_text,
-                 from_address, _from_name, classification, is_important, imap_flags, is_trusted) =
-                row?;
-
-            let preview = distilled_text
-                .as_deref()
-                .and_then(|t| t.lines().map(|l| l.trim()).find(|l| !l.is_empty()))
-                .map(|s| s.to_string());
-
-            let builder = map.entry(conv_id.clone()).or_insert_with(|| {
-                ConversationBuilder {
-                    id: conv_id,
-                    participant_key,
-                    last_message_date: date,
-                    last_message_preview: preview,
-                    has_chat: false,
-                    has_trusted: false,
-                    has_important: false,
-                    unread_count: 0,
-                    total_count: 0,
-                    names: BTreeMap::new(),
-                    initial_sender_email: None,
-                }
-            });
+/// Aggregate the messages of every conversation in the account, or of just
+/// one, into conversation rows.
+fn aggregate(
+    conn: &Connection,
+    account_id: &str,
+    conversation_id: Option<&str>,
+    people: &People,
+) -> Result<HashMap<String, ConversationBuilder>, EddieError> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT m.conversation_id, m.participant_key, m.date,
+                m.distilled_text,
+                m.from_address, m.from_name, m.classification, m.is_important,
+                m.imap_flags,
+                CASE WHEN e.id IS NOT NULL THEN 1 ELSE 0 END AS is_trusted,
+                m.id, m.gmail_labels, m.imap_folder
+         FROM messages m
+         LEFT JOIN entities e ON e.email = m.from_address AND e.account_id = m.account_id
+                               AND e.trust_level NOT IN ('user', 'alias', 'blocked')
+         WHERE m.account_id = ?1 AND m.classification IS NOT 'bounce' {}
+         ORDER BY m.conversation_id, m.date DESC, m.id DESC",
+        if conversation_id.is_some() { "AND m.conversation_id = ?2" } else { "" }
+    ))?;
+
+    let map_row = |row: &rusqlite::Row| {
+        Ok((
+            row.get::<_, String>(0)?,         // conversation_id
+            row.get::<_, String>(1)?,         // participant_key
+            row.get::<_, i64>(2)?,            // date
+            row.get::<_, Option<String>>(3)?, // distilled_text
+            row.get::<_, String>(4)?,         // from_address
+            row.get::<_, Option<String>>(5)?, // from_name
+            row.get::<_, Option<String>>(6)?, // classification
+            row.get::<_, i32>(7)?,            // is_important
+            row.get::<_, String>(8)?,         // imap_flags
+            row.get::<_, i32>(9)?,            // is_trusted
+            row.get::<_, String>(10)?,        // id
+            row.get::<_, String>(11)?,        // gmail_labels
+            row.get::<_, String>(12)?,        // imap_folder
+        ))
+    };
+    let rows = match conversation_id {
+        Some(conversation_id) => stmt.query_map(params![account_id, conversation_id], map_row)?,
+        None => stmt.query_map(params![account_id], map_row)?,
+    };
 
-            // Track initial sender: query is ordered date DESC, so last
-            // non-self from_address we see is from the earliest message.
-            if !is_self(&from_address, &self_emails) {
-                builder.initial_sender_email = Some(from_address.to_lowercase());
+    let mut map: HashMap<String, ConversationBuilder> = HashMap::new();
+    for row in rows {
+        let (conv_id, participant_key, date, distilled_text,
+             from_address, from_name, classification, is_important, imap_flags, is_trusted,
+             id, gmail_labels, imap_folder) = row?;
+
+        let preview = distilled_text
+            .as_deref()
+            .and_then(|t| t.lines().map(|l| l.trim()).find(|l| !l.is_empty()))
+            .map(|s| s.to_string());
+
+        let builder = map.entry(conv_id.clone()).or_insert_with(|| {
+            ConversationBuilder {
+                id: conv_id,
+                participant_key,
+                last_message_date: date,
+                last_message_preview: preview,
+                last_message_id: id,
+                last_message_from_name: from_name,
+                last_message_is_sent: messages::is_sent(&gmail_labels, &imap_folder, &from_address, &people.self_emails),
+                has_chat: false,
+                has_trusted: false,
+                has_important: false,
+                unread_count: 0,
+                total_count: 0,
+                names: BTreeMap::new(),
+                initial_sender_email: None,
             }
+        });
 
-            if classification.as_deref() == Some("chat") {
-                builder.has_chat = true;
-            }
-            if is_trusted != 0 {
-                builder.has_trusted = true;
-            }
-            if is_important != 0 {
-                builder.has_important = true;
-            }
-            let flags: Vec<String> = serde_json::from_str(&imap_flags).unwrap_or_default();
-            if !flags.iter().any(|f| f == "Seen") {
-                builder.unread_count += 1;
-            }
-            builder.total_count += 1;
+        // Track initial sender: query is ordered date DESC, so last
+        // non-self from_address we see is from the earliest message.
+        if !is_self(&from_address, &people.self_emails) {
+            builder.initial_sender_email = Some(from_address.to_lowercase());
         }
-        map
-    };
 
-    // Populate participant names from lookup
-    let mut conv_map = conv_map; // make mutable
-    for builder in conv_map.values_mut() {
+        if classification.as_deref() == Some("chat") {
+            builder.has_chat = true;
+        }
+        if is_trusted != 0 {
+            builder.has_trusted = true;
+        }
+        if is_important != 0 {
+            builder.has_important = true;
+        }
+        let flags: Vec<String> = serde_json::from_str(&imap_flags).unwrap_or_default();
+        if !flags.iter().any(|f| f == "Seen") {
+            builder.unread_count += 1;
+        }
+        builder.total_count += 1;
+    }
+
+    // Name participants after the latest message they sent with a name
+    let mut name_stmt = conn.prepare_cached(
+        "SELECT from_name FROM messages
+         WHERE account_id = ?1 AND lower(from_address) = ?2 AND from_name IS NOT NULL
+         ORDER BY date DESC, id DESC
+         LIMIT 1",
+    )?;
+    for builder in map.values_mut() {
         let mut names: BTreeMap<String, String> = BTreeMap::new();
         for addr in builder.participant_key.split('\n') {
-            if !addr.is_empty() && !is_self(addr, &self_emails) {
-                let name = sender_names.get(addr).cloned()
-                    .unwrap_or_else(|| addr.to_string());
+            if !addr.is_empty() && !is_self(addr, &people.self_emails) {
+                let mut rows = name_stmt.query(params![account_id, addr])?;
+                let name = match rows.next()? {
+                    Some(row) => row.get(0)?,
+                    None => addr.to_string(),
+                };
                 names.insert(addr.to_string(), name);
             }
         }
         builder.names = names;
     }
+    Ok(map)
+}
 
-    // ========================================
-    // PHASE 3: Upsert conversations
-    // ========================================
-
-    let blocked_emails = entities::get_blocked_emails(pool, account_id)?;
-    let trusted_emails = entities::get_trusted_emails(pool, account_id)?;
-
-    let tx = conn.unchecked_transaction()?;
-
-    // Clear old conversations and rebuild fresh
-    tx.execute(
-        "DELETE FROM conversations WHERE account_id = ?1",
-        params![account_id],
-    )?;
-
-    let now = chrono::Utc::now().timestamp_millis();
-    let mut count = 0;
-
-    for builder in conv_map.values() {
-        // Skip blocked conversations:
-        // - 1:1: skip if the single participant is blocked
-        // - Group: skip if the initial sender is blocked
-        if !blocked_emails.is_empty() {
-            let participants: Vec<&str> = builder.participant_key.split('\n')
-                .filter(|s| !s.is_empty())
-                .collect();
-            let is_group = participants.len() > 1;
-            if is_group {
-                if let Some(ref sender) = builder.initial_sender_email {
-                    if blocked_emails.contains(sender.as_str()) {
-                        continue;
-                    }
+/// Upsert a conversation row, keeping its muted and pinned state. Returns
+/// false, and writes nothing, if the conversation is blocked.
+fn write_conversation(
+    conn: &Connection,
+    account_id: &str,
+    builder: &ConversationBuilder,
+    people: &People,
+    now: i64,
+) -> Result<bool, EddieError> {
+    // Skip blocked conversations:
+    // - 1:1: skip if the single participant is blocked
+    // - Group: skip if the initial sender is blocked
+    if !people.blocked.is_empty() {
+        let participants: Vec<&str> = builder.participant_key.split('\n')
+            .filter(|s| !s.is_empty())
+            .collect();
+        let is_group = participants.len() > 1;
+        if is_group {
+            if let Some(ref sender) = builder.initial_sender_email {
+                if people.blocked.contains(sender.as_str()) {
+                    return Ok(false);
                 }
-            } else if !participants.is_empty() && participants.iter().all(|p| blocked_emails.contains(*p)) {
-                continue;
             }
+        } else if !participants.is_empty() && participants.iter().all(|p| people.blocked.contains(*p)) {
+            return Ok(false);
         }
+    }
 
-        // A conversation is trusted if any participant (sender, To, or Cc) is a connection
-        let has_trusted = builder.has_trusted || builder.participant_key.split('\n')
-            .any(|p| !p.is_empty() && trusted_emails.contains(p));
-
-        let names_json = serde_json::to_string(&builder.names).ok();
-
-        let classification = if builder.has_chat && has_trusted {
-            Some("connections")
-        } else if builder.has_chat {
-            Some("others")
-        } else {
-            Some("automated")
-        };
+    // A conversation is trusted if any participant (sender, To, or Cc) is a connection
+    let has_trusted = builder.has_trusted || builder.participant_key.split('\n')
+        .any(|p| !p.is_empty() && people.trusted.contains(p));
 
-        tx.execute(
-            "INSERT INTO conversations (
-                id, account_id, participant_key, participant_names,
-                classification, last_message_date, last_message_preview,
-                unread_count, total_count, is_muted, is_pinned, is_important, updated_at,
-                initial_sender_email
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, 0, ?10, ?11, ?12)",
-            params![
-                builder.id,
-                account_id,
-                builder.participant_key,
-                names_json,
-                classification,
-                builder.last_message_date,
-                builder.last_message_preview,
-                builder.unread_count,
-                builder.total_count,
-                builder.has_important,
-                now,
-                builder.initial_sender_email,
-            ],
-        )?;
+    let names_json = serde_json::to_string(&builder.names).ok();
 
-        count += 1;
-    }
+    let classification = if builder.has_chat && has_trusted {
+        "connections"
+    } else if builder.has_chat {
+        "others"
+    } else {
+        "automated"
+    };
 
-    tx.commit()?;
-    Ok(count)
+    conn.prepare_cached(
+        "INSERT INTO conversations (
+            id, account_id, participant_key, participant_names,
+            classification, last_message_date, last_message_preview,
+            unread_count, total_count, is_important, updated_at,
+            initial_sender_email, last_message_id, last_message_from_name, last_message_is_sent
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+        ON CONFLICT(id) DO UPDATE SET
+            participant_key = excluded.participant_key,
+            participant_names = excluded.participant_names,
+            classification = excluded.classification,
+            last_message_date = excluded.last_message_date,
+            last_message_preview = excluded.last_message_preview,
+            unread_count = excluded.unread_count,
+            total_count = excluded.total_count,
+            is_important = excluded.is_important,
+            updated_at = excluded.updated_at,
+            initial_sender_email = excluded.initial_sender_email,
+            last_message_id = excluded.last_message_id,
+            last_message_from_name = excluded.last_message_from_name,
+            last_message_is_sent = excluded.last_message_is_sent
+        WHERE conversations.account_id = excluded.account_id",
+    )?.execute(params![
+        builder.id,
+        account_id,
+        builder.participant_key,
+        names_json,
+        classification,
+        builder.last_message_date,
+        builder.last_message_preview,
+        builder.unread_count,
+        builder.total_count,
+        builder.has_important,
+        now,
+        builder.initial_sender_email,
+        builder.last_message_id,
+        builder.last_message_from_name,
+        builder.last_message_is_sent,
+    ])?;
+    Ok(true)
 }
 
 struct ConversationBuilder {
@@ -348,6 +639,9 @@ struct ConversationBuilder {
     participant_key: String,
     last_message_date: i64,
     last_message_preview: Option<String>,
+    last_message_id: String,
+    last_message_from_name: Option<String>,
+    last_message_is_sent: bool,
     has_chat: bool,
     has_trusted: bool,
     has_important: bool,
@@ -377,69 +671,170 @@ pub struct Conversation {
     pub initial_sender_email: Option<String>,
 }
 
+#[derive(serde::Serialize)]
+pub struct ConversationPage {
+    pub conversations: Vec<Conversation>,
+    /// Pass back as `cursor` for the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Most conversations returned per page.
+const MAX_LIMIT: u32 = 100;
+
+const CONVERSATION_COLUMNS: &str =
+    "c.id, c.account_id, c.participant_key, c.participant_names,
+     c.classification, c.last_message_date, c.last_message_preview,
+     c.unread_count, c.is_muted, c.is_pinned, c.is_important, c.updated_at,
+     c.total_count, c.last_message_from_name, c.last_message_is_sent,
+     c.initial_sender_email";
+
+fn map_conversation_row(row: &rusqlite::Row) -> rusqlite::Result<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        participant_key: row.get(2)?,
+        participant_names: row.get(3)?,
+        classification: row.get(4)?,
+        last_message_date: row.get(5)?,
+        last_message_preview: row.get(6)?,
+        last_message_is_sent: row.get::<_, Option<i32>>(14)?.unwrap_or(0) != 0,
+        last_message_from_name: row.get(13)?,
+        unread_count: row.get(7)?,
+        is_muted: row.get::<_, i32>(8)? != 0,
+        is_pinned: row.get::<_, i32>(9)? != 0,
+        is_important: row.get::<_, i32>(10)? != 0,
+        updated_at: row.get(11)?,
+        total_count: row.get(12)?,
+        initial_sender_email: row.get(15)?,
+    })
+}
+
+/// All conversations of an account, or with `view_id` only those with a
+/// message matching that smart view's query.
 pub fn fetch_conversations(
     pool: &DbPool,
     account_id: &str,
+    view_id: Option<&str>,
 ) -> Result<Vec<Conversation>, EddieError> {
-    let self_emails = entities::get_self_emails(pool, account_id)?;
-    let conn = pool.get()?;
-    let mut stmt = conn
-        .prepare(
-            "SELECT c.id, c.account_id, c.participant_key, c.participant_names,
-                    c.classification, c.last_message_date,
-                    lm.distilled_text,
-                    c.unread_count, c.is_muted, c.is_pinned, c.is_important, c.updated_at,
-                    c.total_count,
-                    lm.from_name, lm.from_address, lm.gmail_labels, lm.imap_folder,
-                    c.initial_sender_email
-             FROM conversations c
-             LEFT JOIN (
-                 SELECT conversation_id, distilled_text, from_name, from_address,
-                        gmail_labels, imap_folder,
-                        ROW_NUMBER() OVER (PARTITION BY conversation_id ORDER BY date DESC) AS rn
-                 FROM messages
-                 WHERE account_id = ?1
-             ) lm ON lm.conversation_id = c.id AND lm.rn = 1
-             WHERE c.account_id = ?1
-             ORDER BY c.last_message_date DESC",
-        )?;
+    let mut values: Vec<Value> = vec![Value::from(account_id.to_string())];
+    let mut view_filter = String::new();
+    if let Some(view_id) = view_id {
+        let view = smart_views::get_view(pool, account_id, view_id)?;
+        if let Some(query) = search_query::parse(&view.query)? {
+            view_filter = format!(
+                "AND c.id IN (SELECT m.conversation_id FROM messages m WHERE m.account_id = ?1 AND ({}))",
+                search::message_condition(&query, &mut values)
+            );
+        }
+    }
 
-    let rows = stmt
-        .query_map(params![account_id], |row| {
-            let from_name: Option<String> = row.get(13)?;
-            let from_address: Option<String> = row.get(14)?;
-            let gmail_labels: Option<String> = row.get(15)?;
-            let imap_folder: Option<String> = row.get(16)?;
-            let is_sent = match (&from_address, &gmail_labels, &imap_folder) {
-                (Some(addr), Some(labels), Some(folder)) =>
-                    messages::is_sent(labels, folder, addr, &self_emails),
-                _ => false,
-            };
-            Ok(Conversation {
-                id: row.get(0)?,
-                account_id: row.get(1)?,
-                participant_key: row.get(2)?,
-                participant_names: row.get(3)?,
-                classification: row.get(4)?,
-                last_message_date: row.get(5)?,
-                last_message_preview: row.get(6)?,
-                last_message_is_sent: is_sent,
-                last_message_from_name: from_name,
-                unread_count: row.get(7)?,
-                is_muted: row.get::<_, i32>(8)? != 0,
-                is_pinned: row.get::<_, i32>(9)? != 0,
-                is_important: row.get::<_, i32>(10)? != 0,
-                updated_at: row.get(11)?,
-                total_count: row.get(12)?,
-                initial_sender_email: row.get(17)?,
-            })
-        })?;
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM conversations c
+         WHERE c.account_id = ?1 {}
+         ORDER BY c.is_pinned DESC, c.last_message_date DESC, c.id DESC",
+        CONVERSATION_COLUMNS, view_filter
+    ))?;
+    let conversations = stmt.query_map(params_from_iter(values), map_conversation_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(conversations)
+}
 
-    let mut conversations = Vec::new();
-    for row in rows {
-        conversations.push(row?);
+/// One page of an account's conversations in list order: pinned first, then
+/// newest first. Optionally only those of one `classification`, or only
+/// pinned or unpinned ones. `cursor` is the `next_cursor` of the previous
+/// page; pages pick up after the last conversation shown, so conversations
+/// changing in between don't shift the next page.
+pub fn fetch_conversation_page(
+    pool: &DbPool,
+    account_id: &str,
+    classification: Option<&str>,
+    pinned: Option<bool>,
+    limit: u32,
+    cursor: Option<&str>,
+) -> Result<ConversationPage, EddieError> {
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let mut values: Vec<Value> = vec![Value::from(account_id.to_string())];
+    let mut conditions = String::new();
+    if let Some(classification) = classification {
+        values.push(Value::from(classification.to_string()));
+        conditions.push_str(&format!(" AND c.classification = ?{}", values.len()));
     }
-    Ok(conversations)
+    if let Some(pinned) = pinned {
+        values.push(Value::from(pinned as i64));
+        conditions.push_str(&format!(" AND c.is_pinned = ?{}", values.len()));
+    }
+    if let Some(cursor) = cursor {
+        let (pinned, date, id) = parse_conversation_cursor(cursor)?;
+        values.extend([Value::from(pinned), Value::from(date), Value::from(id)]);
+        let n = values.len();
+        conditions.push_str(&format!(
+            " AND (c.is_pinned, c.last_message_date, c.id) < (?{}, ?{}, ?{})",
+            n - 2, n - 1, n
+        ));
+    }
+    values.push(Value::from(limit as i64 + 1));
+
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM conversations c
+         WHERE c.account_id = ?1{}
+         ORDER BY c.is_pinned DESC, c.last_message_date DESC, c.id DESC
+         LIMIT ?{}",
+        CONVERSATION_COLUMNS, conditions, values.len()
+    ))?;
+    // One extra row tells whether there is a next page
+    let mut conversations = stmt.query_map(params_from_iter(values), map_conversation_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if conversations.len() > limit as usize {
+        conversations.truncate(limit as usize);
+        conversations.last().map(|c| format!("{}:{}:{}", c.is_pinned as i32, c.last_message_date, c.id))
+    } else {
+        None
+    };
+    Ok(ConversationPage { conversations, next_cursor })
+}
+
+/// (is_pinned, last_message_date, id) from a `fetch_conversation_page` cursor
+fn parse_conversation_cursor(cursor: &str) -> Result<(i64, i64, String), EddieError> {
+    let invalid = || EddieError::InvalidInput(format!("Invalid conversation cursor: {}", cursor));
+    let mut parts = cursor.splitn(3, ':');
+    let pinned = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+    let date = parts.next().and_then(|d| d.parse().ok()).ok_or_else(invalid)?;
+    let id = parts.next().filter(|id| !id.is_empty()).ok_or_else(invalid)?;
+    Ok((pinned, date, id.to_string()))
+}
+
+/// Mute or unmute a conversation. Survives `rebuild_conversations`.
+pub fn set_muted(
+    pool: &DbPool,
+    account_id: &str,
+    conversation_id: &str,
+    muted: bool,
+) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "UPDATE conversations SET is_muted = ?1 WHERE id = ?2 AND account_id = ?3",
+        params![muted as i32, conversation_id, account_id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Pin or unpin a conversation. Pinned conversations sort first in
+/// `fetch_conversations` and survive `rebuild_conversations`.
+pub fn set_pinned(
+    pool: &DbPool,
+    account_id: &str,
+    conversation_id: &str,
+    pinned: bool,
+) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "UPDATE conversations SET is_pinned = ?1 WHERE id = ?2 AND account_id = ?3",
+        params![pinned as i32, conversation_id, account_id],
+    )?;
+    Ok(rows > 0)
 }
 
 // ----- Union-Find -----
@@ -470,7 +865,11 @@ impl UnionFind {
     fn union(&mut self, a: &str, b: &str) {
         let ra = self.find(a);
         let rb = self.find(b);
-        if ra != rb {
+        // The smaller root wins, so a thread's root is its smallest id
+        // whatever order the messages come in
+        if ra < rb {
+            self.parent.insert(rb, ra);
+        } else if rb < ra {
             self.parent.insert(ra, rb);
         }
     }
@@ -509,30 +908,6 @@ fn collect_participants(
     participants
 }
 
-// Build a name lookup from all messages in the account
-fn name_lookup(
-    pool: &DbPool,
-    account_id: &str,
-) -> Result<HashMap<String, String>, EddieError> {
-    let conn = pool.get()?;
-
-    let names: HashMap<String, String> = {
-        let mut stmt = conn.prepare(
-            "SELECT DISTINCT from_address, from_name FROM messages
-            WHERE account_id = ?1 AND from_name IS NOT NULL"
-        )?;
-
-        let rows = stmt.query_map(params![account_id], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-        })?;
-
-        rows.filter_map(|r| r.ok())
-            .map(|(addr, name)| (addr.to_lowercase(), name))
-            .collect()
-    };
-    Ok(names)
-}
-
 pub fn get_connection_emails(pool: &DbPool, account_id: &str) -> Result<Vec<String>, EddieError> {
     let conn = pool.get()?;
     let mut stmt = conn.prepare(
@@ -607,3 +982,284 @@ pub fn count_trust_contacts(pool: &DbPool, account_id: &str) -> Result<usize, Ed
     )?;
     Ok(count as usize)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::{entities, test_support};
+
+    fn pool() -> DbPool {
+        let pool = test_support::pool();
+        entities::insert_entity(&pool, "acc1", "me@example.com", "account", "user").unwrap();
+        pool
+    }
+
+    /// Insert a chat message from `from` to me, replying to `reply_to`.
+    fn insert(pool: &DbPool, id: &str, date: i64, from: &str, reply_to: Option<&str>) {
+        pool.get().unwrap().execute(
+            "INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address, from_name,
+                                   to_addresses, in_reply_to, references_ids, fetched_at, classification,
+                                   distilled_text, participant_key, conversation_id)
+             VALUES (?1, 'acc1', ?1 || '@x', ?2, 'INBOX', ?2, ?3, upper(?3), '[\"me@example.com\"]', ?4,
+                     CASE WHEN ?4 IS NULL THEN '[]' ELSE json_array(?4) END, 0, 'chat', 'text of ' || ?1, '', '')",
+            params![id, date, from, reply_to.map(|r| format!("{}@x", r))],
+        ).unwrap();
+    }
+
+    /// Everything conversation maintenance writes, minus timestamps.
+    fn snapshot(pool: &DbPool) -> Vec<String> {
+        let conn = pool.get().unwrap();
+        let mut rows = Vec::new();
+        for sql in [
+            "SELECT id, participant_key, participant_names, classification, last_message_date,
+                    last_message_preview, unread_count, total_count, is_muted, is_pinned, initial_sender_email
+             FROM conversations ORDER BY id",
+            "SELECT id, participant_key, conversation_id, participant_changes, thread_id FROM messages ORDER BY id",
+            "SELECT ref_id, thread_id FROM thread_refs ORDER BY ref_id",
+        ] {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let columns = stmt.column_count();
+            let mut result = stmt.query([]).unwrap();
+            while let Some(row) = result.next().unwrap() {
+                let values: Vec<String> = (0..columns)
+                    .map(|i| format!("{:?}", row.get::<_, rusqlite::types::Value>(i).unwrap()))
+                    .collect();
+                rows.push(values.join(" | "));
+            }
+        }
+        rows
+    }
+
+    /// Update incrementally, then check a full rebuild has nothing to add.
+    fn assert_update_matches_rebuild(pool: &DbPool) -> usize {
+        let count = update_conversations(pool, "acc1").unwrap();
+        let updated = snapshot(pool);
+        rebuild_conversations(pool, "acc1").unwrap();
+        assert_eq!(updated, snapshot(pool));
+        count
+    }
+
+    fn conversation_count(pool: &DbPool) -> i64 {
+        pool.get().unwrap().query_row("SELECT COUNT(*) FROM conversations", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_update_matches_full_rebuild() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        insert(&pool, "b1", 2, "bo@example.org", None);
+        insert(&pool, "c1", 3, "cy@example.org", None);
+        assert_update_matches_rebuild(&pool);
+        assert_eq!(conversation_count(&pool), 3);
+
+        // A reply that references both threads joins them into one group conversation
+        insert(&pool, "b2", 4, "bo@example.org", Some("a1"));
+        pool.get().unwrap().execute(
+            "UPDATE messages SET references_ids = '[\"a1@x\", \"b1@x\"]', cc_addresses = '[\"anna@example.org\"]'
+             WHERE id = 'b2'",
+            [],
+        ).unwrap();
+        assert_update_matches_rebuild(&pool);
+        assert_eq!(conversation_count(&pool), 2);
+
+        // Removing it splits them again
+        messages::delete_by_ids(&pool, &["b2".to_string()]).unwrap();
+        assert_update_matches_rebuild(&pool);
+        assert_eq!(conversation_count(&pool), 3);
+
+        entities::insert_entity(&pool, "acc1", "cy@example.org", "manual", "blocked").unwrap();
+        entities::insert_entity(&pool, "acc1", "anna@example.org", "manual", "connection").unwrap();
+        assert_update_matches_rebuild(&pool);
+        assert_eq!(conversation_count(&pool), 2);
+        assert_eq!(update_conversations(&pool, "acc1").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_thread_ids_do_not_depend_on_message_order() {
+        let msg = |id: &str, refs: &[&str]| ThreadMsg {
+            db_id: id.to_string(),
+            message_id: format!("{}@x", id),
+            in_reply_to: refs.last().map(|r| format!("{}@x", r)),
+            references: refs.iter().map(|r| format!("{}@x", r)).collect(),
+            date: 0,
+            from_address: "anna@example.org".to_string(),
+            to_addresses: vec!["me@example.com".to_string()],
+            cc_addresses: Vec::new(),
+            conversation_id: None,
+        };
+        // "a0" is only ever referenced, never synced
+        let mut msgs = vec![
+            msg("d4", &["c3"]),
+            msg("b2", &["a0"]),
+            msg("c3", &["b2"]),
+            msg("e5", &["d4", "a0"]),
+        ];
+        let self_emails = ["me@example.com".to_string()];
+        let expected = compute_thread_id("a0@x");
+        // Every rotation, forwards and backwards
+        for round in 0..2 * msgs.len() {
+            if round == msgs.len() {
+                msgs.reverse();
+            }
+            msgs.rotate_left(1);
+            let threads = assign_threads(&msgs, &self_emails);
+            assert_eq!(threads.len(), 1);
+            assert_eq!(threads[0].id, expected);
+        }
+    }
+
+    #[test]
+    fn test_name_change_updates_every_conversation_with_the_sender() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        insert(&pool, "b1", 2, "bo@example.org", None);
+        pool.get().unwrap().execute(
+            "UPDATE messages SET cc_addresses = '[\"anna@example.org\"]' WHERE id = 'b1'", [],
+        ).unwrap();
+        assert_update_matches_rebuild(&pool);
+
+        // Anna's new name, in her own thread, shows up in Bo's conversation too
+        insert(&pool, "a2", 3, "anna@example.org", Some("a1"));
+        pool.get().unwrap().execute("UPDATE messages SET from_name = 'Anna B' WHERE id = 'a2'", []).unwrap();
+        assert_update_matches_rebuild(&pool);
+        let names = || -> Vec<String> {
+            let conn = pool.get().unwrap();
+            let mut stmt = conn.prepare("SELECT participant_names FROM conversations ORDER BY participant_key").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap()
+        };
+        assert!(names().iter().all(|n| n.contains("Anna B")), "{:?}", names());
+
+        messages::delete_by_ids(&pool, &["a2".to_string()]).unwrap();
+        assert_update_matches_rebuild(&pool);
+        assert!(names().iter().all(|n| !n.contains("Anna B")), "{:?}", names());
+    }
+
+    #[test]
+    fn test_bounces_stay_out_of_threads() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        insert(&pool, "d1", 2, "mailer-daemon@example.org", Some("a1"));
+        pool.get().unwrap().execute(
+            "UPDATE messages SET classification = 'bounce', conversation_id = NULL WHERE id = 'd1'",
+            [],
+        ).unwrap();
+        assert_update_matches_rebuild(&pool);
+
+        let conn = pool.get().unwrap();
+        let (conversation_id, thread_id): (Option<String>, Option<String>) = conn.query_row(
+            "SELECT conversation_id, thread_id FROM messages WHERE id = 'd1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((conversation_id, thread_id), (None, None));
+        let anna: String = conn
+            .query_row("SELECT conversation_id FROM messages WHERE id = 'a1'", [], |row| row.get(0)).unwrap();
+        drop(conn);
+        assert_eq!(conversation_count(&pool), 1);
+        let ids: Vec<String> = messages::fetch_conversation_messages(&pool, "acc1", &anna).unwrap()
+            .into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["a1"]);
+    }
+
+    #[test]
+    fn test_mute_and_pin_are_scoped_to_the_account() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        update_conversations(&pool, "acc1").unwrap();
+        let anna: String = pool.get().unwrap()
+            .query_row("SELECT conversation_id FROM messages WHERE id = 'a1'", [], |row| row.get(0)).unwrap();
+
+        assert!(!set_pinned(&pool, "acc2", &anna, true).unwrap());
+        assert!(!set_muted(&pool, "acc2", &anna, true).unwrap());
+        assert!(set_pinned(&pool, "acc1", &anna, true).unwrap());
+        assert!(set_muted(&pool, "acc1", &anna, true).unwrap());
+    }
+
+    #[test]
+    fn test_mark_read_updates_only_its_conversation() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        insert(&pool, "a2", 2, "anna@example.org", Some("a1"));
+        insert(&pool, "b1", 3, "bo@example.org", None);
+        update_conversations(&pool, "acc1").unwrap();
+
+        let anna: String = pool.get().unwrap()
+            .query_row("SELECT conversation_id FROM messages WHERE id = 'a1'", [], |row| row.get(0)).unwrap();
+        set_pinned(&pool, "acc1", &anna, true).unwrap();
+        pool.get().unwrap().execute("UPDATE conversations SET updated_at = 0", []).unwrap();
+
+        messages::mark_messages_seen(&pool, &["a2".to_string()]).unwrap();
+        assert_eq!(update_conversations(&pool, "acc1").unwrap(), 1);
+
+        let conn = pool.get().unwrap();
+        let (unread, pinned): (i32, bool) = conn.query_row(
+            "SELECT unread_count, is_pinned FROM conversations WHERE id = ?1",
+            params![anna],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((unread, pinned), (1, true));
+        let untouched: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM conversations WHERE updated_at = 0", [], |row| row.get(0),
+        ).unwrap();
+        assert_eq!(untouched, 1);
+    }
+
+    #[test]
+    fn test_moving_a_message_updates_its_conversation() {
+        let pool = pool();
+        insert(&pool, "a1", 1, "anna@example.org", None);
+        update_conversations(&pool, "acc1").unwrap();
+
+        messages::rehome_message(&pool, "a1", "Sent", 1).unwrap();
+        assert_eq!(update_conversations(&pool, "acc1").unwrap(), 1);
+        let is_sent: bool = pool.get().unwrap()
+            .query_row("SELECT last_message_is_sent FROM conversations", [], |row| row.get(0)).unwrap();
+        assert!(is_sent);
+    }
+
+    #[test]
+    fn test_keyset_pages() {
+        let pool = pool();
+        for (i, from) in ["anna", "bo", "cy", "di", "ed"].iter().enumerate() {
+            insert(&pool, from, i as i64 + 1, &format!("{}@example.org", from), None);
+        }
+        for i in 2..=4 {
+            insert(&pool, &format!("ed{}", i), 10 + i, "ed@example.org", Some("ed"));
+        }
+        update_conversations(&pool, "acc1").unwrap();
+        pool.get().unwrap().execute(
+            "UPDATE conversations SET is_pinned = 1, classification = 'others' WHERE participant_key = 'bo@example.org'",
+            [],
+        ).unwrap();
+
+        let mut cursor = None;
+        let mut keys = Vec::new();
+        loop {
+            let page = fetch_conversation_page(&pool, "acc1", None, None, 2, cursor.as_deref()).unwrap();
+            keys.extend(page.conversations.iter().map(|c| c.participant_key.clone()));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(keys, ["bo", "ed", "di", "cy", "anna"].map(|p| format!("{}@example.org", p)));
+
+        let page = fetch_conversation_page(&pool, "acc1", Some("connections"), Some(false), 10, None).unwrap();
+        assert!(page.conversations.is_empty());
+        let page = fetch_conversation_page(&pool, "acc1", Some("others"), None, 10, None).unwrap();
+        assert_eq!(page.conversations.len(), 5);
+        let ed = fetch_conversation_page(&pool, "acc1", None, Some(false), 1, None).unwrap().conversations.remove(0);
+        assert_eq!(ed.last_message_from_name.as_deref(), Some("ED@EXAMPLE.ORG"));
+        assert_eq!(ed.last_message_preview.as_deref(), Some("text of ed4"));
+        assert!(fetch_conversation_page(&pool, "acc1", None, None, 2, Some("bogus")).is_err());
+
+        let older = messages::fetch_conversation_message_page(&pool, "acc1", &ed.id, 3, None).unwrap();
+        let ids: Vec<&str> = older.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["ed2", "ed3", "ed4"]);
+        assert!(older.messages.iter().all(|m| m.body_html.is_none()));
+        let oldest = messages::fetch_conversation_message_page(&pool, "acc1", &ed.id, 3, older.next_cursor.as_deref()).unwrap();
+        assert_eq!(oldest.messages.len(), 1);
+        assert_eq!(oldest.messages[0].id, "ed");
+        assert!(oldest.next_cursor.is_none());
+    }
+}
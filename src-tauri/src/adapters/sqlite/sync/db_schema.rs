@@ -1,63 +1,99 @@
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
 
 use crate::error::EddieError;
 
-const SCHEMA_VERSION: &str = "2";
+/// One step of the schema history. Migrations are applied in order, each in
+/// its own transaction, and recorded in `schema_migrations`. Released
+/// migrations must never change; schema changes go in a new one at the end.
+struct Migration {
+    version: i64,
+    name: &'static str,
+    up: fn(&Connection) -> Result<(), EddieError>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "initial", up: initial },
+    Migration { version: 2, name: "folder_sync_highest_modseq", up: folder_sync_highest_modseq },
+    Migration { version: 3, name: "folder_sync_exists_count", up: folder_sync_exists_count },
+    Migration { version: 4, name: "accounts_imap_security", up: accounts_imap_security },
+    Migration { version: 5, name: "oauth", up: oauth },
+    Migration { version: 6, name: "attachments", up: attachments },
+    Migration { version: 7, name: "action_queue_backoff", up: action_queue_backoff },
+    Migration { version: 8, name: "action_queue_stages", up: action_queue_stages },
+    Migration { version: 9, name: "action_queue_send_at", up: action_queue_send_at },
+    Migration { version: 10, name: "delivery_failures", up: delivery_failures },
+    Migration { version: 11, name: "messages_fts", up: messages_fts },
+    Migration { version: 12, name: "smart_views", up: smart_views },
+    Migration { version: 13, name: "conversation_changes", up: conversation_changes },
+    Migration { version: 14, name: "conversations_last_message", up: conversations_last_message },
+    Migration { version: 15, name: "messages_nullable_conversation", up: messages_nullable_conversation },
+    Migration { version: 16, name: "conversation_changes_names", up: conversation_changes_names },
+    Migration { version: 17, name: "conversation_changes_folders", up: conversation_changes_folders },
+];
 
 pub fn initialize_schema(conn: &Connection) -> Result<(), EddieError> {
-    // Ensure accounts and settings tables exist first (they survive resets).
-    conn.execute_batch("
-        CREATE TABLE IF NOT EXISTS accounts (
-            id              TEXT PRIMARY KEY,
-            email           TEXT NOT NULL UNIQUE,
-            password        TEXT,
-            display_name    TEXT,
-            imap_host       TEXT NOT NULL,
-            imap_port       INTEGER NOT NULL DEFAULT 993,
-            imap_tls        INTEGER NOT NULL DEFAULT 1,
-            smtp_host       TEXT NOT NULL,
-            smtp_port       INTEGER NOT NULL DEFAULT 587,
-            carddav_url     TEXT,
-            created_at      INTEGER NOT NULL,
-            last_full_sync  INTEGER
-        );
+    reset_unversioned_data(conn)?;
+    run_migrations(conn, MIGRATIONS)
+}
 
-        CREATE TABLE IF NOT EXISTS settings (
-            key        TEXT PRIMARY KEY,
-            value      TEXT NOT NULL,
-            updated_at INTEGER NOT NULL
+/// Apply every migration newer than the database, in order.
+fn run_migrations(conn: &Connection, migrations: &[Migration]) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version     INTEGER PRIMARY KEY,
+            name        TEXT NOT NULL,
+            applied_at  INTEGER NOT NULL
         );
     ")?;
 
-    // Check schema version — if missing or outdated, drop everything else and rebuild.
-    let current_version: Option<String> = conn
+    let current: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if current > latest {
+        return Err(EddieError::Database(format!(
+            "Database schema version {} is newer than this version of the app supports ({})",
+            current, latest
+        )));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx).map_err(|e| EddieError::Database(format!(
+            "Migration {} ({}) failed: {}", migration.version, migration.name, e
+        )))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, chrono::Utc::now().timestamp_millis()],
+        )?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Before versioned migrations, `settings.schema_version` was bumped on every
+/// schema change and everything but accounts and settings was dropped. Version
+/// 2 is what the `initial` migration creates (the later migrations skip any
+/// columns the old startup ALTERs already added); older databases are reset
+/// one last time, the way they always were.
+fn reset_unversioned_data(conn: &Connection) -> Result<(), EddieError> {
+    if table_exists(conn, "schema_migrations")? || !table_exists(conn, "messages")? {
+        return Ok(());
+    }
+    let legacy_version: Option<String> = conn
         .query_row(
             "SELECT value FROM settings WHERE key = 'schema_version'",
             [],
             |row| row.get(0),
         )
         .ok();
-
-    if current_version.as_deref() != Some(SCHEMA_VERSION) {
-        drop_data_tables(conn)?;
-        // Also reset onboarding so accounts re-sync from scratch.
-        conn.execute_batch("UPDATE accounts SET last_full_sync = NULL;")?;
+    if legacy_version.as_deref() == Some("2") {
+        return Ok(());
     }
 
-    create_data_tables(conn)?;
-
-    // Stamp the version
-    conn.execute_batch(&format!(
-        "INSERT OR REPLACE INTO settings (key, value, updated_at) \
-         VALUES ('schema_version', '{}', strftime('%s','now') * 1000);",
-        SCHEMA_VERSION
-    ))?;
-
-    Ok(())
-}
-
-/// Drop all tables except `accounts` and `settings`.
-fn drop_data_tables(conn: &Connection) -> Result<(), EddieError> {
     conn.execute_batch("
         DROP TABLE IF EXISTS messages;
         DROP TABLE IF EXISTS conversations;
@@ -67,12 +103,61 @@ fn drop_data_tables(conn: &Connection) -> Result<(), EddieError> {
         DROP TABLE IF EXISTS folder_sync;
         DROP TABLE IF EXISTS onboarding_tasks;
     ")?;
+    // Also reset onboarding so accounts re-sync from scratch.
+    if table_exists(conn, "accounts")? {
+        conn.execute_batch("UPDATE accounts SET last_full_sync = NULL;")?;
+    }
     Ok(())
 }
 
-/// Create all data tables (idempotent via IF NOT EXISTS).
-fn create_data_tables(conn: &Connection) -> Result<(), EddieError> {
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, EddieError> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |row| row.get(0),
+    )?)
+}
+
+/// Add a column unless it's already there. Databases from before versioned
+/// migrations may have it from the ALTERs that used to run on every start.
+/// Returns whether the column was added.
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool, EddieError> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition))?;
+    }
+    Ok(!exists)
+}
+
+/// The schema_version 2 schema.
+fn initial(conn: &Connection) -> Result<(), EddieError> {
     conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS accounts (
+            id              TEXT PRIMARY KEY,
+            email           TEXT NOT NULL UNIQUE,
+            password        TEXT,
+            display_name    TEXT,
+            imap_host       TEXT NOT NULL,
+            imap_port       INTEGER NOT NULL DEFAULT 993,
+            imap_tls        INTEGER NOT NULL DEFAULT 1,
+            smtp_host       TEXT NOT NULL,
+            smtp_port       INTEGER NOT NULL DEFAULT 587,
+            carddav_url     TEXT,
+            created_at      INTEGER NOT NULL,
+            last_full_sync  INTEGER,
+            smtp_tls        INTEGER NOT NULL DEFAULT 1
+        );
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key        TEXT PRIMARY KEY,
+            value      TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+
         CREATE TABLE IF NOT EXISTS messages (
             id              TEXT PRIMARY KEY,
             account_id      TEXT NOT NULL REFERENCES accounts(id),
@@ -173,7 +258,8 @@ fn create_data_tables(conn: &Connection) -> Result<(), EddieError> {
             max_retries     INTEGER DEFAULT 3,
             created_at      INTEGER NOT NULL,
             completed_at    INTEGER,
-            error           TEXT
+            error           TEXT,
+            message_id      TEXT
         );
 
         CREATE INDEX IF NOT EXISTS idx_action_queue_status ON action_queue(status, created_at);
@@ -205,37 +291,632 @@ fn create_data_tables(conn: &Connection) -> Result<(), EddieError> {
             updated_at  INTEGER,
             PRIMARY KEY (account_id, task)
         );
+
+        DELETE FROM settings WHERE key IN ('schema_version', 'lines_v2_migrated');
     ")?;
 
-    // Migrations for existing databases
-    // Add sent_count column to entities (ignore error if already exists)
-    let _ = conn.execute_batch("ALTER TABLE entities ADD COLUMN sent_count INTEGER DEFAULT 0;");
-    // Add imap_tls column to accounts (defaults to 1 = true for existing accounts)
-    let _ = conn.execute_batch("ALTER TABLE accounts ADD COLUMN imap_tls INTEGER NOT NULL DEFAULT 1;");
-    // Add thread_id column to messages (computed during rebuild_conversations)
-    let _ = conn.execute_batch("ALTER TABLE messages ADD COLUMN thread_id TEXT;");
-
-    // Add revision_hash column to skills (for skill classification engine)
-    let _ = conn.execute_batch("ALTER TABLE skills ADD COLUMN revision_hash TEXT NOT NULL DEFAULT '';");
-    // Add smtp_tls column to accounts (defaults to 1 = true for existing accounts)
-    let _ = conn.execute_batch("ALTER TABLE accounts ADD COLUMN smtp_tls INTEGER NOT NULL DEFAULT 1;");
-
-    // Add message_id column to action_queue (for server confirmation of send actions)
-    let _ = conn.execute_batch("ALTER TABLE action_queue ADD COLUMN message_id TEXT;");
-
-    // Migration: clear domain-based line_groups (Lines now group by sender, not domain).
-    // The 'domain' column is reused to store sender emails.
-    let needs_lines_migration: bool = conn.query_row(
-        "SELECT COUNT(*) = 0 FROM settings WHERE key = 'lines_v2_migrated'",
-        [], |row| row.get(0),
-    ).unwrap_or(true);
-    if needs_lines_migration {
-        let _ = conn.execute("DELETE FROM line_groups", []);
-        let _ = conn.execute(
-            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('lines_v2_migrated', '1', 0)",
-            [],
+    // Older version 2 databases may predate these startup ALTERs
+    add_column(conn, "accounts", "imap_tls", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column(conn, "accounts", "smtp_tls", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column(conn, "entities", "sent_count", "INTEGER DEFAULT 0")?;
+    add_column(conn, "messages", "thread_id", "TEXT")?;
+    add_column(conn, "action_queue", "message_id", "TEXT")?;
+    Ok(())
+}
+
+/// For CONDSTORE flag resync
+fn folder_sync_highest_modseq(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "folder_sync", "highest_modseq", "INTEGER DEFAULT 0")?;
+    Ok(())
+}
+
+/// For skipping unchanged folders in expunge reconcile
+fn folder_sync_exists_count(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "folder_sync", "exists_count", "INTEGER DEFAULT 0")?;
+    add_column(conn, "folder_sync", "uid_next", "INTEGER DEFAULT 0")?;
+    Ok(())
+}
+
+/// Three-way none/starttls/tls, backfilled from the old imap_tls flag
+fn accounts_imap_security(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "accounts", "imap_security", "TEXT")?;
+    add_column(conn, "accounts", "allow_plaintext", "INTEGER NOT NULL DEFAULT 0")?;
+    conn.execute_batch(
+        "UPDATE accounts SET imap_security = CASE imap_tls WHEN 0 THEN 'none' ELSE 'tls' END
+         WHERE imap_security IS NULL;"
+    )?;
+    Ok(())
+}
+
+/// OAuth2 sign-in (oauth_provider NULL = password auth)
+fn oauth(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS oauth_tokens (
+            account_id     TEXT PRIMARY KEY REFERENCES accounts(id),
+            provider       TEXT NOT NULL,
+            access_token   TEXT NOT NULL,
+            refresh_token  TEXT,
+            expires_at     INTEGER NOT NULL,
+            needs_reauth   INTEGER NOT NULL DEFAULT 0
+        );
+    ")?;
+    add_column(conn, "accounts", "oauth_provider", "TEXT")?;
+    Ok(())
+}
+
+fn attachments(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS attachments (
+            id                INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id        TEXT NOT NULL,
+            part_path         TEXT NOT NULL,
+            filename          TEXT,
+            mime_type         TEXT NOT NULL,
+            size_bytes        INTEGER NOT NULL DEFAULT 0,
+            content_id        TEXT,
+            transfer_encoding TEXT NOT NULL,
+            is_inline         INTEGER NOT NULL DEFAULT 0,
+            content_hash      TEXT,
+
+            UNIQUE(message_id, part_path)
+        );
+    ")?;
+    Ok(())
+}
+
+/// Retry backoff. 'failed' becomes terminal, so actions that were
+/// failed-but-retryable go back to pending once.
+fn action_queue_backoff(conn: &Connection) -> Result<(), EddieError> {
+    if add_column(conn, "action_queue", "next_attempt_at", "INTEGER")? {
+        conn.execute_batch(
+            "UPDATE action_queue SET status = 'pending'
+             WHERE status = 'failed' AND retry_count < max_retries;"
+        )?;
+    }
+    Ok(())
+}
+
+/// Checkpointed send pipeline
+fn action_queue_stages(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "action_queue", "stage", "TEXT")?;
+    add_column(conn, "action_queue", "raw_message", "BLOB")?;
+    Ok(())
+}
+
+/// Undo-send window and scheduled send
+fn action_queue_send_at(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "action_queue", "send_at", "INTEGER")?;
+    Ok(())
+}
+
+/// Per-recipient bounces from delivery status notifications
+fn delivery_failures(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS delivery_failures (
+            account_id   TEXT NOT NULL,
+            message_id   TEXT NOT NULL,
+            recipient    TEXT NOT NULL,
+            action       TEXT NOT NULL,
+            status       TEXT,
+            diagnostic   TEXT,
+            reported_at  INTEGER NOT NULL,
+            PRIMARY KEY (account_id, message_id, recipient)
+        );
+    ")?;
+    Ok(())
+}
+
+/// Full-text index for `search::search_messages`, keyed by the messages
+/// rowid and kept in sync by triggers. The index stores its own copy of the
+/// text so snippets work. `remove_diacritics 2` folds é, ü, å and the like,
+/// so "muller" finds "Müller".
+fn messages_fts(conn: &Connection) -> Result<(), EddieError> {
+    let addresses = |row: &str| format!(
+        "{row}.from_address || ' ' || {row}.to_addresses || ' ' || COALESCE({row}.cc_addresses, '') || ' ' || COALESCE({row}.bcc_addresses, '')"
+    );
+    conn.execute_batch(&format!("
+        CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            subject, body_text, distilled_text, from_name, addresses,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        INSERT INTO messages_fts (rowid, subject, body_text, distilled_text, from_name, addresses)
+        SELECT rowid, subject, body_text, distilled_text, from_name, {messages}
+        FROM messages;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, subject, body_text, distilled_text, from_name, addresses)
+            VALUES (new.rowid, new.subject, new.body_text, new.distilled_text, new.from_name, {new});
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+            DELETE FROM messages_fts WHERE rowid = old.rowid;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_update
+        AFTER UPDATE OF subject, body_text, distilled_text, from_name, from_address, to_addresses, cc_addresses, bcc_addresses
+        ON messages
+        WHEN old.subject IS NOT new.subject
+          OR old.body_text IS NOT new.body_text
+          OR old.distilled_text IS NOT new.distilled_text
+          OR old.from_name IS NOT new.from_name
+          OR {old} IS NOT {new}
+        BEGIN
+            DELETE FROM messages_fts WHERE rowid = old.rowid;
+            INSERT INTO messages_fts (rowid, subject, body_text, distilled_text, from_name, addresses)
+            VALUES (new.rowid, new.subject, new.body_text, new.distilled_text, new.from_name, {new});
+        END;
+    ", messages = addresses("messages"), new = addresses("new"), old = addresses("old")))?;
+    Ok(())
+}
+
+/// Saved search queries that `fetch_conversations` can filter by
+fn smart_views(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS smart_views (
+            id          TEXT PRIMARY KEY,
+            account_id  TEXT NOT NULL REFERENCES accounts(id),
+            name        TEXT NOT NULL,
+            query       TEXT NOT NULL,
+            created_at  INTEGER NOT NULL,
+            updated_at  INTEGER NOT NULL,
+
+            UNIQUE(account_id, name)
+        );
+    ")?;
+    Ok(())
+}
+
+/// Incremental conversation maintenance. Triggers queue the messages and
+/// entities that changed since `conversations::update_conversations` last
+/// ran, and `thread_refs` remembers which thread each Message-ID belongs
+/// to so a new message finds the threads it joins without rethreading the
+/// account. Existing accounts get one full rebuild to fill `thread_refs`.
+fn conversation_changes(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        CREATE TABLE IF NOT EXISTS conversation_changes (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id      TEXT NOT NULL,
+            -- thread: a message was added or removed, or its threading or
+            --         participants changed
+            -- conversation: a message changed in a way that only affects
+            --         its conversation's row
+            -- entity: the trust level of a participant changed
+            -- all: our own addresses changed, everything is rebuilt
+            kind            TEXT NOT NULL,
+            message_id      TEXT,  -- messages.id after the change (thread)
+            thread_id       TEXT,  -- messages.thread_id before the change (thread)
+            conversation_id TEXT,  -- messages.conversation_id before the change
+            email           TEXT   -- entities.email (entity)
+        );
+        CREATE INDEX IF NOT EXISTS idx_conversation_changes_account ON conversation_changes(account_id, id);
+
+        CREATE TABLE IF NOT EXISTS thread_refs (
+            account_id  TEXT NOT NULL,
+            ref_id      TEXT NOT NULL,  -- a member's Message-ID, In-Reply-To or References entry
+            thread_id   TEXT NOT NULL,
+            PRIMARY KEY (account_id, ref_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_thread_refs_thread ON thread_refs(account_id, thread_id);
+
+        CREATE INDEX IF NOT EXISTS idx_messages_from_lower ON messages(account_id, lower(from_address));
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO conversation_changes (account_id, kind, message_id)
+            VALUES (new.account_id, 'thread', new.id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_delete AFTER DELETE ON messages BEGIN
+            INSERT INTO conversation_changes (account_id, kind, thread_id, conversation_id)
+            VALUES (old.account_id, 'thread', old.thread_id, old.conversation_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_thread
+        AFTER UPDATE OF message_id, in_reply_to, references_ids, date, from_address, to_addresses, cc_addresses
+        ON messages
+        WHEN old.message_id IS NOT new.message_id
+          OR old.in_reply_to IS NOT new.in_reply_to
+          OR old.references_ids IS NOT new.references_ids
+          OR old.date IS NOT new.date
+          OR old.from_address IS NOT new.from_address
+          OR old.to_addresses IS NOT new.to_addresses
+          OR old.cc_addresses IS NOT new.cc_addresses
+        BEGIN
+            INSERT INTO conversation_changes (account_id, kind, message_id, thread_id, conversation_id)
+            VALUES (new.account_id, 'thread', new.id, old.thread_id, old.conversation_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_conversation
+        AFTER UPDATE OF distilled_text, from_name, classification, is_important, imap_flags
+        ON messages
+        WHEN old.distilled_text IS NOT new.distilled_text
+          OR old.from_name IS NOT new.from_name
+          OR old.classification IS NOT new.classification
+          OR old.is_important IS NOT new.is_important
+          OR old.imap_flags IS NOT new.imap_flags
+        BEGIN
+            INSERT INTO conversation_changes (account_id, kind, conversation_id)
+            VALUES (new.account_id, 'conversation', new.conversation_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS entities_changes_insert AFTER INSERT ON entities BEGIN
+            INSERT INTO conversation_changes (account_id, kind, email)
+            VALUES (new.account_id,
+                    CASE WHEN new.trust_level IN ('user', 'alias') THEN 'all' ELSE 'entity' END,
+                    new.email);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS entities_changes_update
+        AFTER UPDATE OF trust_level ON entities
+        WHEN old.trust_level IS NOT new.trust_level
+        BEGIN
+            INSERT INTO conversation_changes (account_id, kind, email)
+            VALUES (new.account_id,
+                    CASE WHEN new.trust_level IN ('user', 'alias') OR old.trust_level IN ('user', 'alias')
+                         THEN 'all' ELSE 'entity' END,
+                    new.email);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS entities_changes_delete AFTER DELETE ON entities BEGIN
+            INSERT INTO conversation_changes (account_id, kind, email)
+            VALUES (old.account_id,
+                    CASE WHEN old.trust_level IN ('user', 'alias') THEN 'all' ELSE 'entity' END,
+                    old.email);
+        END;
+
+        INSERT INTO conversation_changes (account_id, kind) SELECT id, 'all' FROM accounts;
+    ")?;
+    Ok(())
+}
+
+/// The latest message's sender on conversation rows, so a page of
+/// conversations is read without touching messages, and keyset indexes for
+/// `conversations::fetch_conversation_page` and
+/// `messages::fetch_conversation_message_page`. Existing rows are filled in
+/// here as well as SQL can; the queued rebuild fixes up `last_message_is_sent`
+/// for mail sent from our own address outside a Sent folder.
+fn conversations_last_message(conn: &Connection) -> Result<(), EddieError> {
+    add_column(conn, "conversations", "last_message_id", "TEXT")?;
+    add_column(conn, "conversations", "last_message_from_name", "TEXT")?;
+    add_column(conn, "conversations", "last_message_is_sent", "INTEGER DEFAULT 0")?;
+    conn.execute_batch("
+        UPDATE conversations SET last_message_id = (
+            SELECT m.id FROM messages m
+            WHERE m.account_id = conversations.account_id AND m.conversation_id = conversations.id
+              AND m.classification IS NOT 'bounce'
+            ORDER BY m.date DESC, m.id DESC LIMIT 1
         );
+        UPDATE conversations SET
+            last_message_from_name = (SELECT from_name FROM messages WHERE id = conversations.last_message_id),
+            last_message_is_sent = COALESCE((
+                SELECT gmail_labels LIKE '%Sent%' OR lower(imap_folder) LIKE '%sent%'
+                FROM messages WHERE id = conversations.last_message_id
+            ), 0);
+
+        CREATE INDEX IF NOT EXISTS idx_conversations_page
+            ON conversations(account_id, is_pinned DESC, last_message_date DESC, id DESC);
+        CREATE INDEX IF NOT EXISTS idx_conversations_class_page
+            ON conversations(account_id, classification, is_pinned DESC, last_message_date DESC, id DESC);
+        CREATE INDEX IF NOT EXISTS idx_messages_conversation_page
+            ON messages(account_id, conversation_id, date DESC, id DESC);
+
+        INSERT INTO conversation_changes (account_id, kind) SELECT id, 'all' FROM accounts;
+    ")?;
+    Ok(())
+}
+
+/// Bounces aren't in any conversation, so `messages.conversation_id` becomes
+/// nullable. SQLite can't drop NOT NULL in place: the table is copied with
+/// its rowids (which `messages_fts` is keyed on), and its indexes and
+/// triggers are recreated.
+fn messages_nullable_conversation(conn: &Connection) -> Result<(), EddieError> {
+    let table_sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'messages'",
+        [],
+        |row| row.get(0),
+    )?;
+    let body = table_sql.find('(').map(|i| &table_sql[i..]).unwrap_or_default();
+    let body = body.replacen("conversation_id TEXT NOT NULL", "conversation_id TEXT", 1);
+
+    let dependents: Vec<String> = {
+        let mut stmt = conn.prepare(
+            "SELECT sql FROM sqlite_master
+             WHERE tbl_name = 'messages' AND type IN ('index', 'trigger') AND sql IS NOT NULL",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let columns: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('messages')")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let columns = columns.join(", ");
+
+    conn.execute_batch(&format!("
+        CREATE TABLE messages_new {body};
+        INSERT INTO messages_new (rowid, {columns}) SELECT rowid, {columns} FROM messages;
+        DROP TABLE messages;
+        ALTER TABLE messages_new RENAME TO messages;
+    "))?;
+    for sql in &dependents {
+        conn.execute_batch(sql)?;
     }
 
+    conn.execute_batch("
+        UPDATE messages SET conversation_id = NULL, thread_id = NULL WHERE classification = 'bounce';
+        INSERT INTO conversation_changes (account_id, kind) SELECT id, 'all' FROM accounts;
+    ")?;
     Ok(())
 }
+
+/// A conversation names each participant after the latest message they sent
+/// with a name. When that name changes, every conversation they're in is
+/// queued, not just the one the message is in.
+fn conversation_changes_names(conn: &Connection) -> Result<(), EddieError> {
+    // Queue every conversation the sender of `{row}` is in
+    let queue = |row: &str| format!("
+            INSERT INTO conversation_changes (account_id, kind, conversation_id)
+            SELECT account_id, 'conversation', id FROM conversations
+            WHERE account_id = {row}.account_id
+              AND instr(char(10) || participant_key || char(10),
+                        char(10) || lower({row}.from_address) || char(10)) > 0;");
+    // The other named messages from the same sender as `{row}`
+    let others = |row: &str| format!(
+        "FROM messages m
+         WHERE m.account_id = {row}.account_id AND lower(m.from_address) = lower({row}.from_address)
+           AND m.from_name IS NOT NULL AND m.id != {row}.id"
+    );
+    // Whether `{row}` is, or was, that sender's latest named message, with a
+    // name other than the one before it
+    let name_changed = |row: &str| format!(
+        "{row}.from_name IS NOT NULL
+         AND NOT EXISTS (SELECT 1 {others} AND (m.date, m.id) > ({row}.date, {row}.id))
+         AND {row}.from_name IS NOT (SELECT m.from_name {others} ORDER BY m.date DESC, m.id DESC LIMIT 1)",
+        others = others(row),
+    );
+
+    conn.execute_batch(&format!("
+        CREATE TRIGGER IF NOT EXISTS messages_changes_name_insert AFTER INSERT ON messages
+        WHEN {new_changed}
+        BEGIN {queue_new}
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_name_delete AFTER DELETE ON messages
+        WHEN {old_changed}
+        BEGIN {queue_old}
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_changes_name_update
+        AFTER UPDATE OF from_name, from_address, date ON messages
+        WHEN old.from_name IS NOT new.from_name
+          OR old.from_address IS NOT new.from_address
+          OR old.date IS NOT new.date
+        BEGIN {queue_old}{queue_new}
+        END;
+    ",
+        new_changed = name_changed("new"),
+        old_changed = name_changed("old"),
+        queue_new = queue("new"),
+        queue_old = queue("old"),
+    ))?;
+    Ok(())
+}
+
+/// Archive, move and re-home change a message's folder or Gmail labels,
+/// which decide whether its conversation's latest message counts as sent.
+fn conversation_changes_folders(conn: &Connection) -> Result<(), EddieError> {
+    conn.execute_batch("
+        DROP TRIGGER IF EXISTS messages_changes_conversation;
+        CREATE TRIGGER messages_changes_conversation
+        AFTER UPDATE OF distilled_text, from_name, classification, is_important, imap_flags,
+                        imap_folder, gmail_labels
+        ON messages
+        WHEN old.distilled_text IS NOT new.distilled_text
+          OR old.from_name IS NOT new.from_name
+          OR old.classification IS NOT new.classification
+          OR old.is_important IS NOT new.is_important
+          OR old.imap_flags IS NOT new.imap_flags
+          OR old.imap_folder IS NOT new.imap_folder
+          OR old.gmail_labels IS NOT new.gmail_labels
+        BEGIN
+            INSERT INTO conversation_changes (account_id, kind, conversation_id)
+            VALUES (new.account_id, 'conversation', new.conversation_id);
+        END;
+    ")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA_V2: &str = include_str!("fixtures/schema_v2.sql");
+
+    /// Every table's sorted column definitions and every index, to compare
+    /// a migrated database against a fresh one.
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare(
+            "SELECT m.type, m.name, p.name, p.type, p.\"notnull\", p.dflt_value, p.pk
+             FROM sqlite_master m LEFT JOIN pragma_table_info(m.name) p
+             WHERE m.name NOT LIKE 'sqlite_%'",
+        ).unwrap();
+        let mut rows: Vec<String> = stmt.query_map([], |row| {
+            Ok(format!(
+                "{} {} {:?} {:?} {:?} {:?} {:?}",
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<bool>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<i64>>(6)?,
+            ))
+        }).unwrap().collect::<Result<_, _>>().unwrap();
+        rows.sort();
+        rows
+    }
+
+    fn fresh_schema() -> Vec<String> {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&conn).unwrap();
+        schema(&conn)
+    }
+
+    fn applied(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn.prepare("SELECT version FROM schema_migrations ORDER BY version").unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    fn query<T: rusqlite::types::FromSql>(conn: &Connection, sql: &str) -> T {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_fresh_database_applies_every_migration_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&conn).unwrap();
+        let all: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(applied(&conn), all);
+        assert!(MIGRATIONS.windows(2).all(|w| w[0].version + 1 == w[1].version));
+
+        initialize_schema(&conn).unwrap();
+        assert_eq!(applied(&conn), all);
+    }
+
+    #[test]
+    fn test_migrates_from_every_version() {
+        let expected = fresh_schema();
+        for version in 1..=MIGRATIONS.len() {
+            let conn = Connection::open_in_memory().unwrap();
+            run_migrations(&conn, &MIGRATIONS[..version]).unwrap();
+            crate::adapters::sqlite::test_support::add_account(&conn);
+            // Only columns the initial schema has
+            conn.execute_batch("
+                INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                      to_addresses, fetched_at, distilled_text, participant_key, conversation_id)
+                VALUES ('m1', 'acc1', 'm1@x', 7, 'INBOX', 5, 'anna@example.org', '[]', 0,
+                        'lunch on friday', 'anna@example.org', 'c1');
+                INSERT INTO conversations (id, account_id, participant_key, classification, last_message_date, updated_at)
+                VALUES ('c1', 'acc1', 'anna@example.org', 'chat', 5, 0);
+                INSERT INTO action_queue (id, account_id, action_type, payload, status, created_at)
+                VALUES ('act1', 'acc1', 'mark_read', '{\"ids\":[\"m1\"]}', 'pending', 0);
+            ").unwrap();
+
+            initialize_schema(&conn).unwrap();
+            let context = format!("migrating from version {}", version);
+            assert_eq!(schema(&conn), expected, "{}", context);
+            assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM accounts"), 1);
+            assert_eq!(
+                query::<String>(&conn, "SELECT conversation_id || ' ' || imap_uid || ' ' || distilled_text FROM messages"),
+                "c1 7 lunch on friday", "{}", context,
+            );
+            assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'lunch'"), 1, "{}", context);
+            assert_eq!(query::<String>(&conn, "SELECT participant_key FROM conversations WHERE id = 'c1'"), "anna@example.org");
+            // Added columns get their defaults, or are filled in from the existing rows
+            assert_eq!(query::<i64>(&conn, "SELECT last_message_is_sent FROM conversations"), 0, "{}", context);
+            if version < 14 {
+                assert_eq!(query::<String>(&conn, "SELECT last_message_id FROM conversations"), "m1", "{}", context);
+            }
+            assert_eq!(query::<String>(&conn, "SELECT payload FROM action_queue WHERE id = 'act1'"), "{\"ids\":[\"m1\"]}");
+            assert_eq!(query::<String>(&conn, "SELECT status FROM action_queue WHERE id = 'act1'"), "pending");
+        }
+    }
+
+    #[test]
+    fn test_keeps_data_from_schema_v2_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA_V2).unwrap();
+        initialize_schema(&conn).unwrap();
+
+        assert_eq!(schema(&conn), fresh_schema());
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM messages"), 1);
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM conversations"), 1);
+        assert_eq!(query::<String>(&conn, "SELECT trust_level FROM entities WHERE email = 'spam@example.net'"), "blocked");
+        assert_eq!(query::<i64>(&conn, "SELECT last_full_sync FROM accounts"), 1700000500000);
+        assert_eq!(query::<String>(&conn, "SELECT imap_security FROM accounts"), "none");
+        assert_eq!(query::<String>(&conn, "SELECT status FROM onboarding_tasks"), "done");
+        assert_eq!(query::<i64>(&conn, "SELECT highest_uid FROM folder_sync"), 42);
+        // Retryable failures go back to pending; exhausted ones stay failed
+        assert_eq!(query::<String>(&conn, "SELECT status FROM action_queue WHERE id = 'act1'"), "pending");
+        assert_eq!(query::<String>(&conn, "SELECT status FROM action_queue WHERE id = 'act2'"), "failed");
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM settings WHERE key = 'schema_version'"), 0);
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'lunch'"), 1);
+    }
+
+    #[test]
+    fn test_schema_v2_database_with_later_startup_alters() {
+        // Builds between releases ran each ALTER on startup, so a version 2
+        // database may already have any of the later columns.
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA_V2).unwrap();
+        conn.execute_batch("
+            ALTER TABLE folder_sync ADD COLUMN highest_modseq INTEGER DEFAULT 0;
+            ALTER TABLE folder_sync ADD COLUMN exists_count INTEGER DEFAULT 0;
+            ALTER TABLE folder_sync ADD COLUMN uid_next INTEGER DEFAULT 0;
+            ALTER TABLE accounts ADD COLUMN imap_security TEXT;
+            ALTER TABLE accounts ADD COLUMN allow_plaintext INTEGER NOT NULL DEFAULT 0;
+            UPDATE accounts SET imap_security = 'starttls';
+            ALTER TABLE action_queue ADD COLUMN next_attempt_at INTEGER;
+        ").unwrap();
+        initialize_schema(&conn).unwrap();
+
+        assert_eq!(schema(&conn), fresh_schema());
+        assert_eq!(query::<String>(&conn, "SELECT imap_security FROM accounts"), "starttls");
+        // The failed-to-pending reset already happened when the column was added
+        assert_eq!(query::<String>(&conn, "SELECT status FROM action_queue WHERE id = 'act1'"), "failed");
+    }
+
+    #[test]
+    fn test_resets_data_from_older_schema_versions() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("
+            CREATE TABLE accounts (id TEXT PRIMARY KEY, email TEXT NOT NULL UNIQUE, imap_host TEXT NOT NULL,
+                imap_port INTEGER NOT NULL DEFAULT 993, imap_tls INTEGER NOT NULL DEFAULT 1, smtp_host TEXT NOT NULL,
+                smtp_port INTEGER NOT NULL DEFAULT 587, password TEXT, display_name TEXT, carddav_url TEXT,
+                created_at INTEGER NOT NULL, last_full_sync INTEGER);
+            CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL, updated_at INTEGER NOT NULL);
+            CREATE TABLE messages (id TEXT PRIMARY KEY, sender TEXT);
+            INSERT INTO accounts (id, email, imap_host, smtp_host, created_at, last_full_sync)
+            VALUES ('acc1', 'me@example.com', 'imap.example.com', 'smtp.example.com', 0, 100);
+            INSERT INTO settings VALUES ('schema_version', '1', 0);
+            INSERT INTO messages VALUES ('msg1', 'alice@example.org');
+        ").unwrap();
+        initialize_schema(&conn).unwrap();
+
+        assert_eq!(schema(&conn), fresh_schema());
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM messages"), 0);
+        assert_eq!(query::<Option<i64>>(&conn, "SELECT last_full_sync FROM accounts"), None);
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        fn create_a(conn: &Connection) -> Result<(), EddieError> {
+            conn.execute_batch("CREATE TABLE a (x INTEGER);")?;
+            Ok(())
+        }
+        fn create_b_then_fail(conn: &Connection) -> Result<(), EddieError> {
+            conn.execute_batch("CREATE TABLE b (x INTEGER); INSERT INTO missing VALUES (1);")?;
+            Ok(())
+        }
+        let migrations = [
+            Migration { version: 1, name: "a", up: create_a },
+            Migration { version: 2, name: "b", up: create_b_then_fail },
+        ];
+
+        let conn = Connection::open_in_memory().unwrap();
+        let err = run_migrations(&conn, &migrations).unwrap_err();
+        assert!(err.to_string().contains("Migration 2 (b) failed"));
+        assert_eq!(applied(&conn), vec![1]);
+        assert!(table_exists(&conn, "a").unwrap());
+        assert!(!table_exists(&conn, "b").unwrap());
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&conn).unwrap();
+        conn.execute("INSERT INTO schema_migrations VALUES (999, 'future', 0)", []).unwrap();
+        assert!(initialize_schema(&conn).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use rusqlite::params;
+use serde::Serialize;
+
+use super::DbPool;
+use crate::error::EddieError;
+
+/// A recipient a sent message couldn't be delivered to (yet), from a
+/// delivery status notification.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryFailure {
+    pub recipient: String,
+    /// failed or delayed
+    pub action: String,
+    pub status: Option<String>,
+    pub diagnostic: Option<String>,
+    pub reported_at: i64,
+}
+
+/// Record the failures reported for a sent message (by Message-ID). Reports
+/// about messages we don't have are ignored. A later report replaces an
+/// earlier one for the same recipient, except that a delay never
+/// overwrites a failure.
+pub fn record(
+    pool: &DbPool,
+    account_id: &str,
+    message_id: &str,
+    failures: &[DeliveryFailure],
+) -> Result<usize, EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+    for failure in failures {
+        count += tx.execute(
+            "INSERT INTO delivery_failures (account_id, message_id, recipient, action, status, diagnostic, reported_at)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7
+             WHERE EXISTS (SELECT 1 FROM messages WHERE account_id = ?1 AND message_id = ?2)
+             ON CONFLICT(account_id, message_id, recipient) DO UPDATE SET
+                action = excluded.action,
+                status = excluded.status,
+                diagnostic = excluded.diagnostic,
+                reported_at = excluded.reported_at
+             WHERE delivery_failures.action != 'failed' OR excluded.action = 'failed'",
+            params![
+                account_id,
+                message_id,
+                failure.recipient.to_lowercase(),
+                failure.action,
+                failure.status,
+                failure.diagnostic,
+                failure.reported_at,
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(count)
+}
+
+/// Failures of the given messages (by Message-ID), keyed by Message-ID.
+pub fn get_for_messages(
+    pool: &DbPool,
+    account_id: &str,
+    message_ids: &[&str],
+) -> Result<HashMap<String, Vec<DeliveryFailure>>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT recipient, action, status, diagnostic, reported_at
+         FROM delivery_failures
+         WHERE account_id = ?1 AND message_id = ?2
+         ORDER BY recipient",
+    )?;
+
+    let mut results = HashMap::new();
+    for message_id in message_ids {
+        let failures = stmt.query_map(params![account_id, message_id], |row| {
+            Ok(DeliveryFailure {
+                recipient: row.get(0)?,
+                action: row.get(1)?,
+                status: row.get(2)?,
+                diagnostic: row.get(3)?,
+                reported_at: row.get(4)?,
+            })
+        })?.collect::<Result<Vec<_>, _>>()?;
+        if !failures.is_empty() {
+            results.insert(message_id.to_string(), failures);
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,155 @@
+-- sync.db as left by the last release before versioned migrations
+-- (settings.schema_version = '2'), with a little data in every table.
+BEGIN TRANSACTION;
+CREATE TABLE accounts (
+            id              TEXT PRIMARY KEY,
+            email           TEXT NOT NULL UNIQUE,
+            password        TEXT,
+            display_name    TEXT,
+            imap_host       TEXT NOT NULL,
+            imap_port       INTEGER NOT NULL DEFAULT 993,
+            imap_tls        INTEGER NOT NULL DEFAULT 1,
+            smtp_host       TEXT NOT NULL,
+            smtp_port       INTEGER NOT NULL DEFAULT 587,
+            carddav_url     TEXT,
+            created_at      INTEGER NOT NULL,
+            last_full_sync  INTEGER
+        , smtp_tls INTEGER NOT NULL DEFAULT 1);
+INSERT INTO accounts VALUES('acc1','me@example.com','secret','Me','imap.example.com',143,0,'smtp.example.com',587,NULL,1700000000000,1700000500000,1);
+CREATE TABLE settings (
+            key        TEXT PRIMARY KEY,
+            value      TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+INSERT INTO settings VALUES('schema_version','2',0);
+CREATE TABLE messages (
+            id              TEXT PRIMARY KEY,
+            account_id      TEXT NOT NULL REFERENCES accounts(id),
+            message_id      TEXT NOT NULL,
+            imap_uid        INTEGER NOT NULL,
+            imap_folder     TEXT NOT NULL,
+            date            INTEGER NOT NULL,
+            from_address    TEXT NOT NULL,
+            from_name       TEXT,
+            to_addresses    TEXT NOT NULL,
+            cc_addresses    TEXT DEFAULT '[]',
+            bcc_addresses   TEXT DEFAULT '[]',
+            subject         TEXT,
+            body_text       TEXT,
+            body_html       TEXT,
+            size_bytes      INTEGER,
+            has_attachments  INTEGER DEFAULT 0,
+
+            in_reply_to     TEXT,
+            references_ids  TEXT DEFAULT '[]',
+            participant_changes TEXT,
+
+            imap_flags      TEXT DEFAULT '[]',
+            gmail_labels    TEXT DEFAULT '[]',
+            fetched_at      INTEGER NOT NULL,
+
+            classification  TEXT,
+            classification_source TEXT,
+            classification_confidence REAL,
+            classification_reason TEXT,
+            classification_headers TEXT DEFAULT '{}',
+            is_important    INTEGER DEFAULT 0,
+            distilled_text  TEXT,
+            processed_at    INTEGER,
+
+            participant_key TEXT NOT NULL,
+            conversation_id TEXT NOT NULL,
+            thread_id       TEXT,
+
+            UNIQUE(account_id, imap_folder, imap_uid)
+        );
+INSERT INTO messages VALUES('msg1','acc1','abc@example.org',42,'INBOX',1700000100000,'alice@example.org','Alice','["me@example.com"]','[]','[]','Lunch?','Tomorrow at noon?',NULL,NULL,0,NULL,'[]',NULL,'[]','[]',1700000200000,'chat','user',NULL,NULL,'{}',0,NULL,NULL,'alice@example.org','conv1','abc@example.org');
+CREATE TABLE conversations (
+            id                  TEXT PRIMARY KEY,
+            account_id          TEXT NOT NULL REFERENCES accounts(id),
+            participant_key     TEXT NOT NULL,
+            participant_names   TEXT,
+            classification      TEXT NOT NULL,
+            last_message_date   INTEGER NOT NULL,
+            last_message_preview TEXT,
+            unread_count        INTEGER DEFAULT 0,
+            total_count         INTEGER DEFAULT 0,
+            is_muted            INTEGER DEFAULT 0,
+            is_pinned           INTEGER DEFAULT 0,
+            is_important        INTEGER DEFAULT 0,
+            initial_sender_email TEXT,
+            updated_at          INTEGER NOT NULL
+        );
+INSERT INTO conversations VALUES('conv1','acc1','alice@example.org','["Alice"]','chat',1700000100000,'Tomorrow at noon?',1,1,0,0,0,NULL,1700000200000);
+CREATE TABLE entities (
+            id              TEXT PRIMARY KEY,
+            account_id      TEXT NOT NULL REFERENCES accounts(id),
+            email           TEXT NOT NULL,
+            display_name    TEXT,
+            trust_level     TEXT NOT NULL,
+            source          TEXT,
+            first_seen      INTEGER NOT NULL,
+            last_seen       INTEGER,
+            sent_count      INTEGER DEFAULT 0,
+            metadata        TEXT DEFAULT '{}',
+
+            UNIQUE(account_id, email)
+        );
+INSERT INTO entities VALUES('ent1','acc1','spam@example.net',NULL,'blocked','manual',1700000000000,NULL,0,'{}');
+INSERT INTO entities VALUES('ent2','acc1','alice@example.org','Alice','connection','sent',1700000000000,NULL,3,'{}');
+CREATE TABLE action_queue (
+            id              TEXT PRIMARY KEY,
+            account_id      TEXT NOT NULL REFERENCES accounts(id),
+            action_type     TEXT NOT NULL,
+            payload         TEXT NOT NULL,
+            status          TEXT NOT NULL DEFAULT 'pending',
+            retry_count     INTEGER DEFAULT 0,
+            max_retries     INTEGER DEFAULT 3,
+            created_at      INTEGER NOT NULL,
+            completed_at    INTEGER,
+            error           TEXT
+        , message_id TEXT);
+INSERT INTO action_queue VALUES('act1','acc1','move','{"uid":42}','failed',1,3,1700000300000,NULL,'connection reset',NULL);
+INSERT INTO action_queue VALUES('act2','acc1','move','{"uid":43}','failed',3,3,1700000300000,NULL,'connection reset',NULL);
+CREATE TABLE sync_state (
+            account_id      TEXT PRIMARY KEY REFERENCES accounts(id),
+            draft_uid       INTEGER,
+            draft_version   INTEGER DEFAULT 0,
+            last_pushed     INTEGER,
+            last_pulled     INTEGER
+        );
+INSERT INTO sync_state VALUES('acc1',NULL,0,NULL,NULL);
+CREATE TABLE folder_sync (
+            account_id    TEXT NOT NULL,
+            folder        TEXT NOT NULL,
+            uid_validity  INTEGER NOT NULL DEFAULT 0,
+            highest_uid   INTEGER DEFAULT 0,
+            lowest_uid    INTEGER DEFAULT 0,
+            sync_status   TEXT DEFAULT 'pending',
+            last_sync     INTEGER,
+            PRIMARY KEY (account_id, folder)
+        );
+INSERT INTO folder_sync VALUES('acc1','INBOX',7,42,1,'synced',1700000400000);
+CREATE TABLE onboarding_tasks (
+            account_id  TEXT NOT NULL REFERENCES accounts(id),
+            task        TEXT NOT NULL,
+            status      TEXT NOT NULL DEFAULT 'pending',
+            cursor      TEXT,
+            updated_at  INTEGER,
+            PRIMARY KEY (account_id, task)
+        );
+INSERT INTO onboarding_tasks VALUES('acc1','historical_fetch','done',NULL,1700000500000);
+CREATE INDEX idx_messages_conversation   ON messages(conversation_id, date DESC);
+CREATE INDEX idx_messages_date           ON messages(account_id, date DESC);
+CREATE INDEX idx_messages_classification ON messages(classification);
+CREATE INDEX idx_messages_from           ON messages(from_address);
+CREATE INDEX idx_messages_message_id     ON messages(message_id);
+CREATE INDEX idx_messages_thread          ON messages(account_id, thread_id);
+CREATE UNIQUE INDEX idx_messages_dedup
+        ON messages(account_id, message_id) WHERE message_id != '';
+CREATE INDEX idx_conversations_class ON conversations(account_id, classification, last_message_date DESC);
+CREATE INDEX idx_conversations_date  ON conversations(account_id, last_message_date DESC);
+CREATE INDEX idx_entities_trust ON entities(account_id, trust_level);
+CREATE INDEX idx_entities_email ON entities(email);
+CREATE INDEX idx_action_queue_status ON action_queue(status, created_at);
+COMMIT;
@@ -6,6 +6,14 @@ pub struct FolderState {
     pub name: String,
     pub highest_uid: u32,
     pub lowest_uid: u32,
+    /// HIGHESTMODSEQ seen at the last flag resync (0 = never / no CONDSTORE)
+    pub highest_modseq: u64,
+    /// UIDVALIDITY the cached UIDs belong to (0 = not recorded yet)
+    pub uid_validity: u32,
+    /// EXISTS and UIDNEXT at the last expunge reconcile — if both are unchanged,
+    /// nothing can have been expunged since
+    pub exists_count: u32,
+    pub uid_next: u32,
 }
 
 pub fn ensure_folder(
@@ -28,7 +36,7 @@ pub fn next_pending_folder(
 ) -> Result<Option<FolderState>, EddieError> {
     let conn = pool.get()?;
     let result = conn.query_row(
-        "SELECT folder, highest_uid, lowest_uid
+        "SELECT folder, highest_uid, lowest_uid, highest_modseq, uid_validity, exists_count, uid_next
             FROM folder_sync
             WHERE account_id = ?1 AND sync_status != 'done'
             ORDER BY
@@ -45,6 +53,10 @@ pub fn next_pending_folder(
                 name: row.get(0)?,
                 highest_uid: row.get(1)?,
                 lowest_uid: row.get(2)?,
+                highest_modseq: row.get::<_, i64>(3)? as u64,
+                uid_validity: row.get(4)?,
+                exists_count: row.get(5)?,
+                uid_next: row.get(6)?,
             })
         },
     );
@@ -104,6 +116,93 @@ pub fn update_highest_uid(
     Ok(())
 }
 
+pub fn set_highest_modseq(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    modseq: u64,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE folder_sync SET highest_modseq = ?1
+         WHERE account_id = ?2 AND folder = ?3",
+        params![modseq as i64, account_id, folder],
+    )?;
+    Ok(())
+}
+
+pub fn set_uid_validity(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    uid_validity: u32,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE folder_sync SET uid_validity = ?1
+         WHERE account_id = ?2 AND folder = ?3",
+        params![uid_validity, account_id, folder],
+    )?;
+    Ok(())
+}
+
+/// The sync window after a UIDVALIDITY change, as (lowest_uid, highest_uid),
+/// and the server UIDs inside it that no cached message was re-mapped to.
+/// Those have to be fetched now: incremental sync only looks above the
+/// window and historical sync only below it. With nothing re-mapped the
+/// window covers the whole folder.
+pub fn remap_window(server_uids: &[u32], remapped: &[u32], uid_next: u32) -> (u32, u32, Vec<u32>) {
+    let bounds = if remapped.is_empty() { server_uids } else { remapped };
+    let (lowest, highest) = match (bounds.iter().min(), bounds.iter().max()) {
+        (Some(&min), Some(&max)) => (min, max),
+        _ => (uid_next, uid_next.saturating_sub(1)),
+    };
+    let remapped: std::collections::HashSet<u32> = remapped.iter().copied().collect();
+    let mut missing: Vec<u32> = server_uids.iter()
+        .copied()
+        .filter(|uid| (lowest..=highest).contains(uid) && !remapped.contains(uid))
+        .collect();
+    missing.sort_unstable();
+    (lowest, highest, missing)
+}
+
+/// Reset a folder's cursors after a UIDVALIDITY change to the window from
+/// `remap_window`; the modseq baseline belongs to the old UIDs and is dropped.
+pub fn reset_uid_validity(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    uid_validity: u32,
+    lowest_uid: u32,
+    highest_uid: u32,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE folder_sync
+         SET uid_validity = ?1, lowest_uid = ?2, highest_uid = ?3, highest_modseq = 0,
+             exists_count = 0, uid_next = 0
+         WHERE account_id = ?4 AND folder = ?5",
+        params![uid_validity, lowest_uid as i64, highest_uid as i64, account_id, folder],
+    )?;
+    Ok(())
+}
+
+pub fn set_reconciled(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    exists_count: u32,
+    uid_next: u32,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE folder_sync SET exists_count = ?1, uid_next = ?2
+         WHERE account_id = ?3 AND folder = ?4",
+        params![exists_count, uid_next, account_id, folder],
+    )?;
+    Ok(())
+}
+
 pub fn get_folder(
     pool: &DbPool,
     account_id: &str,
@@ -111,7 +210,7 @@ pub fn get_folder(
 ) -> Result<Option<FolderState>, EddieError> {
     let conn = pool.get()?;
     let result = conn.query_row(
-        "SELECT folder, highest_uid, lowest_uid
+        "SELECT folder, highest_uid, lowest_uid, highest_modseq, uid_validity, exists_count, uid_next
          FROM folder_sync
          WHERE account_id = ?1 AND folder = ?2",
         params![account_id, folder],
@@ -119,6 +218,10 @@ pub fn get_folder(
             name: row.get(0)?,
             highest_uid: row.get(1)?,
             lowest_uid: row.get(2)?,
+            highest_modseq: row.get::<_, i64>(3)? as u64,
+            uid_validity: row.get(4)?,
+            exists_count: row.get(5)?,
+            uid_next: row.get(6)?,
         }),
     );
     match result {
@@ -127,3 +230,20 @@ pub fn get_folder(
         Err(e) => Err(EddieError::Database(e.to_string())),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remap_window_fetches_unmatched_uids_inside_it() {
+        // 4 is new to us (or had no Message-ID) and sits between re-mapped
+        // mail; 1 and 9 are left to historical and incremental sync
+        let (lowest, highest, missing) = remap_window(&[1, 2, 4, 6, 9], &[6, 2], 10);
+        assert_eq!((lowest, highest, missing), (2, 6, vec![4]));
+
+        // Nothing re-mapped: the whole folder is fetched again
+        assert_eq!(remap_window(&[3, 5], &[], 6), (3, 5, vec![3, 5]));
+        assert_eq!(remap_window(&[], &[], 6), (6, 5, vec![]));
+    }
+}
@@ -1,8 +1,10 @@
 use rusqlite::params;
+use std::collections::HashMap;
 use uuid::Uuid;
 use crate::services::logger;
 
 use super::DbPool;
+use super::attachments::{self, NewAttachment};
 use super::entities;
 use crate::error::EddieError;
 use crate::services::sync::helpers::email_normalization::normalize_email;
@@ -49,8 +51,10 @@ pub struct NewMessage {
     pub distilled_text: Option<String>,
     pub processed_at: Option<i64>,
     pub participant_key: String,
-    pub conversation_id: String,
+    /// None for bounces, which aren't in any conversation
+    pub conversation_id: Option<String>,
     pub classification_headers: String, // JSON map of RFC headers for classification
+    pub attachments: Vec<NewAttachment>,
 }
 
 pub fn insert_messages(pool: &DbPool, messages: &[NewMessage]) -> Result<usize, EddieError> {
@@ -84,6 +88,8 @@ pub fn insert_messages(pool: &DbPool, messages: &[NewMessage]) -> Result<usize,
                 imap_flags = excluded.imap_flags,
                 gmail_labels = excluded.gmail_labels,
                 fetched_at = excluded.fetched_at,
+                bcc_addresses = CASE WHEN excluded.bcc_addresses != '[]'
+                                     THEN excluded.bcc_addresses ELSE bcc_addresses END,
                 body_text = COALESCE(excluded.body_text, body_text),
                 body_html = COALESCE(excluded.body_html, body_html),
                 size_bytes = COALESCE(excluded.size_bytes, size_bytes)",
@@ -121,7 +127,21 @@ pub fn insert_messages(pool: &DbPool, messages: &[NewMessage]) -> Result<usize,
 
         match result {
             Ok(_) => count += 1,
-            Err(e) => logger::warn(&format!("Failed to insert message {}: {}", msg.message_id, e)),
+            Err(e) => {
+                logger::warn(&format!("Failed to insert message {}: {}", msg.message_id, e));
+                continue;
+            }
+        }
+
+        // Only fetches that included BODYSTRUCTURE carry attachments; leave
+        // existing rows alone otherwise.
+        if !msg.attachments.is_empty() {
+            let id: String = tx.query_row(
+                "SELECT id FROM messages WHERE account_id = ?1 AND imap_folder = ?2 AND imap_uid = ?3",
+                params![msg.account_id, msg.imap_folder, msg.imap_uid],
+                |row| row.get(0),
+            )?;
+            attachments::replace_for_message(&tx, &id, &msg.attachments)?;
         }
     }
 
@@ -287,7 +307,8 @@ pub fn get_new_sent_recipients(pool: &DbPool, account_id: &str) -> Result<Vec<(S
 pub fn reset_classifications(pool: &DbPool, account_id: &str) -> Result<(), EddieError> {
     let conn = pool.get()?;
     conn.execute(
-        "UPDATE messages SET processed_at = NULL, classification = NULL, distilled_text = NULL WHERE account_id = ?1",
+        "UPDATE messages SET processed_at = NULL, classification = NULL, distilled_text = NULL
+         WHERE account_id = ?1 AND classification IS NOT 'bounce'",
         params![account_id],
     )?;
     Ok(())
@@ -302,6 +323,7 @@ pub struct Message {
     pub from_name: Option<String>,
     pub to_addresses: String,
     pub cc_addresses: String,
+    pub bcc_addresses: String,
     pub subject: Option<String>,
     pub body_text: Option<String>,
     pub body_html: Option<String>,
@@ -313,12 +335,34 @@ pub struct Message {
     pub references_ids: String,
     pub distilled_text: Option<String>,
     pub is_sent: bool,
+    /// When a queued send goes out, while it is held back for undo or scheduled
+    pub scheduled_at: Option<i64>,
+    /// Recipients a sent message bounced for, from delivery status notifications
+    pub delivery_failures: Vec<super::delivery_failures::DeliveryFailure>,
 }
 
 const MESSAGE_COLUMNS: &str =
     "id, date, from_address, from_name, to_addresses, cc_addresses,
      subject, body_text, body_html, has_attachments, imap_flags, distilled_text,
-     gmail_labels, imap_folder, message_id, imap_uid, in_reply_to, references_ids";
+     gmail_labels, imap_folder, message_id, imap_uid, in_reply_to, references_ids, bcc_addresses";
+
+/// `MESSAGE_COLUMNS` without the HTML body, which `fetch_message_html`
+/// loads when a message is opened.
+const MESSAGE_SUMMARY_COLUMNS: &str =
+    "id, date, from_address, from_name, to_addresses, cc_addresses,
+     subject, body_text, NULL, has_attachments, imap_flags, distilled_text,
+     gmail_labels, imap_folder, message_id, imap_uid, in_reply_to, references_ids, bcc_addresses";
+
+/// Most messages returned per page.
+const MAX_LIMIT: u32 = 100;
+
+#[derive(serde::Serialize)]
+pub struct MessagePage {
+    /// Oldest first
+    pub messages: Vec<Message>,
+    /// Pass back as `cursor` for the page of older messages; None on the last page
+    pub next_cursor: Option<String>,
+}
 
 fn map_message_row(row: &rusqlite::Row) -> rusqlite::Result<(Message, String, String, String)> {
     let gmail_labels: String = row.get(12)?;
@@ -332,6 +376,7 @@ fn map_message_row(row: &rusqlite::Row) -> rusqlite::Result<(Message, String, St
         from_name: row.get(3)?,
         to_addresses: row.get(4)?,
         cc_addresses: row.get(5)?,
+        bcc_addresses: row.get::<_, Option<String>>(18)?.unwrap_or_else(|| "[]".to_string()),
         subject: row.get(6)?,
         body_text: row.get(7)?,
         body_html: row.get(8)?,
@@ -343,6 +388,8 @@ fn map_message_row(row: &rusqlite::Row) -> rusqlite::Result<(Message, String, St
         references_ids: row.get(17)?,
         distilled_text: row.get(11)?,
         is_sent: false, // computed by caller
+        scheduled_at: None,
+        delivery_failures: vec![],
     }, gmail_labels, imap_folder, from_address))
 }
 
@@ -368,12 +415,84 @@ pub fn fetch_conversation_messages(
     let self_emails = entities::get_self_emails(pool, account_id)?;
     let conn = pool.get()?;
     let query = format!(
-        "SELECT {} FROM messages WHERE account_id = ?1 AND conversation_id = ?2 ORDER BY date ASC",
+        "SELECT {} FROM messages
+         WHERE account_id = ?1 AND conversation_id = ?2 AND classification IS NOT 'bounce'
+         ORDER BY date ASC",
         MESSAGE_COLUMNS
     );
     let mut stmt = conn.prepare(&query)?;
     let rows = stmt.query(params![account_id, conversation_id])?;
-    collect_messages(rows, &self_emails)
+    let mut messages = collect_messages(rows, &self_emails)?;
+    drop(stmt);
+    drop(conn);
+    add_send_state(pool, account_id, &mut messages)?;
+    Ok(messages)
+}
+
+/// One page of a conversation's messages without their HTML bodies, newest
+/// page first. `cursor` is the `next_cursor` of the previous page.
+pub fn fetch_conversation_message_page(
+    pool: &DbPool,
+    account_id: &str,
+    conversation_id: &str,
+    limit: u32,
+    cursor: Option<&str>,
+) -> Result<MessagePage, EddieError> {
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let (before_date, before_id) = match cursor {
+        Some(cursor) => {
+            let invalid = || EddieError::InvalidInput(format!("Invalid message cursor: {}", cursor));
+            let (date, id) = cursor.split_once(':').ok_or_else(invalid)?;
+            (date.parse::<i64>().map_err(|_| invalid())?, id.to_string())
+        }
+        None => (i64::MAX, String::new()),
+    };
+    let self_emails = entities::get_self_emails(pool, account_id)?;
+    let conn = pool.get()?;
+    let query = format!(
+        "SELECT {} FROM messages
+         WHERE account_id = ?1 AND conversation_id = ?2 AND classification IS NOT 'bounce'
+           AND (date, id) < (?3, ?4)
+         ORDER BY date DESC, id DESC
+         LIMIT ?5",
+        MESSAGE_SUMMARY_COLUMNS
+    );
+    let mut stmt = conn.prepare(&query)?;
+    // One extra row tells whether there is a next page
+    let rows = stmt.query(params![account_id, conversation_id, before_date, before_id, limit + 1])?;
+    let mut messages = collect_messages(rows, &self_emails)?;
+    drop(stmt);
+    drop(conn);
+
+    let next_cursor = if messages.len() > limit as usize {
+        messages.truncate(limit as usize);
+        messages.last().map(|m| format!("{}:{}", m.date, m.id))
+    } else {
+        None
+    };
+    messages.reverse();
+    add_send_state(pool, account_id, &mut messages)?;
+    Ok(MessagePage { messages, next_cursor })
+}
+
+/// Fill in when queued sends go out and which recipients sent messages bounced for.
+fn add_send_state(pool: &DbPool, account_id: &str, messages: &mut [Message]) -> Result<(), EddieError> {
+    if messages.iter().any(|m| m.imap_folder == "OUTBOX") {
+        let scheduled = super::action_queue::get_scheduled_sends(pool, account_id)?;
+        for msg in messages.iter_mut().filter(|m| m.imap_folder == "OUTBOX") {
+            msg.scheduled_at = scheduled.get(&msg.message_id).copied();
+        }
+    }
+
+    let sent_ids: Vec<&str> = messages.iter()
+        .filter(|m| m.is_sent && !m.message_id.is_empty())
+        .map(|m| m.message_id.as_str())
+        .collect();
+    let mut failures = super::delivery_failures::get_for_messages(pool, account_id, &sent_ids)?;
+    for msg in messages.iter_mut().filter(|m| m.is_sent) {
+        msg.delivery_failures = failures.remove(&msg.message_id).unwrap_or_default();
+    }
+    Ok(())
 }
 
 pub fn fetch_skill_match_messages(
@@ -446,6 +565,28 @@ pub fn get_uids_for_folder(
     Ok(uids)
 }
 
+/// Current UIDs in `folder` for the given message row ids.
+pub fn get_uids_by_ids(
+    pool: &DbPool,
+    folder: &str,
+    ids: &[String],
+) -> Result<Vec<u32>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT imap_uid FROM messages WHERE id = ?1 AND imap_folder = ?2"
+    )?;
+
+    let mut uids = Vec::new();
+    for id in ids {
+        match stmt.query_row(params![id, folder], |row| row.get::<_, u32>(0)) {
+            Ok(uid) => uids.push(uid),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(uids)
+}
+
 /// Returns (imap_uid, imap_flags) for all messages in a folder, ordered by UID DESC (latest first).
 pub fn get_uids_and_flags_for_folder(
     pool: &DbPool,
@@ -520,21 +661,181 @@ pub fn update_flags_and_labels_batch(
     Ok(count)
 }
 
+/// (id, message_id) for the cached messages at the given UIDs in a folder.
+pub fn get_ids_for_uids(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    uids: &[u32],
+) -> Result<Vec<(String, String)>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, message_id FROM messages
+         WHERE account_id = ?1 AND imap_folder = ?2 AND imap_uid = ?3"
+    )?;
+
+    let mut results = Vec::new();
+    for uid in uids {
+        match stmt.query_row(params![account_id, folder, *uid as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        }) {
+            Ok(pair) => results.push(pair),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(results)
+}
+
+/// Point a cached message at its new IMAP location after a move on another
+/// client. The row keeps its id, classification and distilled text.
+pub fn rehome_message(
+    pool: &DbPool,
+    id: &str,
+    folder: &str,
+    uid: u32,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE messages SET imap_folder = ?1, imap_uid = ?2 WHERE id = ?3",
+        params![folder, uid as i64, id],
+    )?;
+    Ok(())
+}
+
+/// Delete messages that were expunged on the server.
+pub fn delete_by_ids(pool: &DbPool, ids: &[String]) -> Result<usize, EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+
+    for id in ids {
+        tx.execute("DELETE FROM attachments WHERE message_id = ?1", params![id])?;
+        count += tx.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+    }
+
+    tx.commit()?;
+    Ok(count)
+}
+
+/// After a UIDVALIDITY change, re-map a folder's cached messages to their new
+/// UIDs by Message-ID. `server_ids` is (new_uid, message_id) for every message
+/// now in the folder. Rows keep their `id`, so classification and user state survive.
+///
+/// Messages that can't be matched (gone from the server, or without a
+/// Message-ID) are deleted; see `folder_sync::remap_window` for fetching
+/// what's left on the server. Returns the re-mapped UIDs and the number deleted.
+pub fn remap_folder_uids(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    server_ids: &[(u32, String)],
+) -> Result<(Vec<u32>, usize), EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+
+    // Park every cached UID on a unique negative value so the new UIDs
+    // can't collide with stale ones under UNIQUE(account_id, imap_folder, imap_uid).
+    tx.execute(
+        "UPDATE messages SET imap_uid = -rowid
+         WHERE account_id = ?1 AND imap_folder = ?2",
+        params![account_id, folder],
+    )?;
+
+    let mut remapped = Vec::new();
+    for (uid, message_id) in server_ids {
+        if message_id.is_empty() {
+            continue;
+        }
+        let rows = tx.execute(
+            "UPDATE messages SET imap_uid = ?1
+             WHERE account_id = ?2 AND imap_folder = ?3 AND message_id = ?4 AND imap_uid < 0",
+            params![*uid as i64, account_id, folder, message_id],
+        )?;
+        if rows > 0 {
+            remapped.push(*uid);
+        }
+    }
+
+    tx.execute(
+        "DELETE FROM attachments WHERE message_id IN (
+            SELECT id FROM messages WHERE account_id = ?1 AND imap_folder = ?2 AND imap_uid < 0
+         )",
+        params![account_id, folder],
+    )?;
+    let deleted = tx.execute(
+        "DELETE FROM messages
+         WHERE account_id = ?1 AND imap_folder = ?2 AND imap_uid < 0",
+        params![account_id, folder],
+    )?;
+
+    tx.commit()?;
+    Ok((remapped, deleted))
+}
+
 pub struct MessageImapInfo {
     pub account_id: String,
+    pub message_id: String,
     pub imap_uid: u32,
     pub imap_folder: String,
     pub body_html: Option<String>,
 }
 
+/// What `build_reply` needs from the message being replied to or forwarded.
+pub struct ReplySource {
+    pub account_id: String,
+    pub message_id: String,
+    pub date: i64,
+    pub from_address: String,
+    pub from_name: Option<String>,
+    pub to_addresses: Vec<String>,
+    pub cc_addresses: Vec<String>,
+    pub subject: String,
+    pub body_text: Option<String>,
+    pub references: Vec<String>,
+    /// Lowercase header name → raw value, including Reply-To and Mail-Followup-To
+    pub headers: HashMap<String, String>,
+}
+
+pub fn get_reply_source(pool: &DbPool, id: &str) -> Result<ReplySource, EddieError> {
+    let conn = pool.get()?;
+    let json_list = |value: Option<String>| -> Vec<String> {
+        value.and_then(|v| serde_json::from_str(&v).ok()).unwrap_or_default()
+    };
+    conn.query_row(
+        "SELECT account_id, message_id, date, from_address, from_name, to_addresses, cc_addresses,
+                subject, COALESCE(body_text, distilled_text), references_ids, classification_headers
+         FROM messages WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(ReplySource {
+                account_id: row.get(0)?,
+                message_id: row.get(1)?,
+                date: row.get(2)?,
+                from_address: row.get(3)?,
+                from_name: row.get(4)?,
+                to_addresses: json_list(row.get(5)?),
+                cc_addresses: json_list(row.get(6)?),
+                subject: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+                body_text: row.get(8)?,
+                references: json_list(row.get(9)?),
+                headers: row.get::<_, Option<String>>(10)?
+                    .and_then(|v| serde_json::from_str(&v).ok())
+                    .unwrap_or_default(),
+            })
+        },
+    ).map_err(|e| EddieError::Database(format!("Message not found: {}", e)))
+}
+
 pub fn get_message_imap_info(pool: &DbPool, message_id: &str) -> Result<MessageImapInfo, EddieError> {
     let conn = pool.get()?;
     conn.query_row(
-        "SELECT account_id, imap_uid, imap_folder, body_html FROM messages WHERE id = ?1",
+        "SELECT account_id, imap_uid, imap_folder, body_html, message_id FROM messages WHERE id = ?1",
         params![message_id],
         |row| {
             Ok(MessageImapInfo {
                 account_id: row.get(0)?,
+                message_id: row.get(4)?,
                 imap_uid: row.get(1)?,
                 imap_folder: row.get(2)?,
                 body_html: row.get(3)?,
@@ -543,6 +844,31 @@ pub fn get_message_imap_info(pool: &DbPool, message_id: &str) -> Result<MessageI
     ).map_err(|e| EddieError::Database(format!("Message not found: {}", e)))
 }
 
+/// Merge `headers` into a message's stored classification headers.
+pub fn merge_classification_headers(
+    pool: &DbPool,
+    id: &str,
+    headers: &HashMap<String, String>,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+    let current: Option<String> = tx.query_row(
+        "SELECT classification_headers FROM messages WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?;
+    let mut merged: HashMap<String, String> = current
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default();
+    merged.extend(headers.iter().map(|(k, v)| (k.clone(), v.clone())));
+    tx.execute(
+        "UPDATE messages SET classification_headers = ?1 WHERE id = ?2",
+        params![serde_json::to_string(&merged).unwrap_or_else(|_| "{}".to_string()), id],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
 pub fn update_body_html_by_id(pool: &DbPool, message_id: &str, html: &str) -> Result<(), EddieError> {
     let conn = pool.get()?;
     conn.execute(
@@ -611,11 +937,67 @@ pub fn delete_message_by_message_id(pool: &DbPool, message_id: &str) -> Result<b
     Ok(rows > 0)
 }
 
+/// DB id of the optimistic OUTBOX row for a queued send, if it hasn't been
+/// replaced by the synced copy from the Sent folder yet.
+pub fn get_outbox_id(pool: &DbPool, account_id: &str, message_id: &str) -> Result<Option<String>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        "SELECT id FROM messages WHERE account_id = ?1 AND message_id = ?2 AND imap_folder = 'OUTBOX'",
+        params![account_id, message_id],
+        |row| row.get(0),
+    );
+
+    match result {
+        Ok(id) => Ok(Some(id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Rewrite an OUTBOX row after its failed send was edited. Changing the
+/// recipients moves it to a different conversation.
+pub fn update_outbox_message(
+    pool: &DbPool,
+    id: &str,
+    to: &[String],
+    cc: &[String],
+    subject: &str,
+    body: &str,
+    body_html: &str,
+    participant_key: &str,
+) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE messages SET to_addresses = ?1, cc_addresses = ?2, subject = ?3,
+                             body_text = ?4, distilled_text = ?4, body_html = ?8,
+                             participant_key = ?5, conversation_id = ?6
+         WHERE id = ?7 AND imap_folder = 'OUTBOX'",
+        params![
+            serde_json::to_string(to).unwrap_or_default(),
+            serde_json::to_string(cc).unwrap_or_default(),
+            subject,
+            body,
+            participant_key,
+            super::conversations::compute_conversation_id(participant_key),
+            id,
+            body_html,
+        ],
+    )?;
+    Ok(())
+}
+
 /// Optimistically mark messages as seen by adding \\Seen to their imap_flags JSON array.
-pub fn mark_messages_seen(pool: &DbPool, message_ids: &[String]) -> Result<usize, EddieError> {
+/// Returns the ids of the messages that were unread.
+pub fn mark_messages_seen(pool: &DbPool, message_ids: &[String]) -> Result<Vec<String>, EddieError> {
+    set_flag(pool, message_ids, "Seen", true)
+}
+
+/// Optimistically add or remove a flag (in stored form, e.g. "Flagged") in
+/// the imap_flags JSON array of each message. Returns the ids of the rows changed.
+pub fn set_flag(pool: &DbPool, message_ids: &[String], flag: &str, present: bool) -> Result<Vec<String>, EddieError> {
     let conn = pool.get()?;
     let tx = conn.unchecked_transaction()?;
-    let mut count = 0;
+    let mut changed = Vec::new();
 
     for id in message_ids {
         let current_flags: String = match tx.query_row(
@@ -628,17 +1010,167 @@ pub fn mark_messages_seen(pool: &DbPool, message_ids: &[String]) -> Result<usize
         };
 
         let mut flags: Vec<String> = serde_json::from_str(&current_flags).unwrap_or_default();
-        if !flags.iter().any(|f| f == "Seen") {
-            flags.push("Seen".to_string());
-            let new_flags = serde_json::to_string(&flags).unwrap_or_default();
-            let rows = tx.execute(
-                "UPDATE messages SET imap_flags = ?1 WHERE id = ?2",
-                params![new_flags, id],
-            )?;
-            count += rows;
+        let has_flag = flags.iter().any(|f| f == flag);
+        if has_flag == present {
+            continue;
+        }
+        if present {
+            flags.push(flag.to_string());
+        } else {
+            flags.retain(|f| f != flag);
         }
+        let new_flags = serde_json::to_string(&flags).unwrap_or_default();
+        tx.execute(
+            "UPDATE messages SET imap_flags = ?1 WHERE id = ?2",
+            params![new_flags, id],
+        )?;
+        changed.push(id.clone());
     }
 
     tx.commit()?;
-    Ok(count)
-}
\ No newline at end of file
+    Ok(changed)
+}
+
+/// Optimistically add or drop a Gmail label in each message's gmail_labels.
+/// Returns the ids of the rows changed. Only pass ids of Gmail messages when
+/// adding; other providers' messages have no labels.
+pub fn set_gmail_label(pool: &DbPool, message_ids: &[String], label: &str, present: bool) -> Result<Vec<String>, EddieError> {
+    let conn = pool.get()?;
+    let tx = conn.unchecked_transaction()?;
+    let mut changed = Vec::new();
+
+    for id in message_ids {
+        let current_labels: String = match tx.query_row(
+            "SELECT gmail_labels FROM messages WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        ) {
+            Ok(l) => l,
+            Err(_) => continue,
+        };
+
+        let mut labels: Vec<String> = serde_json::from_str(&current_labels).unwrap_or_default();
+        if labels.iter().any(|l| l == label) == present {
+            continue;
+        }
+        if present {
+            labels.push(label.to_string());
+        } else {
+            labels.retain(|l| l != label);
+        }
+        let new_labels = serde_json::to_string(&labels).unwrap_or_default();
+        tx.execute(
+            "UPDATE messages SET gmail_labels = ?1 WHERE id = ?2",
+            params![new_labels, id],
+        )?;
+        changed.push(id.clone());
+    }
+
+    tx.commit()?;
+    Ok(changed)
+}
+
+/// (id, imap_uid, message_id) for the given message row ids still in `folder`.
+pub fn get_locations_by_ids(
+    pool: &DbPool,
+    folder: &str,
+    ids: &[String],
+) -> Result<Vec<(String, u32, String)>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT imap_uid, message_id FROM messages WHERE id = ?1 AND imap_folder = ?2"
+    )?;
+
+    let mut results = Vec::new();
+    for id in ids {
+        match stmt.query_row(params![id, folder], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+        }) {
+            Ok((uid, message_id)) => results.push((id.clone(), uid, message_id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::test_support;
+
+    fn uids(pool: &DbPool) -> Vec<(String, i64)> {
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT id, imap_uid FROM messages ORDER BY id").unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_remap_folder_uids() {
+        let pool = test_support::pool();
+        pool.get().unwrap().execute_batch("
+            INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                  to_addresses, fetched_at, participant_key, conversation_id)
+            VALUES ('kept', 'acc1', 'kept@x', 1, 'INBOX', 1, 'a@x', '[]', 0, 'a', 'c'),
+                   ('swapped', 'acc1', 'swapped@x', 2, 'INBOX', 2, 'a@x', '[]', 0, 'a', 'c'),
+                   ('gone', 'acc1', 'gone@x', 3, 'INBOX', 3, 'a@x', '[]', 0, 'a', 'c'),
+                   ('no-id', 'acc1', '', 4, 'INBOX', 4, 'a@x', '[]', 0, 'a', 'c'),
+                   ('archived', 'acc1', 'archived@x', 1, 'Archive', 5, 'a@x', '[]', 0, 'a', 'c');
+            INSERT INTO attachments (message_id, part_path, mime_type, size_bytes, transfer_encoding)
+            VALUES ('gone', '2', 'text/plain', 1, '7bit');
+        ").unwrap();
+
+        // The new UIDs reuse old ones for other messages; the server lists
+        // one Message-ID twice and has mail we never cached
+        let server_ids = vec![
+            (2, "kept@x".to_string()),
+            (1, "swapped@x".to_string()),
+            (3, "swapped@x".to_string()),
+            (4, "".to_string()),
+            (5, "new@x".to_string()),
+        ];
+        let (remapped, deleted) = remap_folder_uids(&pool, "acc1", "INBOX", &server_ids).unwrap();
+        assert_eq!(remapped, vec![2, 1]);
+        assert_eq!(deleted, 2);
+        assert_eq!(uids(&pool), vec![
+            ("archived".to_string(), 1),
+            ("kept".to_string(), 2),
+            ("swapped".to_string(), 1),
+        ]);
+        let attachments: i64 = pool.get().unwrap()
+            .query_row("SELECT COUNT(*) FROM attachments", [], |row| row.get(0)).unwrap();
+        assert_eq!(attachments, 0);
+    }
+
+    #[test]
+    fn test_flag_and_label_changes_undo_exactly() {
+        let pool = test_support::pool();
+        pool.get().unwrap().execute_batch(r#"
+            INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                  to_addresses, fetched_at, participant_key, conversation_id,
+                                  imap_flags, gmail_labels)
+            VALUES ('unread', 'acc1', 'unread@x', 1, 'INBOX', 1, 'a@x', '[]', 0, 'a', 'c', '[]', '["Inbox"]'),
+                   ('read', 'acc1', 'read@x', 2, 'INBOX', 2, 'a@x', '[]', 0, 'a', 'c', '["Seen"]', '[]');
+        "#).unwrap();
+        let ids = vec!["unread".to_string(), "read".to_string()];
+
+        let changed = mark_messages_seen(&pool, &ids).unwrap();
+        assert_eq!(changed, vec!["unread"]);
+        assert_eq!(set_flag(&pool, &changed, "Seen", false).unwrap(), vec!["unread"]);
+
+        let changed = set_gmail_label(&pool, &ids, "Inbox", false).unwrap();
+        assert_eq!(changed, vec!["unread"]);
+        assert_eq!(set_gmail_label(&pool, &changed, "Inbox", true).unwrap(), vec!["unread"]);
+
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT id, imap_flags, gmail_labels FROM messages ORDER BY id").unwrap();
+        let rows: Vec<(String, String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![
+            ("read".to_string(), r#"["Seen"]"#.to_string(), "[]".to_string()),
+            ("unread".to_string(), "[]".to_string(), r#"["Inbox"]"#.to_string()),
+        ]);
+    }
+}
@@ -8,5 +8,12 @@ pub mod onboarding_tasks;
 pub mod folder_sync;
 pub mod settings;
 pub mod action_queue;
+pub mod oauth_tokens;
+pub mod attachments;
+pub mod delivery_failures;
+pub mod search;
+pub mod smart_views;
+#[cfg(test)]
+pub mod test_support;
 
 pub use db::DbPool;
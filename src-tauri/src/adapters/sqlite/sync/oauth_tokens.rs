@@ -0,0 +1,64 @@
+use rusqlite::params;
+
+use super::DbPool;
+use crate::adapters::oauth::TokenSet;
+use crate::error::EddieError;
+
+pub struct StoredToken {
+    pub provider: String,
+    pub tokens: TokenSet,
+    pub needs_reauth: bool,
+}
+
+pub fn get_token(pool: &DbPool, account_id: &str) -> Result<Option<StoredToken>, EddieError> {
+    let conn = pool.get()?;
+    let result = conn.query_row(
+        "SELECT provider, access_token, refresh_token, expires_at, needs_reauth
+         FROM oauth_tokens WHERE account_id = ?1",
+        params![account_id],
+        |row| {
+            Ok(StoredToken {
+                provider: row.get(0)?,
+                tokens: TokenSet {
+                    access_token: row.get(1)?,
+                    refresh_token: row.get(2)?,
+                    expires_at: row.get(3)?,
+                },
+                needs_reauth: row.get(4)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(token) => Ok(Some(token)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(EddieError::Database(e.to_string())),
+    }
+}
+
+/// Store freshly issued tokens and clear any reauth flag. A refresh that
+/// didn't rotate the refresh token keeps the one already stored.
+pub fn save_token(pool: &DbPool, account_id: &str, provider: &str, tokens: &TokenSet) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "INSERT INTO oauth_tokens (account_id, provider, access_token, refresh_token, expires_at, needs_reauth)
+         VALUES (?1, ?2, ?3, ?4, ?5, 0)
+         ON CONFLICT(account_id) DO UPDATE SET
+             provider = excluded.provider,
+             access_token = excluded.access_token,
+             refresh_token = COALESCE(excluded.refresh_token, oauth_tokens.refresh_token),
+             expires_at = excluded.expires_at,
+             needs_reauth = 0",
+        params![account_id, provider, tokens.access_token, tokens.refresh_token, tokens.expires_at],
+    )?;
+    Ok(())
+}
+
+pub fn mark_needs_reauth(pool: &DbPool, account_id: &str) -> Result<(), EddieError> {
+    let conn = pool.get()?;
+    conn.execute(
+        "UPDATE oauth_tokens SET needs_reauth = 1 WHERE account_id = ?1",
+        params![account_id],
+    )?;
+    Ok(())
+}
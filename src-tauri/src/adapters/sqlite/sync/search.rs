@@ -0,0 +1,333 @@
+use rusqlite::{params_from_iter, types::Value, Connection};
+use serde::Serialize;
+
+use super::DbPool;
+use crate::error::EddieError;
+use crate::services::search_query::{self, Filter, Query};
+
+/// Most hits returned per page.
+const MAX_LIMIT: u32 = 100;
+
+/// Column weights for bm25: subject, body_text, distilled_text, from_name, addresses.
+const RANK: &str = "bm25(messages_fts, 10.0, 1.0, 1.0, 5.0, 3.0)";
+
+/// Marks around matched terms in snippets. Control characters can't occur
+/// in indexed text, unlike `<b>` which a message body might well contain.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub id: String,
+    pub conversation_id: String,
+    pub date: i64,
+    pub from_address: String,
+    pub from_name: Option<String>,
+    pub subject: Option<String>,
+    /// Best matching excerpt, split into matched and unmatched runs
+    pub snippet: Vec<SnippetPart>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    /// Pass back as `cursor` for the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Search an account's messages with a `search_query` query, best text
+/// matches first. `cursor` is the `next_cursor` of the previous page.
+pub fn search_messages(
+    pool: &DbPool,
+    account_id: &str,
+    query: &str,
+    limit: u32,
+    cursor: Option<&str>,
+) -> Result<SearchPage, EddieError> {
+    let conn = pool.get()?;
+    search(&conn, account_id, query, limit, cursor)
+}
+
+fn search(
+    conn: &Connection,
+    account_id: &str,
+    query: &str,
+    limit: u32,
+    cursor: Option<&str>,
+) -> Result<SearchPage, EddieError> {
+    let offset: u32 = match cursor {
+        Some(c) => c.parse().map_err(|_| EddieError::InvalidInput(format!("Invalid search cursor: {}", c)))?,
+        None => 0,
+    };
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let Some(query) = search_query::parse(query)? else {
+        return Ok(SearchPage { hits: vec![], next_cursor: None });
+    };
+
+    let mut values: Vec<Value> = vec![Value::from(account_id.to_string())];
+    // Hits are ranked and highlighted by the words they match, if the query
+    // has any; filter-only queries list newest first.
+    let ranked: Vec<String> = query.ranked_terms().into_iter()
+        .filter_map(|(text, prefix)| fts_term(text, prefix))
+        .collect();
+    let fallback = "substr(COALESCE(m.distilled_text, m.body_text, ''), 1, 160)";
+    let (ranking, snippet, order) = if ranked.is_empty() {
+        (String::new(), fallback.to_string(), "m.date DESC")
+    } else {
+        (format!(
+            "LEFT JOIN (
+                 SELECT rowid, {} AS score, snippet(messages_fts, -1, char(2), char(3), '…', 16) AS snippet
+                 FROM messages_fts WHERE messages_fts MATCH {}
+             ) f ON f.rowid = m.rowid",
+            RANK,
+            param(&mut values, ranked.join(" OR ")),
+        ), format!("COALESCE(f.snippet, {})", fallback), "f.score IS NULL, f.score, m.date DESC")
+    };
+    let condition = message_condition(&query, &mut values);
+    let limit_param = param(&mut values, limit + 1);
+    let offset_param = param(&mut values, offset);
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id, m.conversation_id, m.date, m.from_address, m.from_name, m.subject, {}
+         FROM messages m
+         {}
+         WHERE m.account_id = ?1
+           AND m.classification IS NOT 'bounce'
+           AND ({})
+         ORDER BY {}
+         LIMIT {} OFFSET {}",
+        snippet, ranking, condition, order, limit_param, offset_param,
+    ))?;
+    // One extra row tells whether there is a next page
+    let mut hits = stmt.query_map(params_from_iter(values), |row| {
+        Ok(SearchHit {
+            id: row.get(0)?,
+            conversation_id: row.get(1)?,
+            date: row.get(2)?,
+            from_address: row.get(3)?,
+            from_name: row.get(4)?,
+            subject: row.get(5)?,
+            snippet: snippet_parts(&row.get::<_, String>(6)?),
+        })
+    })?.collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if hits.len() > limit as usize {
+        hits.truncate(limit as usize);
+        Some((offset + limit).to_string())
+    } else {
+        None
+    };
+    Ok(SearchPage { hits, next_cursor })
+}
+
+/// Compile a parsed query into an SQL condition on `messages m`, adding
+/// its parameters to `values`.
+pub fn message_condition(query: &Query, values: &mut Vec<Value>) -> String {
+    match query {
+        Query::And(parts) | Query::Or(parts) => {
+            let joiner = if matches!(query, Query::And(_)) { " AND " } else { " OR " };
+            let parts: Vec<String> = parts.iter().map(|p| format!("({})", message_condition(p, values))).collect();
+            parts.join(joiner)
+        }
+        Query::Not(inner) => format!("NOT ({})", message_condition(inner, values)),
+        Query::Text { text, prefix } => match fts_term(text, *prefix) {
+            Some(term) => format!(
+                "m.rowid IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH {})",
+                param(values, term)
+            ),
+            // Nothing searchable in it, like a lone "&"
+            None => "1".to_string(),
+        },
+        Query::Filter(filter) => filter_condition(filter, values),
+    }
+}
+
+fn filter_condition(filter: &Filter, values: &mut Vec<Value>) -> String {
+    match filter {
+        Filter::From(text) => {
+            let p = param(values, text.clone());
+            format!("(instr(lower(m.from_address), {p}) > 0 OR instr(lower(COALESCE(m.from_name, '')), {p}) > 0)")
+        }
+        Filter::To(text) => {
+            let p = param(values, text.clone());
+            format!(
+                "(instr(lower(m.to_addresses), {p}) > 0 OR instr(lower(COALESCE(m.cc_addresses, '')), {p}) > 0
+                  OR instr(lower(COALESCE(m.bcc_addresses, '')), {p}) > 0)"
+            )
+        }
+        Filter::HasAttachment => "m.has_attachments = 1".to_string(),
+        Filter::Unread => "NOT EXISTS (SELECT 1 FROM json_each(m.imap_flags) WHERE value = 'Seen')".to_string(),
+        Filter::Read => "EXISTS (SELECT 1 FROM json_each(m.imap_flags) WHERE value = 'Seen')".to_string(),
+        Filter::Important => "m.is_important = 1".to_string(),
+        Filter::Pinned => "m.conversation_id IN (SELECT id FROM conversations WHERE is_pinned = 1)".to_string(),
+        Filter::Muted => "m.conversation_id IN (SELECT id FROM conversations WHERE is_muted = 1)".to_string(),
+        Filter::Before(ms) => format!("m.date < {}", param(values, *ms)),
+        Filter::After(ms) => format!("m.date >= {}", param(values, *ms)),
+        Filter::In(folder) => {
+            let p = param(values, folder.clone());
+            format!(
+                "(m.imap_folder = {p} COLLATE NOCASE
+                  OR EXISTS (SELECT 1 FROM json_each(m.gmail_labels) WHERE value = {p} COLLATE NOCASE))"
+            )
+        }
+        Filter::Class(class) => format!("m.classification = {}", param(values, class.clone())),
+        Filter::Trust(level) => format!(
+            "EXISTS (SELECT 1 FROM entities e
+                     WHERE e.account_id = m.account_id AND e.email = m.from_address AND e.trust_level = {})",
+            param(values, level.clone())
+        ),
+    }
+}
+
+/// Add a parameter and return its placeholder.
+fn param(values: &mut Vec<Value>, value: impl Into<Value>) -> String {
+    values.push(value.into());
+    format!("?{}", values.len())
+}
+
+/// A word or phrase as an FTS5 string, so operators and punctuation in it
+/// are taken literally. None if it has nothing the tokenizer would index.
+fn fts_term(text: &str, prefix: bool) -> Option<String> {
+    if !text.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let quoted = format!("\"{}\"", text.replace('"', "\"\""));
+    Some(if prefix { format!("{}*", quoted) } else { quoted })
+}
+
+fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut rest = snippet;
+    while let Some(start) = rest.find(MATCH_START) {
+        if start > 0 {
+            parts.push(SnippetPart { text: rest[..start].to_string(), highlight: false });
+        }
+        let matched = &rest[start + MATCH_START.len_utf8()..];
+        let end = matched.find(MATCH_END).unwrap_or(matched.len());
+        parts.push(SnippetPart { text: matched[..end].to_string(), highlight: true });
+        rest = matched.get(end + MATCH_END.len_utf8()..).unwrap_or_default();
+    }
+    if !rest.is_empty() {
+        parts.push(SnippetPart { text: rest.to_string(), highlight: false });
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::test_support::db;
+    use rusqlite::params;
+
+    fn insert(conn: &Connection, id: &str, from_name: &str, subject: &str, body: &str, date: i64) {
+        conn.execute(
+            "INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                   from_name, to_addresses, subject, body_text, fetched_at, participant_key, conversation_id)
+             VALUES (?1, 'acc1', ?1, ?2, 'INBOX', ?2, 'sender@example.org', ?3, '[\"me@example.com\"]', ?4, ?5, 0, 'k', ?6)",
+            params![id, date, from_name, subject, body, format!("conv-{}", id)],
+        ).unwrap();
+    }
+
+    fn ids(page: &SearchPage) -> Vec<&str> {
+        page.hits.iter().map(|h| h.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_ranks_subject_matches_first_and_highlights() {
+        let conn = db();
+        insert(&conn, "m1", "Bo", "Weekly notes", "We talked about the budget for next year", 1);
+        insert(&conn, "m2", "Bo", "Budget", "Numbers attached", 2);
+        insert(&conn, "m3", "Bo", "Lunch", "Pizza?", 3);
+
+        let page = search(&conn, "acc1", "budget", 10, None).unwrap();
+        assert_eq!(ids(&page), vec!["m2", "m1"]);
+        assert_eq!(page.hits[0].conversation_id, "conv-m2");
+        assert!(page.next_cursor.is_none());
+
+        let highlighted: Vec<&str> = page.hits[1].snippet.iter()
+            .filter(|p| p.highlight)
+            .map(|p| p.text.as_str())
+            .collect();
+        assert_eq!(highlighted, vec!["budget"]);
+    }
+
+    #[test]
+    fn test_ignores_diacritics() {
+        let conn = db();
+        insert(&conn, "m1", "Jürgen Müller", "Mødet i Århus", "Grüße aus Köln", 1);
+
+        assert_eq!(ids(&search(&conn, "acc1", "muller", 10, None).unwrap()), vec!["m1"]);
+        assert_eq!(ids(&search(&conn, "acc1", "arhus", 10, None).unwrap()), vec!["m1"]);
+        assert_eq!(ids(&search(&conn, "acc1", "Koln grü", 10, None).unwrap()), vec!["m1"]);
+        assert_eq!(ids(&search(&conn, "acc1", "mødet", 10, None).unwrap()), vec!["m1"]);
+    }
+
+    #[test]
+    fn test_index_follows_updates_and_deletes() {
+        let conn = db();
+        insert(&conn, "m1", "Bo", "Hello", "first draft", 1);
+        conn.execute("UPDATE messages SET body_text = 'final version' WHERE id = 'm1'", []).unwrap();
+        assert!(search(&conn, "acc1", "draft", 10, None).unwrap().hits.is_empty());
+        assert_eq!(ids(&search(&conn, "acc1", "final", 10, None).unwrap()), vec!["m1"]);
+
+        assert_eq!(ids(&search(&conn, "acc1", "sender@example", 10, None).unwrap()), vec!["m1"]);
+        conn.execute("DELETE FROM messages WHERE id = 'm1'", []).unwrap();
+        assert!(search(&conn, "acc1", "final", 10, None).unwrap().hits.is_empty());
+    }
+
+    #[test]
+    fn test_pages_with_cursor() {
+        let conn = db();
+        for i in 0..5 {
+            insert(&conn, &format!("m{}", i), "Bo", "Report", "quarterly report", i);
+        }
+        let first = search(&conn, "acc1", "report", 2, None).unwrap();
+        assert_eq!(ids(&first), vec!["m4", "m3"]);
+        let second = search(&conn, "acc1", "report", 2, first.next_cursor.as_deref()).unwrap();
+        assert_eq!(ids(&second), vec!["m2", "m1"]);
+        let last = search(&conn, "acc1", "report", 2, second.next_cursor.as_deref()).unwrap();
+        assert_eq!(ids(&last), vec!["m0"]);
+        assert!(last.next_cursor.is_none());
+
+        assert!(search(&conn, "acc1", "report", 2, Some("x")).is_err());
+    }
+
+    #[test]
+    fn test_filters_and_boolean_operators() {
+        let conn = db();
+        insert(&conn, "m1", "Anna", "Invoice", "see attached", 1);
+        insert(&conn, "m2", "Bo", "Invoice", "paid", 2);
+        insert(&conn, "m3", "Bo", "Lunch", "pizza", 3);
+        conn.execute_batch("
+            UPDATE messages SET has_attachments = 1, imap_flags = '[\"Seen\"]' WHERE id = 'm1';
+            UPDATE messages SET classification = 'chat', imap_folder = 'Archive' WHERE id = 'm3';
+            INSERT INTO entities (id, account_id, email, trust_level, first_seen)
+            VALUES ('e1', 'acc1', 'sender@example.org', 'connection', 0);
+        ").unwrap();
+
+        let find = |q: &str| ids(&search(&conn, "acc1", q, 10, None).unwrap()).join(",");
+        assert_eq!(find("invoice has:attachment"), "m1");
+        assert_eq!(find("invoice -from:anna"), "m2");
+        assert_eq!(find("is:unread"), "m3,m2");
+        assert_eq!(find("in:archive OR from:anna"), "m3,m1");
+        assert_eq!(find("class:chat"), "m3");
+        assert_eq!(find("trust:connection after:1970-01-01 piz"), "m3");
+        assert_eq!(find("NOT (invoice OR lunch)"), "");
+        assert_eq!(find("\"see attached\""), "m1");
+        assert_eq!(find("\"attached see\""), "");
+        assert!(search(&conn, "acc1", "is:banana", 10, None).is_err());
+    }
+
+    #[test]
+    fn test_fts_term_quotes_input() {
+        assert_eq!(fts_term("wor", true).as_deref(), Some("\"wor\"*"));
+        assert_eq!(fts_term("say \"hi\" OR", false).as_deref(), Some("\"say \"\"hi\"\" OR\""));
+        assert_eq!(fts_term("-", false), None);
+    }
+}
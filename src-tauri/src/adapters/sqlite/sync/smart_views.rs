@@ -0,0 +1,139 @@
+use rusqlite::params;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::DbPool;
+use crate::error::EddieError;
+
+/// A named, saved search query.
+#[derive(Debug, Serialize)]
+pub struct SmartView {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+fn map_row(row: &rusqlite::Row) -> rusqlite::Result<SmartView> {
+    Ok(SmartView {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        query: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+pub fn list_views(pool: &DbPool, account_id: &str) -> Result<Vec<SmartView>, EddieError> {
+    let conn = pool.get()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, query, created_at, updated_at FROM smart_views
+         WHERE account_id = ?1 ORDER BY name COLLATE NOCASE",
+    )?;
+    let views = stmt.query_map(params![account_id], map_row)?.collect::<Result<Vec<_>, _>>()?;
+    Ok(views)
+}
+
+pub fn get_view(pool: &DbPool, account_id: &str, id: &str) -> Result<SmartView, EddieError> {
+    let conn = pool.get()?;
+    conn.query_row(
+        "SELECT id, name, query, created_at, updated_at FROM smart_views
+         WHERE account_id = ?1 AND id = ?2",
+        params![account_id, id],
+        map_row,
+    ).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => EddieError::InvalidInput(format!("No such view: {}", id)),
+        e => e.into(),
+    })
+}
+
+/// Create a view, or rename and change the query of an existing one.
+pub fn save_view(
+    pool: &DbPool,
+    account_id: &str,
+    id: Option<&str>,
+    name: &str,
+    query: &str,
+) -> Result<SmartView, EddieError> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let id = match id {
+        Some(id) => id.to_string(),
+        None => Uuid::new_v4().to_string(),
+    };
+    let conn = pool.get()?;
+    let result = conn.execute(
+        "INSERT INTO smart_views (id, account_id, name, query, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            query = excluded.query,
+            updated_at = excluded.updated_at
+         WHERE smart_views.account_id = excluded.account_id",
+        params![id, account_id, name, query, now],
+    );
+    match result {
+        Ok(_) => {}
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+            return Err(EddieError::InvalidInput(format!("A view named \"{}\" already exists", name)));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    drop(conn);
+    get_view(pool, account_id, &id)
+}
+
+pub fn delete_view(pool: &DbPool, account_id: &str, id: &str) -> Result<bool, EddieError> {
+    let conn = pool.get()?;
+    let rows = conn.execute(
+        "DELETE FROM smart_views WHERE account_id = ?1 AND id = ?2",
+        params![account_id, id],
+    )?;
+    Ok(rows > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::{conversations, test_support};
+
+    fn pool() -> DbPool {
+        let pool = test_support::pool();
+        pool.get().unwrap().execute_batch("
+            INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                  to_addresses, subject, body_text, fetched_at, participant_key, conversation_id, has_attachments)
+            VALUES ('m1', 'acc1', 'm1@x', 1, 'INBOX', 1, 'anna@example.org', '[]', 'Invoice', 'attached', 0, 'anna', 'c1', 1),
+                   ('m2', 'acc1', 'm2@x', 2, 'INBOX', 2, 'bo@example.org', '[]', 'Lunch', 'pizza', 0, 'bo', 'c2', 0);
+            INSERT INTO conversations (id, account_id, participant_key, classification, last_message_date, updated_at)
+            VALUES ('c1', 'acc1', 'anna', 'connections', 1, 0),
+                   ('c2', 'acc1', 'bo', 'connections', 2, 0);
+        ").unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_views_filter_conversations() {
+        let pool = pool();
+        let view = save_view(&pool, "acc1", None, "Invoices", "has:attachment invoice").unwrap();
+        let ids = |view_id: Option<&str>| -> Vec<String> {
+            conversations::fetch_conversations(&pool, "acc1", view_id).unwrap()
+                .into_iter().map(|c| c.id).collect()
+        };
+        assert_eq!(ids(None), vec!["c2", "c1"]);
+        assert_eq!(ids(Some(&view.id)), vec!["c1"]);
+
+        let view = save_view(&pool, "acc1", Some(&view.id), "Bo", "from:bo").unwrap();
+        assert_eq!(ids(Some(&view.id)), vec!["c2"]);
+        assert_eq!(list_views(&pool, "acc1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_view_names_are_unique() {
+        let pool = pool();
+        let first = save_view(&pool, "acc1", None, "Unread", "is:unread").unwrap();
+        assert!(matches!(save_view(&pool, "acc1", None, "Unread", "is:read"), Err(EddieError::InvalidInput(_))));
+
+        assert!(delete_view(&pool, "acc1", &first.id).unwrap());
+        assert!(get_view(&pool, "acc1", &first.id).is_err());
+    }
+}
@@ -0,0 +1,35 @@
+//! Database fixtures shared by the adapter tests.
+
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+use super::db_schema::initialize_schema;
+use super::DbPool;
+
+/// Insert the test account, `acc1` for me@example.com.
+pub fn add_account(conn: &Connection) {
+    conn.execute(
+        "INSERT INTO accounts (id, email, imap_host, smtp_host, created_at)
+         VALUES ('acc1', 'me@example.com', 'imap.example.com', 'smtp.example.com', 0)",
+        [],
+    ).unwrap();
+}
+
+/// An in-memory database with the current schema and the test account.
+pub fn db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    initialize_schema(&conn).unwrap();
+    add_account(&conn);
+    conn
+}
+
+/// `db()` behind a pool, for code that takes a `DbPool`. The pool has one
+/// connection, so every checkout sees the same in-memory database.
+pub fn pool() -> DbPool {
+    let pool = r2d2::Pool::builder().max_size(1).build(SqliteConnectionManager::memory()).unwrap();
+    let conn = pool.get().unwrap();
+    initialize_schema(&conn).unwrap();
+    add_account(&conn);
+    drop(conn);
+    pool
+}
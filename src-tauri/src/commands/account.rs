@@ -1,9 +1,11 @@
 use serde::Serialize;
 
-use crate::adapters::{imap, sqlite};
+use crate::adapters::{self, imap, sqlite};
+use crate::adapters::imap::connection::{ImapAuth, ImapSecurity};
 use crate::error::EddieError;
 use tokio::sync::mpsc;
-use crate::services::logger;
+use crate::services::{logger, oauth};
+use crate::services::sync::connection_manager;
 
 #[tauri::command]
 pub async fn connect_account(
@@ -13,41 +15,128 @@ pub async fn connect_account(
     password: String,
     imap_host: String,
     imap_port: u16,
-    imap_tls: Option<bool>,
+    imap_security: Option<ImapSecurity>,
+    allow_plaintext: Option<bool>,
     smtp_host: String,
     smtp_port: u16,
     smtp_tls: Option<bool>,
     aliases: Option<String>,
 ) -> Result<String, EddieError> {
-    let use_tls = imap_tls.unwrap_or(true);
+    let imap_security = imap_security.unwrap_or(ImapSecurity::Tls);
+    let allow_plaintext = allow_plaintext.unwrap_or(false);
     let use_smtp_tls = smtp_tls.unwrap_or(true);
-    logger::info(&format!("Connecting account: email={}, imap_host={}, imap_tls={}", email, imap_host, use_tls));
+    logger::info(&format!(
+        "Connecting account: email={}, imap_host={}, imap_security={}",
+        email, imap_host, imap_security.as_str()
+    ));
 
     // Verify IMAP credentials before saving the account
-    let mut conn = imap::connection::connect_with_tls(&imap_host, imap_port, use_tls, &email, &password, true).await?;
+    let mut conn = imap::connection::connect_with_tls(
+        &imap_host, imap_port, imap_security, allow_plaintext, &email,
+        &ImapAuth::Password(password.clone()), true,
+    ).await?;
     conn.session.logout().await.ok();
     logger::info("IMAP credentials verified");
 
     let id = sqlite::accounts::insert_account(
-        &pool, &email, &password, &imap_host, imap_port, use_tls, &smtp_host, smtp_port, use_smtp_tls,
+        &pool, &email, &password, &imap_host, imap_port, imap_security, allow_plaintext,
+        &smtp_host, smtp_port, use_smtp_tls, None,
     )?;
-    sqlite::entities::insert_entity(&pool, &id, &email, "account", "user")?;
+    finish_connect(&pool, &wake_tx, &id, &email, &imap_host, aliases.as_deref()).await?;
+    Ok(id)
+}
+
+/// Like `connect_account`, but signs in through the provider's OAuth2
+/// consent page in the browser instead of taking a password.
+#[tauri::command]
+pub async fn connect_oauth_account(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, sqlite::DbPool>,
+    wake_tx: tauri::State<'_, mpsc::Sender<()>>,
+    email: String,
+    oauth_provider: String,
+    imap_host: String,
+    imap_port: u16,
+    imap_security: Option<ImapSecurity>,
+    smtp_host: String,
+    smtp_port: u16,
+    smtp_tls: Option<bool>,
+    aliases: Option<String>,
+) -> Result<String, EddieError> {
+    let imap_security = imap_security.unwrap_or(ImapSecurity::Tls);
+    let use_smtp_tls = smtp_tls.unwrap_or(true);
+    logger::info(&format!("Connecting account via OAuth: email={}, provider={}", email, oauth_provider));
+
+    let tokens = oauth::authorize(&app, &oauth_provider, Some(&email)).await?;
+
+    // Verify the token works for IMAP before saving the account
+    let auth = ImapAuth::OAuth2 {
+        mechanism: adapters::oauth::provider(&oauth_provider)?.imap_mechanism,
+        access_token: tokens.access_token.clone(),
+    };
+    let mut conn = imap::connection::connect_with_tls(
+        &imap_host, imap_port, imap_security, false, &email, &auth, true,
+    ).await?;
+    conn.session.logout().await.ok();
+    logger::info("IMAP OAuth token verified");
+
+    let id = sqlite::accounts::insert_account(
+        &pool, &email, "", &imap_host, imap_port, imap_security, false,
+        &smtp_host, smtp_port, use_smtp_tls, Some(&oauth_provider),
+    )?;
+    sqlite::oauth_tokens::save_token(&pool, &id, &oauth_provider, &tokens)?;
+    finish_connect(&pool, &wake_tx, &id, &email, &imap_host, aliases.as_deref()).await?;
+    Ok(id)
+}
+
+/// Sign an OAuth2 account in again after its refresh token was revoked or expired.
+#[tauri::command]
+pub async fn reauthorize_account(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, sqlite::DbPool>,
+    wake_tx: tauri::State<'_, mpsc::Sender<()>>,
+    account_id: String,
+) -> Result<(), EddieError> {
+    let creds = sqlite::accounts::get_credentials(&pool, &account_id)?
+        .ok_or(EddieError::AccountNotFound(account_id.clone()))?;
+    let provider = creds.oauth_provider
+        .ok_or_else(|| EddieError::InvalidInput("Account does not use OAuth".into()))?;
+
+    let tokens = oauth::authorize(&app, &provider, Some(&creds.email)).await?;
+    sqlite::oauth_tokens::save_token(&pool, &account_id, &provider, &tokens)?;
+    connection_manager::invalidate(&account_id);
 
-    if let Some(alias_str) = &aliases {
+    let _ = wake_tx.send(()).await;
+    logger::info(&format!("Account reauthorized: {}", account_id));
+    Ok(())
+}
+
+/// Register the account's own address and aliases, then wake the engine.
+async fn finish_connect(
+    pool: &sqlite::DbPool,
+    wake_tx: &mpsc::Sender<()>,
+    id: &str,
+    email: &str,
+    imap_host: &str,
+    aliases: Option<&str>,
+) -> Result<(), EddieError> {
+    sqlite::entities::insert_entity(pool, id, email, "account", "user")?;
+
+    if let Some(alias_str) = aliases {
         logger::debug(&format!("Registering aliases: {}", alias_str));
         for alias in alias_str.split(&[',', ' '][..]) {
             let trimmed = alias.trim();
             if !trimmed.is_empty() {
-                sqlite::entities::insert_entity(&pool, &id, trimmed, "account", "alias")?;
+                sqlite::entities::insert_entity(pool, id, trimmed, "account", "alias")?;
             }
         }
     }
 
-    logger::set_source(&email);
-    logger::set_host(&imap_host);
+    logger::set_source(email);
+    logger::set_host(imap_host);
     let _ = wake_tx.send(()).await;
     logger::info(&format!("Account connected, engine woken: account_id={}", id));
-    Ok(id)
+    Ok(())
 }
 
 #[derive(Debug, Serialize)]
@@ -74,11 +163,14 @@ pub struct AccountDetails {
     pub display_name: Option<String>,
     pub imap_host: String,
     pub imap_port: u16,
-    pub imap_tls: bool,
+    pub imap_security: ImapSecurity,
+    pub allow_plaintext: bool,
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_tls: bool,
     pub aliases: Vec<String>,
+    pub oauth_provider: Option<String>,
+    pub reauth_required: bool,
 }
 
 #[tauri::command]
@@ -97,7 +189,8 @@ pub async fn update_account(
     password: Option<String>,
     imap_host: Option<String>,
     imap_port: Option<u16>,
-    imap_tls: Option<bool>,
+    imap_security: Option<ImapSecurity>,
+    allow_plaintext: Option<bool>,
     smtp_host: Option<String>,
     smtp_port: Option<u16>,
     smtp_tls: Option<bool>,
@@ -106,7 +199,7 @@ pub async fn update_account(
     sqlite::accounts::update_account(
         &pool, &account_id,
         display_name.as_deref(), password.as_deref(),
-        imap_host.as_deref(), imap_port, imap_tls,
+        imap_host.as_deref(), imap_port, imap_security, allow_plaintext,
         smtp_host.as_deref(), smtp_port, smtp_tls,
     )?;
 
@@ -132,6 +225,9 @@ pub async fn update_account(
         }
     }
 
+    // Pooled sessions may be logged in with the old password or server
+    connection_manager::invalidate(&account_id);
+
     logger::info(&format!("Account updated: {}", account_id));
     Ok(())
 }
@@ -1,5 +1,7 @@
 use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::sqlite::action_queue::LOCAL_ACTIONS;
 use crate::error::EddieError;
+use crate::services::sync::helpers::status_emit;
 use tokio::sync::mpsc;
 
 #[tauri::command]
@@ -14,30 +16,175 @@ pub async fn queue_action(
     let write_mode = sqlite::settings::get_setting(&pool, "write_mode")?
         .map(|v| v == "true")
         .unwrap_or(false);
-    if !write_mode {
+    if !write_mode && !LOCAL_ACTIONS.contains(&action_type.as_str()) {
         return Err(EddieError::InvalidInput("Read-only mode: action not permitted".into()));
     }
 
-    let action_id = sqlite::action_queue::enqueue(&pool, &account_id, &action_type, &payload, None)?;
+    let mut parsed: serde_json::Value = serde_json::from_str(&payload)
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid {} payload: {}", action_type, e)))?;
+    let ids: Vec<String> = parsed["ids"].as_array()
+        .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
 
-    // Optimistic DB update for mark_read: persist \\Seen flag locally
-    if action_type == "mark_read" {
-        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&payload) {
-            if let Some(ids) = parsed["ids"].as_array() {
-                let id_strings: Vec<String> = ids.iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect();
-                if !id_strings.is_empty() {
-                    let _ = sqlite::messages::mark_messages_seen(&pool, &id_strings);
-                }
+    // Moves are replayed by Message-ID, since the rows may be gone (delete)
+    // and UIDs go stale across UIDVALIDITY changes. Record both now.
+    if matches!(action_type.as_str(), "archive" | "delete" | "move") {
+        let folder = parsed["folder"].as_str()
+            .ok_or(EddieError::InvalidInput(format!("{}: missing folder", action_type)))?;
+        if action_type == "move" && parsed["destination"].as_str().is_none() {
+            return Err(EddieError::InvalidInput("move: missing destination".into()));
+        }
+        let locations = sqlite::messages::get_locations_by_ids(&pool, folder, &ids)?;
+        parsed["uids"] = locations.iter().map(|(_, uid, _)| *uid).collect();
+        parsed["message_ids"] = locations.iter().map(|(_, _, message_id)| message_id.clone()).collect();
+    }
+    if LOCAL_ACTIONS.contains(&action_type.as_str()) && parsed["conversation_id"].as_str().is_none() {
+        return Err(EddieError::InvalidInput(format!("{}: missing conversation_id", action_type)));
+    }
+
+    // Optimistic DB update so the UI reflects the action before replay. Flag
+    // and label changes record the rows they changed, so cancelling the
+    // action can undo exactly those.
+    let changed = match action_type.as_str() {
+        "mark_read" => Some(sqlite::messages::mark_messages_seen(&pool, &ids)?),
+        "flag" => {
+            let flagged = parsed["flagged"].as_bool().unwrap_or(true);
+            Some(sqlite::messages::set_flag(&pool, &ids, "Flagged", flagged)?)
+        }
+        // Archived and moved mail usually stays in a synced folder, so only Gmail's
+        // Inbox label changes locally; replay re-homes rows to their new UIDs
+        "archive" => Some(sqlite::messages::set_gmail_label(&pool, &ids, "Inbox", false)?),
+        _ => None,
+    };
+    if let Some(changed) = &changed {
+        parsed["changed"] = serde_json::json!(changed);
+    }
+
+    let action_id = sqlite::action_queue::enqueue(&pool, &account_id, &action_type, &parsed.to_string(), None, None)?;
+
+    let mut removed_ids: Vec<String> = Vec::new();
+    // Update conversations so unread_count changes immediately
+    let mut update = action_type == "mark_read";
+    match action_type.as_str() {
+        "delete" => removed_ids = ids,
+        "mute" | "pin" => {
+            let conversation_id = parsed["conversation_id"].as_str().unwrap_or_default();
+            if action_type == "mute" {
+                sqlite::conversations::set_muted(&pool, &account_id, conversation_id, parsed["muted"].as_bool().unwrap_or(true))?;
+            } else {
+                sqlite::conversations::set_pinned(&pool, &account_id, conversation_id, parsed["pinned"].as_bool().unwrap_or(true))?;
             }
+            update = true;
         }
+        _ => {}
+    }
 
-        // Rebuild conversations so unread_count is updated immediately
-        let conv_count = sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
-        crate::services::sync::helpers::status_emit::emit_conversations_updated(&app, &account_id, conv_count);
+    if !removed_ids.is_empty() {
+        sqlite::messages::delete_by_ids(&pool, &removed_ids)?;
+        status_emit::emit_messages_removed(&app, &account_id, &removed_ids);
+        update = true;
+    }
+    if update {
+        let conv_count = sqlite::conversations::update_conversations(&pool, &account_id)?;
+        status_emit::emit_conversations_updated(&app, &account_id, conv_count);
     }
 
     let _ = wake_tx.send(()).await;
     Ok(action_id)
 }
+
+/// Queued, running, failed and awaiting-confirmation actions of an account.
+#[tauri::command]
+pub async fn list_outbox(
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+) -> Result<Vec<sqlite::action_queue::QueuedAction>, EddieError> {
+    sqlite::action_queue::list_outbox(&pool, &account_id)
+}
+
+/// Run a failed action again with a fresh retry budget, or a backed-off one now.
+#[tauri::command]
+pub async fn retry_action(
+    pool: tauri::State<'_, DbPool>,
+    wake_tx: tauri::State<'_, mpsc::Sender<()>>,
+    action_id: String,
+) -> Result<(), EddieError> {
+    if !sqlite::action_queue::retry_now(&pool, &action_id)? {
+        return Err(EddieError::InvalidInput("Only pending or failed actions can be retried".into()));
+    }
+    let _ = wake_tx.send(()).await;
+    Ok(())
+}
+
+/// Drop a pending or failed action and undo its optimistic update. A send
+/// removes its OUTBOX row; flag changes and archives restore the flags and
+/// labels of the rows they changed. Deletes can't be cancelled — the rows
+/// are already gone locally.
+#[tauri::command]
+pub async fn cancel_action(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, DbPool>,
+    action_id: String,
+) -> Result<(), EddieError> {
+    let action = sqlite::action_queue::get_action(&pool, &action_id)?
+        .ok_or_else(|| EddieError::InvalidInput(format!("No queued action {}", action_id)))?;
+    cancel_queued(&app, &pool, &action)
+}
+
+/// Cancel an action and roll back its optimistic update (see `cancel_action`).
+pub(crate) fn cancel_queued(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    action: &sqlite::action_queue::QueuedAction,
+) -> Result<(), EddieError> {
+    if action.action_type == "delete" {
+        return Err(EddieError::InvalidInput("Deletes can't be cancelled".into()));
+    }
+    if !sqlite::action_queue::cancel(pool, &action.id)? {
+        return Err(EddieError::InvalidInput("Only pending or failed actions that haven't been sent can be cancelled".into()));
+    }
+
+    let payload: serde_json::Value = serde_json::from_str(&action.payload).unwrap_or_default();
+    match action.action_type.as_str() {
+        "send" => {
+            crate::services::attachments::remove_outgoing(&payload);
+            let message_id = action.message_id.as_deref().unwrap_or_default();
+            if let Some(id) = sqlite::messages::get_outbox_id(pool, &action.account_id, message_id)? {
+                let ids = vec![id];
+                sqlite::messages::delete_by_ids(pool, &ids)?;
+                status_emit::emit_messages_removed(app, &action.account_id, &ids);
+                let conv_count = sqlite::conversations::update_conversations(pool, &action.account_id)?;
+                status_emit::emit_conversations_updated(app, &action.account_id, conv_count);
+            }
+        }
+        "mark_read" | "flag" | "archive" => {
+            let changed: Option<Vec<String>> = payload["changed"].as_array()
+                .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect());
+            match changed {
+                Some(ids) => {
+                    match action.action_type.as_str() {
+                        "mark_read" => sqlite::messages::set_flag(pool, &ids, "Seen", false)?,
+                        "flag" => {
+                            let flagged = payload["flagged"].as_bool().unwrap_or(true);
+                            sqlite::messages::set_flag(pool, &ids, "Flagged", !flagged)?
+                        }
+                        _ => sqlite::messages::set_gmail_label(pool, &ids, "Inbox", true)?,
+                    };
+                }
+                // Queued before changed rows were recorded: dropping the
+                // CONDSTORE baseline makes the next flag resync a full scan
+                None => {
+                    if let Some(folder) = payload["folder"].as_str() {
+                        sqlite::folder_sync::set_highest_modseq(pool, &action.account_id, folder, 0)?;
+                    }
+                }
+            }
+            let conv_count = sqlite::conversations::update_conversations(pool, &action.account_id)?;
+            status_emit::emit_conversations_updated(app, &action.account_id, conv_count);
+        }
+        // Moves change nothing locally until replay; mute and pin are already
+        // applied locally. There is nothing to undo
+        _ => {}
+    }
+    Ok(())
+}
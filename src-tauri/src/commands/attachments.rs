@@ -0,0 +1,38 @@
+use tauri_plugin_opener::OpenerExt;
+
+use crate::adapters::sqlite::DbPool;
+use crate::adapters::sqlite::attachments::Attachment;
+use crate::error::EddieError;
+use crate::services::attachments::{self, CachedAttachment};
+
+#[tauri::command]
+pub async fn list_attachments(
+    pool: tauri::State<'_, DbPool>,
+    message_id: String,
+) -> Result<Vec<Attachment>, EddieError> {
+    attachments::list_attachments(&pool, &message_id).await
+}
+
+#[tauri::command]
+pub async fn fetch_attachment(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, DbPool>,
+    message_id: String,
+    part_path: String,
+) -> Result<CachedAttachment, EddieError> {
+    attachments::fetch_attachment(&app, &pool, &message_id, &part_path).await
+}
+
+/// Download (if needed) and open an attachment with the system's default app.
+#[tauri::command]
+pub async fn open_attachment(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, DbPool>,
+    message_id: String,
+    part_path: String,
+) -> Result<(), EddieError> {
+    let cached = attachments::fetch_attachment(&app, &pool, &message_id, &part_path).await?;
+    app.opener()
+        .open_path(cached.path, None::<&str>)
+        .map_err(|e| EddieError::Backend(format!("Failed to open attachment: {}", e)))
+}
@@ -1,7 +1,7 @@
 use crate::adapters::sqlite;
 use crate::adapters::imap::historical;
-use crate::adapters::sqlite::conversations::Conversation;
-use crate::adapters::sqlite::messages::Message;
+use crate::adapters::sqlite::conversations::{Conversation, ConversationPage};
+use crate::adapters::sqlite::messages::{Message, MessagePage};
 use crate::error::EddieError;
 use crate::services::logger;
 use crate::services::sync::worker;
@@ -10,8 +10,9 @@ use crate::services::sync::worker;
 pub async fn fetch_conversations(
     pool: tauri::State<'_, sqlite::DbPool>,
     account_id: String,
+    view_id: Option<String>,
 ) -> Result<Vec<Conversation>, EddieError> {
-    sqlite::conversations::fetch_conversations(&pool, &account_id)
+    sqlite::conversations::fetch_conversations(&pool, &account_id, view_id.as_deref())
 }
 
 #[tauri::command]
@@ -23,6 +24,31 @@ pub async fn fetch_conversation_messages(
     sqlite::messages::fetch_conversation_messages(&pool, &account_id, &conversation_id)
 }
 
+#[tauri::command]
+pub async fn fetch_conversation_page(
+    pool: tauri::State<'_, sqlite::DbPool>,
+    account_id: String,
+    classification: Option<String>,
+    pinned: Option<bool>,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<ConversationPage, EddieError> {
+    sqlite::conversations::fetch_conversation_page(
+        &pool, &account_id, classification.as_deref(), pinned, limit, cursor.as_deref(),
+    )
+}
+
+#[tauri::command]
+pub async fn fetch_conversation_message_page(
+    pool: tauri::State<'_, sqlite::DbPool>,
+    account_id: String,
+    conversation_id: String,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<MessagePage, EddieError> {
+    sqlite::messages::fetch_conversation_message_page(&pool, &account_id, &conversation_id, limit, cursor.as_deref())
+}
+
 #[tauri::command]
 pub async fn move_to_requests(
     pool: tauri::State<'_, sqlite::DbPool>,
@@ -32,8 +58,8 @@ pub async fn move_to_requests(
     for email in &emails {
         sqlite::entities::delete_entity(&pool, &account_id, email)?;
     }
-    logger::info(&format!("Deleted entities, rebuilding conversations: account_id={}", account_id));
-    sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
+    logger::info(&format!("Deleted entities, updating conversations: account_id={}", account_id));
+    sqlite::conversations::update_conversations(&pool, &account_id)?;
     Ok(())
 }
 
@@ -46,8 +72,8 @@ pub async fn move_to_points(
     for email in &emails {
         sqlite::entities::insert_entity(&pool, &account_id, email, "manual", "connection")?;
     }
-    logger::info(&format!("Added entities, rebuilding conversations: account_id={}", account_id));
-    sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
+    logger::info(&format!("Added entities, updating conversations: account_id={}", account_id));
+    sqlite::conversations::update_conversations(&pool, &account_id)?;
     Ok(())
 }
 
@@ -60,11 +86,27 @@ pub async fn block_entities(
     for email in &emails {
         sqlite::entities::insert_entity(&pool, &account_id, email, "manual", "blocked")?;
     }
-    logger::info(&format!("Blocked entities, rebuilding conversations: account_id={}", account_id));
-    sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
+    logger::info(&format!("Blocked entities, updating conversations: account_id={}", account_id));
+    sqlite::conversations::update_conversations(&pool, &account_id)?;
     Ok(())
 }
 
+/// Rethread and re-aggregate every conversation of the account from scratch,
+/// to repair conversations that incremental updates got wrong.
+#[tauri::command]
+pub async fn rebuild_conversations(
+    pool: tauri::State<'_, sqlite::DbPool>,
+    account_id: String,
+) -> Result<usize, EddieError> {
+    let start = std::time::Instant::now();
+    let count = sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
+    logger::info(&format!(
+        "Rebuilt {} conversations in {}: account_id={}",
+        count, logger::fmt_ms(start.elapsed()), account_id
+    ));
+    Ok(count)
+}
+
 #[tauri::command]
 pub async fn fetch_recent_messages(
     pool: tauri::State<'_, sqlite::DbPool>,
@@ -90,7 +132,7 @@ pub async fn fetch_message_html(
 
     // Connect to IMAP and fetch the HTML part
     let (_creds, _self_emails, mut conn) = worker::connect_account(&pool, &info.account_id).await?;
-    conn.select_folder(&info.imap_folder).await?;
+    worker::select_folder(&mut conn, &pool, &info.account_id, &info.imap_folder).await?;
 
     // Round trip 1: Get BODYSTRUCTURE to find the HTML MIME part and inline images
     let uid_str = info.imap_uid.to_string();
@@ -102,20 +144,14 @@ pub async fn fetch_message_html(
         "message html bodystructure",
     ).await;
 
-    let fetch = match fetches.first() {
-        Some(f) => f,
-        None => return Ok(None),
-    };
-
-    let bs = match fetch.bodystructure() {
-        Some(bs) => bs,
-        None => return Ok(None),
-    };
-
-    let (part, encoding) = match historical::find_mime_part(bs, &[], "html") {
-        Some(found) => (found.0, historical::encoding_to_string(found.1)),
-        None => return Ok(None),
+    let html_part = fetches.first()
+        .and_then(|f| f.bodystructure())
+        .and_then(|bs| Some((historical::find_mime_part(bs, &[], "html")?, bs)));
+    let Some(((part, encoding, charset), bs)) = html_part else {
+        conn.release();
+        return Ok(None);
     };
+    let encoding = historical::encoding_to_string(encoding);
 
     // Also find inline image parts
     let inline_images = historical::find_inline_images(bs, &[]);
@@ -140,6 +176,7 @@ pub async fn fetch_message_html(
             .map_err(|e| EddieError::Backend(format!("FETCH body failed: {}", e)))?,
         "message html body + images",
     ).await;
+    conn.release();
 
     let body_fetch = match body_fetches.first() {
         Some(f) => f,
@@ -152,7 +189,7 @@ pub async fn fetch_message_html(
         Some(data) => data,
         None => return Ok(None),
     };
-    let mut html = historical::decode_body(section_data, &encoding)?;
+    let mut html = historical::decode_body(section_data, &encoding, charset.as_deref())?;
 
     // Log cid: references found in the HTML
     let cid_refs: Vec<&str> = html_cid_refs(&html);
@@ -165,7 +202,7 @@ pub async fn fetch_message_html(
         logger::info(&format!("  image cid={}: section({:?}) data={}", cid, img_part, img_data_opt.map_or("NONE".into(), |d| format!("{} bytes", d.len()))));
         if let Some(img_data) = img_data_opt {
             // Decode transfer encoding, then re-encode as base64 for data: URI
-            let raw_bytes = decode_transfer_encoding(img_data, img_encoding);
+            let raw_bytes = historical::decode_transfer_encoding(img_data, img_encoding);
             use base64::Engine;
             let b64 = base64::engine::general_purpose::STANDARD.encode(&raw_bytes);
             let data_uri = format!("data:{};base64,{}", mime_type, b64);
@@ -195,23 +232,3 @@ fn html_cid_refs(html: &str) -> Vec<&str> {
     }
     refs
 }
-
-fn decode_transfer_encoding(raw: &[u8], encoding: &str) -> Vec<u8> {
-    match encoding {
-        "base64" => {
-            let cleaned: Vec<u8> = raw.iter()
-                .filter(|b| !b.is_ascii_whitespace())
-                .copied()
-                .collect();
-            use base64::Engine;
-            base64::engine::general_purpose::STANDARD
-                .decode(&cleaned)
-                .unwrap_or_else(|_| raw.to_vec())
-        }
-        "quoted-printable" => {
-            quoted_printable::decode(raw, quoted_printable::ParseMode::Robust)
-                .unwrap_or_else(|_| raw.to_vec())
-        }
-        _ => raw.to_vec(),
-    }
-}
\ No newline at end of file
@@ -6,6 +6,8 @@ use crate::services::logger;
 use crate::autodiscovery::{
     AuthMethod, DiscoveryPipeline, EmailDiscoveryConfig, Security, UsernameHint,
 };
+use crate::adapters::imap::connection::ImapSecurity;
+use crate::adapters::oauth;
 use crate::error::EddieError;
 
 /// Flattened discovery result for the frontend
@@ -15,12 +17,14 @@ pub struct DiscoveryResult {
     pub provider_id: Option<String>,
     pub imap_host: String,
     pub imap_port: u16,
-    pub imap_tls: bool,
+    pub imap_security: ImapSecurity,
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_tls: bool,
     pub auth_method: String,
     pub requires_app_password: bool,
+    /// Set when the provider supports OAuth2 sign-in ("google", "microsoft")
+    pub oauth_provider: Option<String>,
     pub username_hint: String,
     pub source: String,
 }
@@ -28,11 +32,18 @@ pub struct DiscoveryResult {
 impl From<EmailDiscoveryConfig> for DiscoveryResult {
     fn from(config: EmailDiscoveryConfig) -> Self {
         DiscoveryResult {
+            oauth_provider: config.provider_id.as_deref()
+                .and_then(oauth::provider_for_domain)
+                .map(|p| p.to_string()),
             provider: config.provider,
             provider_id: config.provider_id,
             imap_host: config.imap.hostname,
             imap_port: config.imap.port,
-            imap_tls: !matches!(config.imap.security, Security::None),
+            imap_security: match config.imap.security {
+                Security::None => ImapSecurity::None,
+                Security::Starttls => ImapSecurity::Starttls,
+                Security::Tls => ImapSecurity::Tls,
+            },
             smtp_host: config.smtp.hostname,
             smtp_port: config.smtp.port,
             smtp_tls: !matches!(config.smtp.security, Security::None),
@@ -1,14 +1,25 @@
 use serde::Serialize;
+use std::collections::BTreeMap;
 
-use crate::adapters::sqlite::{self, DbPool, messages::NewMessage, conversations::compute_conversation_id, entities::{upsert_entities, NewEntity}};
+use crate::adapters::sqlite::{self, DbPool, messages::NewMessage, attachments::NewAttachment, conversations::compute_conversation_id, entities::{upsert_entities, NewEntity}};
+use crate::services::attachments::{resolve_outgoing, AttachmentSource};
+use crate::services::reply::{self, ReplyDraft, ReplyMode};
 use crate::services::sync::helpers::message_builder::compute_participant_key;
 use crate::services::sync::helpers::email_normalization::normalize_email;
+use crate::adapters::smtp;
 use crate::error::EddieError;
 use crate::services::logger;
 use tokio::sync::mpsc;
 
+/// How long a send is held back so it can be undone, unless the
+/// `undo_send_seconds` setting says otherwise.
+const DEFAULT_UNDO_SEND_SECONDS: i64 = 10;
+
 #[derive(Debug, Serialize)]
 pub struct SendResult {
+    /// The OUTBOX message's row id
+    pub id: String,
+    /// The RFC Message-ID, which `unsend` takes
     pub message_id: String,
     pub conversation_id: String,
 }
@@ -23,10 +34,16 @@ pub async fn send_message(
     from_name: Option<String>,
     to: Vec<String>,
     cc: Vec<String>,
+    bcc: Option<Vec<String>>,
+    reply_to: Option<Vec<String>>,
+    sender: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
     subject: String,
     body: String,
     in_reply_to: Option<String>,
     references: Vec<String>,
+    send_at: Option<i64>,
+    attachments: Vec<AttachmentSource>,
 ) -> Result<SendResult, EddieError> {
     // Block sends unless write-mode is explicitly enabled
     let write_mode = sqlite::settings::get_setting(&pool, "write_mode")?
@@ -35,6 +52,30 @@ pub async fn send_message(
     if !write_mode {
         return Err(EddieError::InvalidInput("Read-only mode: sending not permitted".into()));
     }
+    // Reject what `build_message` would, before anything is queued
+    let bcc = bcc.unwrap_or_default();
+    let reply_to = reply_to.unwrap_or_default();
+    let headers = headers.unwrap_or_default();
+    for addr in &reply_to {
+        smtp::parse_mailbox("reply-to", addr)?;
+    }
+    if let Some(sender) = &sender {
+        smtp::parse_mailbox("sender", sender)?;
+    }
+    for (name, value) in &headers {
+        smtp::check_custom_header(name, value)?;
+    }
+
+    let real_message_id = format!("{}@eddie.app", uuid::Uuid::new_v4());
+
+    // Copy attachments aside now, so the send doesn't depend on the original
+    // files and the OUTBOX message can show and open them
+    let mut outgoing = Vec::with_capacity(attachments.len());
+    for source in &attachments {
+        outgoing.push(resolve_outgoing(&app, &pool, &real_message_id, source).await?);
+    }
+    let inline: Vec<bool> = outgoing.iter().map(|att| att.content_id.is_some()).collect();
+    let part_paths = smtp::part_paths(&inline, true);
 
     let self_emails = sqlite::entities::get_self_emails(&pool, &account_id)?;
 
@@ -48,14 +89,18 @@ pub async fn send_message(
     let conversation_id = compute_conversation_id(&participant_key);
 
     let now = chrono::Utc::now().timestamp_millis();
-    let db_id = uuid::Uuid::new_v4().to_string();
-    let real_message_id = format!("{}@eddie.app", uuid::Uuid::new_v4());
+    // Held back for the undo window, or until the scheduled time if later
+    let undo_seconds = sqlite::settings::get_setting(&pool, "undo_send_seconds")?
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_UNDO_SEND_SECONDS);
+    let send_at = sqlite::action_queue::send_hold_until(send_at, now, undo_seconds);
     // Random UID to avoid UNIQUE(account_id, imap_folder, imap_uid) collisions between OUTBOX placeholders
     let outbox_uid = uuid::Uuid::new_v4().as_u128() as u32;
 
     // Insert optimistic message into local DB
     let to_json = serde_json::to_string(&to).unwrap_or_default();
     let cc_json = serde_json::to_string(&cc).unwrap_or_default();
+    let bcc_json = serde_json::to_string(&bcc).unwrap_or_default();
     let refs_json = serde_json::to_string(&references).unwrap_or_default();
 
     let new_msg = NewMessage {
@@ -63,17 +108,17 @@ pub async fn send_message(
         message_id: real_message_id.clone(),
         imap_uid: outbox_uid,
         imap_folder: "OUTBOX".to_string(),
-        date: now,
+        date: send_at,
         from_address: normalize_email(&from_email),
         from_name: from_name.clone(),
         to_addresses: to_json,
         cc_addresses: cc_json,
-        bcc_addresses: "[]".to_string(),
+        bcc_addresses: bcc_json,
         subject: Some(subject.clone()),
         body_text: Some(body.clone()),
-        body_html: None,
+        body_html: Some(smtp::markdown_to_html(&body)),
         size_bytes: None,
-        has_attachments: false,
+        has_attachments: !outgoing.is_empty(),
         in_reply_to: in_reply_to.clone(),
         references_ids: refs_json,
         imap_flags: "[\"Seen\"]".to_string(),
@@ -83,18 +128,34 @@ pub async fn send_message(
         distilled_text: Some(body.clone()),
         processed_at: Some(now),
         participant_key,
-        conversation_id: conversation_id.clone(),
+        conversation_id: Some(conversation_id.clone()),
         classification_headers: "{}".to_string(),
+        attachments: outgoing.iter().zip(&part_paths)
+            .map(|(att, part_path)| NewAttachment {
+                part_path: part_path.clone(),
+                filename: Some(att.filename.clone()),
+                mime_type: att.mime_type.clone(),
+                size_bytes: att.size_bytes as u32,
+                content_id: att.content_id.clone(),
+                transfer_encoding: "base64".to_string(),
+                is_inline: att.content_id.is_some(),
+            })
+            .collect(),
     };
 
     sqlite::messages::insert_messages(&pool, &[new_msg])?;
-    logger::debug(&format!("Optimistic message inserted: id={}", db_id));
+    let outbox_id = sqlite::messages::get_outbox_id(&pool, &account_id, &real_message_id)?
+        .ok_or_else(|| EddieError::Database(format!("OUTBOX message {} missing after insert", real_message_id)))?;
+    for (att, part_path) in outgoing.iter().zip(&part_paths) {
+        sqlite::attachments::set_content_hash(&pool, &outbox_id, part_path, &att.content_hash)?;
+    }
+    logger::debug(&format!("Optimistic message inserted: id={}", outbox_id));
 
     // Upsert recipients into entities table to expand trust network
     let self_normalized: std::collections::HashSet<String> = self_emails.iter()
         .map(|e| normalize_email(e))
         .collect();
-    let recipient_entities: Vec<NewEntity> = to.iter().chain(cc.iter())
+    let recipient_entities: Vec<NewEntity> = to.iter().chain(&cc).chain(&bcc)
         .map(|e| normalize_email(e))
         .filter(|e| !self_normalized.contains(e))
         .collect::<std::collections::HashSet<_>>()
@@ -122,12 +183,17 @@ pub async fn send_message(
         "from_name": from_name,
         "to": to,
         "cc": cc,
+        "bcc": bcc,
+        "reply_to": reply_to,
+        "sender": sender,
+        "headers": headers,
         "subject": subject,
         "body": body,
         "in_reply_to": in_reply_to,
         "references": references,
-        "message_db_id": db_id,
+        "message_db_id": outbox_id,
         "message_id": real_message_id,
+        "attachments": outgoing,
     });
 
     sqlite::action_queue::enqueue(
@@ -136,17 +202,107 @@ pub async fn send_message(
         "send",
         &payload.to_string(),
         Some(&real_message_id),
+        Some(send_at),
     )?;
 
-    // Rebuild conversations so the new message shows up immediately
-    let conv_count = sqlite::conversations::rebuild_conversations(&pool, &account_id)?;
+    // Update conversations so the new message shows up immediately
+    let conv_count = sqlite::conversations::update_conversations(&pool, &account_id)?;
     crate::services::sync::helpers::status_emit::emit_conversations_updated(&app, &account_id, conv_count);
 
-    // Wake worker to replay the send action
+    // Wake the worker so it sleeps only until `send_at` (see `next_due_at`)
     let _ = wake_tx.send(()).await;
 
     Ok(SendResult {
-        message_id: db_id,
+        id: outbox_id,
+        message_id: real_message_id,
         conversation_id,
     })
 }
+
+/// Prefill the compose box to reply, reply-all or forward a message:
+/// recipients, From alias, subject, quoted body and threading headers.
+#[tauri::command]
+pub async fn build_reply(
+    pool: tauri::State<'_, DbPool>,
+    message_id: String,
+    mode: ReplyMode,
+) -> Result<ReplyDraft, EddieError> {
+    let mut original = sqlite::messages::get_reply_source(&pool, &message_id)?;
+    if mode != ReplyMode::Forward {
+        if let Err(e) = reply::backfill_reply_headers(&pool, &message_id, &mut original).await {
+            logger::warn(&format!("Couldn't fetch reply headers of {}: {}", message_id, e));
+        }
+    }
+    let self_emails: Vec<String> = sqlite::entities::get_user_aliases(&pool, &original.account_id)?
+        .into_iter()
+        .map(|alias| alias.email)
+        .collect();
+
+    let mut draft = reply::build_draft(&original, mode, &self_emails);
+    if mode == ReplyMode::Forward {
+        draft.attachments = sqlite::attachments::list_attachments(&pool, &message_id)?
+            .into_iter()
+            .filter(|att| !att.is_inline)
+            .collect();
+    }
+    Ok(draft)
+}
+
+/// Take back a send that is still held back (undo window or scheduled):
+/// the action is cancelled and the OUTBOX message removed.
+#[tauri::command]
+pub async fn unsend(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+    message_id: String,
+) -> Result<(), EddieError> {
+    let action = sqlite::action_queue::get_send(&pool, &account_id, &message_id)?
+        .ok_or_else(|| EddieError::InvalidInput(format!("No queued send for {}", message_id)))?;
+    if action.status != "pending" {
+        return Err(EddieError::InvalidInput("Message has already been sent".into()));
+    }
+    super::actions::cancel_queued(&app, &pool, &action)
+}
+
+/// Change the recipients, subject or body of a failed send. The OUTBOX row
+/// is updated to match; `retry_action` sends it again.
+#[tauri::command]
+pub async fn edit_failed_send(
+    app: tauri::AppHandle,
+    pool: tauri::State<'_, DbPool>,
+    action_id: String,
+    to: Vec<String>,
+    cc: Vec<String>,
+    subject: String,
+    body: String,
+) -> Result<(), EddieError> {
+    let action = sqlite::action_queue::get_action(&pool, &action_id)?
+        .filter(|a| a.action_type == "send")
+        .ok_or_else(|| EddieError::InvalidInput(format!("No queued send {}", action_id)))?;
+
+    let mut payload: serde_json::Value = serde_json::from_str(&action.payload)
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid send payload: {}", e)))?;
+    payload["to"] = serde_json::json!(to);
+    payload["cc"] = serde_json::json!(cc);
+    payload["subject"] = serde_json::json!(subject);
+    payload["body"] = serde_json::json!(body);
+
+    if !sqlite::action_queue::update_failed_payload(&pool, &action_id, &payload.to_string())? {
+        return Err(EddieError::InvalidInput("Only failed sends that haven't been submitted can be edited".into()));
+    }
+
+    let message_id = action.message_id.as_deref().unwrap_or_default();
+    if let Some(id) = sqlite::messages::get_outbox_id(&pool, &action.account_id, message_id)? {
+        let self_emails = sqlite::entities::get_self_emails(&pool, &action.account_id)?;
+        let from = payload["from"].as_str().unwrap_or_default();
+        let participant_key = compute_participant_key(from, &to, &cc, &self_emails);
+        sqlite::messages::update_outbox_message(
+            &pool, &id, &to, &cc, &subject, &body, &smtp::markdown_to_html(&body), &participant_key,
+        )?;
+
+        let conv_count = sqlite::conversations::update_conversations(&pool, &action.account_id)?;
+        crate::services::sync::helpers::status_emit::emit_conversations_updated(&app, &action.account_id, conv_count);
+    }
+    Ok(())
+}
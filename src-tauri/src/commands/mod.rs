@@ -1,10 +1,12 @@
 pub mod app;
 pub mod account;
 pub mod actions;
+pub mod attachments;
 pub mod conversations;
 pub mod classify;
 pub mod discovery;
 pub mod entities;
 pub mod messages;
+pub mod search;
 pub mod sync;
 pub mod settings;
\ No newline at end of file
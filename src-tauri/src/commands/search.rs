@@ -0,0 +1,50 @@
+use crate::adapters::sqlite::{self, DbPool, search::SearchPage, smart_views::SmartView};
+use crate::error::EddieError;
+use crate::services::search_query;
+
+#[tauri::command]
+pub async fn search_messages(
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+    query: String,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<SearchPage, EddieError> {
+    sqlite::search::search_messages(&pool, &account_id, &query, limit, cursor.as_deref())
+}
+
+#[tauri::command]
+pub async fn list_smart_views(
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+) -> Result<Vec<SmartView>, EddieError> {
+    sqlite::smart_views::list_views(&pool, &account_id)
+}
+
+/// Create a smart view, or update the one with `id`. The query must parse.
+#[tauri::command]
+pub async fn save_smart_view(
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+    id: Option<String>,
+    name: String,
+    query: String,
+) -> Result<SmartView, EddieError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(EddieError::InvalidInput("A view needs a name".into()));
+    }
+    if search_query::parse(&query)?.is_none() {
+        return Err(EddieError::InvalidInput("A view needs a query".into()));
+    }
+    sqlite::smart_views::save_view(&pool, &account_id, id.as_deref(), name, query.trim())
+}
+
+#[tauri::command]
+pub async fn delete_smart_view(
+    pool: tauri::State<'_, DbPool>,
+    account_id: String,
+    id: String,
+) -> Result<bool, EddieError> {
+    sqlite::smart_views::delete_view(&pool, &account_id, &id)
+}
@@ -19,6 +19,30 @@ pub enum EddieError {
 
     #[error("No active account")]
     NoActiveAccount,
+
+    #[error("Re-authentication required: {0}")]
+    ReauthRequired(String),
+
+    /// The server refused the command (SMTP 5xx, IMAP NO/BAD) — retrying won't help.
+    #[error("Rejected by server: {0}")]
+    Rejected(String),
+}
+
+impl EddieError {
+    /// Whether the same operation can't succeed on a later attempt: invalid
+    /// input, a missing account, revoked credentials or an outright rejection
+    /// by the server. Network, I/O and database failures are transient.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            EddieError::InvalidInput(_)
+                | EddieError::AccountNotFound(_)
+                | EddieError::NoActiveAccount
+                | EddieError::Config(_)
+                | EddieError::ReauthRequired(_)
+                | EddieError::Rejected(_)
+        )
+    }
 }
 
 // Tauri requires Serialize for command error types.
@@ -14,6 +14,7 @@ use tracing_subscriber::{prelude::*, Layer};
 pub type SharedClassifier = Arc<RwLock<Option<Arc<ClassifierState>>>>;
 
 const SYNC_WORKER_TICK_FREQ: u64 = 15; // seconds
+const SYNC_WORKER_IDLE_TICK_FREQ: u64 = 300; // seconds, when every account has a live IDLE session
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -63,6 +64,7 @@ pub fn run() {
             let engine_classifier = classifier.clone();
 
             let (wake_tx, mut wake_rx) = mpsc::channel::<()>(1);
+            let mut idle_watchers = services::sync::idle_watcher::IdleWatchers::new(wake_tx.clone());
             app.manage(wake_tx);
 
             tauri::async_runtime::spawn(async move {
@@ -72,8 +74,17 @@ pub fn run() {
                     Err(e) => services::logger::warn(&format!("Action purge failed: {}", e)),
                     _ => {}
                 }
+                match services::attachments::sweep_outgoing(&engine_app, &engine_pool) {
+                    Ok(n) if n > 0 => services::logger::info(&format!("Removed attachments of {} finished sends", n)),
+                    Err(e) => services::logger::warn(&format!("Outgoing attachment sweep failed: {}", e)),
+                    _ => {}
+                }
 
                 loop {
+                    if let Err(e) = idle_watchers.ensure_started(&engine_pool) {
+                        services::logger::warn(&format!("Failed to start IDLE watchers: {}", e));
+                    }
+
                     match services::sync::worker::tick(&engine_app, &engine_pool, &engine_classifier).await {
                         Ok(did_work) => {
                             if did_work {
@@ -84,10 +95,25 @@ pub fn run() {
                             services::logger::error(&format!("Engine error: {}", e));
                         }
                     }
-                    // Sleep until woken or timeout
+                    // Sleep until woken (by a command or an IDLE push) or timeout,
+                    // but no later than the next scheduled send or action retry
+                    let tick_freq = if idle_watchers.all_live() {
+                        SYNC_WORKER_IDLE_TICK_FREQ
+                    } else {
+                        SYNC_WORKER_TICK_FREQ
+                    };
+                    let mut timeout = std::time::Duration::from_secs(tick_freq);
+                    match adapters::sqlite::sync::action_queue::next_due_at(&engine_pool) {
+                        Ok(Some(due)) => {
+                            let wait = (due - chrono::Utc::now().timestamp_millis()).max(0) as u64;
+                            timeout = timeout.min(std::time::Duration::from_millis(wait));
+                        }
+                        Ok(None) => {}
+                        Err(e) => services::logger::warn(&format!("Failed to read next action due time: {}", e)),
+                    }
                     tokio::select! {
                         _ = wake_rx.recv() => {},
-                        _ = tokio::time::sleep(std::time::Duration::from_secs(SYNC_WORKER_TICK_FREQ)) => {},
+                        _ = tokio::time::sleep(timeout) => {},
                     }
                 }
             });
@@ -100,9 +126,13 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::account::connect_account,
+            commands::account::connect_oauth_account,
+            commands::account::reauthorize_account,
             commands::account::get_existing_account,
             commands::conversations::fetch_conversations,
             commands::conversations::fetch_conversation_messages,
+            commands::conversations::fetch_conversation_page,
+            commands::conversations::fetch_conversation_message_page,
             commands::classify::reclassify,
             commands::sync::sync_now,
             commands::sync::get_onboarding_status,
@@ -111,14 +141,28 @@ pub fn run() {
             commands::conversations::move_to_requests,
             commands::conversations::move_to_points,
             commands::conversations::block_entities,
+            commands::conversations::rebuild_conversations,
             commands::conversations::fetch_recent_messages,
             commands::conversations::fetch_message_html,
+            commands::attachments::list_attachments,
+            commands::attachments::fetch_attachment,
+            commands::attachments::open_attachment,
             commands::discovery::discover_email_config,
             commands::app::get_app_version,
             commands::actions::queue_action,
+            commands::actions::list_outbox,
+            commands::actions::retry_action,
+            commands::actions::cancel_action,
             commands::entities::search_entities,
+            commands::search::search_messages,
+            commands::search::list_smart_views,
+            commands::search::save_smart_view,
+            commands::search::delete_smart_view,
             commands::entities::get_user_aliases,
             commands::messages::send_message,
+            commands::messages::unsend,
+            commands::messages::build_reply,
+            commands::messages::edit_failed_send,
             commands::account::get_account,
             commands::account::update_account,
         ])
@@ -0,0 +1,512 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+use crate::adapters::imap::{attachments as imap_attachments, historical};
+use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::sqlite::attachments::Attachment;
+use crate::error::EddieError;
+use crate::services::logger;
+use crate::services::sync::helpers::message_builder::to_new_attachment;
+use crate::services::sync::worker;
+
+/// Cap on the on-disk attachment cache unless the `attachment_cache_mb`
+/// setting says otherwise. Least recently opened files are evicted first.
+const DEFAULT_CACHE_LIMIT_MB: u64 = 500;
+
+/// Directory (next to the cache) for attachments of messages being sent.
+const OUTGOING_DIR: &str = "outgoing";
+
+/// A downloaded attachment on disk.
+#[derive(Debug, Serialize)]
+pub struct CachedAttachment {
+    pub path: String,
+    pub filename: Option<String>,
+    pub mime_type: String,
+    pub size_bytes: u64,
+}
+
+/// Where an outgoing attachment comes from: a file picked on disk, or an
+/// attachment of another message (when forwarding). A `content_id` makes it
+/// an inline image, referenced from the Markdown body as `cid:`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AttachmentSource {
+    File {
+        path: String,
+        #[serde(default)]
+        content_id: Option<String>,
+    },
+    Message {
+        message_id: String,
+        part_path: String,
+        #[serde(default)]
+        content_id: Option<String>,
+    },
+}
+
+/// An outgoing attachment, copied aside so the OUTBOX message can open it
+/// and later edits to the original file don't change what is sent. Stored
+/// in the send payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutgoingAttachment {
+    pub path: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    pub content_hash: String,
+    #[serde(default)]
+    pub content_id: Option<String>,
+}
+
+/// Attachments of a message. Messages synced before attachment metadata
+/// was recorded get their BODYSTRUCTURE fetched once and stored.
+pub async fn list_attachments(pool: &DbPool, message_id: &str) -> Result<Vec<Attachment>, EddieError> {
+    let existing = sqlite::attachments::list_attachments(pool, message_id)?;
+    if !existing.is_empty() {
+        return Ok(existing);
+    }
+
+    let info = sqlite::messages::get_message_imap_info(pool, message_id)?;
+    if info.imap_folder == "OUTBOX" {
+        return Ok(Vec::new());
+    }
+
+    let (_creds, _self_emails, mut conn) = worker::connect_account(pool, &info.account_id).await?;
+    worker::select_folder(&mut conn, pool, &info.account_id, &info.imap_folder).await?;
+
+    let fetches = historical::collect_tolerant(
+        conn.session
+            .uid_fetch(info.imap_uid.to_string(), "(UID BODYSTRUCTURE)")
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH BODYSTRUCTURE failed: {}", e)))?,
+        "attachment bodystructure",
+    ).await;
+    conn.release();
+
+    let found: Vec<_> = fetches.first()
+        .and_then(|f| f.bodystructure())
+        .map(|bs| imap_attachments::find_attachments(bs, &[]))
+        .unwrap_or_default()
+        .iter()
+        .map(to_new_attachment)
+        .collect();
+    if found.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    sqlite::attachments::replace_attachments(pool, message_id, &found)?;
+    sqlite::attachments::list_attachments(pool, message_id)
+}
+
+/// Download an attachment into the cache (or find it there) and return its
+/// path. Files are stored by the SHA-256 of their decoded bytes, so the same
+/// file sent in several messages is kept once.
+pub async fn fetch_attachment(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    message_id: &str,
+    part_path: &str,
+) -> Result<CachedAttachment, EddieError> {
+    let attachment = match sqlite::attachments::get_attachment(pool, message_id, part_path)? {
+        Some(att) => att,
+        None => {
+            list_attachments(pool, message_id).await?;
+            sqlite::attachments::get_attachment(pool, message_id, part_path)?
+                .ok_or_else(|| EddieError::InvalidInput(format!("No attachment {} on message {}", part_path, message_id)))?
+        }
+    };
+
+    let dir = cache_dir(app)?;
+    let info = sqlite::messages::get_message_imap_info(pool, message_id)?;
+
+    // Attachments of an OUTBOX message are only on disk, in its outgoing copy
+    if info.imap_folder == "OUTBOX" {
+        let path = attachment.content_hash.as_deref()
+            .map(|hash| outgoing_dir(app, &info.message_id).map(|dir| dir.join(file_name(hash, attachment.filename.as_deref()))))
+            .transpose()?
+            .filter(|path| path.exists())
+            .ok_or_else(|| EddieError::InvalidInput(format!("Attachment {} of {} is no longer available", part_path, message_id)))?;
+        let size = std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or_default();
+        return Ok(cached(&attachment, &path, size));
+    }
+
+    if let Some(hash) = &attachment.content_hash {
+        let path = cache_path(&dir, hash, attachment.filename.as_deref());
+        if let Ok(meta) = std::fs::metadata(&path) {
+            touch(&path);
+            return Ok(cached(&attachment, &path, meta.len()));
+        }
+    }
+
+    let part: Vec<u32> = part_path.split('.')
+        .map(|n| n.parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| EddieError::InvalidInput(format!("Invalid part path: {}", part_path)))?;
+
+    let (_creds, _self_emails, mut conn) = worker::connect_account(pool, &info.account_id).await?;
+    worker::select_folder(&mut conn, pool, &info.account_id, &info.imap_folder).await?;
+
+    let raw = imap_attachments::fetch_part(&mut conn, info.imap_uid, &part).await?;
+    conn.release();
+    let bytes = historical::decode_transfer_encoding(&raw, &attachment.transfer_encoding);
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+    let path = cache_path(&dir, &hash, attachment.filename.as_deref());
+    write_atomic(&path, &bytes)?;
+    sqlite::attachments::set_content_hash(pool, message_id, part_path, &hash)?;
+    logger::debug(&format!("Cached attachment {} of {} ({} bytes)", part_path, message_id, bytes.len()));
+
+    let limit_mb = sqlite::settings::get_setting(pool, "attachment_cache_mb")?
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CACHE_LIMIT_MB);
+    match evict(&dir, limit_mb * 1024 * 1024, &path) {
+        Ok(0) => {}
+        Ok(removed) => logger::debug(&format!("Evicted {} cached attachments", removed)),
+        Err(e) => logger::warn(&format!("Attachment cache eviction failed: {}", e)),
+    }
+
+    Ok(cached(&attachment, &path, bytes.len() as u64))
+}
+
+/// Resolve an attachment picked in compose to a copy kept with the outgoing
+/// message `message_id`. The copies live outside the cache, so eviction can't
+/// remove a file before it is sent; `remove_outgoing` deletes them once the
+/// send is done or cancelled.
+pub async fn resolve_outgoing(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    message_id: &str,
+    source: &AttachmentSource,
+) -> Result<OutgoingAttachment, EddieError> {
+    let dir = outgoing_dir(app, message_id)?;
+    match source {
+        AttachmentSource::File { path, content_id } => {
+            let filename = Path::new(path).file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "attachment".to_string());
+            let (source, name) = (path.clone(), filename.clone());
+            let (stored, size_bytes, hash) = run_blocking(move || {
+                let bytes = std::fs::read(&source)
+                    .map_err(|e| EddieError::InvalidInput(format!("Can't read attachment {}: {}", source, e)))?;
+                let hash = format!("{:x}", Sha256::digest(&bytes));
+                let stored = dir.join(file_name(&hash, Some(&name)));
+                write_atomic(&stored, &bytes)?;
+                Ok((stored, bytes.len() as u64, hash))
+            }).await?;
+            Ok(OutgoingAttachment {
+                path: stored.to_string_lossy().to_string(),
+                mime_type: mime_type_for(&filename).to_string(),
+                filename,
+                size_bytes,
+                content_hash: hash,
+                content_id: content_id.clone(),
+            })
+        }
+        AttachmentSource::Message { message_id, part_path, content_id } => {
+            let cached = fetch_attachment(app, pool, message_id, part_path).await?;
+            let hash = sqlite::attachments::get_attachment(pool, message_id, part_path)?
+                .and_then(|att| att.content_hash)
+                .ok_or_else(|| EddieError::Backend(format!("Attachment {} of {} was not cached", part_path, message_id)))?;
+            let stored = dir.join(file_name(&hash, cached.filename.as_deref()));
+            let (source, target) = (cached.path.clone(), stored.clone());
+            run_blocking(move || {
+                let bytes = std::fs::read(&source)
+                    .map_err(|e| EddieError::Backend(format!("Can't read cached attachment {}: {}", source, e)))?;
+                write_atomic(&target, &bytes)
+            }).await?;
+            Ok(OutgoingAttachment {
+                path: stored.to_string_lossy().to_string(),
+                filename: cached.filename.unwrap_or_else(|| "attachment".to_string()),
+                mime_type: cached.mime_type,
+                size_bytes: cached.size_bytes,
+                content_hash: hash,
+                content_id: content_id.clone(),
+            })
+        }
+    }
+}
+
+/// Delete the outgoing copies of a send's attachments, given its payload.
+/// Called once the send is done or cancelled.
+pub fn remove_outgoing(payload: &serde_json::Value) {
+    let outgoing: Vec<OutgoingAttachment> = serde_json::from_value(payload["attachments"].clone())
+        .unwrap_or_default();
+    let dirs: HashSet<PathBuf> = outgoing.iter()
+        .filter_map(|att| Path::new(&att.path).parent().map(Path::to_path_buf))
+        .filter(|dir| is_outgoing_dir(dir))
+        .collect();
+    for dir in dirs {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            logger::warn(&format!("Failed to remove outgoing attachments {}: {}", dir.display(), e));
+        }
+    }
+}
+
+/// Delete outgoing attachment copies whose send is no longer queued, e.g.
+/// completed sends purged before the server confirmed them. Run on startup.
+pub fn sweep_outgoing(app: &tauri::AppHandle, pool: &DbPool) -> Result<usize, EddieError> {
+    let root = outgoing_root(app)?;
+    let entries = match std::fs::read_dir(&root) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let message_id = entry.file_name().to_string_lossy().to_string();
+        if !sqlite::action_queue::send_exists(pool, &message_id)? {
+            std::fs::remove_dir_all(entry.path())
+                .map_err(|e| EddieError::Backend(format!("Failed to remove outgoing attachments: {}", e)))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// MIME type for a filename by its extension; unknown types are sent as
+/// application/octet-stream.
+pub fn mime_type_for(filename: &str) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "json" => "application/json",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "md" => "text/markdown",
+        "ics" => "text/calendar",
+        "vcf" => "text/vcard",
+        "eml" => "message/rfc822",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        _ => "application/octet-stream",
+    }
+}
+
+fn cached(attachment: &Attachment, path: &Path, size_bytes: u64) -> CachedAttachment {
+    CachedAttachment {
+        path: path.to_string_lossy().to_string(),
+        filename: attachment.filename.clone(),
+        mime_type: attachment.mime_type.clone(),
+        size_bytes,
+    }
+}
+
+fn cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, EddieError> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join("attachments"))
+        .map_err(|e| EddieError::Backend(format!("Failed to resolve cache directory: {}", e)))
+}
+
+/// Outgoing attachments are kept next to the cache, not in it, so `evict`
+/// never sees them.
+fn outgoing_root(app: &tauri::AppHandle) -> Result<PathBuf, EddieError> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join(OUTGOING_DIR))
+        .map_err(|e| EddieError::Backend(format!("Failed to resolve cache directory: {}", e)))
+}
+
+/// `<outgoing root>/<Message-ID>` holds the attachments of one outgoing message.
+fn outgoing_dir(app: &tauri::AppHandle, message_id: &str) -> Result<PathBuf, EddieError> {
+    if message_id.is_empty() || message_id.contains(['/', '\\']) || message_id.starts_with('.') {
+        return Err(EddieError::InvalidInput(format!("Invalid Message-ID for attachments: {}", message_id)));
+    }
+    Ok(outgoing_root(app)?.join(message_id))
+}
+
+fn is_outgoing_dir(dir: &Path) -> bool {
+    dir.parent().and_then(|root| root.file_name()).is_some_and(|name| name == OUTGOING_DIR)
+}
+
+/// `<dir>/<first two hex chars>/<hash>.<ext>`.
+fn cache_path(dir: &Path, hash: &str, filename: Option<&str>) -> PathBuf {
+    dir.join(&hash[..2]).join(file_name(hash, filename))
+}
+
+/// `<hash>.<ext>`. The extension is kept so the system opens the file with
+/// the right application.
+fn file_name(hash: &str, filename: Option<&str>) -> String {
+    let ext = filename
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 10 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|ext| format!(".{}", ext.to_lowercase()))
+        .unwrap_or_default();
+    format!("{}{}", hash, ext)
+}
+
+/// Run file I/O off the async runtime.
+pub(crate) async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, EddieError> + Send + 'static,
+) -> Result<T, EddieError> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| EddieError::Backend(format!("Attachment I/O task failed: {}", e)))?
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), EddieError> {
+    let io_err = |e: std::io::Error| EddieError::Backend(format!("Failed to write attachment cache: {}", e));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    let tmp = path.with_extension("part");
+    std::fs::write(&tmp, bytes).map_err(io_err)?;
+    std::fs::rename(&tmp, path).map_err(io_err)
+}
+
+/// Bump the modification time, which eviction treats as "last opened".
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// Delete the least recently used files until the cache fits in
+/// `limit_bytes`. `keep` (the file just written) is never removed.
+/// Returns the number of files deleted.
+fn evict(dir: &Path, limit_bytes: u64, keep: &Path) -> std::io::Result<usize> {
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = Vec::new();
+    for shard in std::fs::read_dir(dir)? {
+        let shard = shard?.path();
+        if !shard.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&shard)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                files.push((entry.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+            }
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    let mut removed = 0;
+    for (path, size, _) in files {
+        if total <= limit_bytes {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        std::fs::remove_file(&path)?;
+        total -= size;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_cache(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("eddie-attachments-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn put(dir: &Path, hash: &str, size: usize, age_secs: u64) -> PathBuf {
+        let path = cache_path(dir, hash, Some("file.pdf"));
+        write_atomic(&path, &vec![0u8; size]).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age_secs)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_cache_path_keeps_extension() {
+        let dir = Path::new("/cache");
+        assert_eq!(cache_path(dir, "abcdef", Some("Contract.PDF")), Path::new("/cache/ab/abcdef.pdf"));
+        assert_eq!(cache_path(dir, "abcdef", Some("no-extension")), Path::new("/cache/ab/abcdef"));
+        assert_eq!(cache_path(dir, "abcdef", Some("x.p d f")), Path::new("/cache/ab/abcdef"));
+        assert_eq!(cache_path(dir, "abcdef", None), Path::new("/cache/ab/abcdef"));
+    }
+
+    #[test]
+    fn test_remove_outgoing_only_deletes_outgoing_dirs() {
+        let root = temp_cache("outgoing");
+        let cache = root.join("attachments");
+        let sent = root.join(OUTGOING_DIR).join("a@eddie.app");
+        let other = root.join(OUTGOING_DIR).join("b@eddie.app");
+        let cached = put(&cache, "aa01", 10, 0);
+        for dir in [&sent, &other] {
+            write_atomic(&dir.join(file_name("bb02", Some("file.pdf"))), b"pdf").unwrap();
+        }
+
+        let attachment = |path: &Path| serde_json::json!({
+            "path": path.to_string_lossy(), "filename": "file.pdf", "mime_type": "application/pdf",
+            "size_bytes": 3, "content_hash": "bb02",
+        });
+        remove_outgoing(&serde_json::json!({
+            "attachments": [attachment(&sent.join("bb02.pdf")), attachment(&cached)],
+        }));
+        assert!(!sent.exists());
+        assert!(other.join("bb02.pdf").exists());
+        assert!(cached.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_mime_type_for() {
+        assert_eq!(mime_type_for("Report.PDF"), "application/pdf");
+        assert_eq!(mime_type_for("photo.jpeg"), "image/jpeg");
+        assert_eq!(mime_type_for("archive.tar.gz"), "application/gzip");
+        assert_eq!(mime_type_for("Makefile"), "application/octet-stream");
+        assert_eq!(mime_type_for("data.xyz"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_evict_removes_oldest_until_under_limit() {
+        let dir = temp_cache("evict");
+        let oldest = put(&dir, "aa01", 400, 300);
+        let middle = put(&dir, "bb02", 400, 200);
+        let newest = put(&dir, "cc03", 400, 100);
+
+        assert_eq!(evict(&dir, 900, &newest).unwrap(), 1);
+        assert!(!oldest.exists());
+        assert!(middle.exists());
+        assert!(newest.exists());
+
+        // The file just written survives even when it alone is over the cap
+        assert_eq!(evict(&dir, 100, &newest).unwrap(), 1);
+        assert!(!middle.exists());
+        assert!(newest.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,2 +1,6 @@
 pub mod sync;
 pub mod logger;
+pub mod oauth;
+pub mod attachments;
+pub mod reply;
+pub mod search_query;
@@ -0,0 +1,119 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tauri_plugin_opener::OpenerExt;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::adapters::imap::connection::ImapAuth;
+use crate::adapters::oauth::{self, TokenSet};
+use crate::adapters::smtp::SmtpAuth;
+use crate::adapters::sqlite::{oauth_tokens, DbPool};
+use crate::error::EddieError;
+use crate::services::logger;
+
+/// How long to wait for the user to finish signing in in the browser.
+const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Serializes refreshes so concurrent connections don't both redeem a
+/// refresh token the provider rotates on first use.
+static REFRESH_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+/// Run the authorization-code + PKCE flow: open the provider's consent page
+/// in the system browser and catch the redirect on a loopback port.
+pub async fn authorize(
+    app: &tauri::AppHandle,
+    provider_id: &str,
+    login_hint: Option<&str>,
+) -> Result<TokenSet, EddieError> {
+    let provider = oauth::provider(provider_id)?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| EddieError::Backend(format!("Failed to open OAuth redirect listener: {}", e)))?;
+    let port = listener.local_addr()
+        .map_err(|e| EddieError::Backend(format!("Failed to open OAuth redirect listener: {}", e)))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{}/", port);
+
+    let pkce = oauth::Pkce::generate();
+    let state = Uuid::new_v4().simple().to_string();
+    let url = oauth::authorization_url(&provider, &redirect_uri, &state, &pkce, login_hint)?;
+
+    logger::info(&format!("Starting OAuth authorization with {}", provider.id));
+    app.opener()
+        .open_url(url, None::<&str>)
+        .map_err(|e| EddieError::Backend(format!("Failed to open browser: {}", e)))?;
+
+    let code = tokio::time::timeout(AUTHORIZE_TIMEOUT, oauth::wait_for_redirect(&listener, &state))
+        .await
+        .map_err(|_| EddieError::Backend("Timed out waiting for sign-in".into()))??;
+
+    oauth::exchange_code(&provider, &code, &pkce.verifier, &redirect_uri).await
+}
+
+/// A valid access token for the account, refreshed (and the refresh token
+/// rotated) if it's about to expire. When the provider rejects the refresh
+/// token the account is flagged and `ReauthRequired` is returned until the
+/// user signs in again.
+pub async fn access_token(pool: &DbPool, account_id: &str) -> Result<String, EddieError> {
+    let _guard = REFRESH_LOCK.get_or_init(|| Mutex::new(())).lock().await;
+
+    let stored = oauth_tokens::get_token(pool, account_id)?
+        .ok_or_else(|| EddieError::ReauthRequired(format!("no OAuth token for {}", account_id)))?;
+    if stored.needs_reauth {
+        return Err(EddieError::ReauthRequired(account_id.to_string()));
+    }
+    if !stored.tokens.is_expired(chrono::Utc::now().timestamp_millis()) {
+        return Ok(stored.tokens.access_token);
+    }
+
+    let refresh_token = stored.tokens.refresh_token
+        .ok_or_else(|| EddieError::ReauthRequired(format!("no refresh token for {}", account_id)))?;
+    let provider = oauth::provider(&stored.provider)?;
+
+    match oauth::refresh(&provider, &refresh_token).await {
+        Ok(tokens) => {
+            oauth_tokens::save_token(pool, account_id, &provider.id, &tokens)?;
+            logger::debug(&format!("Refreshed OAuth token for {}", account_id));
+            Ok(tokens.access_token)
+        }
+        Err(EddieError::ReauthRequired(reason)) => {
+            logger::warn(&format!("OAuth refresh rejected for {}: {}", account_id, reason));
+            oauth_tokens::mark_needs_reauth(pool, account_id)?;
+            Err(EddieError::ReauthRequired(account_id.to_string()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// IMAP authentication for an account: the stored password, or a fresh
+/// OAuth2 access token over the provider's SASL mechanism.
+pub async fn imap_auth(
+    pool: &DbPool,
+    account_id: &str,
+    oauth_provider: Option<&str>,
+    password: &str,
+) -> Result<ImapAuth, EddieError> {
+    match oauth_provider {
+        None => Ok(ImapAuth::Password(password.to_string())),
+        Some(provider_id) => Ok(ImapAuth::OAuth2 {
+            mechanism: oauth::provider(provider_id)?.imap_mechanism,
+            access_token: access_token(pool, account_id).await?,
+        }),
+    }
+}
+
+/// SMTP authentication for an account (XOAUTH2 for OAuth2 accounts).
+pub async fn smtp_auth(
+    pool: &DbPool,
+    account_id: &str,
+    oauth_provider: Option<&str>,
+    password: &str,
+) -> Result<SmtpAuth, EddieError> {
+    match oauth_provider {
+        None => Ok(SmtpAuth::Password(password.to_string())),
+        Some(_) => Ok(SmtpAuth::XOAuth2(access_token(pool, account_id).await?)),
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::adapters::imap::envelopes::{parse_classification_headers, REPLY_HEADERS};
+use crate::adapters::imap::historical;
+use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::sqlite::attachments::Attachment;
+use crate::adapters::sqlite::messages::ReplySource;
+use crate::error::EddieError;
+use crate::services::sync::helpers::email_normalization::normalize_email;
+use crate::services::sync::worker;
+
+/// Most Message-IDs kept in a reply's References. Longer chains keep the
+/// first (thread root) and the most recent ones, dropping from the middle.
+const MAX_REFERENCES: usize = 20;
+
+/// Reply and forward prefixes used by common clients in other languages, so
+/// "AW: Re: SV: x" becomes "Re: x" instead of growing with every round trip.
+const REPLY_PREFIXES: &[&str] = &["re", "aw", "sv", "vs", "antw", "odp", "rif", "réf", "ynt", "res", "atb"];
+const FORWARD_PREFIXES: &[&str] = &["fwd", "fw", "wg", "tr", "vs", "rv", "enc", "doorst", "pd"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplyMode {
+    Reply,
+    ReplyAll,
+    Forward,
+}
+
+/// Everything the compose box needs to send a reply or forward.
+#[derive(Debug, Serialize)]
+pub struct ReplyDraft {
+    pub from_email: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: String,
+    /// Markdown: an empty line for the reply, then the attributed quote
+    pub body: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// A forward's attachments, to pass back to `send_message` by part path
+    pub attachments: Vec<Attachment>,
+}
+
+/// Messages synced before Reply-To and Mail-Followup-To were recorded have
+/// neither key in their headers. Fetch the two once from the server, store
+/// them (empty when absent) and add them to `original`.
+pub async fn backfill_reply_headers(
+    pool: &DbPool,
+    id: &str,
+    original: &mut ReplySource,
+) -> Result<(), EddieError> {
+    if REPLY_HEADERS.iter().all(|name| original.headers.contains_key(*name)) {
+        return Ok(());
+    }
+    let info = sqlite::messages::get_message_imap_info(pool, id)?;
+    if info.imap_folder == "OUTBOX" {
+        return Ok(());
+    }
+
+    let (_creds, _self_emails, mut conn) = worker::connect_account(pool, &info.account_id).await?;
+    worker::select_folder(&mut conn, pool, &info.account_id, &info.imap_folder).await?;
+    let fetches = historical::collect_tolerant(
+        conn.session
+            .uid_fetch(info.imap_uid.to_string(), "(UID BODY.PEEK[HEADER.FIELDS (Reply-To Mail-Followup-To)])")
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH reply headers failed: {}", e)))?,
+        "reply headers",
+    ).await;
+    conn.release();
+
+    let Some(raw) = fetches.first().and_then(|f| f.header()) else {
+        return Ok(());
+    };
+    let headers = parse_classification_headers(raw);
+    sqlite::messages::merge_classification_headers(pool, id, &headers)?;
+    original.headers.extend(headers);
+    Ok(())
+}
+
+/// Build a reply or forward of `original`. `self_emails` are the user's
+/// addresses with the primary one first; they are left out of the
+/// recipients, and the one the original was sent to becomes the From.
+pub fn build_draft(original: &ReplySource, mode: ReplyMode, self_emails: &[String]) -> ReplyDraft {
+    let is_self = |addr: &str| {
+        let addr = normalize_email(addr);
+        self_emails.iter().any(|e| normalize_email(e) == addr)
+    };
+    let header_addresses = |name: &str| original.headers.get(name).map(|v| parse_addresses(v)).unwrap_or_default();
+
+    let from_email = original.to_addresses.iter()
+        .chain(&original.cc_addresses)
+        .chain(std::iter::once(&original.from_address))
+        .find(|addr| is_self(addr))
+        .or(self_emails.first())
+        .cloned()
+        .unwrap_or_default();
+
+    let (to, cc) = match mode {
+        ReplyMode::Forward => (vec![], vec![]),
+        _ if is_self(&original.from_address) => {
+            // Replying to our own message continues with its recipients
+            let cc = if mode == ReplyMode::ReplyAll { original.cc_addresses.clone() } else { vec![] };
+            (original.to_addresses.clone(), cc)
+        }
+        ReplyMode::Reply => {
+            let reply_to = header_addresses("reply-to");
+            let to = if reply_to.is_empty() { vec![original.from_address.clone()] } else { reply_to };
+            (to, vec![])
+        }
+        ReplyMode::ReplyAll => {
+            let followup = header_addresses("mail-followup-to");
+            if !followup.is_empty() {
+                (followup, vec![])
+            } else {
+                let mut to = header_addresses("reply-to");
+                if to.is_empty() {
+                    to.push(original.from_address.clone());
+                }
+                to.extend(original.to_addresses.iter().cloned());
+                (to, original.cc_addresses.clone())
+            }
+        }
+    };
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut keep = |addr: &String| {
+        let normalized = normalize_email(addr);
+        if is_self(addr) || seen.contains(&normalized) {
+            return false;
+        }
+        seen.push(normalized);
+        true
+    };
+    let mut to: Vec<String> = to.into_iter().filter(|a| keep(a)).collect();
+    let cc: Vec<String> = cc.into_iter().filter(|a| keep(a)).collect();
+    // A note to self is answered to self
+    if to.is_empty() && cc.is_empty() && mode != ReplyMode::Forward {
+        to.push(original.from_address.clone());
+    }
+
+    let date = DateTime::from_timestamp_millis(original.date)
+        .map(|d| d.with_timezone(&Local).format("%a, %b %-d, %Y at %H:%M").to_string())
+        .unwrap_or_default();
+    let sender = match &original.from_name {
+        Some(name) if !name.is_empty() => format!("{} <{}>", name, original.from_address),
+        _ => original.from_address.clone(),
+    };
+    let text = original.body_text.as_deref().unwrap_or_default().trim_end();
+
+    if mode == ReplyMode::Forward {
+        let mut header = vec![
+            "---------- Forwarded message ---------".to_string(),
+            format!("From: {}", sender),
+            format!("Date: {}", date),
+            format!("Subject: {}", original.subject),
+            format!("To: {}", original.to_addresses.join(", ")),
+        ];
+        if !original.cc_addresses.is_empty() {
+            header.push(format!("Cc: {}", original.cc_addresses.join(", ")));
+        }
+        return ReplyDraft {
+            from_email,
+            to,
+            cc,
+            subject: prefixed_subject(&original.subject, "Fwd:", FORWARD_PREFIXES),
+            body: format!("\n\n{}\n\n{}\n", header.join("\n"), text),
+            in_reply_to: None,
+            references: vec![],
+            attachments: vec![],
+        };
+    }
+
+    ReplyDraft {
+        from_email,
+        to,
+        cc,
+        subject: prefixed_subject(&original.subject, "Re:", REPLY_PREFIXES),
+        body: format!("\n\nOn {}, {} wrote:\n{}\n", date, sender, quote(text)),
+        in_reply_to: Some(original.message_id.clone()).filter(|id| !id.is_empty()),
+        references: reply_references(&original.references, &original.message_id),
+        attachments: vec![],
+    }
+}
+
+/// Parse an address list header ("Name <a@x>, b@y") into bare addresses.
+fn parse_addresses(value: &str) -> Vec<String> {
+    let Ok(list) = mailparse::addrparse(value) else { return vec![] };
+    list.iter()
+        .flat_map(|addr| match addr {
+            mailparse::MailAddr::Single(info) => vec![info.addr.clone()],
+            mailparse::MailAddr::Group(group) => group.addrs.iter().map(|info| info.addr.clone()).collect(),
+        })
+        .collect()
+}
+
+/// `prefix` followed by the subject with any stacked reply or forward
+/// prefixes (in `known`, case-insensitive, with or without `[n]`) removed.
+pub fn prefixed_subject(subject: &str, prefix: &str, known: &[&str]) -> String {
+    let mut rest = subject.trim();
+    while let Some((head, tail)) = rest.split_once(':') {
+        let word = head.trim_end_matches(|c: char| c == ']' || c.is_ascii_digit()).trim_end_matches('[');
+        if !known.iter().any(|k| word.trim().eq_ignore_ascii_case(k)) {
+            break;
+        }
+        rest = tail.trim_start();
+    }
+    format!("{} {}", prefix, rest).trim_end().to_string()
+}
+
+/// The parent's References plus its Message-ID, truncated to `MAX_REFERENCES`.
+pub fn reply_references(parent_references: &[String], parent_id: &str) -> Vec<String> {
+    let mut refs: Vec<String> = Vec::new();
+    for id in parent_references.iter().map(String::as_str).chain(std::iter::once(parent_id)) {
+        if !id.is_empty() && !refs.iter().any(|r| r == id) {
+            refs.push(id.to_string());
+        }
+    }
+    if refs.len() > MAX_REFERENCES {
+        refs.drain(1..refs.len() - (MAX_REFERENCES - 1));
+    }
+    refs
+}
+
+/// Prefix every line with `>`, nesting already quoted lines without a space.
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| match line {
+            "" => ">".to_string(),
+            _ if line.starts_with('>') => format!(">{}", line),
+            _ => format!("> {}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn original(from: &str, to: &[&str], cc: &[&str], headers: &[(&str, &str)]) -> ReplySource {
+        ReplySource {
+            account_id: "acc".into(),
+            message_id: "m3@x".into(),
+            date: 0,
+            from_address: from.into(),
+            from_name: Some("Alice".into()),
+            to_addresses: to.iter().map(|s| s.to_string()).collect(),
+            cc_addresses: cc.iter().map(|s| s.to_string()).collect(),
+            subject: "AW: Re: Plans".into(),
+            body_text: Some("See you\n\n> earlier".into()),
+            references: vec!["m1@x".into(), "m2@x".into()],
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+        }
+    }
+
+    fn me() -> Vec<String> {
+        vec!["me@home.org".into(), "me@work.com".into()]
+    }
+
+    #[test]
+    fn test_reply_honors_reply_to_and_alias() {
+        let msg = original("alice@x.com", &["me@work.com"], &["bob@y.com"], &[("reply-to", "\"List\" <list@x.com>")]);
+        let draft = build_draft(&msg, ReplyMode::Reply, &me());
+        assert_eq!(draft.to, vec!["list@x.com"]);
+        assert!(draft.cc.is_empty());
+        assert_eq!(draft.from_email, "me@work.com");
+        assert_eq!(draft.subject, "Re: Plans");
+        assert_eq!(draft.in_reply_to.as_deref(), Some("m3@x"));
+        assert_eq!(draft.references, vec!["m1@x", "m2@x", "m3@x"]);
+        assert!(draft.body.contains("Alice <alice@x.com> wrote:\n> See you\n>\n>> earlier"));
+    }
+
+    #[test]
+    fn test_absent_reply_headers_are_recorded_empty() {
+        let headers = parse_classification_headers(b"Reply-To: list@x.com\r\n\r\n");
+        assert_eq!(headers.get("reply-to").map(String::as_str), Some("list@x.com"));
+        assert_eq!(headers.get("mail-followup-to").map(String::as_str), Some(""));
+        // Nothing fetched: leave the keys missing so they are fetched later
+        assert!(parse_classification_headers(b"").is_empty());
+
+        let pairs: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let msg = original("alice@x.com", &["me@work.com"], &[], &pairs);
+        assert_eq!(build_draft(&msg, ReplyMode::ReplyAll, &me()).to, vec!["list@x.com"]);
+        let msg = original("alice@x.com", &["me@work.com"], &[], &[("reply-to", ""), ("mail-followup-to", "")]);
+        assert_eq!(build_draft(&msg, ReplyMode::Reply, &me()).to, vec!["alice@x.com"]);
+    }
+
+    #[test]
+    fn test_reply_all_excludes_self_and_follows_followup_to() {
+        let msg = original("alice@x.com", &["Me@Home.org", "carol@z.com"], &["bob@y.com", "me+tag@work.com"], &[]);
+        let draft = build_draft(&msg, ReplyMode::ReplyAll, &me());
+        assert_eq!(draft.to, vec!["alice@x.com", "carol@z.com"]);
+        assert_eq!(draft.cc, vec!["bob@y.com"]);
+        assert_eq!(draft.from_email, "Me@Home.org");
+
+        let msg = original("alice@x.com", &["me@home.org"], &["bob@y.com"], &[("mail-followup-to", "dev@lists.org, me@home.org")]);
+        let draft = build_draft(&msg, ReplyMode::ReplyAll, &me());
+        assert_eq!(draft.to, vec!["dev@lists.org"]);
+        assert!(draft.cc.is_empty());
+    }
+
+    #[test]
+    fn test_reply_to_own_message_goes_to_its_recipients() {
+        let msg = original("me@home.org", &["carol@z.com"], &[], &[]);
+        let draft = build_draft(&msg, ReplyMode::Reply, &me());
+        assert_eq!(draft.to, vec!["carol@z.com"]);
+        assert_eq!(draft.from_email, "me@home.org");
+    }
+
+    #[test]
+    fn test_forward_has_no_recipients_or_threading() {
+        let msg = original("alice@x.com", &["me@home.org"], &[], &[]);
+        let draft = build_draft(&msg, ReplyMode::Forward, &me());
+        assert!(draft.to.is_empty());
+        assert_eq!(draft.subject, "Fwd: AW: Re: Plans");
+        assert!(draft.in_reply_to.is_none());
+        assert!(draft.body.contains("---------- Forwarded message ---------\nFrom: Alice <alice@x.com>"));
+    }
+
+    #[test]
+    fn test_subject_prefixes() {
+        assert_eq!(prefixed_subject("Re: RE[2]: SV:  Lunch", "Re:", REPLY_PREFIXES), "Re: Lunch");
+        assert_eq!(prefixed_subject("WG: Fw: Report", "Fwd:", FORWARD_PREFIXES), "Fwd: Report");
+        assert_eq!(prefixed_subject("Meeting: Tuesday", "Re:", REPLY_PREFIXES), "Re: Meeting: Tuesday");
+        assert_eq!(prefixed_subject("", "Re:", REPLY_PREFIXES), "Re:");
+    }
+
+    #[test]
+    fn test_references_truncate_from_middle() {
+        let refs: Vec<String> = (0..30).map(|i| format!("r{}@x", i)).collect();
+        let out = reply_references(&refs, "parent@x");
+        assert_eq!(out.len(), MAX_REFERENCES);
+        assert_eq!(out[0], "r0@x");
+        assert_eq!(out[MAX_REFERENCES - 1], "parent@x");
+        assert_eq!(out[MAX_REFERENCES - 2], "r29@x");
+    }
+}
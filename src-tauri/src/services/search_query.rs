@@ -0,0 +1,312 @@
+use chrono::{Local, NaiveDate, TimeZone};
+
+use crate::error::EddieError;
+
+/// A parsed search query. Words and phrases match message text through the
+/// full-text index; filters match message fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    /// A word or quoted phrase. `prefix` is set on a bare word at the very
+    /// end of the input, so results show up while typing.
+    Text { text: String, prefix: bool },
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Sender address or name contains
+    From(String),
+    /// To, Cc or Bcc contains
+    To(String),
+    HasAttachment,
+    Unread,
+    Read,
+    Important,
+    Pinned,
+    Muted,
+    /// Sent before local midnight starting this day (epoch ms)
+    Before(i64),
+    /// Sent on or after local midnight starting this day (epoch ms)
+    After(i64),
+    /// IMAP folder or Gmail label
+    In(String),
+    /// Message classification: chat or not_chat
+    Class(String),
+    /// Trust level of the sender's entity, e.g. connection
+    Trust(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Phrase(String),
+    Field(String, String),
+}
+
+/// Parse a search query such as `from:anna has:attachment "tax return" OR -is:read`.
+/// Terms next to each other are ANDed; AND, OR and NOT must be upper case
+/// (`-term` is NOT too), and parentheses group. Unknown `key:value` terms are
+/// searched as text. Returns None when there is nothing to search for.
+pub fn parse(input: &str) -> Result<Option<Query>, EddieError> {
+    let mut parser = Parser { tokens: tokenize(input), pos: 0 };
+    if parser.tokens.is_empty() {
+        return Ok(None);
+    }
+    let query = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(EddieError::InvalidInput("Unbalanced ')' in search query".into()));
+    }
+    Ok(Some(query))
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Phrase(quoted(&mut chars)));
+            }
+            '-' => {
+                chars.next();
+                if chars.peek().is_some_and(|c| !c.is_whitespace() && *c != ')') {
+                    tokens.push(Token::Not);
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => match word.split_once(':') {
+                        Some((key, "")) if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            Token::Field(key.to_lowercase(), quoted(&mut chars))
+                        }
+                        Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+                            Token::Field(key.to_lowercase(), value.to_string())
+                        }
+                        _ => Token::Word(word),
+                    },
+                });
+            }
+        }
+    }
+    tokens
+}
+
+/// The rest of a quoted string, up to the closing quote or the end of input.
+fn quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            break;
+        }
+        text.push(c);
+    }
+    text
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Query, EddieError> {
+        let mut parts = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            parts.push(self.and()?);
+        }
+        Ok(if parts.len() == 1 { parts.remove(0) } else { Query::Or(parts) })
+    }
+
+    fn and(&mut self) -> Result<Query, EddieError> {
+        let mut parts = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                None | Some(Token::Or) | Some(Token::RParen) => break,
+                _ => {}
+            }
+            parts.push(self.unary()?);
+        }
+        Ok(if parts.len() == 1 { parts.remove(0) } else { Query::And(parts) })
+    }
+
+    fn unary(&mut self) -> Result<Query, EddieError> {
+        let is_last = self.pos + 1 == self.tokens.len();
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| EddieError::InvalidInput("Search query ends with an operator".into()))?;
+        self.pos += 1;
+        match token {
+            Token::Not => Ok(Query::Not(Box::new(self.unary()?))),
+            Token::LParen => {
+                let inner = self.or()?;
+                // A missing ')' at the end is forgiven, it's likely still being typed
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                }
+                Ok(inner)
+            }
+            Token::Word(text) => Ok(Query::Text { text, prefix: is_last }),
+            Token::Phrase(text) => Ok(Query::Text { text, prefix: false }),
+            Token::Field(key, value) => Ok(field(&key, &value)?.unwrap_or(Query::Text {
+                text: format!("{}:{}", key, value),
+                prefix: is_last,
+            })),
+            Token::RParen | Token::And | Token::Or => Err(EddieError::InvalidInput(
+                "Search query has an operator without a term".into(),
+            )),
+        }
+    }
+}
+
+/// The filter for a `key:value` term, or None if `key` isn't one.
+fn field(key: &str, value: &str) -> Result<Option<Query>, EddieError> {
+    let invalid = || EddieError::InvalidInput(format!("Unknown value in search filter {}:{}", key, value));
+    let filter = match key {
+        "from" => Filter::From(value.to_lowercase()),
+        "to" => Filter::To(value.to_lowercase()),
+        "has" => match value.to_lowercase().as_str() {
+            "attachment" | "attachments" => Filter::HasAttachment,
+            _ => return Err(invalid()),
+        },
+        "is" => match value.to_lowercase().as_str() {
+            "unread" => Filter::Unread,
+            "read" => Filter::Read,
+            "important" => Filter::Important,
+            "pinned" => Filter::Pinned,
+            "muted" => Filter::Muted,
+            _ => return Err(invalid()),
+        },
+        "before" => Filter::Before(day_start(value).ok_or_else(invalid)?),
+        "after" => Filter::After(day_start(value).ok_or_else(invalid)?),
+        "in" => Filter::In(value.to_string()),
+        "class" => match value.to_lowercase().as_str() {
+            class @ ("chat" | "not_chat") => Filter::Class(class.to_string()),
+            _ => return Err(invalid()),
+        },
+        "trust" => Filter::Trust(value.to_lowercase()),
+        _ => return Ok(None),
+    };
+    Ok(Some(Query::Filter(filter)))
+}
+
+/// Local midnight at the start of a YYYY-MM-DD (or YYYY/MM/DD) date, in epoch ms.
+fn day_start(value: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(&value.replace('/', "-"), "%Y-%m-%d").ok()?;
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+    Local.from_local_datetime(&midnight).earliest().map(|t| t.timestamp_millis())
+}
+
+impl Query {
+    /// The words and phrases that a match is ranked and highlighted by:
+    /// all text terms except negated ones.
+    pub fn ranked_terms(&self) -> Vec<(&str, bool)> {
+        match self {
+            Query::And(parts) | Query::Or(parts) => parts.iter().flat_map(Query::ranked_terms).collect(),
+            Query::Not(_) | Query::Filter(_) => vec![],
+            Query::Text { text, prefix } => vec![(text.as_str(), *prefix)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, prefix: bool) -> Query {
+        Query::Text { text: text.into(), prefix }
+    }
+
+    #[test]
+    fn test_implicit_and_with_filters_and_phrase() {
+        let query = parse("from:Anna has:attachment \"tax return\" is:unread").unwrap().unwrap();
+        assert_eq!(query, Query::And(vec![
+            Query::Filter(Filter::From("anna".into())),
+            Query::Filter(Filter::HasAttachment),
+            text("tax return", false),
+            Query::Filter(Filter::Unread),
+        ]));
+    }
+
+    #[test]
+    fn test_or_binds_looser_than_and() {
+        let query = parse("budget OR in:Archive -class:not_chat").unwrap().unwrap();
+        assert_eq!(query, Query::Or(vec![
+            text("budget", false),
+            Query::And(vec![
+                Query::Filter(Filter::In("Archive".into())),
+                Query::Not(Box::new(Query::Filter(Filter::Class("not_chat".into())))),
+            ]),
+        ]));
+    }
+
+    #[test]
+    fn test_parentheses_and_not() {
+        let query = parse("NOT (from:bo OR to:\"Bo Berg\") AND trust:Connection lun").unwrap().unwrap();
+        assert_eq!(query, Query::And(vec![
+            Query::Not(Box::new(Query::Or(vec![
+                Query::Filter(Filter::From("bo".into())),
+                Query::Filter(Filter::To("bo berg".into())),
+            ]))),
+            Query::Filter(Filter::Trust("connection".into())),
+            text("lun", true),
+        ]));
+        assert_eq!(query.ranked_terms(), vec![("lun", true)]);
+    }
+
+    #[test]
+    fn test_dates_and_unknown_keys() {
+        let expected = Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap().timestamp_millis();
+        assert_eq!(parse("after:2024-03-01").unwrap(), Some(Query::Filter(Filter::After(expected))));
+        assert_eq!(parse("before:2024/03/01").unwrap(), Some(Query::Filter(Filter::Before(expected))));
+        assert_eq!(parse("meet 10:30").unwrap(), Some(Query::And(vec![text("meet", false), text("10:30", true)])));
+        assert_eq!(parse("re: or").unwrap(), Some(Query::And(vec![text("re:", false), text("or", true)])));
+    }
+
+    #[test]
+    fn test_rejects_bad_queries() {
+        assert!(parse("has:banana").is_err());
+        assert!(parse("before:yesterday").is_err());
+        assert!(parse("class:spam").is_err());
+        assert!(parse("budget OR").is_err());
+        assert!(parse("a )").is_err());
+        assert_eq!(parse("   ").unwrap(), None);
+        assert_eq!(parse("(budget").unwrap(), Some(text("budget", true)));
+    }
+}
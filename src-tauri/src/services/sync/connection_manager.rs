@@ -0,0 +1,150 @@
+use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::imap::connection::{self, ImapConnection};
+use crate::error::EddieError;
+use crate::services::{logger, oauth};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Sessions open at once per account, pooled or checked out.
+/// Leaves headroom under the usual per-user server limits (Gmail allows 15).
+const MAX_CONNECTIONS_PER_ACCOUNT: usize = 3;
+/// Pooled sessions unused for longer than this are logged out instead of reused.
+const MAX_IDLE: Duration = Duration::from_secs(10 * 60);
+/// A health-check NOOP that takes longer than this marks the session dead.
+const NOOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+static MANAGER: OnceLock<ConnectionManager> = OnceLock::new();
+
+/// Keeps authenticated IMAP sessions per account so sync tasks and Tauri
+/// commands don't log in from scratch every time.
+///
+/// Sessions are health-checked with NOOP on checkout and replaced
+/// transparently when the server has dropped them. The IDLE watchers hold
+/// their own dedicated sessions and don't count against the cap.
+struct ConnectionManager {
+    accounts: Mutex<HashMap<String, Arc<AccountSlots>>>,
+}
+
+struct AccountSlots {
+    permits: Arc<Semaphore>,
+    /// Sessions waiting to be reused, with the time they were returned
+    idle: Mutex<Vec<(ImapConnection, Instant)>>,
+    /// Bumped by `invalidate` so sessions opened with stale credentials
+    /// are dropped instead of returned to the pool.
+    generation: Mutex<u64>,
+}
+
+/// An IMAP session borrowed from the pool. Derefs to `ImapConnection`.
+///
+/// Only `release` returns the session to the pool. One that is just dropped
+/// (an error path, or a FETCH or IDLE stream abandoned midway) may still
+/// have responses in flight for the next borrower to trip over, so it is
+/// closed instead.
+pub struct PooledConnection {
+    conn: ImapConnection,
+    slots: Arc<AccountSlots>,
+    generation: u64,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledConnection {
+    /// Return the session to the pool for reuse. Call once every command
+    /// sent on it has been read to completion.
+    pub fn release(self) {
+        let PooledConnection { conn, slots, generation, _permit } = self;
+        if *slots.generation.lock().unwrap() != generation {
+            return; // account was updated while checked out
+        }
+        slots.idle.lock().unwrap().push((conn, Instant::now()));
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = ImapConnection;
+
+    fn deref(&self) -> &ImapConnection {
+        &self.conn
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut ImapConnection {
+        &mut self.conn
+    }
+}
+
+fn manager() -> &'static ConnectionManager {
+    MANAGER.get_or_init(|| ConnectionManager { accounts: Mutex::new(HashMap::new()) })
+}
+
+fn slots_for(account_id: &str) -> Arc<AccountSlots> {
+    manager().accounts.lock().unwrap()
+        .entry(account_id.to_string())
+        .or_insert_with(|| Arc::new(AccountSlots {
+            permits: Arc::new(Semaphore::new(MAX_CONNECTIONS_PER_ACCOUNT)),
+            idle: Mutex::new(Vec::new()),
+            generation: Mutex::new(0),
+        }))
+        .clone()
+}
+
+/// Borrow an authenticated session for `account_id`, waiting for a free
+/// slot if the account is at its connection cap. Reuses a pooled session
+/// when one passes a NOOP health check, otherwise logs in again.
+pub async fn checkout(pool: &DbPool, account_id: &str) -> Result<PooledConnection, EddieError> {
+    let slots = slots_for(account_id);
+    let permit = slots.permits.clone()
+        .acquire_owned()
+        .await
+        .map_err(|e| EddieError::Backend(format!("Connection pool closed: {}", e)))?;
+
+    let write_mode = sqlite::settings::get_setting(pool, "write_mode")?
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    let generation = *slots.generation.lock().unwrap();
+
+    loop {
+        let pooled = slots.idle.lock().unwrap().pop();
+        let Some((mut conn, returned_at)) = pooled else { break };
+
+        if returned_at.elapsed() > MAX_IDLE {
+            tokio::time::timeout(NOOP_TIMEOUT, conn.session.logout()).await.ok();
+            continue;
+        }
+
+        match tokio::time::timeout(NOOP_TIMEOUT, conn.session.noop()).await {
+            Ok(Ok(())) => {
+                // Updates reported by the NOOP are picked up by the next sync pass
+                while conn.session.unsolicited_responses.try_recv().is_ok() {}
+                conn.write_mode = write_mode;
+                return Ok(PooledConnection { conn, slots, generation, _permit: permit });
+            }
+            Ok(Err(e)) => logger::debug(&format!("Pooled IMAP session for {} is dead: {}", account_id, e)),
+            Err(_) => logger::debug(&format!("Pooled IMAP session for {} timed out on NOOP", account_id)),
+        }
+    }
+
+    let creds = sqlite::accounts::get_credentials(pool, account_id)?
+        .ok_or(EddieError::AccountNotFound(account_id.to_string()))?;
+    let auth = oauth::imap_auth(pool, account_id, creds.oauth_provider.as_deref(), &creds.password).await?;
+    let conn = connection::connect_with_tls(
+        &creds.host, creds.port, creds.security, creds.allow_plaintext, &creds.email, &auth, write_mode,
+    ).await?;
+
+    Ok(PooledConnection { conn, slots, generation, _permit: permit })
+}
+
+/// Drop every pooled session for `account_id`. Called when the account's
+/// server settings or password change; sessions currently checked out are
+/// discarded when released.
+pub fn invalidate(account_id: &str) {
+    let slots = slots_for(account_id);
+    *slots.generation.lock().unwrap() += 1;
+    let stale: Vec<_> = slots.idle.lock().unwrap().drain(..).collect();
+    if !stale.is_empty() {
+        logger::debug(&format!("Dropped {} pooled IMAP sessions for {}", stale.len(), account_id));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::adapters::imap::connection::ImapConnection;
+use crate::adapters::imap::dsn::{self, ReportParts};
+use crate::adapters::imap::envelopes::Envelope;
+use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::sqlite::delivery_failures::DeliveryFailure;
+use crate::services::logger;
+
+/// A bounce waiting to be read: its UID, report parts and In-Reply-To.
+pub type PendingReport = (u32, ReportParts, Option<String>);
+
+/// The delivery status notifications among freshly fetched envelopes.
+pub fn pending_reports(envelopes: &[Envelope]) -> Vec<PendingReport> {
+    envelopes.iter()
+        .filter_map(|e| e.delivery_report.clone().map(|parts| (e.uid, parts, e.in_reply_to.clone())))
+        .collect()
+}
+
+/// Fetch the reports and record their failed and delayed recipients against
+/// the sent message they are about — by the returned message's Message-ID,
+/// or the report's In-Reply-To. The folder must still be selected. A report
+/// that can't be read is skipped; the bounce stays hidden either way.
+pub async fn process_reports(
+    conn: &mut ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    reports: &[PendingReport],
+) -> usize {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut recorded = 0;
+
+    for (uid, parts, in_reply_to) in reports {
+        let report = match dsn::fetch_delivery_report(conn, *uid, parts).await {
+            Ok(report) => report,
+            Err(e) => {
+                logger::warn(&format!("Failed to read delivery report UID {}: {}", uid, e));
+                continue;
+            }
+        };
+        let Some(message_id) = report.original_message_id.or_else(|| in_reply_to.clone()) else {
+            logger::debug(&format!("Delivery report UID {} doesn't name the original message", uid));
+            continue;
+        };
+
+        let failures: Vec<DeliveryFailure> = report.recipients.into_iter()
+            .filter(|r| r.action == "failed" || r.action == "delayed")
+            .map(|r| DeliveryFailure {
+                recipient: r.recipient,
+                action: r.action,
+                status: r.status,
+                diagnostic: r.diagnostic,
+                reported_at: now,
+            })
+            .collect();
+        match sqlite::delivery_failures::record(pool, account_id, &message_id, &failures) {
+            Ok(count) => {
+                if count > 0 {
+                    logger::info(&format!("Delivery failed for {} recipient(s) of {}", count, message_id));
+                }
+                recorded += count;
+            }
+            Err(e) => logger::warn(&format!("Failed to record delivery report for {}: {}", message_id, e)),
+        }
+    }
+    recorded
+}
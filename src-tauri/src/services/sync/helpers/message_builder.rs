@@ -1,5 +1,8 @@
 use crate::adapters::sqlite::conversations::compute_conversation_id;
+use crate::adapters::sqlite::attachments::NewAttachment;
 use crate::adapters::sqlite::messages::NewMessage;
+use crate::adapters::imap::historical::part_to_string;
+use crate::adapters::imap::attachments::AttachmentPart;
 use crate::adapters::imap::envelopes::Envelope;
 use crate::services::sync::helpers::email_normalization::normalize_email;
 use chrono::DateTime;
@@ -82,7 +85,7 @@ fn envelope_to_new_message(
         from_name: envelope.from_name.clone(),
         to_addresses: serde_json::to_string(&to_sorted).unwrap_or_default(),
         cc_addresses: serde_json::to_string(&cc_sorted).unwrap_or_default(),
-        bcc_addresses: "[]".to_string(),
+        bcc_addresses: serde_json::to_string(&envelope.bcc_addresses).unwrap_or_else(|_| "[]".to_string()),
         subject: Some(envelope.subject.clone()),
         body_text: None,
         body_html: None,
@@ -92,13 +95,27 @@ fn envelope_to_new_message(
         references_ids: serde_json::to_string(&envelope.references).unwrap_or_else(|_| "[]".to_string()),
         imap_flags: serde_json::to_string(&flags_sorted).unwrap_or_default(),
         gmail_labels: serde_json::to_string(&envelope.gmail_labels).unwrap_or_default(),
-        classification: None,
+        // Bounces are hidden from conversations and never run through the classifier
+        classification: envelope.delivery_report.as_ref().map(|_| "bounce".to_string()),
         is_important: false,
         distilled_text: None,
-        processed_at: None,
+        processed_at: envelope.delivery_report.as_ref().map(|_| chrono::Utc::now().timestamp_millis()),
         participant_key,
-        conversation_id,
+        conversation_id: envelope.delivery_report.is_none().then_some(conversation_id),
         classification_headers: serde_json::to_string(&envelope.classification_headers).unwrap_or_else(|_| "{}".to_string()),
+        attachments: envelope.attachments.iter().map(to_new_attachment).collect(),
+    }
+}
+
+pub fn to_new_attachment(part: &AttachmentPart) -> NewAttachment {
+    NewAttachment {
+        part_path: part_to_string(&part.part),
+        filename: part.filename.clone(),
+        mime_type: part.mime_type.clone(),
+        size_bytes: part.size_bytes,
+        content_id: part.content_id.clone(),
+        transfer_encoding: part.encoding.clone(),
+        is_inline: part.is_inline,
     }
 }
 
@@ -1,3 +1,4 @@
+pub mod delivery_reports;
 pub mod email_normalization;
 pub mod entity_extraction;
 pub mod message_builder;
@@ -26,6 +26,19 @@ pub fn emit_conversations_updated(app: &tauri::AppHandle, account_id: &str, coun
     });
 }
 
+#[derive(Clone, serde::Serialize)]
+pub struct MessagesRemoved {
+    pub account_id: String,
+    pub message_ids: Vec<String>,
+}
+
+pub fn emit_messages_removed(app: &tauri::AppHandle, account_id: &str, message_ids: &[String]) {
+    let _ = app.emit("sync:messages-removed", MessagesRemoved {
+        account_id: account_id.to_string(),
+        message_ids: message_ids.to_vec(),
+    });
+}
+
 #[derive(Clone, serde::Serialize)]
 pub struct OnboardingComplete {
     pub account_id: String,
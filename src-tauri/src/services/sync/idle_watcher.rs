@@ -0,0 +1,279 @@
+use crate::adapters::sqlite::{self, DbPool};
+use crate::adapters::imap::{connection, folders, idle};
+use crate::error::EddieError;
+use crate::services::{logger, oauth};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::mpsc;
+
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(300);
+
+/// Long-lived IMAP IDLE sessions, one per watched mailbox, that wake the
+/// engine through `wake_tx` as soon as the server reports a change.
+///
+/// Each account gets an IDLE session on INBOX, plus All Mail for Gmail.
+/// Accounts whose server lacks the IDLE capability are left to the engine's
+/// regular polling tick.
+pub struct IdleWatchers {
+    wake_tx: mpsc::Sender<()>,
+    accounts: HashMap<String, Watcher>,
+}
+
+struct Watcher {
+    /// Number of sessions currently parked in IDLE
+    live: Arc<AtomicUsize>,
+    /// Aborting it ends every IDLE session of the account
+    task: JoinHandle<()>,
+}
+
+impl IdleWatchers {
+    pub fn new(wake_tx: mpsc::Sender<()>) -> Self {
+        Self { wake_tx, accounts: HashMap::new() }
+    }
+
+    /// Start watching every onboarded account that isn't watched yet, and
+    /// stop watching accounts that were removed.
+    pub fn ensure_started(&mut self, pool: &DbPool) -> Result<(), EddieError> {
+        let account_ids = sqlite::accounts::list_onboarded_account_ids(pool)?;
+        let wake_tx = self.wake_tx.clone();
+        self.sync_accounts(&account_ids, |account_id, live| {
+            let pool = pool.clone();
+            let wake_tx = wake_tx.clone();
+            tauri::async_runtime::spawn(watch_account(pool, account_id, wake_tx, live))
+        });
+        Ok(())
+    }
+
+    fn sync_accounts(
+        &mut self,
+        account_ids: &[String],
+        mut spawn: impl FnMut(String, Arc<AtomicUsize>) -> JoinHandle<()>,
+    ) {
+        self.accounts.retain(|account_id, watcher| {
+            let keep = account_ids.contains(account_id);
+            if !keep {
+                watcher.task.abort();
+            }
+            keep
+        });
+        for account_id in account_ids {
+            if !self.accounts.contains_key(account_id) {
+                let live = Arc::new(AtomicUsize::new(0));
+                let task = spawn(account_id.clone(), live.clone());
+                self.accounts.insert(account_id.clone(), Watcher { live, task });
+            }
+        }
+    }
+
+    /// True when every watched account has at least one live IDLE session,
+    /// so the engine only needs a slow safety-net poll.
+    pub fn all_live(&self) -> bool {
+        !self.accounts.is_empty()
+            && self.accounts.values().all(|w| w.live.load(Ordering::Relaxed) > 0)
+    }
+}
+
+/// Discover which mailboxes to watch and IDLE on each of them until the
+/// task is aborted. Discovery is retried with backoff, so a server that is
+/// down at startup gets watched once it's back. Returns if the server
+/// doesn't support IDLE.
+async fn watch_account(
+    pool: DbPool,
+    account_id: String,
+    wake_tx: mpsc::Sender<()>,
+    live: Arc<AtomicUsize>,
+) {
+    let mut delay = RECONNECT_DELAY_MIN;
+    let watched = loop {
+        match discover_folders(&pool, &account_id).await {
+            Ok(Some(watched)) => break watched,
+            Ok(None) => {
+                logger::info(&format!("Server for {} has no IDLE support — falling back to polling", account_id));
+                return;
+            }
+            Err(e) => logger::warn(&format!(
+                "IDLE setup for {} failed: {} — retrying in {}s",
+                account_id, e, delay.as_secs()
+            )),
+        }
+        tokio::time::sleep(delay).await;
+        delay = backoff(delay);
+    };
+
+    futures::future::join_all(watched.into_iter().map(|folder| {
+        watch_folder(pool.clone(), account_id.clone(), folder, wake_tx.clone(), live.clone())
+    })).await;
+}
+
+/// The mailboxes to IDLE on: INBOX, plus All Mail for Gmail. None if the
+/// server can't IDLE.
+async fn discover_folders(pool: &DbPool, account_id: &str) -> Result<Option<Vec<String>>, EddieError> {
+    let mut conn = connect(pool, account_id).await?;
+
+    if !conn.caps.idle {
+        conn.session.logout().await.ok();
+        return Ok(None);
+    }
+
+    let mut watched = vec!["INBOX".to_string()];
+    if conn.caps.gmail_ext {
+        let folder_list = folders::list_folders(&mut conn.session).await?;
+        if let Some(all_mail) = folders::find_folder_by_attribute(&folder_list, "All") {
+            watched.push(all_mail);
+        }
+    }
+    conn.session.logout().await.ok();
+    Ok(Some(watched))
+}
+
+fn backoff(delay: Duration) -> Duration {
+    (delay * 2).min(RECONNECT_DELAY_MAX)
+}
+
+/// IDLE on a single mailbox forever, reconnecting with backoff on errors.
+async fn watch_folder(
+    pool: DbPool,
+    account_id: String,
+    folder: String,
+    wake_tx: mpsc::Sender<()>,
+    live: Arc<AtomicUsize>,
+) {
+    let mut delay = RECONNECT_DELAY_MIN;
+    loop {
+        match idle_session(&pool, &account_id, &folder, &wake_tx, &live, &mut delay).await {
+            Ok(()) => return,
+            Err(e) => logger::warn(&format!(
+                "IDLE on {} for {} lost: {} — reconnecting in {}s",
+                folder, account_id, e, delay.as_secs()
+            )),
+        }
+        tokio::time::sleep(delay).await;
+        delay = backoff(delay);
+    }
+}
+
+/// Connect, SELECT the folder, and re-IDLE until the connection fails.
+/// Only returns Ok if the wake channel is closed (engine shut down).
+async fn idle_session(
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    wake_tx: &mpsc::Sender<()>,
+    live: &AtomicUsize,
+    delay: &mut Duration,
+) -> Result<(), EddieError> {
+    let mut conn = connect(pool, account_id).await?;
+    conn.select_folder(folder).await?;
+    logger::debug(&format!("IDLE started on {} for {}", folder, account_id));
+
+    live.fetch_add(1, Ordering::Relaxed);
+    *delay = RECONNECT_DELAY_MIN;
+
+    let result = idle_loop(conn, account_id, folder, wake_tx).await;
+    live.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+async fn idle_loop(
+    mut conn: connection::ImapConnection,
+    account_id: &str,
+    folder: &str,
+    wake_tx: &mpsc::Sender<()>,
+) -> Result<(), EddieError> {
+    loop {
+        let (next, event) = idle::idle_once(conn, idle::IDLE_TIMEOUT).await?;
+        conn = next;
+        if event == idle::IdleEvent::Changed {
+            logger::debug(&format!("IDLE: {} changed for {}, waking engine", folder, account_id));
+            // A full channel means a wake-up is already pending.
+            if let Err(mpsc::error::TrySendError::Closed(_)) = wake_tx.try_send(()) {
+                conn.session.logout().await.ok();
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn connect(pool: &DbPool, account_id: &str) -> Result<connection::ImapConnection, EddieError> {
+    let creds = sqlite::accounts::get_credentials(pool, account_id)?
+        .ok_or(EddieError::AccountNotFound(account_id.to_string()))?;
+
+    // EXAMINE is enough — IDLE only observes the mailbox.
+    let auth = oauth::imap_auth(pool, account_id, creds.oauth_provider.as_deref(), &creds.password).await?;
+    connection::connect_with_tls(
+        &creds.host, creds.port, creds.security, creds.allow_plaintext, &creds.email, &auth, false,
+    ).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn watchers() -> IdleWatchers {
+        IdleWatchers::new(mpsc::channel(1).0)
+    }
+
+    fn spawn_pending(started: &mut Vec<String>) -> impl FnMut(String, Arc<AtomicUsize>) -> JoinHandle<()> + '_ {
+        |account_id, _live| {
+            started.push(account_id);
+            tauri::async_runtime::spawn(std::future::pending())
+        }
+    }
+
+    #[test]
+    fn test_starts_new_accounts_and_stops_removed_ones() {
+        let mut watchers = watchers();
+        let mut started = Vec::new();
+        watchers.sync_accounts(&ids(&["a", "b"]), spawn_pending(&mut started));
+        watchers.sync_accounts(&ids(&["a", "b"]), spawn_pending(&mut started));
+        assert_eq!(started, ids(&["a", "b"]));
+
+        let b = watchers.accounts["b"].task.inner().abort_handle();
+        watchers.sync_accounts(&ids(&["a", "c"]), spawn_pending(&mut started));
+        assert_eq!(started, ids(&["a", "b", "c"]));
+        let mut watched: Vec<&String> = watchers.accounts.keys().collect();
+        watched.sort();
+        assert_eq!(watched, ["a", "c"]);
+        tauri::async_runtime::block_on(async {
+            while !b.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        });
+    }
+
+    #[test]
+    fn test_all_live_needs_a_session_per_account() {
+        let mut watchers = watchers();
+        assert!(!watchers.all_live());
+        let mut started = Vec::new();
+        watchers.sync_accounts(&ids(&["a", "b"]), spawn_pending(&mut started));
+        watchers.accounts["a"].live.fetch_add(1, Ordering::Relaxed);
+        assert!(!watchers.all_live());
+        watchers.accounts["b"].live.fetch_add(2, Ordering::Relaxed);
+        assert!(watchers.all_live());
+
+        // Dropping the account whose sessions were down leaves only live ones
+        watchers.accounts["a"].live.fetch_sub(1, Ordering::Relaxed);
+        watchers.sync_accounts(&ids(&["b"]), spawn_pending(&mut started));
+        assert!(watchers.all_live());
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let mut delay = RECONNECT_DELAY_MIN;
+        let mut delays = vec![];
+        for _ in 0..5 {
+            delays.push(delay.as_secs());
+            delay = backoff(delay);
+        }
+        assert_eq!(delays, [30, 60, 120, 240, 300]);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod connection_manager;
 pub mod helpers;
+pub mod idle_watcher;
 pub mod tasks;
 pub mod worker;
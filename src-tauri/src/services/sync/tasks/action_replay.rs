@@ -1,9 +1,12 @@
 use crate::adapters::sqlite::{self, DbPool, action_queue};
+use crate::adapters::sqlite::action_queue::LOCAL_ACTIONS;
 use crate::adapters::sqlite::accounts;
-use crate::adapters::imap::{connection, folders};
+use crate::adapters::imap::{connection, folders, historical};
 use crate::adapters::smtp;
 use crate::error::EddieError;
-use crate::services::logger;
+use crate::services::{logger, oauth};
+use crate::services::attachments::OutgoingAttachment;
+use crate::services::sync::{connection_manager, worker};
 
 /// Replay all pending actions for all onboarded accounts.
 /// Called at the start of each worker tick, before incremental sync.
@@ -30,44 +33,56 @@ pub async fn replay_pending_actions(
             .unwrap_or(false);
 
         // We connect to IMAP if there are actions that need it
-        let needs_imap = actions.iter().any(|a| a.action_type == "mark_read" || a.action_type == "send");
+        let needs_imap = actions.iter().any(|a| !LOCAL_ACTIONS.contains(&a.action_type.as_str()));
         let mut imap_conn = if needs_imap && (write_mode || actions.iter().any(|a| a.action_type == "send")) {
-            let creds = accounts::get_credentials(pool, account_id)?
-                .ok_or(EddieError::AccountNotFound(account_id.clone()))?;
-            Some(
-                connection::connect_with_tls(
-                    &creds.host, creds.port, creds.tls,
-                    &creds.email, &creds.password, true, // write_mode: SELECT for mutations
-                ).await?
-            )
+            let mut conn = connection_manager::checkout(pool, account_id).await?;
+            conn.write_mode = true; // SELECT for mutations
+            Some(conn)
         } else {
             None
         };
 
+        // A failed action may leave the session mid-command, so it isn't reused
+        let mut failed = false;
         for action in &actions {
-            action_queue::mark_in_progress(pool, &action.id)?;
+            if !action_queue::mark_in_progress(pool, &action.id)? {
+                continue; // cancelled since get_pending
+            }
 
-            let result = execute_action(pool, imap_conn.as_mut(), action, write_mode).await;
+            let result = execute_action(pool, imap_conn.as_deref_mut(), action, write_mode).await;
+            failed |= result.is_err();
 
             match result {
+                // Nothing on the server to confirm
+                Ok(()) if LOCAL_ACTIONS.contains(&action.action_type.as_str()) => {
+                    action_queue::mark_done(pool, &action.id)?;
+                }
                 Ok(()) => {
                     action_queue::mark_completed(pool, &action.id)?;
                     logger::debug(&format!("Action {} completed: {}", action.id, action.action_type));
                 }
-                Err(e) => {
+                Err(e) if e.is_permanent() || action.retry_count + 1 >= action.max_retries => {
                     let err_msg = e.to_string();
                     action_queue::mark_failed(pool, &action.id, &err_msg)?;
                     logger::warn(&format!(
-                        "Action {} failed (retry {}/{}): {}",
-                        action.id, action.retry_count + 1, action.max_retries, err_msg
+                        "Action {} ({}) failed permanently after {} attempts: {}",
+                        action.id, action.action_type, action.retry_count + 1, err_msg
+                    ));
+                }
+                Err(e) => {
+                    let err_msg = e.to_string();
+                    let delay = action_queue::retry_delay(action.retry_count);
+                    let next_attempt_at = chrono::Utc::now().timestamp_millis() + delay.as_millis() as i64;
+                    action_queue::schedule_retry(pool, &action.id, &err_msg, next_attempt_at)?;
+                    logger::warn(&format!(
+                        "Action {} failed (retry {}/{} in {}s): {}",
+                        action.id, action.retry_count + 1, action.max_retries, delay.as_secs(), err_msg
                     ));
                 }
             }
         }
-
-        // Logout IMAP if we connected
-        if let Some(mut conn) = imap_conn {
-            conn.session.logout().await.ok();
+        if let Some(conn) = imap_conn.filter(|_| !failed) {
+            conn.release();
         }
     }
 
@@ -82,35 +97,69 @@ async fn execute_action(
 ) -> Result<(), EddieError> {
     match action.action_type.as_str() {
         "mark_read" => execute_mark_read(pool, imap_conn, action, write_mode).await,
+        "flag" => execute_flag(pool, imap_conn, action, write_mode).await,
+        "archive" | "delete" | "move" => execute_move(pool, imap_conn, action, write_mode).await,
+        // Applied to SQLite when queued; there is no IMAP counterpart
+        "mute" | "pin" => Ok(()),
         "send" => execute_send(pool, imap_conn, action).await,
         _ => Err(EddieError::InvalidInput(format!("Unknown action type: {}", action.action_type))),
     }
 }
 
 /// Mark messages as read on IMAP server.
-/// Payload: { "folder": "INBOX", "uids": [123, 456] }
+/// Payload: { "folder": "INBOX", "uids": [123, 456], "ids": ["<messages.id>", ...] }
 async fn execute_mark_read(
-    _pool: &DbPool,
+    pool: &DbPool,
     imap_conn: Option<&mut connection::ImapConnection>,
     action: &action_queue::QueuedAction,
     write_mode: bool,
 ) -> Result<(), EddieError> {
+    store_flag(pool, imap_conn, action, write_mode, "+FLAGS (\\Seen)").await
+}
+
+/// Flag or unflag (star) messages on IMAP server.
+/// Payload: { "folder", "uids", "ids", "flagged": true }
+async fn execute_flag(
+    pool: &DbPool,
+    imap_conn: Option<&mut connection::ImapConnection>,
+    action: &action_queue::QueuedAction,
+    write_mode: bool,
+) -> Result<(), EddieError> {
+    let payload: serde_json::Value = serde_json::from_str(&action.payload)
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid flag payload: {}", e)))?;
+    let flag_op = if payload["flagged"].as_bool().unwrap_or(true) {
+        "+FLAGS (\\Flagged)"
+    } else {
+        "-FLAGS (\\Flagged)"
+    };
+    store_flag(pool, imap_conn, action, write_mode, flag_op).await
+}
+
+/// UID STORE `flag_op` on the messages of a `{ folder, uids, ids }` payload.
+async fn store_flag(
+    pool: &DbPool,
+    imap_conn: Option<&mut connection::ImapConnection>,
+    action: &action_queue::QueuedAction,
+    write_mode: bool,
+    flag_op: &str,
+) -> Result<(), EddieError> {
+    let kind = action.action_type.as_str();
     if !write_mode {
         return Err(EddieError::Backend("Read-only mode: skipping IMAP STORE".into()));
     }
 
-    let conn = imap_conn.ok_or(EddieError::Backend("No IMAP connection for mark_read".into()))?;
+    let conn = imap_conn.ok_or(EddieError::Backend(format!("No IMAP connection for {}", kind)))?;
 
     let payload: serde_json::Value = serde_json::from_str(&action.payload)
-        .map_err(|e| EddieError::InvalidInput(format!("Invalid mark_read payload: {}", e)))?;
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid {} payload: {}", kind, e)))?;
 
     let folder = payload["folder"]
         .as_str()
-        .ok_or(EddieError::InvalidInput("mark_read: missing folder".into()))?;
+        .ok_or(EddieError::InvalidInput(format!("{}: missing folder", kind)))?;
 
-    let uids: Vec<u32> = payload["uids"]
+    let mut uids: Vec<u32> = payload["uids"]
         .as_array()
-        .ok_or(EddieError::InvalidInput("mark_read: missing uids".into()))?
+        .ok_or(EddieError::InvalidInput(format!("{}: missing uids", kind)))?
         .iter()
         .filter_map(|v| v.as_u64().map(|u| u as u32))
         .collect();
@@ -119,14 +168,160 @@ async fn execute_mark_read(
         return Ok(());
     }
 
-    conn.select_folder(folder).await?;
-    conn.store_flags(&uids, "+FLAGS (\\Seen)").await?;
+    worker::select_folder(conn, pool, &action.account_id, folder).await?;
+
+    // Queued UIDs go stale if SELECT just re-mapped the folder — resolve by row id instead
+    if let Some(ids) = payload["ids"].as_array() {
+        let ids: Vec<String> = ids.iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+        if !ids.is_empty() {
+            uids = sqlite::messages::get_uids_by_ids(pool, folder, &ids)?;
+        }
+    }
+
+    conn.store_flags(&uids, flag_op).await?;
+
+    Ok(())
+}
+
+/// Archive, delete or move messages on the IMAP server.
+/// Payload: { "folder", "uids", "ids", "message_ids", "destination" (move only) }
+///
+/// Archive goes to the folder marked \\Archive (RFC 6154), delete to \\Trash —
+/// or is expunged outright when there is no Trash or the messages are already
+/// in it. On Gmail, where only All Mail is synced, archive and move change
+/// labels instead. Moved rows are re-homed to their new UID in the destination.
+async fn execute_move(
+    pool: &DbPool,
+    imap_conn: Option<&mut connection::ImapConnection>,
+    action: &action_queue::QueuedAction,
+    write_mode: bool,
+) -> Result<(), EddieError> {
+    let kind = action.action_type.as_str();
+    if !write_mode {
+        return Err(EddieError::Backend(format!("Read-only mode: skipping IMAP {}", kind)));
+    }
+
+    let conn = imap_conn.ok_or(EddieError::Backend(format!("No IMAP connection for {}", kind)))?;
+
+    let payload: serde_json::Value = serde_json::from_str(&action.payload)
+        .map_err(|e| EddieError::InvalidInput(format!("Invalid {} payload: {}", kind, e)))?;
+
+    let folder = payload["folder"]
+        .as_str()
+        .ok_or(EddieError::InvalidInput(format!("{}: missing folder", kind)))?;
+    let ids: Vec<String> = payload["ids"].as_array()
+        .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+
+    let is_gmail = conn.caps.gmail_ext;
+    let folder_list = folders::list_folders(&mut conn.session).await?;
+    let destination = match kind {
+        "archive" if is_gmail => None,
+        "archive" => Some(folders::find_archive_folder(&folder_list)
+            .ok_or(EddieError::Backend("No Archive folder found".into()))?),
+        "delete" => folders::find_trash_folder(&folder_list).filter(|trash| trash != folder),
+        _ => Some(payload["destination"].as_str()
+            .ok_or(EddieError::InvalidInput("move: missing destination".into()))?
+            .to_string()),
+    };
+
+    worker::select_folder(conn, pool, &action.account_id, folder).await?;
+    let uids = resolve_uids(conn, folder, &payload).await?;
+    if uids.is_empty() {
+        // Already gone — an earlier attempt got through before failing
+        return Ok(());
+    }
+
+    match (kind, destination.as_deref()) {
+        ("archive", None) => {
+            conn.store_flags(&uids, "-X-GM-LABELS (\\Inbox)").await?;
+        }
+        ("move", Some(label)) if is_gmail => {
+            conn.store_flags(&uids, &format!("+X-GM-LABELS (\"{}\")", label.replace('\\', "\\\\").replace('"', "\\\""))).await?;
+            conn.store_flags(&uids, "-X-GM-LABELS (\\Inbox)").await?;
+        }
+        (_, Some(dest)) => {
+            conn.move_messages(&uids, dest).await?;
+            logger::debug(&format!("Moved {} messages from {} to {}", uids.len(), folder, dest));
+            if kind != "delete" {
+                rehome_moved(conn, pool, &action.account_id, folder, dest, &ids).await?;
+            }
+        }
+        (_, None) => {
+            conn.delete_messages(&uids).await?;
+            logger::debug(&format!("Deleted {} messages from {}", uids.len(), folder));
+        }
+    }
 
     Ok(())
 }
 
-/// Send an email via SMTP, then APPEND to Sent folder.
-/// Payload: { "from", "from_name", "to", "cc", "subject", "body", "in_reply_to", "references", "message_db_id", "message_id" }
+/// Current UIDs in the selected `folder` of the payload's messages, found by
+/// Message-ID. Messages without one fall back to the UID recorded at queue time.
+async fn resolve_uids(
+    conn: &mut connection::ImapConnection,
+    folder: &str,
+    payload: &serde_json::Value,
+) -> Result<Vec<u32>, EddieError> {
+    let queued_uids: Vec<u32> = payload["uids"].as_array()
+        .map(|uids| uids.iter().filter_map(|v| v.as_u64().map(|u| u as u32)).collect())
+        .unwrap_or_default();
+    let message_ids: Vec<String> = payload["message_ids"].as_array()
+        .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    if message_ids.len() != queued_uids.len() {
+        return Ok(queued_uids);
+    }
+
+    let mut uids: Vec<u32> = queued_uids.iter().zip(&message_ids)
+        .filter(|(_, message_id)| message_id.is_empty())
+        .map(|(uid, _)| *uid)
+        .collect();
+    let searchable: Vec<String> = message_ids.into_iter().filter(|id| !id.is_empty()).collect();
+    if !searchable.is_empty() {
+        let found = historical::search_message_ids(conn, folder, &searchable).await?;
+        uids.extend(found.into_iter().map(|(uid, _)| uid));
+    }
+    Ok(uids)
+}
+
+/// Point moved rows at their UIDs in `destination`. Rows not found there are
+/// left for expunge reconcile, which re-homes or deletes them once the source
+/// UIDs are gone.
+async fn rehome_moved(
+    conn: &mut connection::ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    destination: &str,
+    ids: &[String],
+) -> Result<(), EddieError> {
+    let rows = sqlite::messages::get_locations_by_ids(pool, folder, ids)?;
+    let message_ids: Vec<String> = rows.iter()
+        .filter(|(_, _, message_id)| !message_id.is_empty())
+        .map(|(_, _, message_id)| message_id.clone())
+        .collect();
+    if message_ids.is_empty() || sqlite::folder_sync::get_folder(pool, account_id, destination)?.is_none() {
+        return Ok(());
+    }
+
+    worker::select_folder(conn, pool, account_id, destination).await?;
+    let found = historical::search_message_ids(conn, destination, &message_ids).await?;
+    for (new_uid, message_id) in &found {
+        if let Some((id, _, _)) = rows.iter().find(|(_, _, m)| m == message_id) {
+            sqlite::messages::rehome_message(pool, id, destination, *new_uid)?;
+        }
+    }
+    Ok(())
+}
+
+/// Send an email in checkpointed stages: build the raw message and store it,
+/// submit it over SMTP, then APPEND it to the Sent folder. Each stage is
+/// recorded on the action, so a retry resumes after the last one that
+/// succeeded — a message that was submitted is never submitted again.
+/// Payload: { "from", "from_name", "to", "cc", "bcc", "reply_to", "sender", "headers", "subject", "body", "in_reply_to", "references", "message_db_id", "message_id", "attachments" }
 async fn execute_send(
     pool: &DbPool,
     imap_conn: Option<&mut connection::ImapConnection>,
@@ -137,64 +332,123 @@ async fn execute_send(
 
     let from = payload["from"].as_str()
         .ok_or(EddieError::InvalidInput("send: missing from".into()))?;
-    let from_name = payload["from_name"].as_str().map(|s| s.to_string());
     let to: Vec<String> = payload["to"].as_array()
         .ok_or(EddieError::InvalidInput("send: missing to".into()))?
         .iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
-    let cc: Vec<String> = payload["cc"].as_array()
-        .unwrap_or(&vec![])
-        .iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
-    let subject = payload["subject"].as_str()
-        .ok_or(EddieError::InvalidInput("send: missing subject".into()))?;
-    let body = payload["body"].as_str()
-        .ok_or(EddieError::InvalidInput("send: missing body".into()))?;
-    let in_reply_to = payload["in_reply_to"].as_str().map(|s| s.to_string());
-    let references: Vec<String> = payload["references"].as_array()
-        .unwrap_or(&vec![])
-        .iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
-
-    // Get SMTP credentials
-    let smtp_creds = accounts::get_smtp_credentials(pool, &action.account_id)?
-        .ok_or(EddieError::AccountNotFound(action.account_id.clone()))?;
+    let cc = string_list(&payload["cc"]);
+    let bcc = string_list(&payload["bcc"]);
+    let stage = action.stage.as_deref();
 
-    let message_id = payload["message_id"].as_str().map(|s| s.to_string());
-
-    let smtp_msg = smtp::SmtpMessage {
-        from: from.to_string(),
-        from_name,
-        to,
-        cc,
-        subject: subject.to_string(),
-        body: body.to_string(),
-        in_reply_to,
-        references,
-        message_id,
+    let stored = if action_queue::stage_reached(stage, "built") {
+        action_queue::get_raw_message(pool, &action.id)?
+    } else {
+        None
+    };
+    let raw_message = match stored {
+        Some(raw) => raw,
+        None => {
+            // Reading and encoding the attachments blocks, so it runs off the runtime
+            let (payload, from, to, cc, bcc) = (payload.clone(), from.to_string(), to.clone(), cc.clone(), bcc.clone());
+            let raw = crate::services::attachments::run_blocking(move || build_send(&payload, &from, &to, &cc, &bcc)).await?;
+            action_queue::save_built(pool, &action.id, &raw)?;
+            raw
+        }
     };
 
-    // Send via SMTP
-    let raw_message = smtp::send_message(
-        &smtp_creds.host, smtp_creds.port, smtp_creds.tls,
-        &smtp_creds.email, &smtp_creds.password,
-        &smtp_msg,
-    ).await?;
+    if !action_queue::stage_reached(stage, "submitted") {
+        let smtp_creds = accounts::get_smtp_credentials(pool, &action.account_id)?
+            .ok_or(EddieError::AccountNotFound(action.account_id.clone()))?;
+        let auth = oauth::smtp_auth(
+            pool, &action.account_id, smtp_creds.oauth_provider.as_deref(), &smtp_creds.password,
+        ).await?;
+        let recipients: Vec<String> = to.iter().chain(&cc).chain(&bcc).cloned().collect();
+        let envelope = smtp::envelope(from, &recipients)?;
+        smtp::submit(
+            &smtp_creds.host, smtp_creds.port, smtp_creds.tls,
+            &smtp_creds.email, &auth,
+            &envelope, &raw_message,
+        ).await?;
+        action_queue::set_stage(pool, &action.id, "submitted")?;
 
-    logger::info(&format!("Email sent to {:?}", smtp_msg.to));
+        logger::info(&format!("Email sent to {:?}", to));
+    }
 
-    // APPEND to Sent folder via IMAP
-    // Gmail auto-copies sent messages, so skip APPEND for Gmail accounts.
-    if let Some(conn) = imap_conn {
-        if conn.has_gmail_ext {
-            logger::debug("Gmail account — skipping APPEND (auto-copied to Sent)");
+    // APPEND to Sent folder via IMAP, with the Bcc header the recipients didn't get.
+    // Gmail (X-GM-EXT-1) auto-copies sent messages, so skip APPEND there.
+    let conn = imap_conn
+        .ok_or_else(|| EddieError::Backend("No IMAP connection to save the sent message".into()))?;
+    if conn.caps.gmail_ext {
+        logger::debug("Gmail account — skipping APPEND (auto-copied to Sent)");
+    } else {
+        let folder_list = folders::list_folders(&mut conn.session).await?;
+        if let Some(sent_folder) = folders::find_sent_folder(&folder_list) {
+            let sent_copy = smtp::with_bcc(&raw_message, &bcc)?;
+            conn.append_message(&sent_folder, &["\\Seen"], &sent_copy).await?;
+            logger::debug(&format!("Appended sent message to {}", sent_folder));
         } else {
-            let folder_list = folders::list_folders(&mut conn.session).await?;
-            if let Some(sent_folder) = folders::find_sent_folder(&folder_list) {
-                conn.append_message(&sent_folder, &["\\Seen"], &raw_message).await?;
-                logger::debug(&format!("Appended sent message to {}", sent_folder));
-            } else {
-                logger::warn("No Sent folder found — message not saved to IMAP");
-            }
+            logger::warn("No Sent folder found — message not saved to IMAP");
         }
     }
+    action_queue::set_stage(pool, &action.id, "appended")?;
 
     Ok(())
 }
+
+/// Build the raw RFC 5322 message for a send payload. The body is Markdown:
+/// it is sent as is in the text part and rendered for the HTML part.
+fn build_send(
+    payload: &serde_json::Value,
+    from: &str,
+    to: &[String],
+    cc: &[String],
+    bcc: &[String],
+) -> Result<Vec<u8>, EddieError> {
+    let subject = payload["subject"].as_str()
+        .ok_or(EddieError::InvalidInput("send: missing subject".into()))?;
+    let body = payload["body"].as_str()
+        .ok_or(EddieError::InvalidInput("send: missing body".into()))?;
+    let headers: Vec<(String, String)> = payload["headers"].as_object()
+        .map(|headers| headers.iter()
+            .filter_map(|(name, value)| value.as_str().map(|v| (name.clone(), v.to_string())))
+            .collect())
+        .unwrap_or_default();
+
+    let outgoing: Vec<OutgoingAttachment> = serde_json::from_value(payload["attachments"].clone())
+        .unwrap_or_default();
+    let mut attachments = Vec::with_capacity(outgoing.len());
+    for att in outgoing {
+        let content = std::fs::read(&att.path)
+            .map_err(|e| EddieError::InvalidInput(format!("Attachment {} is no longer available: {}", att.filename, e)))?;
+        attachments.push(smtp::SmtpAttachment {
+            filename: att.filename,
+            mime_type: att.mime_type,
+            content,
+            content_id: att.content_id,
+        });
+    }
+
+    smtp::build_message(&smtp::SmtpMessage {
+        from: from.to_string(),
+        from_name: payload["from_name"].as_str().map(|s| s.to_string()),
+        to: to.to_vec(),
+        cc: cc.to_vec(),
+        bcc: bcc.to_vec(),
+        reply_to: string_list(&payload["reply_to"]),
+        sender: payload["sender"].as_str().map(|s| s.to_string()),
+        headers,
+        subject: subject.to_string(),
+        body: body.to_string(),
+        html: Some(smtp::markdown_to_html(body)),
+        in_reply_to: payload["in_reply_to"].as_str().map(|s| s.to_string()),
+        references: string_list(&payload["references"]),
+        message_id: payload["message_id"].as_str().map(|s| s.to_string()),
+        attachments,
+    })
+}
+
+/// The strings in a payload array; missing or non-array values give none.
+fn string_list(value: &serde_json::Value) -> Vec<String> {
+    value.as_array()
+        .map(|items| items.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default()
+}
@@ -59,12 +59,12 @@ pub async fn run_connection_history(
 
     let (_creds, self_emails, mut conn) = worker::connect_account(pool, account_id).await?;
     let folder_list = folders::list_folders(&mut conn.session).await?;
-    let sync_folders = folders::folders_to_sync(&folder_list, conn.has_gmail_ext);
+    let sync_folders = folders::folders_to_sync(&folder_list, conn.caps.gmail_ext);
 
     let mut total_fetched = 0usize;
 
     for folder_info in &sync_folders {
-        conn.select_folder(&folder_info.name).await?;
+        worker::select_folder(&mut conn, pool, account_id, &folder_info.name).await?;
 
         // Get existing UIDs so we can skip them
         let existing_uids = sqlite::messages::get_uids_for_folder(
@@ -100,7 +100,7 @@ pub async fn run_connection_history(
                 .join(",");
 
             // Round trip 1: Envelopes + bodystructure
-            let fetch_query = if conn.has_gmail_ext {
+            let fetch_query = if conn.caps.gmail_ext {
                 "(UID FLAGS ENVELOPE BODYSTRUCTURE X-GM-LABELS)"
             } else {
                 "(UID FLAGS ENVELOPE BODYSTRUCTURE)"
@@ -115,17 +115,17 @@ pub async fn run_connection_history(
             ).await;
 
             let mut envelopes: Vec<envelopes::Envelope> = Vec::new();
-            let mut text_parts: Vec<(u32, Vec<u32>, bool, String)> = Vec::new();
+            let mut text_parts: Vec<historical::TextPart> = Vec::new();
 
             for fetch in &fetches {
                 if let Some(env) = envelopes::parse_envelope(fetch) {
                     envelopes.push(env);
                 }
                 if let (Some(uid), Some(bs)) = (fetch.uid, fetch.bodystructure()) {
-                    if let Some((part, encoding)) = historical::find_mime_part(bs, &[], "plain") {
-                        text_parts.push((uid, part, false, historical::encoding_to_string(encoding)));
-                    } else if let Some((part, encoding)) = historical::find_mime_part(bs, &[], "html") {
-                        text_parts.push((uid, part, true, historical::encoding_to_string(encoding)));
+                    if let Some((part, encoding, charset)) = historical::find_mime_part(bs, &[], "plain") {
+                        text_parts.push((uid, part, false, historical::encoding_to_string(encoding), charset));
+                    } else if let Some((part, encoding, charset)) = historical::find_mime_part(bs, &[], "html") {
+                        text_parts.push((uid, part, true, historical::encoding_to_string(encoding), charset));
                     }
                 }
             }
@@ -154,13 +154,17 @@ pub async fn run_connection_history(
             let mut bodies: Vec<(u32, String, bool)> = Vec::new();
             let mut uid_is_html: HashMap<u32, bool> = HashMap::new();
             let mut uid_encoding: HashMap<u32, String> = HashMap::new();
+            let mut uid_charset: HashMap<u32, String> = HashMap::new();
 
             if !text_parts.is_empty() {
                 let mut by_part: HashMap<Vec<u32>, Vec<u32>> = HashMap::new();
-                for (uid, part, is_html, encoding) in &text_parts {
+                for (uid, part, is_html, encoding, charset) in &text_parts {
                     by_part.entry(part.clone()).or_default().push(*uid);
                     uid_is_html.insert(*uid, *is_html);
                     uid_encoding.insert(*uid, encoding.clone());
+                    if let Some(charset) = charset {
+                        uid_charset.insert(*uid, charset.clone());
+                    }
                 }
 
                 for (part, part_uids) in &by_part {
@@ -185,7 +189,8 @@ pub async fn run_connection_history(
                         if let Some(uid) = fetch.uid {
                             if let Some(section_data) = fetch.section(&path) {
                                 let encoding = uid_encoding.get(&uid).cloned().unwrap_or_default();
-                                let decoded = historical::decode_body(section_data, &encoding)
+                                let charset = uid_charset.get(&uid).map(String::as_str);
+                                let decoded = historical::decode_body(section_data, &encoding, charset)
                                     .map_err(|e| EddieError::Backend(format!("Decode body failed: {}", e)))?;
                                 let is_html = uid_is_html.get(&uid).copied().unwrap_or(false);
                                 bodies.push((uid, decoded, is_html));
@@ -222,6 +227,8 @@ pub async fn run_connection_history(
         }
     }
 
+    conn.release();
+
     if total_fetched > 0 {
         worker::process_changes(app, pool, account_id, classifier)?;
     }
@@ -0,0 +1,200 @@
+use crate::adapters::sqlite;
+use crate::adapters::sqlite::DbPool;
+use crate::adapters::sqlite::folder_sync::FolderState;
+use crate::adapters::imap::connection::ImapConnection;
+use crate::adapters::imap::folders::FolderInfo;
+use crate::adapters::imap::historical;
+use crate::services::sync::{helpers, worker};
+use crate::error::EddieError;
+use async_imap::types::Mailbox;
+
+use crate::services::logger;
+use std::collections::HashSet;
+
+/// Compare the locally cached UIDs of the selected folder with `UID SEARCH`
+/// on the server and return the ones that are gone.
+///
+/// Only needed for folders without QRESYNC VANISHED support — flag resync
+/// calls this for them after selecting the folder. Skips the search if EXISTS
+/// and UIDNEXT haven't moved since the last pass, since nothing can have been
+/// expunged. The caller records them with `folder_sync::set_reconciled` once
+/// the returned UIDs are removed, so a failed removal is retried next pass.
+pub(crate) async fn find_expunged(
+    conn: &mut ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    mailbox: &Mailbox,
+    state: &FolderState,
+) -> Result<Vec<u32>, EddieError> {
+    let uid_next = mailbox.uid_next.unwrap_or(0);
+    if uid_next > 0 && mailbox.exists == state.exists_count && uid_next == state.uid_next {
+        return Ok(Vec::new());
+    }
+
+    let local = sqlite::messages::get_uids_for_folder(pool, account_id, folder)?;
+    let mut gone = Vec::new();
+    if let (Some(&min), Some(&max)) = (local.iter().min(), local.iter().max()) {
+        let server: HashSet<u32> = conn.session
+            .uid_search(format!("UID {}:{}", min, max))
+            .await
+            .map_err(|e| EddieError::Backend(format!("SEARCH failed: {}", e)))?
+            .into_iter()
+            .collect();
+
+        gone = local.difference(&server).copied().collect();
+        gone.sort_unstable();
+    }
+
+    Ok(gone)
+}
+
+/// Confirm completed moves once none of their messages is cached in the
+/// source folder any more. Gmail archive/move are label changes, confirmed
+/// by flag resync instead.
+pub(crate) fn confirm_moves(
+    pool: &DbPool,
+    account_id: &str,
+    is_gmail: bool,
+) -> Result<(), EddieError> {
+    let confirmed: &[&str] = if is_gmail { &["delete"] } else { &["archive", "delete", "move"] };
+    for action_type in confirmed {
+        for (action_id, payload) in sqlite::action_queue::get_completed_by_type(pool, account_id, action_type)? {
+            let payload: serde_json::Value = serde_json::from_str(&payload).unwrap_or_default();
+            let folder = payload["folder"].as_str().unwrap_or_default();
+            let ids: Vec<String> = payload["ids"].as_array()
+                .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default();
+            if sqlite::messages::get_locations_by_ids(pool, folder, &ids)?.is_empty() {
+                sqlite::action_queue::mark_done(pool, &action_id)?;
+                logger::debug(&format!("{} action {} confirmed by expunge reconcile", action_type, action_id));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle UIDs that disappeared from `folder`: messages that moved to another
+/// synced folder are re-homed (keeping their row, classification and distilled
+/// text), the rest are deleted. Updates conversations and emits
+/// `sync:messages-removed` if anything was deleted. Returns the number removed.
+pub(crate) async fn remove_vanished(
+    app: &tauri::AppHandle,
+    pool: &DbPool,
+    account_id: &str,
+    conn: &mut ImapConnection,
+    sync_folders: &[&FolderInfo],
+    folder: &str,
+    uids: &[u32],
+) -> Result<usize, EddieError> {
+    let missing = sqlite::messages::get_ids_for_uids(pool, account_id, folder, uids)?;
+    if missing.is_empty() {
+        return Ok(0);
+    }
+
+    // Look for the missing Message-IDs in the other synced folders
+    let mut found: Vec<(String, u32, String)> = Vec::new();
+    let mut remaining: HashSet<&str> = missing.iter()
+        .map(|(_, message_id)| message_id.as_str())
+        .filter(|message_id| !message_id.is_empty())
+        .collect();
+    for other in sync_folders.iter().filter(|f| f.name != folder) {
+        if remaining.is_empty() {
+            break;
+        }
+        let message_ids: Vec<String> = remaining.iter().map(|id| id.to_string()).collect();
+
+        worker::select_folder(conn, pool, account_id, &other.name).await?;
+        for (new_uid, message_id) in historical::search_message_ids(conn, &other.name, &message_ids).await? {
+            if remaining.remove(message_id.as_str()) {
+                found.push((other.name.clone(), new_uid, message_id));
+            }
+        }
+    }
+
+    let (rehomed, removed_ids) = settle_vanished(pool, missing, &found)?;
+    let removed = removed_ids.len();
+
+    logger::info(&format!(
+        "Expunge reconcile for {}: {} removed, {} moved to another folder",
+        folder, removed, rehomed
+    ));
+
+    if removed > 0 || rehomed > 0 {
+        let conv_count = sqlite::conversations::update_conversations(pool, account_id)?;
+        if removed > 0 {
+            helpers::status_emit::emit_messages_removed(app, account_id, &removed_ids);
+        }
+        helpers::status_emit::emit_conversations_updated(app, account_id, conv_count);
+    }
+
+    Ok(removed)
+}
+
+/// Re-home each missing `(id, message_id)` whose Message-ID was `found` in
+/// another folder as `(folder, uid, message_id)`, and delete the rest.
+/// Returns the number re-homed and the ids deleted.
+fn settle_vanished(
+    pool: &DbPool,
+    missing: Vec<(String, String)>,
+    found: &[(String, u32, String)],
+) -> Result<(usize, Vec<String>), EddieError> {
+    let mut rehomed = 0;
+    let mut removed_ids = Vec::new();
+    for (id, message_id) in missing {
+        match found.iter().find(|(_, _, found_id)| !message_id.is_empty() && *found_id == message_id) {
+            Some((other, new_uid, _)) => {
+                sqlite::messages::rehome_message(pool, &id, other, *new_uid)?;
+                rehomed += 1;
+            }
+            None => removed_ids.push(id),
+        }
+    }
+
+    sqlite::messages::delete_by_ids(pool, &removed_ids)?;
+    Ok((rehomed, removed_ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::test_support;
+
+    #[test]
+    fn test_settle_vanished_rehomes_found_and_deletes_the_rest() {
+        let pool = test_support::pool();
+        pool.get().unwrap().execute_batch("
+            INSERT INTO messages (id, account_id, message_id, imap_uid, imap_folder, date, from_address,
+                                  to_addresses, fetched_at, participant_key, conversation_id)
+            VALUES ('moved', 'acc1', 'moved@x', 1, 'INBOX', 1, 'a@x', '[]', 0, 'a', 'c'),
+                   ('gone', 'acc1', 'gone@x', 2, 'INBOX', 2, 'a@x', '[]', 0, 'a', 'c'),
+                   ('no-id', 'acc1', '', 3, 'INBOX', 3, 'a@x', '[]', 0, 'a', 'c'),
+                   ('archived', 'acc1', 'archived@x', 4, 'Archive', 4, 'a@x', '[]', 0, 'a', 'c');
+            INSERT INTO attachments (message_id, part_path, mime_type, size_bytes, transfer_encoding)
+            VALUES ('gone', '2', 'text/plain', 1, '7bit');
+        ").unwrap();
+
+        let missing = sqlite::messages::get_ids_for_uids(&pool, "acc1", "INBOX", &[1, 2, 3]).unwrap();
+        let found = vec![
+            ("Archive".to_string(), 7, "moved@x".to_string()),
+            ("Archive".to_string(), 8, "".to_string()),
+        ];
+        let (rehomed, removed) = settle_vanished(&pool, missing, &found).unwrap();
+        assert_eq!(rehomed, 1);
+        assert_eq!(removed, vec!["gone", "no-id"]);
+
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT id, imap_folder, imap_uid FROM messages ORDER BY id").unwrap();
+        let rows: Vec<(String, String, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![
+            ("archived".to_string(), "Archive".to_string(), 4),
+            ("moved".to_string(), "Archive".to_string(), 7),
+        ]);
+        let attachments: i64 = conn
+            .query_row("SELECT COUNT(*) FROM attachments", [], |row| row.get(0)).unwrap();
+        assert_eq!(attachments, 0);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::adapters::sqlite;
 use crate::adapters::sqlite::DbPool;
 use crate::adapters::imap::{folders, historical};
+use crate::adapters::imap::connection::{ImapConnection, ModSeqSupport};
+use async_imap::types::Fetch;
 use crate::services::sync::worker;
+use super::expunge_reconcile;
 use crate::error::EddieError;
 
 use crate::services::logger;
@@ -24,19 +27,22 @@ pub async fn run_flag_resync_all(
 }
 
 /// Fetch current flags (and Gmail labels) from IMAP for all locally-cached messages
-/// and update any that changed. Rebuilds conversations once at the end if anything changed.
+/// and update any that changed. Also removes (or re-homes) messages expunged on the
+/// server. Updates conversations once at the end if anything changed.
 pub async fn run_flag_resync(
     app: &tauri::AppHandle,
     pool: &DbPool,
     account_id: &str,
 ) -> Result<(), EddieError> {
     let (_creds, _self_emails, mut conn) = worker::connect_account(pool, account_id).await?;
-    let is_gmail = conn.has_gmail_ext;
+    let is_gmail = conn.caps.gmail_ext;
 
     let folder_list = folders::list_folders(&mut conn.session).await?;
     let sync_folders = folders::folders_to_sync(&folder_list, is_gmail);
 
     let mut any_changed = false;
+    let mut vanished: Vec<(String, Vec<u32>)> = Vec::new();
+    let mut seen: Vec<FolderSeen> = Vec::new();
 
     for folder_info in &sync_folders {
         let state = match sqlite::folder_sync::get_folder(pool, account_id, &folder_info.name)? {
@@ -48,159 +54,296 @@ pub async fn run_flag_resync(
             continue; // never synced
         }
 
-        let folder_start = std::time::Instant::now();
+        let mailbox = worker::select_folder(&mut conn, pool, account_id, &folder_info.name).await?;
+        // A UIDVALIDITY change during SELECT drops the modseq baseline
+        let state = sqlite::folder_sync::get_folder(pool, account_id, &folder_info.name)?.unwrap_or(state);
+        let server_modseq = mailbox.highest_modseq
+            .filter(|_| conn.modseq != ModSeqSupport::None);
+
+        let changed = match server_modseq {
+            // Nothing in the folder changed since the last resync
+            Some(modseq) if modseq == state.highest_modseq => false,
+            Some(_) if state.highest_modseq > 0 => {
+                let (changed, gone) = resync_changed_since(
+                    &mut conn, pool, account_id, &folder_info.name, state.highest_modseq
+                ).await?;
+                if !gone.is_empty() {
+                    vanished.push((folder_info.name.clone(), gone));
+                }
+                changed
+            }
+            // No CONDSTORE, or no baseline yet — scan every cached UID
+            _ => resync_all(&mut conn, pool, account_id, &folder_info.name).await?,
+        };
+        any_changed |= changed;
+
+        // Without a QRESYNC baseline there are no VANISHED UIDs — diff the
+        // cached UIDs against the server instead
+        let mut reconciled = None;
+        if conn.modseq != ModSeqSupport::Qresync || state.highest_modseq == 0 {
+            let gone = expunge_reconcile::find_expunged(
+                &mut conn, pool, account_id, &folder_info.name, &mailbox, &state
+            ).await?;
+            if !gone.is_empty() {
+                vanished.push((folder_info.name.clone(), gone));
+            }
+            reconciled = Some((mailbox.exists, mailbox.uid_next.unwrap_or(0)));
+        }
 
-        conn.select_folder(&folder_info.name).await?;
+        seen.push(FolderSeen { folder: folder_info.name.clone(), highest_modseq: server_modseq, reconciled });
+    }
 
-        let mut total_changed: usize = 0;
+    for (folder, uids) in &vanished {
+        expunge_reconcile::remove_vanished(app, pool, account_id, &mut conn, &sync_folders, folder, uids).await?;
+    }
+    conn.release();
+    for s in &seen {
+        if let Some(modseq) = s.highest_modseq {
+            sqlite::folder_sync::set_highest_modseq(pool, account_id, &s.folder, modseq)?;
+        }
+        if let Some((exists, uid_next)) = s.reconciled {
+            sqlite::folder_sync::set_reconciled(pool, account_id, &s.folder, exists, uid_next)?;
+        }
+    }
+    expunge_reconcile::confirm_moves(pool, account_id, is_gmail)?;
+
+    // Confirm completed flag changes — server flags (and Gmail labels, which
+    // archive and move change there) are now up to date
+    let confirmed: &[&str] = if is_gmail { &["mark_read", "flag", "archive", "move"] } else { &["mark_read", "flag"] };
+    for action_type in confirmed {
+        for (action_id, _payload) in sqlite::action_queue::get_completed_by_type(pool, account_id, action_type)? {
+            sqlite::action_queue::mark_done(pool, &action_id)?;
+            logger::debug(&format!("{} action {} confirmed by flag resync", action_type, action_id));
+        }
+    }
 
-        if is_gmail {
-            // Gmail: resync both FLAGS and X-GM-LABELS
-            let local = sqlite::messages::get_uids_flags_and_labels_for_folder(
-                pool, account_id, &folder_info.name
-            )?;
-            if local.is_empty() {
-                continue;
-            }
+    // Update conversations once at the end if anything changed
+    if any_changed {
+        let conv_count = sqlite::conversations::update_conversations(pool, account_id)?;
+        crate::services::sync::helpers::status_emit::emit_conversations_updated(app, account_id, conv_count);
+    }
+
+    Ok(())
+}
+
+/// What a resync saw of a folder on the server, recorded once the folder's
+/// vanished UIDs are removed so that a failed removal is retried.
+struct FolderSeen {
+    folder: String,
+    highest_modseq: Option<u64>,
+    /// EXISTS and UIDNEXT, if expunges were found by `find_expunged`
+    reconciled: Option<(u32, u32)>,
+}
 
-            let total_messages = local.len();
-            let local_lookup: HashMap<u32, (&str, &str)> = local.iter()
-                .map(|(uid, flags, labels)| (*uid, (flags.as_str(), labels.as_str())))
-                .collect();
-
-            for batch in local.chunks(BATCH_SIZE) {
-                let uid_list: String = batch.iter()
-                    .map(|(uid, _, _)| uid.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                let fetches = historical::collect_tolerant(
-                    conn.session
-                        .uid_fetch(&uid_list, "(UID FLAGS X-GM-LABELS)")
-                        .await
-                        .map_err(|e| EddieError::Backend(format!("FETCH flags failed: {}", e)))?,
-                    &format!("flags+labels in {}", folder_info.name),
-                ).await;
-
-                let mut updates: Vec<(u32, String, String)> = Vec::new();
-
-                for fetch in &fetches {
-                    if let Some(uid) = fetch.uid {
-                        let mut new_flags: Vec<String> = fetch.flags()
-                            .map(|f| format!("{:?}", f))
-                            .collect();
-                        new_flags.sort();
-                        let new_flags_json = serde_json::to_string(&new_flags)
-                            .unwrap_or_else(|_| "[]".to_string());
-
-                        let mut new_labels: Vec<String> = fetch.gmail_labels()
-                            .map(|labels| labels.iter()
-                                .map(|l| l.trim_start_matches('\\').to_string())
-                                .collect())
-                            .unwrap_or_default();
-                        new_labels.sort();
-                        let new_labels_json = serde_json::to_string(&new_labels)
-                            .unwrap_or_else(|_| "[]".to_string());
-
-                        if let Some(&(old_flags, old_labels)) = local_lookup.get(&uid) {
-                            if old_flags != new_flags_json || old_labels != new_labels_json {
-                                updates.push((uid, new_flags_json, new_labels_json));
-                            }
+/// Full scan: re-fetch FLAGS (and Gmail labels) for every cached UID in the
+/// selected folder. Returns true if any message changed.
+async fn resync_all(
+    conn: &mut ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+) -> Result<bool, EddieError> {
+    let is_gmail = conn.caps.gmail_ext;
+    let folder_start = std::time::Instant::now();
+
+    let mut total_changed: usize = 0;
+
+    if is_gmail {
+        // Gmail: resync both FLAGS and X-GM-LABELS
+        let local = sqlite::messages::get_uids_flags_and_labels_for_folder(
+            pool, account_id, folder
+        )?;
+        if local.is_empty() {
+            return Ok(false);
+        }
+
+        let total_messages = local.len();
+        let local_lookup: HashMap<u32, (&str, &str)> = local.iter()
+            .map(|(uid, flags, labels)| (*uid, (flags.as_str(), labels.as_str())))
+            .collect();
+
+        for batch in local.chunks(BATCH_SIZE) {
+            let uid_list: String = batch.iter()
+                .map(|(uid, _, _)| uid.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let fetches = historical::collect_tolerant(
+                conn.session
+                    .uid_fetch(&uid_list, "(UID FLAGS X-GM-LABELS)")
+                    .await
+                    .map_err(|e| EddieError::Backend(format!("FETCH flags failed: {}", e)))?,
+                &format!("flags+labels in {}", folder),
+            ).await;
+
+            let mut updates: Vec<(u32, String, String)> = Vec::new();
+
+            for fetch in &fetches {
+                if let Some(uid) = fetch.uid {
+                    let new_flags_json = flags_json(fetch, true);
+                    let new_labels_json = labels_json(fetch);
+
+                    if let Some(&(old_flags, old_labels)) = local_lookup.get(&uid) {
+                        if old_flags != new_flags_json || old_labels != new_labels_json {
+                            updates.push((uid, new_flags_json, new_labels_json));
                         }
                     }
                 }
-
-                if !updates.is_empty() {
-                    total_changed += updates.len();
-                    sqlite::messages::update_flags_and_labels_batch(
-                        pool, account_id, &folder_info.name, &updates
-                    )?;
-                }
             }
 
-            if total_changed > 0 {
-                any_changed = true;
-                logger::info(&format!(
-                    "Flag resync for {}: {} changed out of {} messages in {}",
-                    folder_info.name, total_changed, total_messages, logger::fmt_ms(folder_start.elapsed())
-                ));
-            }
-        } else {
-            // Non-Gmail: resync FLAGS only
-            let local = sqlite::messages::get_uids_and_flags_for_folder(
-                pool, account_id, &folder_info.name
-            )?;
-            if local.is_empty() {
-                continue;
+            if !updates.is_empty() {
+                total_changed += updates.len();
+                sqlite::messages::update_flags_and_labels_batch(
+                    pool, account_id, folder, &updates
+                )?;
             }
+        }
+
+        if total_changed > 0 {
+            logger::info(&format!(
+                "Flag resync for {}: {} changed out of {} messages in {}",
+                folder, total_changed, total_messages, logger::fmt_ms(folder_start.elapsed())
+            ));
+        }
+    } else {
+        // Non-Gmail: resync FLAGS only
+        let local = sqlite::messages::get_uids_and_flags_for_folder(
+            pool, account_id, folder
+        )?;
+        if local.is_empty() {
+            return Ok(false);
+        }
 
-            let total_messages = local.len();
-            let local_flags: HashMap<u32, &str> = local.iter()
-                .map(|(uid, flags)| (*uid, flags.as_str()))
-                .collect();
-
-            for batch in local.chunks(BATCH_SIZE) {
-                let uid_list: String = batch.iter()
-                    .map(|(uid, _)| uid.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                let fetches = historical::collect_tolerant(
-                    conn.session
-                        .uid_fetch(&uid_list, "(UID FLAGS)")
-                        .await
-                        .map_err(|e| EddieError::Backend(format!("FETCH flags failed: {}", e)))?,
-                    &format!("flags in {}", folder_info.name),
-                ).await;
-
-                let mut updates: Vec<(u32, String)> = Vec::new();
-
-                for fetch in &fetches {
-                    if let Some(uid) = fetch.uid {
-                        let new_flags: Vec<String> = fetch.flags()
-                            .map(|f| format!("{:?}", f))
-                            .collect();
-                        let new_flags_json = serde_json::to_string(&new_flags)
-                            .unwrap_or_else(|_| "[]".to_string());
-
-                        if let Some(&old_flags) = local_flags.get(&uid) {
-                            if old_flags != new_flags_json {
-                                updates.push((uid, new_flags_json));
-                            }
+        let total_messages = local.len();
+        let local_flags: HashMap<u32, &str> = local.iter()
+            .map(|(uid, flags)| (*uid, flags.as_str()))
+            .collect();
+
+        for batch in local.chunks(BATCH_SIZE) {
+            let uid_list: String = batch.iter()
+                .map(|(uid, _)| uid.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let fetches = historical::collect_tolerant(
+                conn.session
+                    .uid_fetch(&uid_list, "(UID FLAGS)")
+                    .await
+                    .map_err(|e| EddieError::Backend(format!("FETCH flags failed: {}", e)))?,
+                &format!("flags in {}", folder),
+            ).await;
+
+            let mut updates: Vec<(u32, String)> = Vec::new();
+
+            for fetch in &fetches {
+                if let Some(uid) = fetch.uid {
+                    let new_flags_json = flags_json(fetch, false);
+
+                    if let Some(&old_flags) = local_flags.get(&uid) {
+                        if old_flags != new_flags_json {
+                            updates.push((uid, new_flags_json));
                         }
                     }
                 }
+            }
 
-                if !updates.is_empty() {
-                    total_changed += updates.len();
-                    sqlite::messages::update_flags_batch(
-                        pool, account_id, &folder_info.name, &updates
-                    )?;
-                }
+            if !updates.is_empty() {
+                total_changed += updates.len();
+                sqlite::messages::update_flags_batch(
+                    pool, account_id, folder, &updates
+                )?;
             }
+        }
+
+        if total_changed > 0 {
+            logger::info(&format!(
+                "Flag resync for {}: {} changed out of {} messages in {}",
+                folder, total_changed, total_messages, logger::fmt_ms(folder_start.elapsed())
+            ));
+        }
+    }
+
+    Ok(total_changed > 0)
+}
+
+/// CONDSTORE path: fetch only messages whose MODSEQ moved past `since`.
+/// Returns whether any message changed, plus the UIDs the server reported
+/// as VANISHED (QRESYNC only) for the caller to remove.
+async fn resync_changed_since(
+    conn: &mut ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    since: u64,
+) -> Result<(bool, Vec<u32>), EddieError> {
+    let is_gmail = conn.caps.gmail_ext;
+    let folder_start = std::time::Instant::now();
 
-            if total_changed > 0 {
-                any_changed = true;
-                logger::info(&format!(
-                    "Flag resync for {}: {} changed out of {} messages in {}",
-                    folder_info.name, total_changed, total_messages, logger::fmt_ms(folder_start.elapsed())
-                ));
+    let items = if is_gmail { "(UID FLAGS X-GM-LABELS)" } else { "(UID FLAGS)" };
+    let (fetches, vanished) = conn.fetch_changed_since(items, since).await?;
+
+    let mut total_changed: usize = 0;
+
+    if !fetches.is_empty() {
+        let local = sqlite::messages::get_uids_flags_and_labels_for_folder(pool, account_id, folder)?;
+        let local_lookup: HashMap<u32, (&str, &str)> = local.iter()
+            .map(|(uid, flags, labels)| (*uid, (flags.as_str(), labels.as_str())))
+            .collect();
+
+        if is_gmail {
+            let mut updates: Vec<(u32, String, String)> = Vec::new();
+            for fetch in &fetches {
+                let Some(uid) = fetch.uid else { continue };
+                let Some(&(old_flags, old_labels)) = local_lookup.get(&uid) else { continue };
+                let new_flags_json = flags_json(fetch, true);
+                let new_labels_json = labels_json(fetch);
+                if old_flags != new_flags_json || old_labels != new_labels_json {
+                    updates.push((uid, new_flags_json, new_labels_json));
+                }
+            }
+            total_changed += sqlite::messages::update_flags_and_labels_batch(pool, account_id, folder, &updates)?;
+        } else {
+            let mut updates: Vec<(u32, String)> = Vec::new();
+            for fetch in &fetches {
+                let Some(uid) = fetch.uid else { continue };
+                let Some(&(old_flags, _)) = local_lookup.get(&uid) else { continue };
+                let new_flags_json = flags_json(fetch, false);
+                if old_flags != new_flags_json {
+                    updates.push((uid, new_flags_json));
+                }
             }
+            total_changed += sqlite::messages::update_flags_batch(pool, account_id, folder, &updates)?;
         }
     }
 
-    // Confirm completed mark_read actions — server flags are now up to date
-    let completed_actions = sqlite::action_queue::get_completed_mark_read(pool, account_id)?;
-    for (action_id, _payload) in &completed_actions {
-        sqlite::action_queue::mark_done(pool, action_id)?;
-        logger::debug(&format!("mark_read action {} confirmed by flag resync", action_id));
+    if total_changed > 0 || !vanished.is_empty() {
+        logger::info(&format!(
+            "Flag resync for {} (CHANGEDSINCE {}): {} changed, {} vanished in {}",
+            folder, since, total_changed, vanished.len(), logger::fmt_ms(folder_start.elapsed())
+        ));
     }
 
-    // Rebuild conversations once at the end if anything changed
-    if any_changed {
-        let conv_count = sqlite::conversations::rebuild_conversations(pool, account_id)?;
-        crate::services::sync::helpers::status_emit::emit_conversations_updated(app, account_id, conv_count);
+    Ok((total_changed > 0, vanished))
+}
+
+/// FLAGS as a JSON array, in the form stored in `messages.imap_flags`.
+fn flags_json(fetch: &Fetch, sorted: bool) -> String {
+    let mut flags: Vec<String> = fetch.flags()
+        .map(|f| format!("{:?}", f))
+        .collect();
+    if sorted {
+        flags.sort();
     }
+    serde_json::to_string(&flags).unwrap_or_else(|_| "[]".to_string())
+}
 
-    Ok(())
+/// X-GM-LABELS as a sorted JSON array, in the form stored in `messages.gmail_labels`.
+fn labels_json(fetch: &Fetch) -> String {
+    let mut labels: Vec<String> = fetch.gmail_labels()
+        .map(|labels| labels.iter()
+            .map(|l| l.trim_start_matches('\\').to_string())
+            .collect())
+        .unwrap_or_default();
+    labels.sort();
+    serde_json::to_string(&labels).unwrap_or_else(|_| "[]".to_string())
 }
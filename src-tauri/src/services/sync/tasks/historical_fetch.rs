@@ -19,7 +19,7 @@ pub async fn run_historical_fetch(
     // Discover and seed folders first
     let (_creds, self_emails, mut conn) = worker::connect_account(pool, account_id).await?;
     let folder_list = folders::list_folders(&mut conn.session).await?;
-    let sync_folders = folders::folders_to_sync(&folder_list, conn.has_gmail_ext);
+    let sync_folders = folders::folders_to_sync(&folder_list, conn.caps.gmail_ext);
 
     for folder in &sync_folders {
         sqlite::folder_sync::ensure_folder(pool, account_id, &folder.name)?;
@@ -33,6 +33,7 @@ pub async fn run_historical_fetch(
         None => {
             logger::debug("Historical fetch: all folders done");
             onboarding_tasks::mark_task_done(pool, account_id, &task.name)?;
+            conn.release();
             return Ok(());
         }
     };
@@ -48,8 +49,10 @@ pub async fn run_historical_fetch(
     let local_count = sqlite::messages::get_uids_for_folder(pool, account_id, &folder.name)?
         .len();
 
-    let mailbox = conn.select_folder(&folder.name).await?;
+    let mailbox = worker::select_folder(&mut conn, pool, account_id, &folder.name).await?;
     let server_count = mailbox.exists;
+    // A UIDVALIDITY change during SELECT resets the cursor
+    let folder = sqlite::folder_sync::get_folder(pool, account_id, &folder.name)?.unwrap_or(folder);
 
     helpers::status_emit::emit_status(app, "historical_fetch",
         &format!("{}/{} from {} ingested", local_count, server_count, folder.name));
@@ -61,6 +64,7 @@ pub async fn run_historical_fetch(
     };
 
     let fetch_start = std::time::Instant::now();
+    let mut reports = Vec::new();
     let total = historical::fetch_historical(
         &mut conn,
         &folder.name,
@@ -74,6 +78,7 @@ pub async fn run_historical_fetch(
             );
             sqlite::messages::insert_messages(pool, &messages)
                 .map_err(|e| e.to_string())?;
+            reports.extend(helpers::delivery_reports::pending_reports(&envelopes));
 
             for (uid, text, is_html) in &bodies {
                 if *is_html {
@@ -110,6 +115,12 @@ pub async fn run_historical_fetch(
         },
     ).await?;
 
+    // The folder is still selected, so read the batch's bounces now
+    if !reports.is_empty() {
+        helpers::delivery_reports::process_reports(&mut conn, pool, account_id, &reports).await;
+    }
+    conn.release();
+
     logger::debug(&format!(
         "Historical fetch: {} fetched {} messages in {}",
         folder.name, total, logger::fmt_ms(fetch_start.elapsed())
@@ -1,6 +1,7 @@
 use crate::adapters::sqlite;
 use crate::adapters::sqlite::DbPool;
 use crate::adapters::imap::{envelopes, folders, historical};
+use crate::adapters::imap::connection::ImapConnection;
 use crate::services::sync::{helpers, worker};
 use crate::services::sync::helpers::message_classification::ClassifierState;
 use crate::error::EddieError;
@@ -37,7 +38,7 @@ pub async fn run_incremental_sync(
     let (_creds, self_emails, mut conn) = worker::connect_account(pool, account_id).await?;
 
     let folder_list = folders::list_folders(&mut conn.session).await?;
-    let sync_folders = folders::folders_to_sync(&folder_list, conn.has_gmail_ext);
+    let sync_folders = folders::folders_to_sync(&folder_list, conn.caps.gmail_ext);
 
     let mut total_new = 0;
 
@@ -51,7 +52,9 @@ pub async fn run_incremental_sync(
             continue; // never synced
         }
 
-        conn.select_folder(&folder_info.name).await?;
+        worker::select_folder(&mut conn, pool, account_id, &folder_info.name).await?;
+        // A UIDVALIDITY change during SELECT resets the cursor
+        let state = sqlite::folder_sync::get_folder(pool, account_id, &folder_info.name)?.unwrap_or(state);
 
         let search_query = format!("UID {}:*", state.highest_uid + 1);
         let uid_set = conn.session
@@ -70,158 +73,189 @@ pub async fn run_incremental_sync(
         let folder_start = std::time::Instant::now();
         logger::info(&format!("Found {} new messages in {}", new_uids.len(), folder_info.name));
 
-        let uid_list: String = new_uids.iter()
-            .map(|u| u.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+        fetch_messages(&mut conn, pool, account_id, &folder_info.name, &new_uids, &self_emails).await?;
 
-        // Fetch envelopes + bodystructure
-        let fetch_query = if conn.has_gmail_ext {
-            "(UID FLAGS ENVELOPE BODYSTRUCTURE X-GM-LABELS)"
-        } else {
-            "(UID FLAGS ENVELOPE BODYSTRUCTURE)"
-        };
+        // Update highest_uid
+        if let Some(&max_uid) = new_uids.iter().max() {
+            sqlite::folder_sync::update_highest_uid(pool, account_id, &folder_info.name, max_uid)?;
+        }
 
-        let fetches = historical::collect_tolerant(
-            conn.session
-                .uid_fetch(&uid_list, fetch_query)
-                .await
-                .map_err(|e| EddieError::Backend(format!("FETCH failed: {}", e)))?,
-            &format!("envelopes in {}", folder_info.name),
-        ).await;
+        logger::debug(&format!(
+            "Synced {} messages from {} in {}",
+            new_uids.len(), folder_info.name, logger::fmt_ms(folder_start.elapsed())
+        ));
 
-        let mut envelopes: Vec<envelopes::Envelope> = Vec::new();
-        let mut text_parts: Vec<(u32, Vec<u32>, bool, String)> = Vec::new();
+        total_new += new_uids.len();
+    }
+    conn.release();
 
-        for fetch in &fetches {
-            if let Some(env) = envelopes::parse_envelope(fetch) {
-                envelopes.push(env);
-            }
-            if let (Some(uid), Some(bs)) = (fetch.uid, fetch.bodystructure()) {
-                if let Some((part, encoding)) = historical::find_mime_part(bs, &[], "plain") {
-                    text_parts.push((uid, part, false, historical::encoding_to_string(encoding)));
-                } else if let Some((part, encoding)) = historical::find_mime_part(bs, &[], "html") {
-                    text_parts.push((uid, part, true, historical::encoding_to_string(encoding)));
-                }
+    if total_new > 0 {
+        worker::process_changes(app, pool, account_id, classifier)?;
+    }
+
+    Ok(total_new > 0)
+}
+
+/// Fetch the given UIDs of the selected folder and store them: envelopes,
+/// threading and classification headers, text bodies and bounce reports.
+/// Sends waiting for the server's copy are confirmed as their mail arrives.
+pub(crate) async fn fetch_messages(
+    conn: &mut ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+    uids: &[u32],
+    self_emails: &[String],
+) -> Result<(), EddieError> {
+    let uid_list: String = uids.iter()
+        .map(|u| u.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    // Fetch envelopes + bodystructure
+    let fetch_query = if conn.caps.gmail_ext {
+        "(UID FLAGS ENVELOPE BODYSTRUCTURE X-GM-LABELS)"
+    } else {
+        "(UID FLAGS ENVELOPE BODYSTRUCTURE)"
+    };
+
+    let fetches = historical::collect_tolerant(
+        conn.session
+            .uid_fetch(&uid_list, fetch_query)
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH failed: {}", e)))?,
+        &format!("envelopes in {}", folder),
+    ).await;
+
+    let mut envelopes: Vec<envelopes::Envelope> = Vec::new();
+    let mut text_parts: Vec<historical::TextPart> = Vec::new();
+
+    for fetch in &fetches {
+        if let Some(env) = envelopes::parse_envelope(fetch) {
+            envelopes.push(env);
+        }
+        if let (Some(uid), Some(bs)) = (fetch.uid, fetch.bodystructure()) {
+            if let Some((part, encoding, charset)) = historical::find_mime_part(bs, &[], "plain") {
+                text_parts.push((uid, part, false, historical::encoding_to_string(encoding), charset));
+            } else if let Some((part, encoding, charset)) = historical::find_mime_part(bs, &[], "html") {
+                text_parts.push((uid, part, true, historical::encoding_to_string(encoding), charset));
             }
         }
+    }
 
-        // Fetch references + classification headers
-        let refs_fetches = historical::collect_tolerant(
-            conn.session
-                .uid_fetch(&uid_list, "(UID BODY.PEEK[HEADER.FIELDS (References List-Id Auto-Submitted List-Unsubscribe Precedence Feedback-ID X-Mailer Return-Path)])")
-                .await
-                .map_err(|e| EddieError::Backend(format!("FETCH refs failed: {}", e)))?,
-            &format!("references in {}", folder_info.name),
-        ).await;
-
-        for fetch in &refs_fetches {
-            if let Some(uid) = fetch.uid {
-                let raw = fetch.header().unwrap_or(&[]);
-                let header_text = String::from_utf8_lossy(raw);
-                let refs = envelopes::parse_references_value(&header_text);
-                let cls_headers = envelopes::parse_classification_headers(raw);
-                if let Some(env) = envelopes.iter_mut().find(|e| e.uid == uid) {
-                    env.references = refs;
-                    env.classification_headers = cls_headers;
-                }
+    // Fetch references + classification headers
+    let refs_fetches = historical::collect_tolerant(
+        conn.session
+            .uid_fetch(&uid_list, "(UID BODY.PEEK[HEADER.FIELDS (References List-Id Auto-Submitted List-Unsubscribe Precedence Feedback-ID X-Mailer Return-Path Reply-To Mail-Followup-To)])")
+            .await
+            .map_err(|e| EddieError::Backend(format!("FETCH refs failed: {}", e)))?,
+        &format!("references in {}", folder),
+    ).await;
+
+    for fetch in &refs_fetches {
+        if let Some(uid) = fetch.uid {
+            let raw = fetch.header().unwrap_or(&[]);
+            let header_text = String::from_utf8_lossy(raw);
+            let refs = envelopes::parse_references_value(&header_text);
+            let cls_headers = envelopes::parse_classification_headers(raw);
+            if let Some(env) = envelopes.iter_mut().find(|e| e.uid == uid) {
+                env.references = refs;
+                env.classification_headers = cls_headers;
             }
         }
+    }
 
-        // Fetch bodies
-        let mut bodies: Vec<(u32, String, bool)> = Vec::new();
-        let mut uid_is_html: HashMap<u32, bool> = HashMap::new();
-        let mut uid_encoding: HashMap<u32, String> = HashMap::new();
-
-        if !text_parts.is_empty() {
-            let mut by_part: HashMap<Vec<u32>, Vec<u32>> = HashMap::new();
-            for (uid, part, is_html, encoding) in &text_parts {
-                by_part.entry(part.clone()).or_default().push(*uid);
-                uid_is_html.insert(*uid, *is_html);
-                uid_encoding.insert(*uid, encoding.clone());
+    // Fetch bodies
+    let mut bodies: Vec<(u32, String, bool)> = Vec::new();
+    let mut uid_is_html: HashMap<u32, bool> = HashMap::new();
+    let mut uid_encoding: HashMap<u32, String> = HashMap::new();
+    let mut uid_charset: HashMap<u32, String> = HashMap::new();
+
+    if !text_parts.is_empty() {
+        let mut by_part: HashMap<Vec<u32>, Vec<u32>> = HashMap::new();
+        for (uid, part, is_html, encoding, charset) in &text_parts {
+            by_part.entry(part.clone()).or_default().push(*uid);
+            uid_is_html.insert(*uid, *is_html);
+            uid_encoding.insert(*uid, encoding.clone());
+            if let Some(charset) = charset {
+                uid_charset.insert(*uid, charset.clone());
             }
+        }
 
-            for (part, part_uids) in &by_part {
-                let part_uid_list: String = part_uids.iter()
-                    .map(|u| u.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                let body_query = format!("(UID BODY.PEEK[{}])", historical::part_to_string(part));
-
-                let body_fetches = historical::collect_tolerant(
-                    conn.session
-                        .uid_fetch(&part_uid_list, &body_query)
-                        .await
-                        .map_err(|e| EddieError::Backend(format!("FETCH body failed: {}", e)))?,
-                    &format!("bodies in {}", folder_info.name),
-                ).await;
-
-                let path = historical::part_to_section_path(part);
-
-                for fetch in &body_fetches {
-                    if let Some(uid) = fetch.uid {
-                        if let Some(section_data) = fetch.section(&path) {
-                            let encoding = uid_encoding.get(&uid).cloned().unwrap_or_default();
-                            let decoded = historical::decode_body(section_data, &encoding)?;
-                            let is_html = uid_is_html.get(&uid).copied().unwrap_or(false);
-                            bodies.push((uid, decoded, is_html));
-                        }
+        for (part, part_uids) in &by_part {
+            let part_uid_list: String = part_uids.iter()
+                .map(|u| u.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let body_query = format!("(UID BODY.PEEK[{}])", historical::part_to_string(part));
+
+            let body_fetches = historical::collect_tolerant(
+                conn.session
+                    .uid_fetch(&part_uid_list, &body_query)
+                    .await
+                    .map_err(|e| EddieError::Backend(format!("FETCH body failed: {}", e)))?,
+                &format!("bodies in {}", folder),
+            ).await;
+
+            let path = historical::part_to_section_path(part);
+
+            for fetch in &body_fetches {
+                if let Some(uid) = fetch.uid {
+                    if let Some(section_data) = fetch.section(&path) {
+                        let encoding = uid_encoding.get(&uid).cloned().unwrap_or_default();
+                        let charset = uid_charset.get(&uid).map(String::as_str);
+                        let decoded = historical::decode_body(section_data, &encoding, charset)?;
+                        let is_html = uid_is_html.get(&uid).copied().unwrap_or(false);
+                        bodies.push((uid, decoded, is_html));
                     }
                 }
             }
         }
+    }
 
-        // Insert messages
-        let messages = helpers::message_builder::prepare_messages(
-            account_id, &folder_info.name, &envelopes, &self_emails,
-        );
-        sqlite::messages::insert_messages(pool, &messages)?;
-
-        // Confirm completed send actions whose message_id was just synced from server
-        for msg in &messages {
-            if !msg.message_id.is_empty() {
-                let action_ids = sqlite::action_queue::get_completed_by_message_id(pool, account_id, &msg.message_id)?;
-                for action_id in &action_ids {
-                    sqlite::action_queue::mark_done(pool, action_id)?;
-                    logger::debug(&format!("Send action {} confirmed by server (message_id={})", action_id, msg.message_id));
+    // Insert messages
+    let messages = helpers::message_builder::prepare_messages(
+        account_id, folder, &envelopes, self_emails,
+    );
+    sqlite::messages::insert_messages(pool, &messages)?;
+
+    // Confirm completed send actions whose message_id was just synced from server
+    for msg in &messages {
+        if !msg.message_id.is_empty() {
+            let action_ids = sqlite::action_queue::get_completed_by_message_id(pool, account_id, &msg.message_id)?;
+            for action_id in &action_ids {
+                sqlite::action_queue::mark_done(pool, action_id)?;
+                if let Some(action) = sqlite::action_queue::get_action(pool, action_id)? {
+                    let payload = serde_json::from_str(&action.payload).unwrap_or_default();
+                    crate::services::attachments::remove_outgoing(&payload);
                 }
+                logger::debug(&format!("Send action {} confirmed by server (message_id={})", action_id, msg.message_id));
             }
         }
+    }
 
-        for (uid, text, is_html) in &bodies {
-            if *is_html {
-                let _ = sqlite::messages::update_body_html_by_uid(pool, account_id, *uid, text);
-            }
-            let clean_text = if *is_html {
-                html2text::from_read(text.as_bytes(), 80)
-                    .unwrap_or_else(|_| text.clone())
-            } else {
-                text.clone()
-            };
-            if let Err(e) = sqlite::messages::update_body_by_uid(
-                pool, account_id, *uid, &clean_text
-            ) {
-                logger::warn(&format!("Failed to store body for UID {}: {}", uid, e));
-            }
+    for (uid, text, is_html) in &bodies {
+        if *is_html {
+            let _ = sqlite::messages::update_body_html_by_uid(pool, account_id, *uid, text);
         }
-
-        // Update highest_uid
-        if let Some(&max_uid) = new_uids.iter().max() {
-            sqlite::folder_sync::update_highest_uid(pool, account_id, &folder_info.name, max_uid)?;
+        let clean_text = if *is_html {
+            html2text::from_read(text.as_bytes(), 80)
+                .unwrap_or_else(|_| text.clone())
+        } else {
+            text.clone()
+        };
+        if let Err(e) = sqlite::messages::update_body_by_uid(
+            pool, account_id, *uid, &clean_text
+        ) {
+            logger::warn(&format!("Failed to store body for UID {}: {}", uid, e));
         }
-
-        logger::debug(&format!(
-            "Synced {} messages from {} in {}",
-            new_uids.len(), folder_info.name, logger::fmt_ms(folder_start.elapsed())
-        ));
-
-        total_new += new_uids.len();
     }
 
-    if total_new > 0 {
-        worker::process_changes(app, pool, account_id, classifier)?;
+    // Match bounces to the sent messages they report on
+    let reports = helpers::delivery_reports::pending_reports(&envelopes);
+    if !reports.is_empty() {
+        helpers::delivery_reports::process_reports(conn, pool, account_id, &reports).await;
     }
-
-    Ok(total_new > 0)
+    Ok(())
 }
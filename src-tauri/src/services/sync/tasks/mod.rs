@@ -1,5 +1,6 @@
 mod action_replay;
 mod connection_history;
+mod expunge_reconcile;
 mod flag_resync;
 mod historical_fetch;
 mod incremental_sync;
@@ -10,4 +11,5 @@ pub use connection_history::run_connection_history;
 pub use flag_resync::run_flag_resync_all;
 pub use historical_fetch::run_historical_fetch;
 pub use incremental_sync::run_incremental_sync_all;
+pub(crate) use incremental_sync::fetch_messages;
 pub use trust_network::run_trust_network;
@@ -52,10 +52,11 @@ pub async fn run_trust_network(
         }
         None => {
             // Tier 3: no Sent folder found — scan syncable folders for FROM user messages
-            let sync_folders = folders::folders_to_sync(&folder_list, conn.has_gmail_ext);
+            let sync_folders = folders::folders_to_sync(&folder_list, conn.caps.gmail_ext);
             if sync_folders.is_empty() {
                 logger::info("No syncable folders found, skipping trust network task");
                 onboarding_tasks::mark_task_done(pool, account_id, &task.name)?;
+                conn.release();
                 return Ok(());
             }
 
@@ -64,10 +65,14 @@ pub async fn run_trust_network(
                 "No Sent folder found, scanning {} folders for FROM-user messages",
                 sync_folders.len()
             ));
-            return scan_folders_for_sent(
+            let result = scan_folders_for_sent(
                 app, pool, account_id, task, &creds.email, &self_emails,
                 &mut conn, &sync_folders, classifier,
             ).await;
+            if result.is_ok() {
+                conn.release();
+            }
+            return result;
         }
     };
 
@@ -80,6 +85,7 @@ pub async fn run_trust_network(
     let start = std::time::Instant::now();
     let (recipient_counts, max_uid, remaining) =
         fetch_sent_recipients_batch(&mut conn, 500, above_uid, from_filter.as_deref()).await?;
+    conn.release();
     logger::debug(&format!("fetch_sent_recipients_batch took: {}", logger::fmt_ms(start.elapsed())));
 
     let scanned = server_count as usize - remaining;
@@ -1,7 +1,8 @@
 use crate::adapters::sqlite;
 use crate::adapters::sqlite::{accounts, onboarding_tasks, DbPool};
-use crate::adapters::imap::connection;
-use crate::services::sync::helpers;
+use crate::adapters::imap::{connection, historical};
+use async_imap::types::Mailbox;
+use crate::services::sync::{connection_manager, helpers};
 use crate::services::sync::helpers::message_classification::ClassifierState;
 use crate::services::sync::tasks;
 use crate::error::EddieError;
@@ -114,24 +115,89 @@ pub async fn tick(
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Borrow a pooled IMAP session for the account, along with its credentials
+/// and self emails. `release` it once done to return it to the pool.
 pub(crate) async fn connect_account(
     pool: &DbPool,
     account_id: &str,
-) -> Result<(accounts::Credentials, Vec<String>, connection::ImapConnection), EddieError> {
+) -> Result<(accounts::Credentials, Vec<String>, connection_manager::PooledConnection), EddieError> {
     let creds = sqlite::accounts::get_credentials(pool, account_id)?
         .ok_or(EddieError::AccountNotFound(account_id.to_string()))?;
 
-    let write_mode = sqlite::settings::get_setting(pool, "write_mode")?
-        .map(|v| v == "true")
-        .unwrap_or(false);
-
-    let conn = connection::connect_with_tls(&creds.host, creds.port, creds.tls, &creds.email, &creds.password, write_mode).await?;
+    let conn = connection_manager::checkout(pool, account_id).await?;
 
     let self_emails = sqlite::entities::get_self_emails(pool, account_id)?;
 
     Ok((creds, self_emails, conn))
 }
 
+/// SELECT a folder and compare its UIDVALIDITY with the one stored in `folder_sync`.
+/// If the server rebuilt the mailbox, cached messages are re-mapped to their new
+/// UIDs by Message-ID before any stored UID is used again.
+pub(crate) async fn select_folder(
+    conn: &mut connection::ImapConnection,
+    pool: &DbPool,
+    account_id: &str,
+    folder: &str,
+) -> Result<Mailbox, EddieError> {
+    let mailbox = conn.select_folder(folder).await?;
+
+    let (Some(server_validity), Some(state)) = (
+        mailbox.uid_validity,
+        sqlite::folder_sync::get_folder(pool, account_id, folder)?,
+    ) else {
+        return Ok(mailbox);
+    };
+
+    if state.uid_validity == server_validity {
+        return Ok(mailbox);
+    }
+    if state.uid_validity == 0 {
+        // First SELECT since the column was introduced — just record it
+        sqlite::folder_sync::set_uid_validity(pool, account_id, folder, server_validity)?;
+        return Ok(mailbox);
+    }
+
+    logger::warn(&format!(
+        "UIDVALIDITY of {} changed ({} → {}), re-mapping cached messages",
+        folder, state.uid_validity, server_validity
+    ));
+
+    let server_ids = if mailbox.exists > 0 {
+        historical::fetch_message_ids(conn, folder, "1:*").await?
+    } else {
+        Vec::new()
+    };
+    let (remapped, deleted) = sqlite::messages::remap_folder_uids(pool, account_id, folder, &server_ids)?;
+
+    let server_uids: Vec<u32> = server_ids.iter().map(|(uid, _)| *uid).collect();
+    let (lowest_uid, highest_uid, missing) = sqlite::folder_sync::remap_window(
+        &server_uids, &remapped, mailbox.uid_next.unwrap_or(1),
+    );
+
+    // Mail inside the window that nothing was re-mapped to is fetched now,
+    // since neither incremental nor historical sync will reach it. The new
+    // UIDVALIDITY is only recorded after, so a failed fetch is retried.
+    if !missing.is_empty() {
+        let self_emails = sqlite::entities::get_self_emails(pool, account_id)?;
+        for chunk in missing.chunks(200) {
+            tasks::fetch_messages(conn, pool, account_id, folder, chunk, &self_emails).await?;
+        }
+    }
+    sqlite::folder_sync::reset_uid_validity(pool, account_id, folder, server_validity, lowest_uid, highest_uid)?;
+
+    if deleted > 0 || !missing.is_empty() {
+        sqlite::conversations::update_conversations(pool, account_id)?;
+    }
+
+    logger::info(&format!(
+        "Re-mapped {} messages in {} after UIDVALIDITY change, {} no longer on server, {} fetched again",
+        remapped.len(), folder, deleted, missing.len()
+    ));
+
+    Ok(mailbox)
+}
+
 pub fn process_changes(
     app: &tauri::AppHandle,
     pool: &DbPool,
@@ -166,8 +232,8 @@ pub fn process_changes(
 
     helpers::status_emit::emit_status(app, "rebuilding", "Organizing conversations...");
     let start = std::time::Instant::now();
-    let conv_count = sqlite::conversations::rebuild_conversations(pool, account_id)?;
-    logger::debug(&format!("Rebuilt {} conversations in {}", conv_count, logger::fmt_ms(start.elapsed())));
+    let conv_count = sqlite::conversations::update_conversations(pool, account_id)?;
+    logger::debug(&format!("Updated {} conversations in {}", conv_count, logger::fmt_ms(start.elapsed())));
 
     helpers::status_emit::emit_conversations_updated(app, account_id, conv_count);
     Ok(())
@@ -1,8 +1,9 @@
 import { useState, useEffect, useRef, useCallback } from "react";
 import { createFileRoute, useRouter, useNavigate } from "@tanstack/react-router";
+import { open as openFileDialog } from "@tauri-apps/plugin-dialog";
 import { useAuth, useTheme } from "../../shared/context";
 import { useData } from "../../shared/context";
-import { fetchConversationMessages, queueAction, sendMessage } from "../../tauri";
+import { buildReply, fetchConversationMessages, queueAction, sendMessage, unsend } from "../../tauri";
 import type { Message } from "../../tauri";
 import {
   displayName,
@@ -34,6 +35,7 @@ function ConversationView() {
   const [composeText, setComposeText] = useState("");
   const [sending, setSending] = useState(false);
   const [replyingTo, setReplyingTo] = useState<Message | null>(null);
+  const [attachmentPaths, setAttachmentPaths] = useState<string[]>([]);
   const bottomRef = useRef<HTMLDivElement>(null);
   const scrollRef = useRef<HTMLDivElement>(null);
   const savedScrollRef = useRef<number>(0);
@@ -87,6 +89,19 @@ function ConversationView() {
     }
   }, [messagesLoading, messages]);
 
+  // Re-fetch when the next held-back send goes out, so its Undo button disappears
+  useEffect(() => {
+    if (!conversation) return;
+    const next = Math.min(...messages.map((m) => m.scheduled_at ?? Infinity));
+    if (next === Infinity) return;
+    const timer = setTimeout(() => {
+      fetchConversationMessages(conversation.account_id, conversation.id)
+        .then(setMessages)
+        .catch(() => {});
+    }, Math.max(next - Date.now(), 0) + 1000);
+    return () => clearTimeout(timer);
+  }, [messages, conversation?.id]);
+
   // Auto-focus compose input when arriving from compose flow
   useEffect(() => {
     if (wantsFocus && !messagesLoading && composeRef.current) {
@@ -220,15 +235,20 @@ function ConversationView() {
     let subject: string;
     let inReplyTo: string | undefined;
     let refs: string[] = [];
+    let fromEmail = isNewConversation ? newFrom : myEmail;
 
     if (replyingTo) {
-      // Reply button: thread against the specific message, keep subject stable
-      subject = replyingTo.subject?.match(/^re:/i) ? replyingTo.subject : `Re: ${replyingTo.subject || ""}`;
-      inReplyTo = replyingTo.message_id || undefined;
+      // Reply button: thread against the specific message, from the alias it was sent to
       try {
-        const existingRefs: string[] = JSON.parse(replyingTo.references_ids || "[]");
-        refs = [...existingRefs, replyingTo.message_id].filter(Boolean);
-      } catch { refs = [replyingTo.message_id].filter(Boolean); }
+        const draft = await buildReply(replyingTo.id, "reply");
+        subject = draft.subject;
+        inReplyTo = draft.in_reply_to || undefined;
+        refs = draft.references;
+        fromEmail = draft.from_email || fromEmail;
+      } catch (e) {
+        console.error("Failed to build reply:", e);
+        return;
+      }
     } else if (isNewConversation) {
       // Brand-new conversation with no prior messages
       subject = newSubject || `${myEmail.split("@")[0]} via Eddie`;
@@ -253,15 +273,17 @@ function ConversationView() {
     try {
       const result = await sendMessage({
         accountId,
-        fromEmail: isNewConversation ? newFrom : myEmail,
+        fromEmail,
         to: toAddrs,
         cc: [],
         subject,
         body: text,
         inReplyTo,
         references: refs,
+        attachments: attachmentPaths.map((path) => ({ path })),
       });
       setComposeText("");
+      setAttachmentPaths([]);
       setReplyingTo(null);
 
       if (isNewConversation) {
@@ -280,7 +302,24 @@ function ConversationView() {
     } finally {
       setSending(false);
     }
-  }, [composeText, accountId, myEmail, sending, isNewConversation, newTo, newSubject, newFrom, conversation, replyingTo, messages, navigate]);
+  }, [composeText, accountId, myEmail, sending, isNewConversation, newTo, newSubject, newFrom, conversation, replyingTo, messages, navigate, attachmentPaths]);
+
+  const handleAttach = useCallback(async () => {
+    const picked = await openFileDialog({ multiple: true, directory: false });
+    if (!picked) return;
+    const paths = Array.isArray(picked) ? picked : [picked];
+    setAttachmentPaths((prev) => [...prev, ...paths.filter((p) => !prev.includes(p))]);
+  }, []);
+
+  const handleUnsend = useCallback(async (m: Message) => {
+    if (!accountId) return;
+    try {
+      await unsend(accountId, m.message_id);
+      setMessages((prev) => prev.filter((p) => p.id !== m.id));
+    } catch (e) {
+      console.error("Unsend failed:", e);
+    }
+  }, [accountId]);
 
   const handleReply = useCallback((m: Message) => {
     setReplyingTo(m);
@@ -521,9 +560,30 @@ function ConversationView() {
                   </div>
                 </div>
                 <div className="flex items-center gap-2 px-1 pt-0.5">
-                  {showTime && (
+                  {showTime && !m.scheduled_at && (
                     <span className="text-[11px] text-text-dim font-medium">{fmtTime(m.date)}</span>
                   )}
+                  {m.scheduled_at && m.scheduled_at - Date.now() > 60 * 1000 && (
+                    <span className="text-[11px] text-text-dim font-medium">Scheduled {fmtTime(m.scheduled_at)}</span>
+                  )}
+                  {m.delivery_failures.length > 0 && (
+                    <span
+                      className="text-[11px] text-accent-red font-medium"
+                      title={m.delivery_failures.map((f) => `${f.recipient}: ${f.diagnostic || f.status || f.action}`).join("\n")}
+                    >
+                      {m.delivery_failures.some((f) => f.action === "failed")
+                        ? `Not delivered to ${m.delivery_failures.filter((f) => f.action === "failed").map((f) => f.recipient).join(", ")}`
+                        : `Delivery delayed to ${m.delivery_failures.map((f) => f.recipient).join(", ")}`}
+                    </span>
+                  )}
+                  {m.scheduled_at && (
+                    <button
+                      className="text-[11px] text-text-dim font-medium bg-transparent border-none cursor-pointer p-0 hover:text-accent-green transition-colors"
+                      onClick={(e) => { e.stopPropagation(); handleUnsend(m); }}
+                    >
+                      Undo
+                    </button>
+                  )}
                   {!isSent && (
                     <button
                       className="text-[11px] text-text-dim font-medium bg-transparent border-none cursor-pointer p-0 hover:text-accent-green transition-colors"
@@ -566,7 +626,30 @@ function ConversationView() {
             </button>
           </div>
         )}
+        {attachmentPaths.length > 0 && (
+          <div className="flex flex-wrap gap-1.5 px-3 pt-2">
+            {attachmentPaths.map((path) => (
+              <span key={path} className="flex items-center gap-1 text-[12px] text-text-muted bg-bg-tertiary rounded-full pl-2.5 pr-1 py-0.5 max-w-[200px]">
+                <span className="truncate">{path.split(/[\\/]/).pop()}</span>
+                <button
+                  className="text-text-dim text-[14px] bg-transparent border-none cursor-pointer p-0 px-1 hover:text-accent-red"
+                  onClick={() => setAttachmentPaths((prev) => prev.filter((p) => p !== path))}
+                >
+                  &times;
+                </button>
+              </span>
+            ))}
+          </div>
+        )}
         <div className="flex items-center gap-2 px-3 pt-2.5">
+          <button
+            className="w-10 h-10 rounded-[12px] border-none bg-transparent text-text-dim text-[18px] cursor-pointer flex items-center justify-center shrink-0 hover:text-text-primary transition disabled:opacity-40"
+            onClick={handleAttach}
+            disabled={sending}
+            title="Attach files"
+          >
+            {"\u{1F4CE}"}
+          </button>
           <input
             ref={composeRef}
             className="compose-input flex-1 py-2.5 px-3.5 rounded-[12px] text-[16px] font-medium outline-none bg-bg-primary text-text-primary placeholder:text-text-dim"
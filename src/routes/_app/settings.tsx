@@ -2,8 +2,8 @@ import { useState, useEffect, useCallback } from "react";
 import { createFileRoute, useRouter } from "@tanstack/react-router";
 import { useAuth, useTheme } from "../../shared/context";
 import { SettingsToggle, SettingsSelect, Avatar } from "../../shared/components";
-import { getSetting, setSetting, getAccount, updateAccount } from "../../tauri";
-import type { AccountDetails } from "../../tauri";
+import { getSetting, setSetting, getAccount, updateAccount, reauthorizeAccount } from "../../tauri";
+import type { AccountDetails, ImapSecurity } from "../../tauri";
 
 export const Route = createFileRoute("/_app/settings")({
   component: SettingsScreen,
@@ -13,6 +13,7 @@ const SETTING_KEYS = {
   hideOlderChats: "hide_older_chats",
   showToaster: "show_toaster",
   writeMode: "write_mode",
+  undoSend: "undo_send_seconds",
 } as const;
 
 const TOGGLE_DEFAULTS: Record<string, boolean> = {
@@ -23,6 +24,13 @@ const TOGGLE_DEFAULTS: Record<string, boolean> = {
 const CHAT_AGE_STEPS = ["1", "2", "3", "4", "all"] as const;
 const CHAT_AGE_LABELS: Record<string, string> = { "1": "1w", "2": "2w", "3": "3w", "4": "4w", "all": "All" };
 
+const UNDO_SEND_OPTIONS = [
+  { value: "0", label: "Off" },
+  { value: "5", label: "5 seconds" },
+  { value: "10", label: "10 seconds" },
+  { value: "30", label: "30 seconds" },
+];
+
 const THEME_OPTIONS = [
   { value: "light", label: "Light" },
   { value: "dark", label: "Dark" },
@@ -35,13 +43,15 @@ function SettingsScreen() {
   const { theme, setTheme } = useTheme();
   const [toggles, setToggles] = useState<Record<string, boolean>>(TOGGLE_DEFAULTS);
   const [chatAge, setChatAge] = useState<string>("all");
+  const [undoSend, setUndoSend] = useState<string>("10");
   const [editingAccount, setEditingAccount] = useState<AccountDetails | null>(null);
   const [accountForm, setAccountForm] = useState({
     displayName: "",
     password: "",
     imapHost: "",
     imapPort: "",
-    imapTls: true,
+    imapSecurity: "tls" as ImapSecurity,
+    allowPlaintext: false,
     smtpHost: "",
     smtpPort: "",
     smtpTls: true,
@@ -56,6 +66,7 @@ function SettingsScreen() {
       for (const [k, v] of results) {
         if (v === null) continue;
         if (k === SETTING_KEYS.hideOlderChats) setChatAge(v);
+        else if (k === SETTING_KEYS.undoSend) setUndoSend(v);
         else setToggles((prev) => ({ ...prev, [k]: v === "true" }));
       }
     }
@@ -77,7 +88,8 @@ function SettingsScreen() {
       password: "",
       imapHost: details.imap_host,
       imapPort: String(details.imap_port),
-      imapTls: details.imap_tls,
+      imapSecurity: details.imap_security,
+      allowPlaintext: details.allow_plaintext,
       smtpHost: details.smtp_host,
       smtpPort: String(details.smtp_port),
       smtpTls: details.smtp_tls,
@@ -95,7 +107,8 @@ function SettingsScreen() {
         password: accountForm.password || undefined,
         imapHost: accountForm.imapHost,
         imapPort: parseInt(accountForm.imapPort) || undefined,
-        imapTls: accountForm.imapTls,
+        imapSecurity: accountForm.imapSecurity,
+        allowPlaintext: accountForm.allowPlaintext,
         smtpHost: accountForm.smtpHost,
         smtpPort: parseInt(accountForm.smtpPort) || undefined,
         smtpTls: accountForm.smtpTls,
@@ -107,12 +120,21 @@ function SettingsScreen() {
     }
   }, [editingAccount, accountForm]);
 
+  const reauthorize = useCallback(async () => {
+    if (!editingAccount) return;
+    await reauthorizeAccount(editingAccount.id);
+    setEditingAccount({ ...editingAccount, reauth_required: false });
+  }, [editingAccount]);
+
   const settingsSections = [
     {
       section: "Appearance", items: [
         { label: "Show status toaster", desc: "Show sync status at the bottom", key: SETTING_KEYS.showToaster },
       ]
     },
+    {
+      section: "Sending", items: [],
+    },
     {
       section: "Privacy", items: [
         { label: "Read-only mode", desc: "Prevent Eddie from modifying your mailbox", key: SETTING_KEYS.writeMode, invert: true },
@@ -155,8 +177,22 @@ function SettingsScreen() {
               <div className="px-4 pb-4 border-t border-divider pt-3 flex flex-col gap-2.5" onClick={(e) => e.stopPropagation()}>
                 <AccountField label="Display name" value={accountForm.displayName} placeholder="Your name"
                   onChange={(v) => setAccountForm((f) => ({ ...f, displayName: v }))} />
-                <AccountField label="Password" value={accountForm.password} placeholder="••••••••" type="password"
-                  onChange={(v) => setAccountForm((f) => ({ ...f, password: v }))} />
+                {editingAccount.oauth_provider ? (
+                  <div className="flex items-center gap-2 text-[13px] text-text-muted">
+                    <span className="flex-1">
+                      {editingAccount.reauth_required ? "Sign-in expired — sign in again to keep syncing" : `Signed in with ${editingAccount.oauth_provider === "google" ? "Google" : "Microsoft"}`}
+                    </span>
+                    {editingAccount.reauth_required && (
+                      <button onClick={reauthorize}
+                        className="py-1.5 px-3 rounded-lg border-none bg-accent-green text-white text-[12px] font-semibold cursor-pointer">
+                        Sign in again
+                      </button>
+                    )}
+                  </div>
+                ) : (
+                  <AccountField label="Password" value={accountForm.password} placeholder="••••••••" type="password"
+                    onChange={(v) => setAccountForm((f) => ({ ...f, password: v }))} />
+                )}
 
                 <div className="text-[11px] font-bold text-text-dim tracking-[0.08em] mt-2">IMAP</div>
                 <div className="flex gap-2">
@@ -165,11 +201,22 @@ function SettingsScreen() {
                   <AccountField label="Port" value={accountForm.imapPort} className="w-20"
                     onChange={(v) => setAccountForm((f) => ({ ...f, imapPort: v }))} />
                 </div>
-                <label className="flex items-center gap-2 text-[13px] text-text-muted cursor-pointer">
-                  <input type="checkbox" checked={accountForm.imapTls} onChange={(e) => setAccountForm((f) => ({ ...f, imapTls: e.target.checked }))}
-                    className="accent-accent-green" />
-                  Use TLS
+                <label className="flex items-center gap-2 text-[13px] text-text-muted">
+                  Security
+                  <select value={accountForm.imapSecurity} onChange={(e) => setAccountForm((f) => ({ ...f, imapSecurity: e.target.value as ImapSecurity }))}
+                    className="flex-1 py-1.5 px-2 rounded-lg border border-divider bg-bg-tertiary text-[13px] text-text-primary">
+                    <option value="tls">TLS</option>
+                    <option value="starttls">STARTTLS</option>
+                    <option value="none">None</option>
+                  </select>
                 </label>
+                {accountForm.imapSecurity === "starttls" && (
+                  <label className="flex items-center gap-2 text-[13px] text-text-muted cursor-pointer">
+                    <input type="checkbox" checked={accountForm.allowPlaintext} onChange={(e) => setAccountForm((f) => ({ ...f, allowPlaintext: e.target.checked }))}
+                      className="accent-accent-green" />
+                    Allow unencrypted login if STARTTLS fails
+                  </label>
+                )}
 
                 <div className="text-[11px] font-bold text-text-dim tracking-[0.08em] mt-2">SMTP</div>
                 <div className="flex gap-2">
@@ -233,6 +280,9 @@ function SettingsScreen() {
                 </div>
               </div>
             </>)}
+            {group.section === "Sending" && (
+              <SettingsSelect label="Undo send" desc="Hold messages back so you can take them back" value={undoSend} options={UNDO_SEND_OPTIONS} onChange={(v) => { setUndoSend(v); setSetting(SETTING_KEYS.undoSend, v); }} />
+            )}
             {group.items.map((item) => {
               const invert = "invert" in item && item.invert;
               return <SettingsToggle key={item.key} label={item.label} desc={item.desc} value={invert ? !toggles[item.key] : toggles[item.key]} onChange={(v) => persistToggle(item.key, invert ? !v : v)} />;
@@ -2,7 +2,7 @@ import { useState, useEffect } from "react";
 import { createFileRoute, useNavigate } from "@tanstack/react-router";
 import { useAuth } from "../shared/context";
 import { discoverEmailConfig } from "../tauri";
-import type { DiscoveryResult } from "../tauri";
+import type { DiscoveryResult, ImapSecurity } from "../tauri";
 
 export const Route = createFileRoute("/login")({
   component: LoginScreen,
@@ -19,7 +19,8 @@ function LoginScreen() {
   const [discovery, setDiscovery] = useState<DiscoveryResult | null>(null);
   const [imapHost, setImapHost] = useState("");
   const [imapPort, setImapPort] = useState(993);
-  const [imapTls, setImapTls] = useState(true);
+  const [imapSecurity, setImapSecurity] = useState<ImapSecurity>("tls");
+  const [allowPlaintext, setAllowPlaintext] = useState(false);
   const [smtpHost, setSmtpHost] = useState("");
   const [smtpPort, setSmtpPort] = useState(587);
   const [smtpTls, setSmtpTls] = useState(true);
@@ -46,7 +47,7 @@ function LoginScreen() {
       setDiscovery(result);
       setImapHost(result.imap_host);
       setImapPort(result.imap_port);
-      setImapTls(result.imap_tls);
+      setImapSecurity(result.imap_security);
       setSmtpHost(result.smtp_host);
       setSmtpPort(result.smtp_port);
       setSmtpTls(result.smtp_tls);
@@ -67,7 +68,8 @@ function LoginScreen() {
       await auth.handleLogin({
         imapHost: discovery ? discovery.imap_host : imapHost,
         imapPort: discovery ? discovery.imap_port : imapPort,
-        imapTls: discovery ? discovery.imap_tls : imapTls,
+        imapSecurity: discovery ? discovery.imap_security : imapSecurity,
+        allowPlaintext: discovery ? false : allowPlaintext,
         smtpHost: discovery ? discovery.smtp_host : smtpHost,
         smtpPort: discovery ? discovery.smtp_port : smtpPort,
         smtpTls: discovery ? discovery.smtp_tls : smtpTls,
@@ -79,6 +81,26 @@ function LoginScreen() {
     }
   };
 
+  const handleOAuth = async () => {
+    if (!discovery?.oauth_provider) return;
+    setError(null);
+    setStep("saving");
+    try {
+      await auth.handleOAuthLogin(discovery.oauth_provider, {
+        imapHost: discovery.imap_host,
+        imapPort: discovery.imap_port,
+        imapSecurity: discovery.imap_security,
+        smtpHost: discovery.smtp_host,
+        smtpPort: discovery.smtp_port,
+        smtpTls: discovery.smtp_tls,
+      });
+      navigate({ to: "/onboarding" });
+    } catch (e) {
+      setError(typeof e === "string" ? e : String(e));
+      setStep("auth");
+    }
+  };
+
   const inputClass =
     "w-full py-3 px-3.5 border border-divider rounded-[10px] bg-bg-tertiary text-[14px] font-medium text-text-primary outline-none placeholder:text-text-dim focus:border-accent-green";
 
@@ -154,6 +176,19 @@ function LoginScreen() {
                 </div>
               )}
 
+              {discovery.oauth_provider && (
+                <>
+                  <button
+                    type="button"
+                    className="w-full py-3 border border-divider rounded-[10px] bg-bg-tertiary text-text-primary text-[14px] font-semibold cursor-pointer hover:brightness-95 transition"
+                    onClick={handleOAuth}
+                  >
+                    Sign in with {discovery.oauth_provider === "google" ? "Google" : "Microsoft"}
+                  </button>
+                  <p className="text-[11px] text-text-dim text-center my-3">or use a password</p>
+                </>
+              )}
+
               {discovery.requires_app_password ? (
                 <>
                   <p className="text-sm text-text-muted mb-3">
@@ -301,16 +336,27 @@ function LoginScreen() {
                     value={imapPort}
                     onChange={(e) => setImapPort(parseInt(e.target.value) || 993)}
                   />
-                  <label className="flex items-center gap-2 text-sm text-text-muted">
+                  <select
+                    className={`${inputClass} flex-1`}
+                    value={imapSecurity}
+                    onChange={(e) => setImapSecurity(e.target.value as ImapSecurity)}
+                  >
+                    <option value="tls">TLS</option>
+                    <option value="starttls">STARTTLS</option>
+                    <option value="none">None</option>
+                  </select>
+                </div>
+                {imapSecurity === "starttls" && (
+                  <label className="flex items-center gap-2 text-sm text-text-muted mt-3">
                     <input
                       type="checkbox"
-                      checked={imapTls}
-                      onChange={(e) => setImapTls(e.target.checked)}
+                      checked={allowPlaintext}
+                      onChange={(e) => setAllowPlaintext(e.target.checked)}
                       className="w-4 h-4 rounded"
                     />
-                    TLS
+                    Allow unencrypted login if STARTTLS fails
                   </label>
-                </div>
+                )}
               </fieldset>
 
               <fieldset className="border border-divider rounded-[10px] p-4 mb-4">
@@ -1,6 +1,6 @@
 import { useState, useEffect } from "react";
-import type { Message } from "../../tauri";
-import { fetchMessageHtml } from "../../tauri";
+import type { Message, Attachment } from "../../tauri";
+import { fetchMessageHtml, listAttachments, openAttachment } from "../../tauri";
 import { fmtDate, firstName, parseAddresses, hasAddresses } from "../lib";
 import { Avatar } from "./Avatar";
 
@@ -23,6 +23,15 @@ export function MessageDetail({ message: m, onBack }: MessageDetailProps) {
       .finally(() => setLoading(false));
   }, [m.id, needsFetch]);
 
+  const [attachments, setAttachments] = useState<Attachment[]>([]);
+
+  useEffect(() => {
+    if (!m.has_attachments) return;
+    listAttachments(m.id)
+      .then((list) => setAttachments(list.filter((a) => !a.is_inline)))
+      .catch(() => {});
+  }, [m.id, m.has_attachments]);
+
   const sender = firstName(m.from_name || m.from_address);
 
   return (
@@ -106,6 +115,14 @@ export function MessageDetail({ message: m, onBack }: MessageDetailProps) {
           </div>
         </div>
 
+        {attachments.length > 0 && (
+          <div className="flex flex-wrap gap-2 px-4 pt-3">
+            {attachments.map((a) => (
+              <AttachmentChip key={a.part_path} messageId={m.id} attachment={a} />
+            ))}
+          </div>
+        )}
+
         {/* Body */}
         <div className="px-4 py-4">
           {loading ? (
@@ -125,6 +142,42 @@ export function MessageDetail({ message: m, onBack }: MessageDetailProps) {
   );
 }
 
+function fmtSize(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${Math.round(bytes / 1024)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
+function AttachmentChip({ messageId, attachment: a }: { messageId: string; attachment: Attachment }) {
+  const [opening, setOpening] = useState(false);
+  const [error, setError] = useState(false);
+
+  const open = () => {
+    setOpening(true);
+    setError(false);
+    openAttachment(messageId, a.part_path)
+      .catch(() => setError(true))
+      .finally(() => setOpening(false));
+  };
+
+  // size_bytes is the encoded size; base64 inflates by about a third
+  const approxSize = a.transfer_encoding === "base64" ? Math.round(a.size_bytes * 0.75) : a.size_bytes;
+
+  return (
+    <button
+      className="flex items-center gap-2 px-3 py-1.5 rounded-xl border border-divider bg-transparent cursor-pointer text-[13px] font-semibold text-text-primary max-w-full"
+      onClick={open}
+      disabled={opening}
+      title={error ? "Couldn't open attachment" : a.mime_type}
+    >
+      <span className="truncate">{a.filename || a.mime_type}</span>
+      <span className={error ? "text-accent-red shrink-0" : "text-text-dim shrink-0"}>
+        {opening ? "\u2026" : error ? "!" : fmtSize(approxSize)}
+      </span>
+    </button>
+  );
+}
+
 function sanitizeHtml(html: string): string {
   let clean = html;
   // Remove script tags and their content
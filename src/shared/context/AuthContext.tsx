@@ -1,6 +1,9 @@
 import { createContext, useContext, useState, useMemo, useCallback, useEffect } from "react";
 import type { ReactNode } from "react";
-import { connectAccount, getExistingAccount } from "../../tauri";
+import { connectAccount, connectOAuthAccount, getExistingAccount } from "../../tauri";
+import type { ImapSecurity } from "../../tauri";
+
+type ServerSettings = { imapHost: string; imapPort: number; imapSecurity?: ImapSecurity; allowPlaintext?: boolean; smtpHost: string; smtpPort: number; smtpTls?: boolean };
 
 interface AuthContextValue {
   email: string;
@@ -22,7 +25,8 @@ interface AuthContextValue {
   error: string;
   accountId: string | null;
   myAddrs: Set<string>;
-  handleLogin: (overrides?: { imapHost: string; imapPort: number; imapTls?: boolean; smtpHost: string; smtpPort: number; smtpTls?: boolean }) => Promise<string>;
+  handleLogin: (overrides?: ServerSettings) => Promise<string>;
+  handleOAuthLogin: (oauthProvider: string, server: ServerSettings) => Promise<string>;
 }
 
 const AuthContext = createContext<AuthContextValue | null>(null);
@@ -64,7 +68,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     [email, aliases]
   );
 
-  const handleLogin = useCallback(async (overrides?: { imapHost: string; imapPort: number; imapTls?: boolean; smtpHost: string; smtpPort: number; smtpTls?: boolean }) => {
+  const handleLogin = useCallback(async (overrides?: ServerSettings) => {
     setLoading(true);
     setError("");
     try {
@@ -72,7 +76,8 @@ export function AuthProvider({ children }: { children: ReactNode }) {
         email, password,
         imapHost: overrides?.imapHost ?? imapHost,
         imapPort: overrides?.imapPort ?? imapPort,
-        imapTls: overrides?.imapTls,
+        imapSecurity: overrides?.imapSecurity,
+        allowPlaintext: overrides?.allowPlaintext,
         smtpHost: overrides?.smtpHost ?? smtpHost,
         smtpPort: overrides?.smtpPort ?? smtpPort,
         smtpTls: overrides?.smtpTls,
@@ -95,6 +100,35 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     }
   }, [email, password, imapHost, imapPort, smtpHost, smtpPort, aliases]);
 
+  const handleOAuthLogin = useCallback(async (oauthProvider: string, server: ServerSettings) => {
+    setLoading(true);
+    setError("");
+    try {
+      const id = await connectOAuthAccount({
+        email, oauthProvider,
+        imapHost: server.imapHost,
+        imapPort: server.imapPort,
+        imapSecurity: server.imapSecurity,
+        smtpHost: server.smtpHost,
+        smtpPort: server.smtpPort,
+        smtpTls: server.smtpTls,
+        aliases: aliases || undefined,
+      });
+      setImapHost(server.imapHost);
+      setImapPort(server.imapPort);
+      setSmtpHost(server.smtpHost);
+      setSmtpPort(server.smtpPort);
+      setAccountId(id);
+      setLoggedIn(true);
+      return id;
+    } catch (err) {
+      setError(String(err));
+      throw err;
+    } finally {
+      setLoading(false);
+    }
+  }, [email, aliases]);
+
   const value = useMemo<AuthContextValue>(
     () => ({
       email, setEmail,
@@ -105,9 +139,9 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       smtpHost, setSmtpHost,
       smtpPort, setSmtpPort,
       loggedIn, loading, error, accountId, myAddrs,
-      handleLogin,
+      handleLogin, handleOAuthLogin,
     }),
-    [email, aliases, password, imapHost, imapPort, smtpHost, smtpPort, loggedIn, loading, error, accountId, myAddrs, handleLogin]
+    [email, aliases, password, imapHost, imapPort, smtpHost, smtpPort, loggedIn, loading, error, accountId, myAddrs, handleLogin, handleOAuthLogin]
   );
 
   return <AuthContext.Provider value={value}>{children}</AuthContext.Provider>;
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Conversation, Message, ConnectAccountParams, OnboardingStatus, DiscoveryResult, ExistingAccount, EntityResult, AliasInfo, SendMessageParams, SendResult, AccountDetails, UpdateAccountParams } from "./types";
+import type { Conversation, ConversationPage, Message, MessagePage, ConnectAccountParams, ConnectOAuthAccountParams, OnboardingStatus, DiscoveryResult, ExistingAccount, EntityResult, SearchPage, SmartView, AliasInfo, SendMessageParams, SendResult, QueuedAction, EditSendParams, ReplyMode, ReplyDraft, AccountDetails, UpdateAccountParams, Attachment, CachedAttachment } from "./types";
 
 export async function connectAccount(
   params: ConnectAccountParams
@@ -7,10 +7,21 @@ export async function connectAccount(
   return invoke<string>("connect_account", params);
 }
 
+export async function connectOAuthAccount(
+  params: ConnectOAuthAccountParams
+): Promise<string> {
+  return invoke<string>("connect_oauth_account", params);
+}
+
+export async function reauthorizeAccount(accountId: string): Promise<void> {
+  return invoke<void>("reauthorize_account", { accountId });
+}
+
 export async function fetchConversations(
-  accountId: string
+  accountId: string,
+  viewId?: string | null
 ): Promise<Conversation[]> {
-  return invoke<Conversation[]>("fetch_conversations", { accountId });
+  return invoke<Conversation[]>("fetch_conversations", { accountId, viewId: viewId ?? null });
 }
 
 export async function syncNow(): Promise<string> {
@@ -27,6 +38,34 @@ export async function fetchConversationMessages(
   });
 }
 
+export async function fetchConversationPage(
+  accountId: string,
+  limit: number,
+  options: { classification?: string | null; pinned?: boolean | null; cursor?: string | null } = {}
+): Promise<ConversationPage> {
+  return invoke<ConversationPage>("fetch_conversation_page", {
+    accountId,
+    classification: options.classification ?? null,
+    pinned: options.pinned ?? null,
+    limit,
+    cursor: options.cursor ?? null,
+  });
+}
+
+export async function fetchConversationMessagePage(
+  accountId: string,
+  conversationId: string,
+  limit: number,
+  cursor?: string | null
+): Promise<MessagePage> {
+  return invoke<MessagePage>("fetch_conversation_message_page", {
+    accountId,
+    conversationId,
+    limit,
+    cursor: cursor ?? null,
+  });
+}
+
 export async function reclassify(accountId: string): Promise<string> {
   return invoke<string>("reclassify", { accountId });
 }
@@ -83,6 +122,10 @@ export async function blockEntities(
   return invoke<void>("block_entities", { accountId, emails });
 }
 
+export async function rebuildConversations(accountId: string): Promise<number> {
+  return invoke<number>("rebuild_conversations", { accountId });
+}
+
 export async function getAppVersion(): Promise<string> {
   return invoke<string>("get_app_version");
 }
@@ -93,6 +136,26 @@ export async function fetchMessageHtml(
   return invoke<string | null>("fetch_message_html", { messageId });
 }
 
+export async function listAttachments(
+  messageId: string
+): Promise<Attachment[]> {
+  return invoke<Attachment[]>("list_attachments", { messageId });
+}
+
+export async function fetchAttachment(
+  messageId: string,
+  partPath: string
+): Promise<CachedAttachment> {
+  return invoke<CachedAttachment>("fetch_attachment", { messageId, partPath });
+}
+
+export async function openAttachment(
+  messageId: string,
+  partPath: string
+): Promise<void> {
+  return invoke<void>("open_attachment", { messageId, partPath });
+}
+
 export async function queueAction(
   accountId: string,
   actionType: string,
@@ -105,6 +168,18 @@ export async function queueAction(
   });
 }
 
+export async function listOutbox(accountId: string): Promise<QueuedAction[]> {
+  return invoke<QueuedAction[]>("list_outbox", { accountId });
+}
+
+export async function retryAction(actionId: string): Promise<void> {
+  return invoke<void>("retry_action", { actionId });
+}
+
+export async function cancelAction(actionId: string): Promise<void> {
+  return invoke<void>("cancel_action", { actionId });
+}
+
 export async function searchEntities(
   accountId: string,
   query: string
@@ -112,6 +187,32 @@ export async function searchEntities(
   return invoke<EntityResult[]>("search_entities", { accountId, query });
 }
 
+export async function searchMessages(
+  accountId: string,
+  query: string,
+  limit: number,
+  cursor?: string | null
+): Promise<SearchPage> {
+  return invoke<SearchPage>("search_messages", { accountId, query, limit, cursor: cursor ?? null });
+}
+
+export async function listSmartViews(accountId: string): Promise<SmartView[]> {
+  return invoke<SmartView[]>("list_smart_views", { accountId });
+}
+
+export async function saveSmartView(
+  accountId: string,
+  name: string,
+  query: string,
+  id?: string | null
+): Promise<SmartView> {
+  return invoke<SmartView>("save_smart_view", { accountId, id: id ?? null, name, query });
+}
+
+export async function deleteSmartView(accountId: string, id: string): Promise<boolean> {
+  return invoke<boolean>("delete_smart_view", { accountId, id });
+}
+
 export async function getUserAliases(
   accountId: string
 ): Promise<AliasInfo[]> {
@@ -127,10 +228,37 @@ export async function sendMessage(
     fromName: params.fromName,
     to: params.to,
     cc: params.cc,
+    bcc: params.bcc ?? [],
+    replyTo: params.replyTo ?? [],
+    sender: params.sender,
+    headers: params.headers ?? {},
     subject: params.subject,
     body: params.body,
     inReplyTo: params.inReplyTo,
     references: params.references ?? [],
+    sendAt: params.sendAt,
+    attachments: params.attachments ?? [],
+  });
+}
+
+export async function unsend(accountId: string, messageId: string): Promise<void> {
+  return invoke<void>("unsend", { accountId, messageId });
+}
+
+export async function buildReply(messageId: string, mode: ReplyMode): Promise<ReplyDraft> {
+  return invoke<ReplyDraft>("build_reply", { messageId, mode });
+}
+
+export async function editFailedSend(
+  actionId: string,
+  params: EditSendParams
+): Promise<void> {
+  return invoke<void>("edit_failed_send", {
+    actionId,
+    to: params.to,
+    cc: params.cc,
+    subject: params.subject,
+    body: params.body,
   });
 }
 
@@ -149,7 +277,8 @@ export async function updateAccount(
     password: params.password,
     imapHost: params.imapHost,
     imapPort: params.imapPort,
-    imapTls: params.imapTls,
+    imapSecurity: params.imapSecurity,
+    allowPlaintext: params.allowPlaintext,
     smtpHost: params.smtpHost,
     smtpPort: params.smtpPort,
     smtpTls: params.smtpTls,
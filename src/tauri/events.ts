@@ -1,5 +1,5 @@
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
-import type { SyncStatus, ConversationsUpdated } from "./types";
+import type { SyncStatus, ConversationsUpdated, MessagesRemoved } from "./types";
 
 export async function onSyncStatus(
   callback: (status: SyncStatus) => void
@@ -20,6 +20,17 @@ export async function onConversationsUpdated(
   );
 }
 
+export async function onMessagesRemoved(
+  callback: (data: MessagesRemoved) => void
+): Promise<UnlistenFn> {
+  return listen<MessagesRemoved>(
+    "sync:messages-removed",
+    (event) => {
+      callback(event.payload);
+    }
+  );
+}
+
 export async function onOnboardingComplete(
   callback: (data: { account_id: string }) => void
 ): Promise<UnlistenFn> {
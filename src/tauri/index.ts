@@ -1,20 +1,38 @@
-export { connectAccount, fetchConversations, fetchConversationMessages, syncNow, reclassify, getSetting, setSetting, fetchRecentMessages, getOnboardingStatus, discoverEmailConfig, getExistingAccount, moveToRequests, moveToPoints, blockEntities, getAppVersion, fetchMessageHtml, queueAction, searchEntities, getUserAliases, sendMessage, getAccount, updateAccount } from "./commands";
-export { onSyncStatus, onConversationsUpdated, onOnboardingComplete } from "./events";
+export { connectAccount, connectOAuthAccount, reauthorizeAccount, fetchConversations, fetchConversationMessages, fetchConversationPage, fetchConversationMessagePage, syncNow, reclassify, getSetting, setSetting, fetchRecentMessages, getOnboardingStatus, discoverEmailConfig, getExistingAccount, moveToRequests, moveToPoints, blockEntities, rebuildConversations, getAppVersion, fetchMessageHtml, listAttachments, fetchAttachment, openAttachment, queueAction, listOutbox, retryAction, cancelAction, searchEntities, searchMessages, listSmartViews, saveSmartView, deleteSmartView, getUserAliases, sendMessage, unsend, buildReply, editFailedSend, getAccount, updateAccount } from "./commands";
+export { onSyncStatus, onConversationsUpdated, onMessagesRemoved, onOnboardingComplete } from "./events";
 export type {
   SyncStatus,
   ConversationsUpdated,
+  MessagesRemoved,
   Conversation,
+  ConversationPage,
   Message,
+  MessagePage,
+  DeliveryFailure,
   ConnectAccountParams,
+  ConnectOAuthAccountParams,
+  ImapSecurity,
   OnboardingStatus,
   TaskStatus,
   TrustContact,
   DiscoveryResult,
   ExistingAccount,
   EntityResult,
+  SearchHit,
+  SearchPage,
+  SnippetPart,
+  SmartView,
   AliasInfo,
   SendMessageParams,
   SendResult,
+  OutgoingAttachmentSource,
+  QueuedAction,
+  QueuedActionStatus,
+  EditSendParams,
+  ReplyMode,
+  ReplyDraft,
   AccountDetails,
   UpdateAccountParams,
+  Attachment,
+  CachedAttachment,
 } from "./types";
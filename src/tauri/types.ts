@@ -8,6 +8,11 @@ export type ConversationsUpdated = {
   count: number;
 };
 
+export type MessagesRemoved = {
+  account_id: string;
+  message_ids: string[];
+};
+
 export type Conversation = {
   id: string;
   account_id: string;
@@ -27,6 +32,11 @@ export type Conversation = {
   initial_sender_email: string | null;
 };
 
+export type ConversationPage = {
+  conversations: Conversation[];
+  next_cursor: string | null;
+};
+
 export type Message = {
   id: string;
   message_id: string;
@@ -35,6 +45,7 @@ export type Message = {
   from_name: string | null;
   to_addresses: string;
   cc_addresses: string;
+  bcc_addresses: string;
   subject: string | null;
   body_text: string | null;
   body_html: string | null;
@@ -46,14 +57,63 @@ export type Message = {
   references_ids: string;
   distilled_text: string | null;
   is_sent: boolean;
+  scheduled_at: number | null;
+  delivery_failures: DeliveryFailure[];
+};
+
+export type MessagePage = {
+  messages: Message[];
+  next_cursor: string | null;
+};
+
+export type DeliveryFailure = {
+  recipient: string;
+  action: "failed" | "delayed";
+  status: string | null;
+  diagnostic: string | null;
+  reported_at: number;
+};
+
+export type Attachment = {
+  message_id: string;
+  part_path: string;
+  filename: string | null;
+  mime_type: string;
+  size_bytes: number;
+  content_id: string | null;
+  transfer_encoding: string;
+  is_inline: boolean;
+  content_hash: string | null;
 };
 
+export type CachedAttachment = {
+  path: string;
+  filename: string | null;
+  mime_type: string;
+  size_bytes: number;
+};
+
+export type ImapSecurity = "none" | "starttls" | "tls";
+
 export type ConnectAccountParams = {
   email: string;
   password: string;
   imapHost: string;
   imapPort: number;
-  imapTls?: boolean;
+  imapSecurity?: ImapSecurity;
+  allowPlaintext?: boolean;
+  smtpHost: string;
+  smtpPort: number;
+  smtpTls?: boolean;
+  aliases?: string;
+};
+
+export type ConnectOAuthAccountParams = {
+  email: string;
+  oauthProvider: string;
+  imapHost: string;
+  imapPort: number;
+  imapSecurity?: ImapSecurity;
   smtpHost: string;
   smtpPort: number;
   smtpTls?: boolean;
@@ -90,6 +150,34 @@ export type EntityResult = {
   trust_level: string;
 };
 
+export type SnippetPart = {
+  text: string;
+  highlight: boolean;
+};
+
+export type SearchHit = {
+  id: string;
+  conversation_id: string;
+  date: number;
+  from_address: string;
+  from_name: string | null;
+  subject: string | null;
+  snippet: SnippetPart[];
+};
+
+export type SearchPage = {
+  hits: SearchHit[];
+  next_cursor: string | null;
+};
+
+export type SmartView = {
+  id: string;
+  name: string;
+  query: string;
+  created_at: number;
+  updated_at: number;
+};
+
 export type AliasInfo = {
   email: string;
   is_primary: boolean;
@@ -101,28 +189,80 @@ export type SendMessageParams = {
   fromName?: string;
   to: string[];
   cc: string[];
+  bcc?: string[];
+  replyTo?: string[];
+  sender?: string;
+  headers?: Record<string, string>;
   subject: string;
   body: string;
   inReplyTo?: string;
   references?: string[];
+  sendAt?: number;
+  attachments?: OutgoingAttachmentSource[];
 };
 
+export type OutgoingAttachmentSource =
+  | { path: string; content_id?: string }
+  | { message_id: string; part_path: string; content_id?: string };
+
 export type SendResult = {
+  id: string;
   message_id: string;
   conversation_id: string;
 };
 
+export type QueuedActionStatus = "pending" | "in_progress" | "completed" | "failed";
+
+export type QueuedAction = {
+  id: string;
+  account_id: string;
+  action_type: string;
+  payload: string;
+  status: QueuedActionStatus;
+  retry_count: number;
+  max_retries: number;
+  created_at: number;
+  error: string | null;
+  message_id: string | null;
+  next_attempt_at: number | null;
+  stage: "built" | "submitted" | "appended" | "confirmed" | null;
+  send_at: number | null;
+};
+
+export type ReplyMode = "reply" | "reply-all" | "forward";
+
+export type ReplyDraft = {
+  from_email: string;
+  to: string[];
+  cc: string[];
+  subject: string;
+  body: string;
+  in_reply_to: string | null;
+  references: string[];
+  attachments: Attachment[];
+};
+
+export type EditSendParams = {
+  to: string[];
+  cc: string[];
+  subject: string;
+  body: string;
+};
+
 export type AccountDetails = {
   id: string;
   email: string;
   display_name: string | null;
   imap_host: string;
   imap_port: number;
-  imap_tls: boolean;
+  imap_security: ImapSecurity;
+  allow_plaintext: boolean;
   smtp_host: string;
   smtp_port: number;
   smtp_tls: boolean;
   aliases: string[];
+  oauth_provider: string | null;
+  reauth_required: boolean;
 };
 
 export type UpdateAccountParams = {
@@ -131,7 +271,8 @@ export type UpdateAccountParams = {
   password?: string;
   imapHost?: string;
   imapPort?: number;
-  imapTls?: boolean;
+  imapSecurity?: ImapSecurity;
+  allowPlaintext?: boolean;
   smtpHost?: string;
   smtpPort?: number;
   smtpTls?: boolean;
@@ -143,12 +284,13 @@ export type DiscoveryResult = {
   provider_id: string | null;
   imap_host: string;
   imap_port: number;
-  imap_tls: boolean;
+  imap_security: ImapSecurity;
   smtp_host: string;
   smtp_port: number;
   smtp_tls: boolean;
   auth_method: string;
   requires_app_password: boolean;
+  oauth_provider: string | null;
   username_hint: string;
   source: string;
 };